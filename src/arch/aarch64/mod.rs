@@ -155,7 +155,19 @@ pub fn virt_to_phys(
 	Ok(pte.address())
 }
 
-pub fn init_guest_mem(mem: &mut [u8]) {
+/// Creates the boot pagetables in the guest memory space.
+///
+/// They map a fixed region, so `_identity_map_length` is ignored, and don't support read-only
+/// regions yet.
+pub fn init_guest_mem(
+	mem: &mut [u8],
+	_identity_map_length: u64,
+	read_only_regions: &[std::ops::Range<GuestPhysAddr>],
+) {
+	assert!(
+		read_only_regions.is_empty(),
+		"Read-only regions are not supported on aarch64"
+	);
 	let mem_addr = std::ptr::addr_of_mut!(mem[0]);
 
 	assert!(mem.len() >= BOOT_PGT.as_u64() as usize + 512 * size_of::<u64>());
@@ -3,11 +3,13 @@ pub mod registers;
 use core::arch::x86_64::_rdtsc as rdtsc;
 use std::{
 	convert::TryInto,
+	fmt::Write,
+	ops::Range,
 	time::{Duration, Instant},
 };
 
 use log::{debug, warn};
-use raw_cpuid::{CpuId, CpuIdReaderNative};
+use raw_cpuid::{CpuId, CpuIdReader};
 use thiserror::Error;
 use uhyve_interface::{GuestPhysAddr, GuestVirtAddr};
 use x86_64::{
@@ -23,19 +25,37 @@ use crate::{consts::*, mem::MmapMemory, paging::PagetableError};
 pub const RAM_START: GuestPhysAddr = GuestPhysAddr::new(0x00);
 const MHZ_TO_HZ: u64 = 1000000;
 const KHZ_TO_HZ: u64 = 1000;
+/// Duration of the busy loop measuring the TSC frequency.
+const TSC_CALIBRATION_DURATION: Duration = Duration::from_millis(10);
 
 #[derive(Error, Debug)]
 #[error("Frequency detection failed")]
 pub struct FrequencyDetectionFailed;
 
-pub fn detect_freq_from_cpuid(
-	cpuid: &CpuId<CpuIdReaderNative>,
+/// Detects the CPU frequency in MHz, falling back to measuring the TSC frequency if `cpuid`
+/// reports no frequency.
+pub fn detect_cpu_freq<R: CpuIdReader>(
+	cpuid: &CpuId<R>,
+) -> std::result::Result<u32, FrequencyDetectionFailed> {
+	detect_freq_from_cpuid(cpuid)
+		.or_else(|_| {
+			debug!("Failed to detect from cpuid");
+			detect_freq_from_cpuid_hypervisor_info(cpuid)
+		})
+		.or_else(|_| {
+			debug!("Failed to detect from hypervisor_info");
+			get_cpu_frequency_from_os()
+		})
+}
+
+pub fn detect_freq_from_cpuid<R: CpuIdReader>(
+	cpuid: &CpuId<R>,
 ) -> std::result::Result<u32, FrequencyDetectionFailed> {
 	debug!("Trying to detect CPU frequency by tsc info");
 
 	let has_invariant_tsc = cpuid
 		.get_advanced_power_mgmt_info()
-		.map_or(false, |apm_info| apm_info.has_invariant_tsc());
+		.is_some_and(|apm_info| apm_info.has_invariant_tsc());
 	if !has_invariant_tsc {
 		warn!("TSC frequency varies with speed-stepping")
 	}
@@ -43,6 +63,9 @@ pub fn detect_freq_from_cpuid(
 	let tsc_frequency_hz = cpuid.get_tsc_info().map(|tinfo| {
 		if tinfo.tsc_frequency().is_some() {
 			tinfo.tsc_frequency()
+		} else if tinfo.numerator() == 0 || tinfo.denominator() == 0 {
+			// The TSC/crystal clock ratio is not enumerated
+			None
 		} else {
 			// Skylake and Kabylake don't report the crystal clock, approximate with base frequency:
 			cpuid
@@ -70,8 +93,8 @@ pub fn detect_freq_from_cpuid(
 	}
 }
 
-pub fn detect_freq_from_cpuid_hypervisor_info(
-	cpuid: &CpuId<CpuIdReaderNative>,
+pub fn detect_freq_from_cpuid_hypervisor_info<R: CpuIdReader>(
+	cpuid: &CpuId<R>,
 ) -> std::result::Result<u32, FrequencyDetectionFailed> {
 	debug!("Trying to detect CPU frequency by hypervisor info");
 	let hypervisor_info = cpuid
@@ -94,18 +117,27 @@ pub fn detect_freq_from_cpuid_hypervisor_info(
 }
 
 pub fn get_cpu_frequency_from_os() -> std::result::Result<u32, FrequencyDetectionFailed> {
-	// Determine TSC frequency by measuring it (loop for a second, record ticks)
-	let duration = Duration::from_millis(10);
+	// Determine TSC frequency by measuring it (busy loop for a few milliseconds, record ticks)
 	let now = Instant::now();
 	let start = unsafe { crate::x86_64::rdtsc() };
-	if start > 0 {
-		loop {
-			if now.elapsed() >= duration {
-				break;
-			}
+	if start == 0 {
+		return Err(FrequencyDetectionFailed);
+	}
+	let elapsed = loop {
+		let elapsed = now.elapsed();
+		if elapsed >= TSC_CALIBRATION_DURATION {
+			break elapsed;
 		}
-		let end = unsafe { rdtsc() };
-		Ok((((end - start) * 100) / MHZ_TO_HZ).try_into().unwrap())
+	};
+	let end = unsafe { rdtsc() };
+	// The loop may overshoot, e.g., if the thread is preempted, so use the actual duration.
+	let hz = u128::from(end.saturating_sub(start)) * 1_000_000_000 / elapsed.as_nanos();
+	let mhz: u32 = (hz / u128::from(MHZ_TO_HZ))
+		.try_into()
+		.map_err(|_| FrequencyDetectionFailed)?;
+	debug!("Measured a TSC frequency of {mhz} MHz");
+	if mhz > 0 {
+		Ok(mhz)
 	} else {
 		Err(FrequencyDetectionFailed)
 	}
@@ -124,12 +156,15 @@ pub const MIN_PHYSMEM_SIZE: usize = BOOT_PDE.as_u64() as usize + 0x1000;
 
 /// Creates the pagetables and the GDT in the guest memory space.
 ///
+/// The first `length` bytes of the address space are identity mapped with 2 MiB pages.
+/// `length` must be a multiple of 2 MiB and at most [`MAX_IDENTITY_MAP_LENGTH`].
 /// The memory slice must be larger than [`MIN_PHYSMEM_SIZE`].
 /// Also, the memory `mem` needs to be zeroed for [`PAGE_SIZE`] bytes at the
 /// offsets [`BOOT_PML4`] and [`BOOT_PDPTE`], otherwise the integrity of the
 /// pagetables and thus the integrity of the guest's memory is not ensured
-pub fn initialize_pagetables(mem: &mut [u8]) {
+pub fn initialize_pagetables(mem: &mut [u8], length: u64) {
 	assert!(mem.len() >= MIN_PHYSMEM_SIZE);
+	assert!(length.is_multiple_of(Page::<Size2MiB>::SIZE) && length <= MAX_IDENTITY_MAP_LENGTH);
 	let mem_addr = std::ptr::addr_of_mut!(mem[0]);
 
 	let (gdt_entry, pml4, pdpte, pde);
@@ -183,7 +218,7 @@ pub fn initialize_pagetables(mem: &mut [u8]) {
 	);
 	pdpte[0].set_addr(BOOT_PDE, PageTableFlags::PRESENT | PageTableFlags::WRITABLE);
 
-	for i in 0..512 {
+	for i in 0..(length / Page::<Size2MiB>::SIZE) as usize {
 		let addr = PhysAddr::new(i as u64 * Page::<Size2MiB>::SIZE);
 		pde[i].set_addr(
 			addr,
@@ -192,6 +227,107 @@ pub fn initialize_pagetables(mem: &mut [u8]) {
 	}
 }
 
+/// Makes the identity mapped `region` read-only in the pagetables created by
+/// [`initialize_pagetables`].
+///
+/// The region must be aligned to the 2 MiB pages of the identity map. Writes to it trap, as long
+/// as the guest doesn't change the pagetables and runs with `CR0.WP` set.
+pub fn write_protect(mem: &mut [u8], region: Range<GuestPhysAddr>) {
+	assert!(mem.len() >= MIN_PHYSMEM_SIZE);
+	assert!(
+		region.start.as_u64().is_multiple_of(Page::<Size2MiB>::SIZE)
+			&& region.end.as_u64().is_multiple_of(Page::<Size2MiB>::SIZE),
+		"The read-only region must be aligned to 2 MiB"
+	);
+	// Safety: `mem` is asserted to contain the PDE.
+	let pde = unsafe {
+		std::ptr::addr_of_mut!(mem[BOOT_PDE.as_u64() as usize])
+			.cast::<PageTable>()
+			.as_mut()
+			.unwrap()
+	};
+	let pages = region.start.as_u64() / Page::<Size2MiB>::SIZE
+		..region.end.as_u64() / Page::<Size2MiB>::SIZE;
+	for entry in pde
+		.iter_mut()
+		.take(pages.end as usize)
+		.skip(pages.start as usize)
+	{
+		entry.set_flags(entry.flags() - PageTableFlags::WRITABLE);
+	}
+}
+
+/// An inconsistency of the boot pagetables, see [`verify_pagetables`].
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum PagetableLayoutError {
+	#[error("The {0} lies outside of the guest memory")]
+	OutOfMemory(String),
+	#[error("The {0} overlaps the {1}")]
+	Overlap(String, String),
+	#[error("Entry {index} of the {table} points to {found:#x} instead of {expected:#x}")]
+	InvalidEntry {
+		table: &'static str,
+		index: usize,
+		expected: u64,
+		found: u64,
+	},
+}
+
+/// Checks that the pagetables created by [`initialize_pagetables`] in `mem` are consistent with
+/// the boot structures `regions`, given as (name, address range).
+///
+/// The regions, which have to include the pagetables themselves, must lie within `mem` and must
+/// not overlap each other. The PML4 must point to the PDPTE and, recursively, to itself, the
+/// PDPTE must point to the PDE, and the PDE may only identity map the guest memory with 2 MiB
+/// pages.
+pub fn verify_pagetables(
+	mem: &[u8],
+	regions: &[(&str, Range<u64>)],
+) -> Result<(), PagetableLayoutError> {
+	for (i, (name, range)) in regions.iter().enumerate() {
+		if range.end > mem.len() as u64 {
+			return Err(PagetableLayoutError::OutOfMemory(name.to_string()));
+		}
+		if let Some((other, _)) = regions[i + 1..]
+			.iter()
+			.find(|(_, other)| other.start < range.end && range.start < other.end)
+		{
+			return Err(PagetableLayoutError::Overlap(
+				name.to_string(),
+				other.to_string(),
+			));
+		}
+	}
+
+	// `mem` isn't necessarily page aligned, so the entries are read one by one.
+	let entry = |table: GuestPhysAddr, index: usize| {
+		let offset = table.as_u64() as usize + index * 8;
+		u64::from_le_bytes(mem[offset..offset + 8].try_into().unwrap())
+	};
+	let expect = |table: &'static str, index: usize, entry: u64, expected: u64| {
+		let found = entry & 0x000f_ffff_ffff_f000;
+		if entry & PageTableFlags::PRESENT.bits() == 0 || found != expected {
+			return Err(PagetableLayoutError::InvalidEntry {
+				table,
+				index,
+				expected,
+				found,
+			});
+		}
+		Ok(())
+	};
+	expect("PML4", 0, entry(BOOT_PML4, 0), BOOT_PDPTE.as_u64())?;
+	expect("PML4", 511, entry(BOOT_PML4, 511), BOOT_PML4.as_u64())?;
+	expect("PDPTE", 0, entry(BOOT_PDPTE, 0), BOOT_PDE.as_u64())?;
+	for index in 0..512 {
+		let pde = entry(BOOT_PDE, index);
+		if pde != 0 {
+			expect("PDE", index, pde, index as u64 * Page::<Size2MiB>::SIZE)?;
+		}
+	}
+	Ok(())
+}
+
 /// Converts a virtual address in the guest to a physical address in the guest
 pub fn virt_to_phys(
 	addr: GuestVirtAddr,
@@ -232,9 +368,102 @@ pub fn virt_to_phys(
 	Ok(entry.addr() + (addr.as_u64() & !((!0u64) << PAGE_BITS)))
 }
 
-pub fn init_guest_mem(mem: &mut [u8]) {
+/// A contiguous range of virtual memory mapped with the same flags.
+struct Mapping {
+	virt: u64,
+	phys: u64,
+	len: u64,
+	flags: PageTableFlags,
+}
+
+/// Returns a human-readable dump of the page tables with the PML4 at `root`.
+///
+/// Each line describes a contiguous virtual range (inclusive), the physical address it maps to
+/// and the flags of the leaf entries, e.g.
+/// `0x0000000000000000-0x000000003fffffff -> 0x0000000000000000 PageTableFlags(PRESENT | WRITABLE | HUGE_PAGE)`.
+/// Tables that lie outside of the guest memory are reported instead of being walked.
+pub fn dump_page_tables(mem: &MmapMemory, root: GuestPhysAddr) -> String {
+	/// Size of the region mapped by an entry on each level, with the PML4 being level 3.
+	const ENTRY_SIZE: [u64; 4] = [1 << 12, 1 << 21, 1 << 30, 1 << 39];
+
+	fn walk(
+		mem: &MmapMemory,
+		table: GuestPhysAddr,
+		level: usize,
+		virt_base: u64,
+		mappings: &mut Vec<Mapping>,
+		out: &mut String,
+	) {
+		let Ok(table_bytes) = (unsafe { mem.slice_at(table, PAGE_SIZE) }) else {
+			writeln!(out, "invalid page table at {:#018x}", table.as_u64()).unwrap();
+			return;
+		};
+		let table_ref = unsafe { &*(table_bytes.as_ptr() as *const PageTable) };
+		for (i, entry) in table_ref.iter().enumerate() {
+			if entry.is_unused() || !entry.flags().contains(PageTableFlags::PRESENT) {
+				continue;
+			}
+			let virt = virt_base + i as u64 * ENTRY_SIZE[level];
+			if level == 0 || entry.flags().contains(PageTableFlags::HUGE_PAGE) {
+				let (phys, len, flags) = (entry.addr().as_u64(), ENTRY_SIZE[level], entry.flags());
+				match mappings.last_mut() {
+					Some(last)
+						if last.virt + last.len == virt
+							&& last.phys + last.len == phys
+							&& last.flags == flags =>
+					{
+						last.len += len
+					}
+					_ => mappings.push(Mapping {
+						virt,
+						phys,
+						len,
+						flags,
+					}),
+				}
+			} else {
+				walk(mem, entry.addr(), level - 1, virt, mappings, out);
+			}
+		}
+	}
+
+	/// Sign-extends a 48 bit virtual address into its canonical form.
+	fn canonical(virt: u64) -> u64 {
+		(((virt << 16) as i64) >> 16) as u64
+	}
+
+	let mut mappings = Vec::new();
+	let mut out = String::new();
+	walk(mem, root, 3, 0, &mut mappings, &mut out);
+	for mapping in mappings {
+		writeln!(
+			out,
+			"{:#018x}-{:#018x} -> {:#018x} {:?}",
+			canonical(mapping.virt),
+			canonical(mapping.virt) + (mapping.len - 1),
+			mapping.phys,
+			mapping.flags
+		)
+		.unwrap();
+	}
+	out
+}
+
+pub fn init_guest_mem(
+	mem: &mut [u8],
+	identity_map_length: u64,
+	read_only_regions: &[Range<GuestPhysAddr>],
+) {
 	// TODO: we should maybe return an error on failure (e.g., the memory is too small)
-	initialize_pagetables(mem);
+	initialize_pagetables(mem, identity_map_length);
+	for region in read_only_regions {
+		write_protect(mem, region.clone());
+	}
+	if cfg!(debug_assertions) {
+		if let Err(err) = verify_pagetables(mem, &RESERVED_REGIONS) {
+			panic!("Invalid boot pagetables: {err}");
+		}
+	}
 }
 
 #[cfg(test)]
@@ -247,11 +476,11 @@ mod tests {
 		let cpuid = raw_cpuid::CpuId::new();
 		let has_tsc = cpuid
 			.get_feature_info()
-			.map_or(false, |finfo| finfo.has_tsc());
+			.is_some_and(|finfo| finfo.has_tsc());
 
 		let has_invariant_tsc = cpuid
 			.get_advanced_power_mgmt_info()
-			.map_or(false, |apm_info| apm_info.has_invariant_tsc());
+			.is_some_and(|apm_info| apm_info.has_invariant_tsc());
 
 		let tsc_frequency_hz = cpuid.get_tsc_info().map(|tinfo| {
 			if tinfo.tsc_frequency().is_some() {
@@ -319,10 +548,30 @@ mod tests {
 		assert!(freq < 10000); //More than 10Ghz is probably wrong
 	}
 
+	#[test]
+	fn test_detect_cpu_freq_fallback() {
+		// A CPU without any cpuid leaves forces the measurement of the TSC
+		let cpuid = CpuId::with_cpuid_reader(|_, _| raw_cpuid::CpuIdResult {
+			eax: 0,
+			ebx: 0,
+			ecx: 0,
+			edx: 0,
+		});
+		assert!(detect_freq_from_cpuid(&cpuid).is_err());
+		assert!(detect_freq_from_cpuid_hypervisor_info(&cpuid).is_err());
+
+		let freq = detect_cpu_freq(&cpuid).unwrap();
+		assert!(freq > 0);
+		assert!(freq < 10000); //More than 10Ghz is probably wrong
+	}
+
 	#[test]
 	fn test_pagetable_initialization() {
 		let mut mem: Vec<u8> = vec![0; MIN_PHYSMEM_SIZE];
-		initialize_pagetables((&mut mem[0..MIN_PHYSMEM_SIZE]).try_into().unwrap());
+		initialize_pagetables(
+			(&mut mem[0..MIN_PHYSMEM_SIZE]).try_into().unwrap(),
+			MAX_IDENTITY_MAP_LENGTH,
+		);
 
 		// Test pagetable setup
 		let addr_pdpte = u64::from_le_bytes(
@@ -371,7 +620,10 @@ mod tests {
 	#[test]
 	fn test_virt_to_phys() {
 		let mem = MmapMemory::new(0, MIN_PHYSMEM_SIZE * 2, GuestPhysAddr::new(0), true, true);
-		initialize_pagetables(unsafe { mem.as_slice_mut() }.try_into().unwrap());
+		initialize_pagetables(
+			unsafe { mem.as_slice_mut() }.try_into().unwrap(),
+			MAX_IDENTITY_MAP_LENGTH,
+		);
 
 		// Get the address of the first entry in PML4 (the address of the PML4 itself)
 		let virt_addr = GuestVirtAddr::new(0xFFFFFFFFFFFFF000);
@@ -402,4 +654,96 @@ mod tests {
 			)
 		);
 	}
+
+	#[test]
+	fn test_dump_page_tables() {
+		let mem = MmapMemory::new(0, MIN_PHYSMEM_SIZE * 2, GuestPhysAddr::new(0), false, false);
+		initialize_pagetables(
+			unsafe { mem.as_slice_mut() }.try_into().unwrap(),
+			MAX_IDENTITY_MAP_LENGTH,
+		);
+		let dump = dump_page_tables(&mem, BOOT_PML4);
+
+		// The first GiB is identity mapped with 2 MiB pages
+		assert!(dump.lines().any(|line| line
+			== "0x0000000000000000-0x000000003fffffff -> 0x0000000000000000 PageTableFlags(PRESENT | WRITABLE | HUGE_PAGE)"));
+		// The recursive PML4 entry maps the PML4 itself
+		assert!(dump.lines().any(|line| line
+			== "0xfffffffffffff000-0xffffffffffffffff -> 0x0000000000010000 PageTableFlags(PRESENT | WRITABLE)"));
+	}
+
+	#[test]
+	fn test_write_protect() {
+		let mem = MmapMemory::new(0, MIN_PHYSMEM_SIZE * 2, GuestPhysAddr::new(0), false, false);
+		init_guest_mem(
+			unsafe { mem.as_slice_mut() }.try_into().unwrap(),
+			MAX_IDENTITY_MAP_LENGTH,
+			&[GuestPhysAddr::new(0x40_0000)..GuestPhysAddr::new(0x80_0000)],
+		);
+
+		let dump = dump_page_tables(&mem, BOOT_PML4);
+		assert!(dump.lines().any(|line| line
+			== "0x0000000000000000-0x00000000003fffff -> 0x0000000000000000 PageTableFlags(PRESENT | WRITABLE | HUGE_PAGE)"));
+		assert!(dump.lines().any(|line| line
+			== "0x0000000000400000-0x00000000007fffff -> 0x0000000000400000 PageTableFlags(PRESENT | HUGE_PAGE)"));
+		assert!(dump.lines().any(|line| line
+			== "0x0000000000800000-0x000000003fffffff -> 0x0000000000800000 PageTableFlags(PRESENT | WRITABLE | HUGE_PAGE)"));
+	}
+
+	#[test]
+	fn test_verify_pagetables() {
+		let mut mem: Vec<u8> = vec![0; MIN_PHYSMEM_SIZE];
+		initialize_pagetables(&mut mem, MAX_IDENTITY_MAP_LENGTH);
+		assert_eq!(verify_pagetables(&mem, &RESERVED_REGIONS), Ok(()));
+
+		// A boot structure placed on top of the pagetables
+		let overlapping = [
+			("GDT", 0x1000..0x2000),
+			("boot info", 0x11000..0x12000),
+			("page tables", 0x10000..0x13000),
+		];
+		assert_eq!(
+			verify_pagetables(&mem, &overlapping),
+			Err(PagetableLayoutError::Overlap(
+				"boot info".to_string(),
+				"page tables".to_string()
+			))
+		);
+
+		let beyond = [("boot info", 0x13000..0x14000)];
+		assert_eq!(
+			verify_pagetables(&mem, &beyond),
+			Err(PagetableLayoutError::OutOfMemory("boot info".to_string()))
+		);
+
+		// A PDE entry that doesn't identity map its page
+		let entry = BOOT_PDE.as_u64() as usize + 3 * 8;
+		let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::HUGE_PAGE;
+		mem[entry..entry + 8].copy_from_slice(&(0x20_0000 | flags.bits()).to_le_bytes());
+		assert_eq!(
+			verify_pagetables(&mem, &RESERVED_REGIONS),
+			Err(PagetableLayoutError::InvalidEntry {
+				table: "PDE",
+				index: 3,
+				expected: 0x60_0000,
+				found: 0x20_0000,
+			})
+		);
+	}
+
+	#[test]
+	fn test_reduced_identity_map() {
+		let mem = MmapMemory::new(0, MIN_PHYSMEM_SIZE * 2, GuestPhysAddr::new(0), false, false);
+		initialize_pagetables(
+			unsafe { mem.as_slice_mut() }.try_into().unwrap(),
+			4 * Page::<Size2MiB>::SIZE,
+		);
+
+		let dump = dump_page_tables(&mem, BOOT_PML4);
+		assert!(dump.lines().any(|line| line
+			== "0x0000000000000000-0x00000000007fffff -> 0x0000000000000000 PageTableFlags(PRESENT | WRITABLE | HUGE_PAGE)"));
+		assert!(virt_to_phys(GuestVirtAddr::new(0x7f_ffff), &mem, BOOT_PML4).is_ok());
+		assert!(virt_to_phys(GuestVirtAddr::new(0x80_0000), &mem, BOOT_PML4).is_err());
+		assert!(virt_to_phys(GuestVirtAddr::new(0x3fff_ffff), &mem, BOOT_PML4).is_err());
+	}
 }
@@ -0,0 +1,85 @@
+//! Read-only file trees the guest sees, unpacked from tar archives on the host.
+
+use std::{
+	fs::File,
+	io::{self, Read},
+	path::{Component, Path, PathBuf},
+};
+
+/// Returns the regular files of the tar archive at `archive` as `(guest_path, contents)`, with
+/// their paths inside the archive placed below `guest_dir`.
+///
+/// Entries whose paths would leave `guest_dir`, e.g., via `..`, are skipped. Other entries, such
+/// as directories and links, are implied by or not supported for virtual files and are skipped
+/// as well.
+pub(crate) fn archive_files(
+	archive: &Path,
+	guest_dir: &Path,
+) -> io::Result<Vec<(PathBuf, Vec<u8>)>> {
+	let mut files = Vec::new();
+	for entry in tar::Archive::new(File::open(archive)?).entries()? {
+		let mut entry = entry?;
+		if !entry.header().entry_type().is_file() {
+			continue;
+		}
+		let path = entry.path()?.into_owned();
+		if !path
+			.components()
+			.all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+		{
+			warn!(
+				"Skipping archive entry {} outside of the archive",
+				path.display()
+			);
+			continue;
+		}
+		let mut content = Vec::with_capacity(entry.size() as usize);
+		entry.read_to_end(&mut content)?;
+		let relative = path
+			.components()
+			.filter(|component| *component != Component::CurDir)
+			.collect::<PathBuf>();
+		files.push((guest_dir.join(relative), content));
+	}
+	Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_archive_files() {
+		let dir = assert_fs::TempDir::new().unwrap();
+		let archive = dir.path().join("fixtures.tar");
+		let mut builder = tar::Builder::new(File::create(&archive).unwrap());
+		let mut append = |path: &str, content: &[u8]| {
+			let mut header = tar::Header::new_gnu();
+			header.set_size(content.len() as u64);
+			header.set_mode(0o644);
+			header.set_cksum();
+			builder.append_data(&mut header, path, content).unwrap();
+		};
+		append("data/input.txt", b"fixture");
+		append("./empty", b"");
+		// Paths leaving the archive can't be added with `append_data`, so write the raw header.
+		let mut header = tar::Header::new_old();
+		header.as_old_mut().name[..9].copy_from_slice(b"../escape");
+		header.set_size(0);
+		header.set_cksum();
+		builder.append(&header, io::empty()).unwrap();
+		builder.into_inner().unwrap();
+
+		let files = archive_files(&archive, Path::new("/fixtures")).unwrap();
+		assert_eq!(
+			files,
+			[
+				(
+					PathBuf::from("/fixtures/data/input.txt"),
+					b"fixture".to_vec()
+				),
+				(PathBuf::from("/fixtures/empty"), Vec::new()),
+			]
+		);
+	}
+}
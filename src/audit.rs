@@ -0,0 +1,56 @@
+//! A log of the file system operations the guest attempted, e.g., for sandboxing compliance.
+//!
+//! Each operation is appended as a line of the form
+//! `<timestamp> op=<operation> guest=<guest path> host=<host path> result=<result>`, where the
+//! timestamp is in seconds since the Unix epoch, the paths are quoted and the result is the
+//! negated error number if the operation failed. Operations whose host path is unknown, e.g.,
+//! because they were rejected before resolving it, are logged with `host=-`.
+
+use std::{
+	fs::{File, OpenOptions},
+	io::{self, Write},
+	path::Path,
+	sync::Mutex,
+	time::SystemTime,
+};
+
+#[derive(Debug)]
+pub(crate) struct AuditLog {
+	file: Mutex<File>,
+}
+
+impl AuditLog {
+	/// Opens the log at `path`, appending to it if it exists.
+	pub(crate) fn open(path: &Path) -> io::Result<Self> {
+		let file = OpenOptions::new().create(true).append(true).open(path)?;
+		Ok(Self {
+			file: Mutex::new(file),
+		})
+	}
+
+	/// Appends the operation `op` on `guest_path` with its `result` to the log.
+	pub(crate) fn record(
+		&self,
+		op: &str,
+		guest_path: &Path,
+		host_path: Option<&Path>,
+		result: i32,
+	) {
+		let timestamp = SystemTime::now()
+			.duration_since(SystemTime::UNIX_EPOCH)
+			.unwrap_or_default();
+		let host_path = match host_path {
+			Some(path) => format!("{path:?}"),
+			None => "-".to_string(),
+		};
+		let line = format!(
+			"{}.{:06} op={op} guest={guest_path:?} host={host_path} result={result}\n",
+			timestamp.as_secs(),
+			timestamp.subsec_micros()
+		);
+		// Write the line at once, so that concurrent vCPUs don't interleave their records.
+		if let Err(err) = self.file.lock().unwrap().write_all(line.as_bytes()) {
+			warn!("Unable to write to the audit log: {err}");
+		}
+	}
+}
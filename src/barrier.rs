@@ -0,0 +1,133 @@
+//! Synchronization of the guest with a harness on the host.
+
+use std::{
+	sync::{Condvar, Mutex},
+	time::Duration,
+};
+
+/// A one-shot barrier the guest waits at via the `Barrier` hypercall until the host releases it.
+///
+/// Once released, the barrier stays open and later waits return immediately.
+#[derive(Debug)]
+pub(crate) struct GuestBarrier {
+	state: Mutex<BarrierState>,
+	condvar: Condvar,
+	/// Delay after which a waiting guest is released without the host's involvement.
+	release_after: Option<Duration>,
+}
+
+#[derive(Debug, Default)]
+struct BarrierState {
+	/// Number of vCPUs waiting at the barrier.
+	waiting: usize,
+	released: bool,
+	/// Set when the VM is stopped while vCPUs may be waiting.
+	aborted: bool,
+}
+
+impl GuestBarrier {
+	pub(crate) fn new(release_after: Option<Duration>) -> Self {
+		Self {
+			state: Default::default(),
+			condvar: Condvar::new(),
+			release_after,
+		}
+	}
+
+	/// Blocks until the barrier is released.
+	///
+	/// Returns `false` if the barrier was aborted because the VM is stopping.
+	pub(crate) fn wait(&self) -> bool {
+		let mut state = self.state.lock().unwrap();
+		state.waiting += 1;
+		let closed = |state: &mut BarrierState| !state.released && !state.aborted;
+		state = match self.release_after {
+			Some(delay) => {
+				let (mut state, timeout) = self
+					.condvar
+					.wait_timeout_while(state, delay, closed)
+					.unwrap();
+				if timeout.timed_out() {
+					state.released = true;
+					self.condvar.notify_all();
+				}
+				state
+			}
+			None => self.condvar.wait_while(state, closed).unwrap(),
+		};
+		state.waiting -= 1;
+		!state.aborted
+	}
+
+	/// Releases the guest from the barrier.
+	pub(crate) fn release(&self) {
+		self.state.lock().unwrap().released = true;
+		self.condvar.notify_all();
+	}
+
+	/// Wakes up the vCPUs waiting at the barrier, so that the VM can be stopped.
+	pub(crate) fn abort(&self) {
+		self.state.lock().unwrap().aborted = true;
+		self.condvar.notify_all();
+	}
+
+	/// Returns whether the barrier was aborted because the VM is stopping.
+	pub(crate) fn is_aborted(&self) -> bool {
+		self.state.lock().unwrap().aborted
+	}
+
+	/// Returns whether a vCPU is waiting at the barrier.
+	pub(crate) fn is_waiting(&self) -> bool {
+		self.state.lock().unwrap().waiting > 0
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{sync::Arc, thread, time::Instant};
+
+	use super::*;
+
+	fn wait_in_background(barrier: &Arc<GuestBarrier>) -> thread::JoinHandle<bool> {
+		let waiter = {
+			let barrier = barrier.clone();
+			thread::spawn(move || barrier.wait())
+		};
+		while !barrier.is_waiting() {
+			thread::yield_now();
+		}
+		waiter
+	}
+
+	#[test]
+	fn test_release() {
+		let barrier = Arc::new(GuestBarrier::new(None));
+		let waiter = wait_in_background(&barrier);
+		assert!(!waiter.is_finished());
+
+		barrier.release();
+		assert!(waiter.join().unwrap());
+		assert!(!barrier.is_waiting());
+
+		// The barrier stays open
+		assert!(barrier.wait());
+	}
+
+	#[test]
+	fn test_abort() {
+		let barrier = Arc::new(GuestBarrier::new(None));
+		let waiter = wait_in_background(&barrier);
+		barrier.abort();
+		assert!(!waiter.join().unwrap());
+		assert!(barrier.is_aborted());
+	}
+
+	#[test]
+	fn test_release_after() {
+		let delay = Duration::from_millis(50);
+		let barrier = GuestBarrier::new(Some(delay));
+		let start = Instant::now();
+		assert!(barrier.wait());
+		assert!(start.elapsed() >= delay);
+	}
+}
@@ -1,16 +1,24 @@
 #![warn(rust_2018_idioms)]
 
 use std::{
-	ffi::OsString, iter, num::ParseIntError, ops::RangeInclusive, path::PathBuf, process,
+	ffi::OsString,
+	iter,
+	net::SocketAddr,
+	num::{NonZeroU32, ParseIntError},
+	ops::RangeInclusive,
+	path::PathBuf,
+	process,
 	str::FromStr,
 };
 
+use byte_unit::Byte;
 use clap::{error::ErrorKind, Command, CommandFactory, Parser};
 use core_affinity::CoreId;
 use either::Either;
+use log::LevelFilter;
 use thiserror::Error;
 use uhyvelib::{
-	params::{CpuCount, GuestMemorySize, Params},
+	params::{ArchiveMount, CpuCount, GuestMemorySize, HugePageSize, Params, UnixSocketMapping},
 	vm::UhyveVm,
 };
 
@@ -58,11 +66,129 @@ struct Args {
 	#[cfg(target_os = "linux")]
 	gdb_port: Option<u16>,
 
+	/// Host directory to which the guest's file system is confined
+	///
+	/// Paths opened or unlinked by the guest, including absolute ones, are resolved beneath this directory and must not escape it.
+	#[clap(long, env = "HERMIT_HOST_ROOT")]
+	host_root: Option<PathBuf>,
+
+	/// Write quota for host files
+	///
+	/// Limits the total number of bytes the guest may write to host files. Writes exceeding the quota fail. Output to stdout and stderr is not limited.
+	#[clap(long)]
+	write_quota: Option<Byte>,
+
+	/// Maximum size of a single write
+	///
+	/// Truncates writes of the guest to host files and the standard streams to this size. The guest sees a short write and writes the remainder separately.
+	#[clap(long)]
+	max_write_size: Option<Byte>,
+
+	/// Maximum number of open files
+	///
+	/// Limits the number of host files the guest may have open at the same time. Further opens fail with `EMFILE`.
+	#[clap(long)]
+	max_open_files: Option<usize>,
+
+	/// Maximum number of created files
+	///
+	/// Limits the number of host files the guest may create. Further creations fail with `ENOSPC`.
+	#[clap(long)]
+	max_created_files: Option<usize>,
+
+	/// Forbid file creation
+	///
+	/// Prevents the guest from creating host files. Opening nonexisting files for creation fails with `ENOENT`.
+	#[clap(long)]
+	disable_file_creation: bool,
+
+	/// Audit log path
+	///
+	/// Appends a line with the guest path, the host path and the result of each file system operation of the guest to the given file, including rejected ones.
+	#[clap(long)]
+	audit_log: Option<PathBuf>,
+
+	/// Guest hostname
+	///
+	/// The hostname the guest sees. Defaults to `hermit`.
+	#[clap(long)]
+	guest_hostname: Option<String>,
+
+	/// UNIX socket mapping
+	///
+	/// Maps a host UNIX socket into the guest as `unix:<host path>:<guest path>`. Opening the guest path connects to the socket, bypassing the host root. Can be given multiple times.
+	#[clap(long)]
+	unix_socket: Vec<UnixSocketMapping>,
+
+	/// Archive mount
+	///
+	/// Serves the files of a tar archive read-only to the guest as `<archive>:<guest directory>`. Can be given multiple times.
+	#[clap(long)]
+	archive: Vec<ArchiveMount>,
+
+	/// Expected kernel SHA-256 digest
+	///
+	/// Refuses to boot the kernel if the SHA-256 digest of the kernel file differs from the given hex string.
+	#[clap(long)]
+	kernel_sha256: Option<String>,
+
+	/// Boot stack size
+	///
+	/// Size of the stack the kernel boots on. Defaults to 32 KiB.
+	#[clap(long)]
+	boot_stack_size: Option<Byte>,
+
+	/// Hypercall rate limit
+	///
+	/// Limits the number of hypercalls the guest may issue per second. vCPUs exceeding the limit are paused until the next second.
+	#[clap(long)]
+	hypercall_rate_limit: Option<NonZeroU32>,
+
+	/// Serial output log prefix
+	///
+	/// Logs the kernel's serial output line by line with the given prefix at info level instead of printing it to stdout. Requires `RUST_LOG` to include `guest=info`.
+	#[clap(long)]
+	log_serial_prefix: Option<String>,
+
+	/// Serial output TCP address
+	///
+	/// Streams the kernel's serial output to a TCP client connecting to the given address, e.g., `127.0.0.1:4000`, instead of printing it to stdout. The output is buffered while no client is connected.
+	#[clap(long, conflicts_with = "log_serial_prefix")]
+	serial_tcp: Option<SocketAddr>,
+
+	/// Maximum guest log level
+	///
+	/// Allows the guest to change the level of its own log messages up to the given level, e.g., `debug`, to trace a problematic phase. Uhyve's own log level is not affected.
+	#[clap(long)]
+	max_guest_log_level: Option<LevelFilter>,
+
+	/// Number of runs
+	///
+	/// Runs the kernel the given number of times and prints statistics of the runs, e.g., for microbenchmarking.
+	#[clap(long)]
+	repeat: Option<NonZeroU32>,
+
+	/// Count guest instructions and cycles
+	///
+	/// Reads the retired instructions and cycles of the guest from the virtual PMU and includes them in the statistics of `--repeat`. Requires a host CPU with fixed performance counters.
+	#[clap(long)]
+	#[cfg(target_os = "linux")]
+	perf_counters: bool,
+
+	/// Core dump path
+	///
+	/// Writes an ELF core dump with the guest memory and the registers of the faulting vCPU to the given path if the guest crashes.
+	#[clap(long)]
+	#[cfg(target_os = "linux")]
+	core_dump: Option<PathBuf>,
+
 	/// The kernel to execute
 	#[clap(value_parser)]
 	kernel: PathBuf,
 
 	/// Arguments to forward to the kernel
+	///
+	/// Arguments after `--` are forwarded to the application.
 	#[clap(value_parser)]
 	kernel_args: Vec<OsString>,
 }
@@ -73,6 +199,12 @@ struct MemoryArgs {
 	#[clap(short = 'm', long, default_value_t, env = "HERMIT_MEMORY_SIZE")]
 	memory_size: GuestMemorySize,
 
+	/// Maximum guest RAM size
+	///
+	/// Maps guest RAM up to this size, of which the guest initially only uses the RAM size. The guest can commit and release the remaining memory at runtime.
+	#[clap(long)]
+	max_memory_size: Option<GuestMemorySize>,
+
 	/// Transparent Hugepages
 	///
 	/// Advise the kernel to enable Transparent Hugepages [THP] on the virtual RAM.
@@ -90,6 +222,24 @@ struct MemoryArgs {
 	#[clap(long)]
 	#[cfg(target_os = "linux")]
 	ksm: bool,
+
+	/// Explicit hugepages
+	///
+	/// Back the virtual RAM with explicitly allocated huge pages [hugetlbpage] of the given size (`2MiB` or `1GiB`).
+	/// The pages have to be reserved beforehand, e.g., via `/proc/sys/vm/nr_hugepages`.
+	/// Uhyve falls back to regular pages if the allocation fails.
+	///
+	/// [hugetlbpage]: https://www.kernel.org/doc/html/latest/admin-guide/mm/hugetlbpage.html
+	#[clap(long)]
+	#[cfg(target_os = "linux")]
+	hugepages: Option<HugePageSize>,
+
+	/// Lock the virtual RAM
+	///
+	/// Locks the virtual RAM into host memory, so that it is never swapped out. The memory size must not exceed the limit of locked memory (`ulimit -l`).
+	#[clap(long)]
+	#[cfg(target_os = "linux")]
+	lock_memory: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -185,6 +335,13 @@ struct CpuArgs {
 	#[cfg(target_os = "linux")]
 	pit: bool,
 
+	/// TSC frequency scale
+	///
+	/// Runs the guest's time stamp counter at the given multiple of the host's frequency, e.g., `2` to make the guest's clock run twice as fast, while the guest is told the unscaled frequency. Requires TSC scaling support of the host CPU.
+	#[clap(long)]
+	#[cfg(target_os = "linux")]
+	tsc_scale: Option<f64>,
+
 	/// Bind guest vCPUs to host cpus
 	///
 	/// A list of host CPU numbers onto which the guest vCPUs should be bound to obtain performance benefits.
@@ -233,38 +390,120 @@ impl From<Args> for Params {
 			memory_args:
 				MemoryArgs {
 					memory_size,
+					max_memory_size,
 					#[cfg(target_os = "linux")]
 					thp,
 					#[cfg(target_os = "linux")]
 					ksm,
+					#[cfg(target_os = "linux")]
+					hugepages,
+					#[cfg(target_os = "linux")]
+					lock_memory,
 				},
 			cpu_args:
 				CpuArgs {
 					cpu_count,
 					#[cfg(target_os = "linux")]
 					pit,
+					#[cfg(target_os = "linux")]
+					tsc_scale,
 					affinity: _,
 				},
 			#[cfg(target_os = "linux")]
 			gdb_port,
+			host_root,
+			write_quota,
+			max_write_size,
+			max_open_files,
+			max_created_files,
+			disable_file_creation,
+			audit_log,
+			guest_hostname,
+			unix_socket,
+			archive,
+			kernel_sha256,
+			boot_stack_size,
+			hypercall_rate_limit,
+			log_serial_prefix,
+			serial_tcp,
+			max_guest_log_level,
+			repeat: _,
+			#[cfg(target_os = "linux")]
+			perf_counters,
+			#[cfg(target_os = "linux")]
+			core_dump,
 			kernel: _,
-			kernel_args,
+			mut kernel_args,
 		} = args;
+		let app_args = match kernel_args.iter().position(|arg| arg == "--") {
+			Some(separator) => kernel_args.split_off(separator).split_off(1),
+			None => Vec::new(),
+		};
 		Self {
 			verbose,
 			memory_size,
+			max_memory_size,
 			#[cfg(target_os = "linux")]
 			thp,
 			#[cfg(target_os = "linux")]
 			ksm,
+			#[cfg(target_os = "linux")]
+			hugepages,
+			#[cfg(target_os = "linux")]
+			lock_memory,
 			cpu_count,
+			identity_map_length: None,
 			#[cfg(target_os = "linux")]
 			pit,
+			apic_base: None,
+			ioapic_base: None,
+			#[cfg(target_os = "linux")]
+			tsc_scale,
+			#[cfg(target_os = "linux")]
+			cpu_features: None,
+			#[cfg(target_os = "linux")]
+			perf_counters,
+			#[cfg(target_os = "linux")]
+			core_dump,
 			#[cfg(target_os = "linux")]
 			gdb_port,
 			#[cfg(target_os = "macos")]
 			gdb_port: None,
+			host_root,
+			write_quota_bytes: write_quota.map(|quota| quota.as_u64()),
+			max_write_size: max_write_size.map(|size| size.as_u64() as usize),
+			max_open_files,
+			max_created_files,
+			disable_file_creation,
+			audit_log,
+			hypercall_rate_limit,
+			guest_env: None,
+			env_allowlist: Vec::new(),
+			guest_hostname,
+			virtual_files: Vec::new(),
+			priority_bounds: None,
+			unix_sockets: unix_socket,
+			archives: archive,
+			kernel_sha256,
+			boot_stack_size: boot_stack_size.map(|size| size.as_u64()),
+			classic_stack_abi: false,
+			raw_mappings: Vec::new(),
+			read_only_regions: Vec::new(),
+			log_serial_prefix,
+			serial_tcp,
+			structured_output: false,
+			unbuffered_serial: false,
+			flush_on_exit: true,
+			event_hook: None,
+			inherited_fds: Vec::new(),
+			rlimits: Vec::new(),
+			deterministic: false,
+			barrier_timeout: None,
+			timeout: None,
+			max_guest_log_level,
+			panic_exit_code: None,
 			kernel_args,
+			app_args,
 		}
 	}
 }
@@ -279,12 +518,24 @@ fn run_uhyve() -> i32 {
 	let args = Args::parse();
 	let kernel = args.kernel.clone();
 	let affinity = args.cpu_args.clone().get_affinity(&mut app);
+	let repeat = args.repeat;
 	let params = Params::from(args);
 
+	if let Some(runs) = repeat {
+		let (results, stats) = UhyveVm::run_repeated(kernel, params, runs, affinity)
+			.expect("Unable to create VM! Is the hypervisor interface (e.g. KVM) activated?");
+		eprint!("{stats}");
+		return results
+			.iter()
+			.map(|result| result.code)
+			.find(|&code| code != 0)
+			.unwrap_or(0);
+	}
+
 	let vm = UhyveVm::new(kernel, params)
 		.expect("Unable to create VM! Is the hypervisor interface (e.g. KVM) activated?");
 
-	vm.run(affinity)
+	vm.run(affinity).expect("Unable to run the VM").code
 }
 
 fn main() {
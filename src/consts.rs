@@ -1,3 +1,5 @@
+use std::ops::Range;
+
 use uhyve_interface::GuestPhysAddr;
 
 pub const PAGE_SIZE: usize = 0x1000;
@@ -13,7 +15,11 @@ pub const BOOT_PML4: GuestPhysAddr = GuestPhysAddr::new(0x10000);
 pub const BOOT_PGT: GuestPhysAddr = BOOT_PML4;
 pub const BOOT_PDPTE: GuestPhysAddr = GuestPhysAddr::new(0x11000);
 pub const BOOT_PDE: GuestPhysAddr = GuestPhysAddr::new(0x12000);
+/// Maximum and default length of the identity map of the boot pagetables.
+pub const MAX_IDENTITY_MAP_LENGTH: u64 = 0x4000_0000;
 pub const BOOT_INFO_ADDR: GuestPhysAddr = GuestPhysAddr::new(0x9000);
+/// The device tree listing the reserved regions, see [`RESERVED_REGIONS`].
+pub const FDT_ADDR: GuestPhysAddr = GuestPhysAddr::new(0xa000);
 pub const EFER_SCE: u64 = 1; /* System Call Extensions */
 pub const EFER_LME: u64 = 1 << 8; /* Long mode enable */
 pub const EFER_LMA: u64 = 1 << 10; /* Long mode active (read-only) */
@@ -27,3 +33,36 @@ pub const UHYVE_QUEUE_SIZE: usize = 8;
 pub const UHYVE_IRQ_NET: u32 = 11;
 
 pub const GUEST_PAGE_SIZE: u64 = 0x200000; /* 2 MB pages in guest */
+/// The exit code of a VM whose guest reported a panic.
+pub const GUEST_PANIC_EXIT_CODE: i32 = 101;
+/// The hostname reported to the guest if none is configured.
+pub const DEFAULT_GUEST_HOSTNAME: &str = "hermit";
+/// The boot time reported to the guest in deterministic mode (2020-01-01T00:00:00Z), in seconds since the Unix epoch.
+pub const DETERMINISTIC_BOOT_TIME_SECS: u64 = 1_577_836_800;
+/// The CPU frequency reported to the guest in deterministic mode, in MHz.
+pub const DETERMINISTIC_CPU_FREQ_MHZ: u32 = 2000;
+/// The locally administered MAC address of the network device in deterministic mode.
+pub const DETERMINISTIC_MAC_ADDR: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x01];
+
+/// Guest-physical regions that uhyve fills with boot structures, as (name, address range).
+///
+/// They are passed to the guest in the memory reservation block of the device tree at
+/// [`FDT_ADDR`].
+pub const RESERVED_REGIONS: [(&str, Range<u64>); 4] = [
+	(
+		"GDT",
+		BOOT_GDT.as_u64()..BOOT_GDT.as_u64() + PAGE_SIZE as u64,
+	),
+	(
+		"boot info",
+		BOOT_INFO_ADDR.as_u64()..BOOT_INFO_ADDR.as_u64() + PAGE_SIZE as u64,
+	),
+	(
+		"device tree",
+		FDT_ADDR.as_u64()..FDT_ADDR.as_u64() + PAGE_SIZE as u64,
+	),
+	(
+		"page tables",
+		BOOT_PML4.as_u64()..BOOT_PDE.as_u64() + PAGE_SIZE as u64,
+	),
+];
@@ -0,0 +1,396 @@
+//! Tracking of the host file descriptors opened by the guest.
+
+use std::{
+	collections::{hash_map::Entry, HashMap},
+	io,
+	os::fd::RawFd,
+	sync::{
+		atomic::{AtomicU64, AtomicUsize, Ordering},
+		RwLock,
+	},
+};
+
+/// Returns `true` if `fd` is one of the standard streams (stdin, stdout or stderr).
+///
+/// The guest shares these with uhyve, so they are only tracked in an [`FdMap`] if they are
+/// replaced by inherited descriptors.
+pub fn is_standard_stream(fd: RawFd) -> bool {
+	(0..=2).contains(&fd)
+}
+
+/// A host file descriptor the guest has access to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostFd {
+	/// A descriptor opened by or passed into the guest.
+	Tracked(RawFd),
+	/// A standard stream that is shared with uhyve.
+	///
+	/// The guest can't close it, and stdin is read-only.
+	Shared(RawFd),
+}
+
+impl HostFd {
+	pub fn raw(self) -> RawFd {
+		match self {
+			Self::Tracked(fd) | Self::Shared(fd) => fd,
+		}
+	}
+
+	/// Returns `true` if the guest may write to the descriptor.
+	pub fn is_writable(self) -> bool {
+		self != Self::Shared(libc::STDIN_FILENO)
+	}
+}
+
+/// The host file descriptors the guest has access to, indexed by the guest's file descriptors.
+///
+/// File hypercalls are only executed on descriptors contained in this map, so that the guest
+/// can't operate on descriptors belonging to uhyve itself.
+/// Descriptors opened by the guest keep their host number, unless it is taken by an inherited
+/// descriptor (see [`FdMap::inherit`]). Each open of the guest opens the host file anew, so
+/// every guest descriptor has a file offset of its own, even if several refer to the same file.
+/// The map is shared by all vCPUs: lookups only take a read lock, so they don't serialize
+/// concurrent file accesses.
+///
+/// The map also accounts the bytes the guest writes to files against an optional quota and
+/// optionally limits the number of tracked descriptors and of files the guest creates, as well
+/// as the size of individual writes.
+#[derive(Debug, Default)]
+pub struct FdMap {
+	fds: RwLock<HashMap<RawFd, RawFd>>,
+	max_open_files: Option<usize>,
+	write_quota: Option<u64>,
+	bytes_written: AtomicU64,
+	file_creation_disabled: bool,
+	max_created_files: Option<usize>,
+	files_created: AtomicUsize,
+	max_write_size: Option<usize>,
+}
+
+impl FdMap {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Creates a map that limits the cumulative number of bytes written to files to `write_quota`.
+	pub fn with_write_quota(write_quota: Option<u64>) -> Self {
+		Self {
+			write_quota,
+			..Default::default()
+		}
+	}
+
+	/// Limits the number of tracked descriptors, including inherited ones, to `max_open_files`.
+	pub fn with_max_open_files(self, max_open_files: Option<usize>) -> Self {
+		Self {
+			max_open_files,
+			..self
+		}
+	}
+
+	/// Limits the number of files the guest may create to `max_created_files`.
+	pub fn with_max_created_files(self, max_created_files: Option<usize>) -> Self {
+		Self {
+			max_created_files,
+			..self
+		}
+	}
+
+	/// Forbids the guest to create files if `disabled` is set.
+	pub fn with_file_creation_disabled(self, disabled: bool) -> Self {
+		Self {
+			file_creation_disabled: disabled,
+			..self
+		}
+	}
+
+	/// Limits the number of bytes a single write hypercall may write to `max_write_size`.
+	pub fn with_max_write_size(self, max_write_size: Option<usize>) -> Self {
+		Self {
+			max_write_size,
+			..self
+		}
+	}
+
+	/// Returns the number of bytes a write of `len` bytes is truncated to.
+	pub fn write_size(&self, len: usize) -> usize {
+		self.max_write_size.map_or(len, |max| len.min(max))
+	}
+
+	/// Returns `true` if no further descriptors can be tracked.
+	pub fn is_full(&self) -> bool {
+		self.max_open_files
+			.is_some_and(|max_open_files| self.len() >= max_open_files)
+	}
+
+	/// Accounts a write of `len` bytes against the write quota.
+	///
+	/// Returns `false` and accounts nothing if the write would exceed the quota.
+	pub fn reserve_write(&self, len: u64) -> bool {
+		let Some(quota) = self.write_quota else {
+			return true;
+		};
+		self.bytes_written
+			.try_update(Ordering::Relaxed, Ordering::Relaxed, |written| {
+				written.checked_add(len).filter(|&total| total <= quota)
+			})
+			.is_ok()
+	}
+
+	/// Returns `len` bytes accounted by [`FdMap::reserve_write`] that weren't written after all.
+	pub fn release_write(&self, len: u64) {
+		if self.write_quota.is_some() {
+			self.bytes_written.fetch_sub(len, Ordering::Relaxed);
+		}
+	}
+
+	/// Accounts the creation of a file against the limit of created files.
+	///
+	/// Fails with `ENOENT` if file creation is disabled and with `ENOSPC` if the limit is reached.
+	pub fn reserve_creation(&self) -> io::Result<()> {
+		if self.file_creation_disabled {
+			return Err(io::Error::from_raw_os_error(libc::ENOENT));
+		}
+		let Some(max_created_files) = self.max_created_files else {
+			return Ok(());
+		};
+		self.files_created
+			.try_update(Ordering::Relaxed, Ordering::Relaxed, |created| {
+				(created < max_created_files).then_some(created + 1)
+			})
+			.map(|_| ())
+			.map_err(|_| io::Error::from_raw_os_error(libc::ENOSPC))
+	}
+
+	/// Starts tracking the host descriptor `fd` opened by the guest and returns the guest's
+	/// descriptor for it.
+	///
+	/// If the number of `fd` is already taken by an inherited descriptor, `fd` is moved to a free
+	/// number. Fails with `EMFILE` if the limit of open files is reached. On failure, `fd` is
+	/// closed.
+	pub fn insert(&self, mut fd: RawFd) -> io::Result<RawFd> {
+		let mut fds = self.fds.write().unwrap();
+		if self
+			.max_open_files
+			.is_some_and(|max_open_files| fds.len() >= max_open_files)
+		{
+			unsafe { libc::close(fd) };
+			return Err(io::Error::from_raw_os_error(libc::EMFILE));
+		}
+		while fds.contains_key(&fd) {
+			let moved = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, fd + 1) };
+			let err = io::Error::last_os_error();
+			unsafe { libc::close(fd) };
+			if moved < 0 {
+				return Err(err);
+			}
+			fd = moved;
+		}
+		fds.insert(fd, fd);
+		Ok(fd)
+	}
+
+	/// Makes the host descriptor `host_fd` available to the guest as `guest_fd`.
+	///
+	/// The map tracks a duplicate of `host_fd`, so the guest closing `guest_fd` leaves `host_fd`
+	/// open. This also allows to replace the guest's standard streams.
+	pub fn inherit(&self, guest_fd: RawFd, host_fd: RawFd) -> io::Result<()> {
+		if guest_fd < 0 {
+			return Err(io::Error::from_raw_os_error(libc::EBADF));
+		}
+		let mut fds = self.fds.write().unwrap();
+		let Entry::Vacant(entry) = fds.entry(guest_fd) else {
+			return Err(io::Error::from_raw_os_error(libc::EBUSY));
+		};
+		let fd = unsafe { libc::fcntl(host_fd, libc::F_DUPFD_CLOEXEC, 0) };
+		if fd < 0 {
+			return Err(io::Error::last_os_error());
+		}
+		entry.insert(fd);
+		Ok(())
+	}
+
+	/// Returns `true` if `fd` is a tracked guest descriptor.
+	pub fn contains(&self, fd: RawFd) -> bool {
+		self.fds.read().unwrap().contains_key(&fd)
+	}
+
+	/// Returns the host descriptor for the guest descriptor `fd`.
+	///
+	/// Standard streams that weren't replaced by inherited descriptors are shared with uhyve.
+	/// All file hypercalls look up descriptors through this method, so they treat the standard
+	/// streams alike. Only writing to stdin is refused, see [`HostFd::is_writable`].
+	pub fn get(&self, fd: RawFd) -> Option<HostFd> {
+		match self.fds.read().unwrap().get(&fd) {
+			Some(&host_fd) => Some(HostFd::Tracked(host_fd)),
+			None if is_standard_stream(fd) => Some(HostFd::Shared(fd)),
+			None => None,
+		}
+	}
+
+	/// Returns the raw host descriptor for the guest descriptor `fd`, see [`FdMap::get`].
+	pub fn host_fd(&self, fd: RawFd) -> Option<RawFd> {
+		self.get(fd).map(HostFd::raw)
+	}
+
+	/// Stops tracking the guest descriptor `fd`. Returns the host descriptor if it was tracked.
+	pub fn remove(&self, fd: RawFd) -> Option<RawFd> {
+		self.fds.write().unwrap().remove(&fd)
+	}
+
+	/// Calls `f` with every tracked host descriptor.
+	///
+	/// The descriptors stay tracked during the calls, so `f` must not modify the map.
+	pub fn for_each_host_fd(&self, mut f: impl FnMut(RawFd)) {
+		for &host_fd in self.fds.read().unwrap().values() {
+			f(host_fd);
+		}
+	}
+
+	/// Closes all tracked host descriptors and stops tracking them.
+	pub fn close_all(&self) {
+		for (_, host_fd) in self.fds.write().unwrap().drain() {
+			unsafe { libc::close(host_fd) };
+		}
+	}
+
+	/// Returns the number of tracked file descriptors.
+	pub fn len(&self) -> usize {
+		self.fds.read().unwrap().len()
+	}
+
+	/// Returns `true` if no file descriptors are tracked.
+	pub fn is_empty(&self) -> bool {
+		self.fds.read().unwrap().is_empty()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{io::Read, os::fd::IntoRawFd, sync::Arc, thread};
+
+	use super::*;
+
+	#[test]
+	fn test_concurrent_access() {
+		const THREADS: i32 = 8;
+		const FDS_PER_THREAD: i32 = 1000;
+
+		let fdmap = Arc::new(FdMap::new());
+		let threads = (0..THREADS)
+			.map(|i| {
+				let fdmap = fdmap.clone();
+				thread::spawn(move || {
+					let fds = i * FDS_PER_THREAD..(i + 1) * FDS_PER_THREAD;
+					for fd in fds.clone() {
+						assert_eq!(fdmap.insert(fd).unwrap(), fd);
+						assert!(fdmap.contains(fd));
+					}
+					for fd in fds.clone().step_by(2) {
+						assert_eq!(fdmap.remove(fd), Some(fd));
+						assert!(!fdmap.contains(fd));
+					}
+				})
+			})
+			.collect::<Vec<_>>();
+		for thread in threads {
+			thread.join().unwrap();
+		}
+
+		assert_eq!(fdmap.len(), (THREADS * FDS_PER_THREAD / 2) as usize);
+		for fd in 0..THREADS * FDS_PER_THREAD {
+			assert_eq!(fdmap.contains(fd), fd % 2 == 1);
+		}
+	}
+
+	#[test]
+	fn test_write_quota() {
+		let fdmap = FdMap::new();
+		assert!(fdmap.reserve_write(u64::MAX));
+
+		let fdmap = FdMap::with_write_quota(Some(16));
+		assert!(fdmap.reserve_write(10));
+		assert!(!fdmap.reserve_write(7));
+		assert!(fdmap.reserve_write(6));
+		assert!(!fdmap.reserve_write(1));
+		assert!(fdmap.reserve_write(0));
+		// Released bytes can be written again
+		fdmap.release_write(3);
+		assert!(fdmap.reserve_write(3));
+		assert!(!fdmap.reserve_write(1));
+	}
+
+	#[test]
+	fn test_max_open_files() {
+		let fdmap = FdMap::new().with_max_open_files(Some(2));
+		let open = || unsafe { libc::dup(2) };
+		let first = fdmap.insert(open()).unwrap();
+		fdmap.insert(open()).unwrap();
+		assert!(fdmap.is_full());
+		assert_eq!(
+			fdmap.insert(open()).unwrap_err().raw_os_error(),
+			Some(libc::EMFILE)
+		);
+
+		unsafe { libc::close(fdmap.remove(first).unwrap()) };
+		assert!(!fdmap.is_full());
+		fdmap.insert(open()).unwrap();
+		fdmap.close_all();
+	}
+
+	#[test]
+	fn test_close_all() {
+		let (mut reader, writer) = std::io::pipe().unwrap();
+		let fdmap = FdMap::new();
+		let fd = fdmap.insert(writer.into_raw_fd()).unwrap();
+		fdmap.inherit(0, fd).unwrap();
+
+		fdmap.close_all();
+		assert!(fdmap.is_empty());
+		// Both write ends of the pipe are closed
+		assert_eq!(reader.read(&mut [0; 1]).unwrap(), 0);
+	}
+
+	#[test]
+	fn test_inherit() {
+		let mut pipe = [0; 2];
+		assert_eq!(unsafe { libc::pipe(pipe.as_mut_ptr()) }, 0);
+		let [read_end, write_end] = pipe;
+
+		let fdmap = FdMap::new();
+		fdmap.inherit(0, read_end).unwrap();
+		fdmap.inherit(write_end, write_end).unwrap();
+		assert_eq!(
+			fdmap.inherit(0, read_end).unwrap_err().raw_os_error(),
+			Some(libc::EBUSY)
+		);
+		assert_eq!(fdmap.host_fd(1), Some(1));
+		assert_eq!(fdmap.host_fd(write_end + 1), None);
+
+		// The map tracks duplicates, closing them leaves the original descriptors open
+		let host_read_end = fdmap.remove(0).unwrap();
+		assert_ne!(host_read_end, read_end);
+		assert_eq!(unsafe { libc::close(host_read_end) }, 0);
+		assert_eq!(
+			unsafe { libc::write(write_end, b"x".as_ptr().cast(), 1) },
+			1
+		);
+
+		// A descriptor opened by the guest doesn't shadow an inherited one with the same number
+		let opened = unsafe { libc::dup(read_end) };
+		fdmap.inherit(opened, read_end).unwrap();
+		let host_fd = fdmap.host_fd(opened).unwrap();
+		let guest_fd = fdmap.insert(opened).unwrap();
+		assert_ne!(guest_fd, opened);
+		assert_eq!(fdmap.host_fd(opened), Some(host_fd));
+		assert_eq!(fdmap.host_fd(guest_fd), Some(guest_fd));
+
+		for fd in [opened, guest_fd, write_end] {
+			unsafe { libc::close(fdmap.remove(fd).unwrap()) };
+		}
+		unsafe {
+			libc::close(write_end);
+			libc::close(read_end);
+		}
+	}
+}
@@ -0,0 +1,359 @@
+//! A minimal flattened device tree that describes the guest memory and the regions of it the
+//! guest must not use.
+//!
+//! The reserved regions are listed in the memory reservation block of the device tree, so that
+//! the guest's allocator can avoid them without hardcoding uhyve's memory layout. The tree
+//! follows version 17 of the devicetree specification.
+//!
+//! [`DeviceTree`] reads a flattened device tree back, e.g., to inspect the one handed to the
+//! guest via [`UhyveVm::device_tree`](crate::vm::UhyveVm::device_tree).
+
+use std::{ffi::CStr, ops::Range};
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_VERSION: u32 = 17;
+const FDT_LAST_COMP_VERSION: u32 = 16;
+const FDT_HEADER_SIZE: usize = 40;
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+const FDT_END: u32 = 9;
+
+/// Returns a device tree with the guest `memory` and the `reserved` regions within it.
+pub(crate) fn reserved_regions_fdt(memory: Range<u64>, reserved: &[Range<u64>]) -> Vec<u8> {
+	let mut mem_rsvmap = Vec::new();
+	for range in reserved.iter().chain([&(0..0)]) {
+		mem_rsvmap.extend_from_slice(&range.start.to_be_bytes());
+		mem_rsvmap.extend_from_slice(&(range.end - range.start).to_be_bytes());
+	}
+
+	let mut tree = TreeWriter::default();
+	tree.begin_node("");
+	tree.property("#address-cells", &2u32.to_be_bytes());
+	tree.property("#size-cells", &2u32.to_be_bytes());
+	// Kernels commonly expect the chosen node to be present.
+	tree.begin_node("chosen");
+	tree.end_node();
+	tree.begin_node(&format!("memory@{:x}", memory.start));
+	tree.property("device_type", b"memory\0");
+	let mut reg = memory.start.to_be_bytes().to_vec();
+	reg.extend_from_slice(&(memory.end - memory.start).to_be_bytes());
+	tree.property("reg", &reg);
+	tree.end_node();
+	tree.end_node();
+	tree.push_u32(FDT_END);
+
+	let off_mem_rsvmap = FDT_HEADER_SIZE;
+	let off_dt_struct = off_mem_rsvmap + mem_rsvmap.len();
+	let off_dt_strings = off_dt_struct + tree.structure.len();
+	let totalsize = off_dt_strings + tree.strings.len();
+	let mut fdt = Vec::with_capacity(totalsize);
+	for field in [
+		FDT_MAGIC,
+		totalsize as u32,
+		off_dt_struct as u32,
+		off_dt_strings as u32,
+		off_mem_rsvmap as u32,
+		FDT_VERSION,
+		FDT_LAST_COMP_VERSION,
+		// boot_cpuid_phys
+		0,
+		tree.strings.len() as u32,
+		tree.structure.len() as u32,
+	] {
+		fdt.extend_from_slice(&field.to_be_bytes());
+	}
+	fdt.extend_from_slice(&mem_rsvmap);
+	fdt.extend_from_slice(&tree.structure);
+	fdt.extend_from_slice(&tree.strings);
+	fdt
+}
+
+/// Writes the structure and strings blocks of a device tree.
+#[derive(Default)]
+struct TreeWriter {
+	structure: Vec<u8>,
+	strings: Vec<u8>,
+}
+
+impl TreeWriter {
+	fn push_u32(&mut self, value: u32) {
+		self.structure.extend_from_slice(&value.to_be_bytes());
+	}
+
+	/// Appends `bytes` to the structure block, padded to 4 bytes.
+	fn push_padded(&mut self, bytes: &[u8]) {
+		self.structure.extend_from_slice(bytes);
+		self.structure
+			.resize(self.structure.len().next_multiple_of(4), 0);
+	}
+
+	fn begin_node(&mut self, name: &str) {
+		self.push_u32(FDT_BEGIN_NODE);
+		self.push_padded(&[name.as_bytes(), b"\0"].concat());
+	}
+
+	fn end_node(&mut self) {
+		self.push_u32(FDT_END_NODE);
+	}
+
+	fn property(&mut self, name: &str, value: &[u8]) {
+		let name_offset = self.strings.len() as u32;
+		self.strings.extend_from_slice(name.as_bytes());
+		self.strings.push(0);
+		self.push_u32(FDT_PROP);
+		self.push_u32(value.len() as u32);
+		self.push_u32(name_offset);
+		self.push_padded(value);
+	}
+}
+
+/// A device tree read back from its flattened form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceTree {
+	/// The regions listed in the memory reservation block, in order.
+	pub reserved: Vec<Range<u64>>,
+	/// The root node.
+	pub root: Node,
+}
+
+/// A node of a [`DeviceTree`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+	/// The name of the node including its unit address, e.g., `memory@0`. Empty for the root node.
+	pub name: String,
+	/// The properties of the node as (name, value), in order.
+	pub properties: Vec<(String, Vec<u8>)>,
+	/// The child nodes, in order.
+	pub children: Vec<Node>,
+}
+
+impl Node {
+	/// Returns the child node with the given name.
+	pub fn child(&self, name: &str) -> Option<&Node> {
+		self.children.iter().find(|child| child.name == name)
+	}
+
+	/// Returns the value of the property with the given name.
+	pub fn property(&self, name: &str) -> Option<&[u8]> {
+		self.properties
+			.iter()
+			.find(|(property, _)| property == name)
+			.map(|(_, value)| value.as_slice())
+	}
+}
+
+impl DeviceTree {
+	/// Parses a flattened device tree. Returns `None` if it is malformed.
+	pub fn parse(fdt: &[u8]) -> Option<Self> {
+		if read_u32(fdt, 0)? != FDT_MAGIC {
+			return None;
+		}
+		let fdt = fdt.get(..read_u32(fdt, 4)? as usize)?;
+		let off_dt_struct = read_u32(fdt, 8)? as usize;
+		let off_dt_strings = read_u32(fdt, 12)? as usize;
+		let mut offset = read_u32(fdt, 16)? as usize;
+		let size_dt_strings = read_u32(fdt, 32)? as usize;
+		let size_dt_struct = read_u32(fdt, 36)? as usize;
+
+		let mut reserved = Vec::new();
+		loop {
+			let (address, size) = (read_u64(fdt, offset)?, read_u64(fdt, offset + 8)?);
+			offset += 16;
+			if (address, size) == (0, 0) {
+				break;
+			}
+			reserved.push(address..address.checked_add(size)?);
+		}
+
+		let mut reader = TreeReader {
+			structure: fdt.get(off_dt_struct..off_dt_struct.checked_add(size_dt_struct)?)?,
+			strings: fdt.get(off_dt_strings..off_dt_strings.checked_add(size_dt_strings)?)?,
+			offset: 0,
+		};
+		if reader.token()? != FDT_BEGIN_NODE {
+			return None;
+		}
+		let root = reader.node()?;
+		(reader.token()? == FDT_END).then_some(Self { reserved, root })
+	}
+
+	/// Returns the address ranges of the memory nodes, i.e., the guest memory.
+	pub fn memory(&self) -> Vec<Range<u64>> {
+		let cells = |name, default| {
+			self.root
+				.property(name)
+				.and_then(|value| read_u32(value, 0))
+				.map_or(default, |cells| cells as usize)
+		};
+		let (address_cells, size_cells) = (cells("#address-cells", 2), cells("#size-cells", 1));
+		let read_cells = |cells: &[u8]| {
+			cells.chunks(4).fold(0, |value, cell| {
+				value << 32 | u64::from(read_u32(cell, 0).unwrap())
+			})
+		};
+
+		self.root
+			.children
+			.iter()
+			.filter(|node| node.property("device_type") == Some(b"memory\0"))
+			.filter_map(|node| node.property("reg"))
+			.flat_map(|reg| reg.chunks_exact((address_cells + size_cells) * 4))
+			.map(|entry| {
+				let (address, size) = entry.split_at(address_cells * 4);
+				let address = read_cells(address);
+				address..address + read_cells(size)
+			})
+			.collect()
+	}
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+	let bytes = bytes.get(offset..offset.checked_add(4)?)?;
+	Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Option<u64> {
+	let bytes = bytes.get(offset..offset.checked_add(8)?)?;
+	Some(u64::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+/// Reads the structure block of a device tree.
+struct TreeReader<'a> {
+	structure: &'a [u8],
+	strings: &'a [u8],
+	offset: usize,
+}
+
+impl<'a> TreeReader<'a> {
+	fn u32(&mut self) -> Option<u32> {
+		let value = read_u32(self.structure, self.offset)?;
+		self.offset += 4;
+		Some(value)
+	}
+
+	/// Reads the next token, skipping `FDT_NOP`s.
+	fn token(&mut self) -> Option<u32> {
+		loop {
+			let token = self.u32()?;
+			if token != FDT_NOP {
+				return Some(token);
+			}
+		}
+	}
+
+	/// Reads `len` bytes and skips their padding to 4 bytes.
+	fn padded(&mut self, len: usize) -> Option<&'a [u8]> {
+		let end = self.offset.checked_add(len)?;
+		let bytes = self.structure.get(self.offset..end)?;
+		self.offset = end.next_multiple_of(4);
+		Some(bytes)
+	}
+
+	/// Reads a node whose `FDT_BEGIN_NODE` token was just read, up to its `FDT_END_NODE` token.
+	fn node(&mut self) -> Option<Node> {
+		let name = CStr::from_bytes_until_nul(self.structure.get(self.offset..)?).ok()?;
+		self.padded(name.to_bytes_with_nul().len())?;
+		let mut node = Node {
+			name: name.to_str().ok()?.to_owned(),
+			properties: Vec::new(),
+			children: Vec::new(),
+		};
+		loop {
+			match self.token()? {
+				FDT_PROP => {
+					let len = self.u32()? as usize;
+					let name_offset = self.u32()? as usize;
+					let value = self.padded(len)?.to_vec();
+					let name = CStr::from_bytes_until_nul(self.strings.get(name_offset..)?).ok()?;
+					node.properties
+						.push((name.to_str().ok()?.to_owned(), value));
+				}
+				FDT_BEGIN_NODE => node.children.push(self.node()?),
+				FDT_END_NODE => return Some(node),
+				_ => return None,
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::consts::RESERVED_REGIONS;
+
+	fn be_u32(fdt: &[u8], offset: usize) -> u32 {
+		u32::from_be_bytes(fdt[offset..][..4].try_into().unwrap())
+	}
+
+	fn be_u64(fdt: &[u8], offset: usize) -> u64 {
+		u64::from_be_bytes(fdt[offset..][..8].try_into().unwrap())
+	}
+
+	#[test]
+	fn test_reserved_regions() {
+		let reserved = RESERVED_REGIONS
+			.iter()
+			.map(|(_, range)| range.clone())
+			.collect::<Vec<_>>();
+		let fdt = reserved_regions_fdt(0..0x400_0000, &reserved);
+
+		assert_eq!(be_u32(&fdt, 0), FDT_MAGIC);
+		assert_eq!(be_u32(&fdt, 4) as usize, fdt.len());
+		assert_eq!(be_u32(&fdt, 20), FDT_VERSION);
+
+		let mut offset = be_u32(&fdt, 16) as usize;
+		assert_eq!(offset % 8, 0);
+		let mut entries = Vec::new();
+		loop {
+			let (address, size) = (be_u64(&fdt, offset), be_u64(&fdt, offset + 8));
+			offset += 16;
+			if (address, size) == (0, 0) {
+				break;
+			}
+			entries.push(address..address + size);
+		}
+		assert_eq!(
+			entries,
+			[
+				0x1000..0x2000,
+				0x9000..0xa000,
+				0xa000..0xb000,
+				0x10000..0x13000
+			]
+		);
+		assert_eq!(offset, be_u32(&fdt, 8) as usize);
+
+		let structure = &fdt[offset..be_u32(&fdt, 12) as usize];
+		assert_eq!(structure.len() % 4, 0);
+		assert_eq!(be_u32(structure, 0), FDT_BEGIN_NODE);
+		assert_eq!(be_u32(structure, structure.len() - 4), FDT_END);
+		let strings = &fdt[be_u32(&fdt, 12) as usize..];
+		assert!(strings.windows(4).any(|name| name == b"reg\0"));
+	}
+
+	#[test]
+	fn test_parse() {
+		let fdt = reserved_regions_fdt(0x20_0000..0x400_0000, &[0x1000..0x2000, 0x9000..0xb000]);
+		let tree = DeviceTree::parse(&fdt).unwrap();
+
+		assert_eq!(tree.reserved, [0x1000..0x2000, 0x9000..0xb000]);
+		assert_eq!(tree.root.name, "");
+		assert_eq!(
+			tree.root.property("#size-cells"),
+			Some(&2u32.to_be_bytes()[..])
+		);
+		assert!(tree.root.child("chosen").unwrap().properties.is_empty());
+		let memory = tree.root.child("memory@200000").unwrap();
+		assert_eq!(memory.property("device_type"), Some(&b"memory\0"[..]));
+		assert_eq!(tree.memory().len(), 1);
+		assert_eq!(tree.memory()[0], 0x20_0000..0x400_0000);
+
+		// Truncated or corrupted trees are rejected
+		assert_eq!(DeviceTree::parse(&fdt[..fdt.len() - 1]), None);
+		let mut corrupted = fdt.clone();
+		corrupted[0] = 0;
+		assert_eq!(DeviceTree::parse(&corrupted), None);
+	}
+}
@@ -1,15 +1,35 @@
 use std::{
-	ffi::{OsStr, OsString},
-	io::{self, Error, ErrorKind, Write},
-	os::unix::ffi::OsStrExt,
+	borrow::Cow,
+	env,
+	ffi::{CStr, CString, OsStr, OsString},
+	fs, io,
+	mem::MaybeUninit,
+	os::{
+		fd::{IntoRawFd, OwnedFd, RawFd},
+		unix::{
+			ffi::{OsStrExt, OsStringExt},
+			net::UnixStream,
+		},
+	},
+	path::{Component, Path, PathBuf},
+	sync::{Mutex, RwLock},
+	time::Duration,
 };
 
-use uhyve_interface::{parameters::*, GuestPhysAddr, Hypercall, HypercallAddress, MAX_ARGC_ENVC};
+use log::LevelFilter;
+use uhyve_interface::{
+	parameters::*, GuestPhysAddr, GuestVirtAddr, Hypercall, HypercallAddress, MAX_ARGC_ENVC,
+};
 
 use crate::{
-	consts::BOOT_PML4,
+	audit::AuditLog,
+	consts::{GUEST_PAGE_SIZE, PAGE_SIZE},
+	fdmap::{FdMap, HostFd},
 	mem::{MemoryError, MmapMemory},
-	virt_to_phys,
+	params::EventHook,
+	procfs::{sealed_file, ProcInfo},
+	serial::SerialOutput,
+	vm::{guest_virt_slices, TestReport},
 };
 
 /// `addr` is the address of the hypercall parameter in the guest's memory space. `data` is the
@@ -39,10 +59,22 @@ pub unsafe fn address_to_hypercall(
 				let sysopen = mem.get_ref_mut::<OpenParams>(data).unwrap();
 				Hypercall::FileOpen(sysopen)
 			}
+			HypercallAddress::FileOpen2 => {
+				let sysopen2 = mem.get_ref_mut(data).unwrap();
+				Hypercall::FileOpen2(sysopen2)
+			}
+			HypercallAddress::FileOpenat => {
+				let sysopenat = mem.get_ref_mut(data).unwrap();
+				Hypercall::FileOpenat(sysopenat)
+			}
 			HypercallAddress::FileRead => {
 				let sysread = mem.get_ref_mut::<ReadPrams>(data).unwrap();
 				Hypercall::FileRead(sysread)
 			}
+			HypercallAddress::FileReadExact => {
+				let sysread = mem.get_ref_mut::<ReadPrams>(data).unwrap();
+				Hypercall::FileReadExact(sysread)
+			}
 			HypercallAddress::FileWrite => {
 				let syswrite = mem.get_ref_mut(data).unwrap();
 				Hypercall::FileWrite(syswrite)
@@ -64,6 +96,123 @@ pub unsafe fn address_to_hypercall(
 				Hypercall::Cmdval(syscmdval)
 			}
 			HypercallAddress::Uart => Hypercall::SerialWriteByte(data.as_u64() as u8),
+			HypercallAddress::SchedYield => Hypercall::SchedYield,
+			HypercallAddress::SetAppName => {
+				let sysappname = mem.get_ref_mut(data).unwrap();
+				Hypercall::SetAppName(sysappname)
+			}
+			HypercallAddress::LogMessage => {
+				let syslog = mem.get_ref_mut(data).unwrap();
+				Hypercall::LogMessage(syslog)
+			}
+			HypercallAddress::Madvise => {
+				let sysmadvise = mem.get_ref_mut(data).unwrap();
+				Hypercall::Madvise(sysmadvise)
+			}
+			HypercallAddress::Panic => {
+				let syspanic = mem.get_ref_mut(data).unwrap();
+				Hypercall::Panic(syspanic)
+			}
+			HypercallAddress::Chdir => {
+				let syschdir = mem.get_ref_mut(data).unwrap();
+				Hypercall::Chdir(syschdir)
+			}
+			HypercallAddress::GetCwd => {
+				let sysgetcwd = mem.get_ref_mut(data).unwrap();
+				Hypercall::GetCwd(sysgetcwd)
+			}
+			HypercallAddress::Notify => {
+				let sysnotify = mem.get_ref_mut(data).unwrap();
+				Hypercall::Notify(sysnotify)
+			}
+			HypercallAddress::Brk => {
+				let sysbrk = mem.get_ref_mut(data).unwrap();
+				Hypercall::Brk(sysbrk)
+			}
+			HypercallAddress::FileFadvise => {
+				let sysfadvise = mem.get_ref_mut(data).unwrap();
+				Hypercall::FileFadvise(sysfadvise)
+			}
+			HypercallAddress::TestResult => {
+				let systestresult = mem.get_ref_mut(data).unwrap();
+				Hypercall::TestResult(systestresult)
+			}
+			HypercallAddress::HostInfo => {
+				let syshostinfo = mem.get_ref_mut(data).unwrap();
+				Hypercall::HostInfo(syshostinfo)
+			}
+			HypercallAddress::GetEnv => {
+				let sysgetenv = mem.get_ref_mut(data).unwrap();
+				Hypercall::GetEnv(sysgetenv)
+			}
+			HypercallAddress::Barrier => {
+				let sysbarrier = mem.get_ref_mut(data).unwrap();
+				Hypercall::Barrier(sysbarrier)
+			}
+			HypercallAddress::NanoSleep => {
+				let sysnanosleep = mem.get_ref_mut(data).unwrap();
+				Hypercall::NanoSleep(sysnanosleep)
+			}
+			HypercallAddress::FileStatvfs => {
+				let sysstatvfs = mem.get_ref_mut(data).unwrap();
+				Hypercall::FileStatvfs(sysstatvfs)
+			}
+			HypercallAddress::SetLogLevel => {
+				let syssetloglevel = mem.get_ref_mut(data).unwrap();
+				Hypercall::SetLogLevel(syssetloglevel)
+			}
+			HypercallAddress::OutOfMemory => {
+				let sysoom = mem.get_ref_mut(data).unwrap();
+				Hypercall::OutOfMemory(sysoom)
+			}
+			HypercallAddress::SetSerialMode => {
+				let sysserialmode = mem.get_ref_mut(data).unwrap();
+				Hypercall::SetSerialMode(sysserialmode)
+			}
+			HypercallAddress::ClockCompare => {
+				let sysclock = mem.get_ref_mut(data).unwrap();
+				Hypercall::ClockCompare(sysclock)
+			}
+			HypercallAddress::ReportMetric => {
+				let sysmetric = mem.get_ref_mut(data).unwrap();
+				Hypercall::ReportMetric(sysmetric)
+			}
+			HypercallAddress::Heartbeat => {
+				let sysheartbeat = mem.get_ref_mut(data).unwrap();
+				Hypercall::Heartbeat(sysheartbeat)
+			}
+			HypercallAddress::SetPriority => {
+				let syssetpriority = mem.get_ref_mut(data).unwrap();
+				Hypercall::SetPriority(syssetpriority)
+			}
+			HypercallAddress::SyncAll => {
+				let syssyncall = mem.get_ref_mut(data).unwrap();
+				Hypercall::SyncAll(syssyncall)
+			}
+			HypercallAddress::GetHostname => {
+				let sysgethostname = mem.get_ref_mut(data).unwrap();
+				Hypercall::GetHostname(sysgethostname)
+			}
+			HypercallAddress::MemBalloon => {
+				let sysballoon = mem.get_ref_mut(data).unwrap();
+				Hypercall::MemBalloon(sysballoon)
+			}
+			HypercallAddress::Sendfile => {
+				let syssendfile = mem.get_ref_mut(data).unwrap();
+				Hypercall::Sendfile(syssendfile)
+			}
+			HypercallAddress::FileReaddir => {
+				let sysreaddir = mem.get_ref_mut(data).unwrap();
+				Hypercall::FileReaddir(sysreaddir)
+			}
+			HypercallAddress::FileWrite2 => {
+				let syswrite = mem.get_ref_mut(data).unwrap();
+				Hypercall::FileWrite2(syswrite)
+			}
+			HypercallAddress::FileLseek2 => {
+				let syslseek = mem.get_ref_mut(data).unwrap();
+				Hypercall::FileLseek2(syslseek)
+			}
 			_ => unimplemented!(),
 		})
 	} else {
@@ -71,135 +220,1312 @@ pub unsafe fn address_to_hypercall(
 	}
 }
 
+/// Copies the buffer of `len` bytes at the guest virtual address `addr` out of the guest memory.
+///
+/// The buffer is translated page by page, so it may cross page boundaries. Returns `None` if any
+/// part of it is not mapped or exceeds the guest's memory.
+fn guest_buf(mem: &MmapMemory, addr: GuestVirtAddr, len: usize) -> Option<Vec<u8>> {
+	let pages = unsafe { guest_virt_slices(mem, addr, len) }.ok()?;
+	Some(pages.concat())
+}
+
+/// Lexically normalizes the relative `path` by removing `.` and resolving `..` components.
+///
+/// Returns `None` if the path is absolute or escapes the directory it is relative to. Symbolic
+/// links are not resolved.
+fn normalize_beneath(path: &Path) -> Option<PathBuf> {
+	let mut normalized = PathBuf::new();
+	for component in path.components() {
+		match component {
+			Component::Normal(name) => normalized.push(name),
+			Component::CurDir => {}
+			Component::ParentDir => {
+				if !normalized.pop() {
+					return None;
+				}
+			}
+			Component::RootDir | Component::Prefix(_) => return None,
+		}
+	}
+	Some(normalized)
+}
+
+/// Returns `true` if the relative `path` stays beneath the directory it is relative to.
+///
+/// This is a purely lexical check, symbolic links are not resolved.
+fn is_beneath(path: &Path) -> bool {
+	normalize_beneath(path).is_some()
+}
+
+/// Resolves a path requested by the guest to a path on the host.
+///
+/// Relative paths are resolved against the guest's working directory `cwd`. If a `host_root` is
+/// given, the guest's file system is confined to it: absolute paths are resolved relative to the
+/// root and `cwd` has to be relative to it as well. All paths are normalized and must not escape
+/// the root via `..`, otherwise `None` is returned. Without a host root, paths are unaffected.
+fn host_path(host_root: Option<&Path>, cwd: &Path, guest_path: &Path) -> Option<PathBuf> {
+	let Some(root) = host_root else {
+		return Some(cwd.join(guest_path));
+	};
+	let path = match guest_path.strip_prefix("/") {
+		Ok(beneath_root) => normalize_beneath(beneath_root)?,
+		Err(_) => normalize_beneath(&cwd.join(guest_path))?,
+	};
+	Some(root.join(path))
+}
+
+/// Resolves a path requested by the guest to a path on the host, see [`host_path`].
+///
+/// Also returns `None` if the host root or working directory contain a nul byte.
+fn resolve_host_path(host_root: Option<&Path>, cwd: &Path, guest_path: &CStr) -> Option<CString> {
+	let path = host_path(
+		host_root,
+		cwd,
+		Path::new(OsStr::from_bytes(guest_path.to_bytes())),
+	)?;
+	CString::new(path.into_os_string().into_vec()).ok()
+}
+
+/// Reads the zero terminated path at `addr` from the guest's memory.
+///
+/// # Safety
+///
+/// The returned reference is only valid as long as the guest is halted.
+unsafe fn guest_path(mem: &MmapMemory, addr: GuestPhysAddr) -> &CStr {
+	CStr::from_ptr(mem.host_address(addr).unwrap() as *const libc::c_char)
+}
+
 /// unlink deletes a name from the filesystem. This is used to handle `unlink` syscalls from the guest.
 /// TODO: UNSAFE AS *%@#. It has to be checked that the VM is allowed to unlink that file!
-pub fn unlink(mem: &MmapMemory, sysunlink: &mut UnlinkParams) {
-	unsafe {
-		sysunlink.ret = libc::unlink(mem.host_address(sysunlink.name).unwrap() as *const i8);
+pub fn unlink(
+	mem: &MmapMemory,
+	sysunlink: &mut UnlinkParams,
+	host_root: Option<&Path>,
+	cwd: &Path,
+	audit: Option<&AuditLog>,
+) {
+	let name = unsafe { guest_path(mem, sysunlink.name) };
+	let Some(host_path) = resolve_host_path(host_root, cwd, name) else {
+		warn!("Guest tried to unlink {name:?} outside of the host root");
+		sysunlink.ret = -libc::EACCES;
+		record(audit, "unlink", name, None, sysunlink.ret);
+		return;
+	};
+	if unsafe { libc::unlink(host_path.as_ptr()) } < 0 {
+		sysunlink.ret = -io::Error::last_os_error().raw_os_error().unwrap();
+	} else {
+		sysunlink.ret = 0;
+	}
+	record(
+		audit,
+		"unlink",
+		name,
+		Some(cstr_path(&host_path)),
+		sysunlink.ret,
+	);
+}
+
+/// Returns the path contained in `path`.
+fn cstr_path(path: &CStr) -> &Path {
+	Path::new(OsStr::from_bytes(path.to_bytes()))
+}
+
+/// Records the operation `op` on the guest path `name` in the `audit` log, if there is one.
+fn record(audit: Option<&AuditLog>, op: &str, name: &CStr, host_path: Option<&Path>, result: i32) {
+	if let Some(audit) = audit {
+		audit.record(op, cstr_path(name), host_path, result);
+	}
+}
+
+/// Handles a statvfs syscall by querying the host file system containing the path.
+///
+/// Paths outside of the host root are reported as nonexistent.
+pub fn statvfs(
+	mem: &MmapMemory,
+	sysstatvfs: &mut StatvfsParams,
+	host_root: Option<&Path>,
+	cwd: &Path,
+) {
+	let name = unsafe { guest_path(mem, sysstatvfs.name) };
+	let Some(host_path) = resolve_host_path(host_root, cwd, name) else {
+		warn!("Guest tried to statvfs {name:?} outside of the host root");
+		sysstatvfs.ret = -libc::ENOENT;
+		return;
+	};
+	let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+	if unsafe { libc::statvfs(host_path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+		sysstatvfs.ret = -io::Error::last_os_error().raw_os_error().unwrap();
+		return;
 	}
+	let stat = unsafe { stat.assume_init() };
+	sysstatvfs.block_size = stat.f_frsize.into();
+	sysstatvfs.blocks = stat.f_blocks.into();
+	sysstatvfs.blocks_free = stat.f_bfree.into();
+	sysstatvfs.blocks_available = stat.f_bavail.into();
+	sysstatvfs.ret = 0;
 }
 
 /// Handles an open syscall by opening a file on the host.
-pub fn open(mem: &MmapMemory, sysopen: &mut OpenParams) {
-	unsafe {
-		sysopen.ret = libc::open(
-			mem.host_address(sysopen.name).unwrap() as *const i8,
-			sysopen.flags,
-			sysopen.mode,
+///
+/// The synthetic and virtual files of `proc_info` take precedence over the host's files and can
+/// only be opened read-only. Opening one of its UNIX socket paths connects to the host socket.
+/// `/dev/null`, `/dev/zero` and the read-only `/dev/urandom` are always available. Returns whether
+/// the file was newly created.
+pub fn open(
+	mem: &MmapMemory,
+	sysopen: &mut OpenParams,
+	host_root: Option<&Path>,
+	cwd: &Path,
+	fdmap: &FdMap,
+	proc_info: Option<&ProcInfo<'_>>,
+	audit: Option<&AuditLog>,
+) -> bool {
+	let name = unsafe { guest_path(mem, sysopen.name) };
+	let mut host_path = None;
+	let result = open_file(
+		name,
+		sysopen,
+		host_root,
+		cwd,
+		fdmap,
+		proc_info,
+		&mut host_path,
+	);
+	let audit_result = match result {
+		Ok(_) => sysopen.ret,
+		Err(errno) => -errno,
+	};
+	record(
+		audit,
+		"open",
+		name,
+		host_path.as_deref().map(cstr_path),
+		audit_result,
+	);
+	result.unwrap_or(false)
+}
+
+/// Devices the guest can always open, regardless of the host root and the virtual files, along
+/// with whether the guest may write to them.
+///
+/// They are backed by the host's devices of the same name, so seeking succeeds without effect and
+/// `/dev/urandom` is filled from the host's random number generator, also in deterministic mode.
+/// Writing to `/dev/urandom` would mix data into the host's entropy pool, so it is read-only.
+const GUEST_DEVICES: [(&str, bool); 3] = [
+	("/dev/null", true),
+	("/dev/zero", true),
+	("/dev/urandom", false),
+];
+
+/// Opens the file `name` for [`open`] and stores the host path it resolves to in `host_path`.
+///
+/// Returns whether the file was newly created, or the error number if it couldn't be opened.
+fn open_file(
+	name: &CStr,
+	sysopen: &mut OpenParams,
+	host_root: Option<&Path>,
+	cwd: &Path,
+	fdmap: &FdMap,
+	proc_info: Option<&ProcInfo<'_>>,
+	host_path: &mut Option<CString>,
+) -> Result<bool, i32> {
+	let path = cwd.join(OsStr::from_bytes(name.to_bytes()));
+	if fdmap.is_full() {
+		sysopen.ret = -libc::EMFILE;
+		return Err(libc::EMFILE);
+	}
+	let content = proc_info.and_then(|proc_info| {
+		proc_info
+			.file(&path)
+			.map(|content| Cow::Owned(content.into_bytes()))
+			.or_else(|| proc_info.virtual_file(&path).map(Cow::Borrowed))
+	});
+	// Registers a descriptor that doesn't belong to a host file with the guest.
+	let insert = |fd: io::Result<OwnedFd>, sysopen: &mut OpenParams| match fd
+		.and_then(|fd| fdmap.insert(fd.into_raw_fd()))
+	{
+		Ok(fd) => {
+			sysopen.ret = fd;
+			Ok(false)
+		}
+		Err(err) => {
+			let errno = err.raw_os_error().unwrap_or(libc::EIO);
+			sysopen.ret = -errno;
+			Err(errno)
+		}
+	};
+	if let Some(&(device, writable)) = GUEST_DEVICES
+		.iter()
+		.find(|(device, _)| path == Path::new(device))
+	{
+		*host_path = CString::new(device).ok();
+		let access = sysopen.flags & libc::O_ACCMODE;
+		if !writable && access != libc::O_RDONLY {
+			warn!("Guest tried to open {name:?} for writing");
+			sysopen.ret = -libc::EACCES;
+			return Err(libc::EACCES);
+		}
+		let device = fs::OpenOptions::new()
+			.read(access != libc::O_WRONLY)
+			.write(access != libc::O_RDONLY)
+			.open(device);
+		return insert(device.map(OwnedFd::from), sysopen);
+	}
+	if let Some(content) = content {
+		if sysopen.flags & libc::O_ACCMODE != libc::O_RDONLY {
+			warn!("Guest tried to open {name:?} for writing");
+			sysopen.ret = -libc::EACCES;
+			return Err(libc::EACCES);
+		}
+		return insert(sealed_file(&content), sysopen);
+	}
+	if let Some(socket) = proc_info.and_then(|proc_info| proc_info.unix_socket(&path)) {
+		*host_path = CString::new(socket.as_os_str().as_bytes()).ok();
+		let stream = UnixStream::connect(socket);
+		if let Err(err) = &stream {
+			warn!("Unable to connect the guest to {}: {err}", socket.display());
+		}
+		return insert(stream.map(OwnedFd::from), sysopen);
+	}
+
+	let Some(resolved) = resolve_host_path(host_root, cwd, name) else {
+		warn!("Guest tried to open {name:?} outside of the host root");
+		sysopen.ret = -libc::EACCES;
+		return Err(libc::EACCES);
+	};
+	let host_path = host_path.insert(resolved);
+	let exists = unsafe { libc::access(host_path.as_ptr(), libc::F_OK) } == 0;
+	if let Err(err) = reserve_creation(sysopen.flags, exists, fdmap) {
+		warn!("Guest may not create {name:?}: {err}");
+		let errno = err.raw_os_error().unwrap();
+		sysopen.ret = -errno;
+		return Err(errno);
+	}
+	let fd = unsafe { libc::open(host_path.as_ptr(), sysopen.flags, sysopen.mode) };
+	if fd < 0 {
+		let errno = io::Error::last_os_error().raw_os_error().unwrap();
+		sysopen.ret = -errno;
+		return Err(errno);
+	}
+	match fdmap.insert(fd) {
+		Ok(fd) => sysopen.ret = fd,
+		Err(err) => {
+			let errno = err.raw_os_error().unwrap();
+			sysopen.ret = -errno;
+			return Err(errno);
+		}
+	}
+	Ok(sysopen.flags & libc::O_CREAT != 0 && !exists)
+}
+
+/// Handles an open syscall like [`open`], but also reports to the guest whether the file was
+/// newly created.
+pub fn open2(
+	mem: &MmapMemory,
+	sysopen2: &mut Open2Params,
+	host_root: Option<&Path>,
+	cwd: &Path,
+	fdmap: &FdMap,
+	proc_info: Option<&ProcInfo<'_>>,
+	audit: Option<&AuditLog>,
+) {
+	let mut sysopen = OpenParams {
+		name: sysopen2.name,
+		flags: sysopen2.flags,
+		mode: sysopen2.mode,
+		ret: -1,
+	};
+	let created = open(mem, &mut sysopen, host_root, cwd, fdmap, proc_info, audit);
+	sysopen2.ret = sysopen.ret;
+	sysopen2.created = created.into();
+}
+
+/// Accounts the file creation of an open with `flags` on a file that `exists` or not against
+/// the limits of `fdmap`.
+fn reserve_creation(flags: i32, exists: bool, fdmap: &FdMap) -> io::Result<()> {
+	if flags & libc::O_CREAT == 0 || exists {
+		return Ok(());
+	}
+	fdmap.reserve_creation()
+}
+
+/// Handles an openat syscall by opening a file relative to a directory opened by the guest.
+///
+/// Names that are absolute or escape the directory via `..` are rejected with `EACCES`.
+///
+/// The host path in the `audit` log is only known on hosts providing `/proc/self/fd`.
+pub fn openat(
+	mem: &MmapMemory,
+	sysopenat: &mut OpenatParams,
+	fdmap: &FdMap,
+	audit: Option<&AuditLog>,
+) {
+	let name = unsafe { guest_path(mem, sysopenat.name) };
+	let dirfd = fdmap.host_fd(sysopenat.dirfd);
+	sysopenat.ret = match openat_file(name, dirfd, sysopenat, fdmap) {
+		Ok(fd) => fd,
+		Err(errno) => -errno,
+	};
+	if let Some(audit) = audit {
+		let host_path = dirfd
+			.and_then(|dirfd| fs::read_link(format!("/proc/self/fd/{dirfd}")).ok())
+			.map(|dir| dir.join(cstr_path(name)));
+		audit.record(
+			"openat",
+			cstr_path(name),
+			host_path.as_deref(),
+			sysopenat.ret,
 		);
 	}
 }
 
+/// Opens the file `name` beneath the host directory `dirfd` for [`openat`].
+fn openat_file(
+	name: &CStr,
+	dirfd: Option<RawFd>,
+	sysopenat: &OpenatParams,
+	fdmap: &FdMap,
+) -> Result<RawFd, i32> {
+	let dirfd = dirfd.ok_or(libc::EBADF)?;
+	if fdmap.is_full() {
+		return Err(libc::EMFILE);
+	}
+	if !is_beneath(cstr_path(name)) {
+		warn!("Guest tried to open {name:?} outside of its directory");
+		return Err(libc::EACCES);
+	}
+
+	let exists = unsafe { libc::faccessat(dirfd, name.as_ptr(), libc::F_OK, 0) } == 0;
+	if let Err(err) = reserve_creation(sysopenat.flags, exists, fdmap) {
+		warn!("Guest may not create {name:?}: {err}");
+		return Err(err.raw_os_error().unwrap());
+	}
+
+	let fd = unsafe { libc::openat(dirfd, name.as_ptr(), sysopenat.flags, sysopenat.mode) };
+	let fd = if fd >= 0 {
+		fdmap.insert(fd)
+	} else {
+		Err(io::Error::last_os_error())
+	};
+	fd.map_err(|err| err.raw_os_error().unwrap())
+}
+
+/// Handles a chdir syscall by changing the guest's working directory `cwd`.
+///
+/// The new working directory has to be a directory on the host. It is resolved like any other
+/// guest path, see [`host_path`], so it must not escape the `host_root`, if one is given. Beneath
+/// a host root, the working directory is kept relative to it.
+pub fn chdir(
+	mem: &MmapMemory,
+	syschdir: &mut ChdirParams,
+	host_root: Option<&Path>,
+	cwd: &RwLock<PathBuf>,
+) {
+	let path = unsafe { guest_path(mem, syschdir.path) };
+	let mut cwd = cwd.write().unwrap();
+	let Some(host_path) = host_path(
+		host_root,
+		&cwd,
+		Path::new(OsStr::from_bytes(path.to_bytes())),
+	) else {
+		warn!("Guest tried to change into {path:?} outside of the host root");
+		syschdir.ret = -libc::EACCES;
+		return;
+	};
+	let new_cwd = match host_root {
+		Some(root) => host_path.strip_prefix(root).unwrap().to_path_buf(),
+		None => host_path.clone(),
+	};
+	syschdir.ret = match fs::metadata(host_path) {
+		Ok(metadata) if metadata.is_dir() => {
+			*cwd = new_cwd;
+			0
+		}
+		Ok(_) => -libc::ENOTDIR,
+		Err(err) => -err.raw_os_error().unwrap_or(libc::EIO),
+	};
+}
+
+/// Handles a getcwd syscall by copying the guest's working directory `cwd` into the guest's buffer.
+///
+/// The initial working directory, against which relative paths are resolved, is reported as `.`.
+pub fn getcwd(mem: &MmapMemory, sysgetcwd: &mut GetCwdParams, cwd: &Path) {
+	let cwd = if cwd.as_os_str().is_empty() {
+		Path::new(".")
+	} else {
+		cwd
+	};
+	let cwd = CString::new(cwd.as_os_str().as_bytes()).unwrap();
+	sysgetcwd.ret = match copy_to_guest(mem, sysgetcwd.buf, sysgetcwd.len, &cwd) {
+		Ok(()) => cwd.as_bytes().len() as isize,
+		Err(errno) => -(errno as isize),
+	};
+}
+
+/// Copies the zero terminated `s` into the guest buffer at `buf` of size `len`.
+///
+/// Fails with `ERANGE` if the buffer is too small and with `EFAULT` if it is not entirely mapped.
+fn copy_to_guest(mem: &MmapMemory, buf: GuestVirtAddr, len: usize, s: &CStr) -> Result<(), i32> {
+	let bytes = s.to_bytes_with_nul();
+	if bytes.len() > len {
+		return Err(libc::ERANGE);
+	}
+	let pages = unsafe { guest_virt_slices(mem, buf, bytes.len()) }.map_err(|_| libc::EFAULT)?;
+
+	let mut offset = 0;
+	for page in pages {
+		page.copy_from_slice(&bytes[offset..][..page.len()]);
+		offset += page.len();
+	}
+	Ok(())
+}
+
+/// Handles a HostInfo hypercall by copying the uhyve version and the name of the
+/// virtualization `backend` into the guest's buffers.
+pub fn host_info(mem: &MmapMemory, syshostinfo: &mut HostInfoParams, backend: &str) {
+	let version = CString::new(env!("CARGO_PKG_VERSION")).unwrap();
+	let backend = CString::new(backend).unwrap();
+	syshostinfo.ret = copy_to_guest(mem, syshostinfo.version, syshostinfo.version_len, &version)
+		.and_then(|()| copy_to_guest(mem, syshostinfo.backend, syshostinfo.backend_len, &backend))
+		.map_or_else(|errno| -errno, |()| 0);
+}
+
+/// Handles a GetEnv hypercall by copying the value of a host environment variable into the
+/// guest's buffer.
+///
+/// Only the variables in `allowlist` can be read. Others are reported as not set, so that the
+/// guest can't probe the host environment.
+pub fn getenv(mem: &MmapMemory, sysgetenv: &mut GetEnvParams, allowlist: &[String]) {
+	let name = unsafe { guest_path(mem, sysgetenv.name) };
+	let value = allowlist
+		.iter()
+		.any(|allowed| allowed.as_bytes() == name.to_bytes())
+		.then(|| env::var_os(OsStr::from_bytes(name.to_bytes())))
+		.flatten();
+	let Some(value) = value else {
+		sysgetenv.ret = -libc::ENOENT as isize;
+		return;
+	};
+	let value = CString::new(value.into_vec()).unwrap();
+	sysgetenv.ret = match copy_to_guest(mem, sysgetenv.buf, sysgetenv.len, &value) {
+		Ok(()) => value.as_bytes().len() as isize,
+		Err(errno) => -(errno as isize),
+	};
+}
+
+/// Handles a GetHostname hypercall by copying the guest's `hostname` into the guest's buffer.
+pub fn gethostname(mem: &MmapMemory, sysgethostname: &mut GetHostnameParams, hostname: &CStr) {
+	sysgethostname.ret = match copy_to_guest(mem, sysgethostname.buf, sysgethostname.len, hostname)
+	{
+		Ok(()) => hostname.to_bytes().len() as isize,
+		Err(errno) => -(errno as isize),
+	};
+}
+
+/// Handles a FileReaddir hypercall by copying the names of the entries of a virtual directory
+/// into the guest's buffer.
+///
+/// The directories are those implied by the virtual files of `proc_info`, e.g., by the entries of
+/// a mounted archive. Host directories can't be listed.
+pub fn readdir(
+	mem: &MmapMemory,
+	sysreaddir: &mut ReaddirParams,
+	cwd: &Path,
+	proc_info: &ProcInfo<'_>,
+) {
+	let name = unsafe { guest_path(mem, sysreaddir.name) };
+	let path = cwd.join(OsStr::from_bytes(name.to_bytes()));
+	if proc_info.virtual_file(&path).is_some() {
+		sysreaddir.ret = -libc::ENOTDIR as isize;
+		return;
+	}
+	let Some(entries) = proc_info.virtual_dir(&path) else {
+		sysreaddir.ret = -libc::ENOENT as isize;
+		return;
+	};
+	sysreaddir.ret = copy_names_to_guest(
+		mem,
+		sysreaddir.buf,
+		sysreaddir.len,
+		entries.iter().map(|entry| entry.as_bytes()),
+	);
+}
+
+/// Copies the zero terminated `names` back to back into the guest buffer at `buf` of size `len`.
+///
+/// Returns the size of all names including their terminating zeros, even if they don't fit into
+/// the buffer and nothing was copied, or `-EFAULT` if the buffer is not entirely mapped.
+fn copy_names_to_guest<'a>(
+	mem: &MmapMemory,
+	buf: GuestVirtAddr,
+	len: usize,
+	names: impl IntoIterator<Item = &'a [u8]>,
+) -> isize {
+	let mut bytes = Vec::new();
+	for name in names {
+		bytes.extend_from_slice(name);
+		bytes.push(0);
+	}
+	if bytes.len() > len {
+		return bytes.len() as isize;
+	}
+	let Ok(pages) = (unsafe { guest_virt_slices(mem, buf, bytes.len()) }) else {
+		return -libc::EFAULT as isize;
+	};
+	let mut offset = 0;
+	for page in pages {
+		page.copy_from_slice(&bytes[offset..][..page.len()]);
+		offset += page.len();
+	}
+	bytes.len() as isize
+}
+
+/// Handles a SchedYield hypercall by letting the host schedule other threads on the CPU of the
+/// calling vCPU.
+pub fn sched_yield() {
+	std::thread::yield_now();
+}
+
+/// Handles a SyncAll hypercall by synchronizing all descriptors the guest has open for writing
+/// with the host's storage.
+///
+/// Descriptors that don't support synchronization, e.g., pipes, are skipped. The standard streams
+/// shared with uhyve aren't synchronized.
+pub fn sync_all(syssyncall: &mut SyncAllParams, fdmap: &FdMap) {
+	let (synced, ret) = sync_writable_fds(fdmap);
+	if syssyncall.sync_host != 0 {
+		unsafe { libc::sync() };
+	}
+	syssyncall.synced = synced;
+	syssyncall.ret = ret;
+}
+
+/// Synchronizes all descriptors of `fdmap` that are open for writing, see [`sync_all`].
+///
+/// Returns the number of synchronized descriptors and `0` or the negated error number of the
+/// first failure.
+pub(crate) fn sync_writable_fds(fdmap: &FdMap) -> (u32, i32) {
+	let mut synced = 0;
+	let mut ret = 0;
+	fdmap.for_each_host_fd(|fd| {
+		let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+		if flags < 0 || flags & libc::O_ACCMODE == libc::O_RDONLY {
+			return;
+		}
+		if unsafe { libc::fsync(fd) } == 0 {
+			synced += 1;
+			return;
+		}
+		let errno = io::Error::last_os_error().raw_os_error().unwrap();
+		if errno != libc::EINVAL && errno != libc::EROFS && ret == 0 {
+			warn!(
+				"Unable to synchronize the guest's fd {fd}: {}",
+				io::Error::from_raw_os_error(errno)
+			);
+			ret = -errno;
+		}
+	});
+	(synced, ret)
+}
+
+/// Handles a SetPriority hypercall by adjusting the niceness of the calling vCPU thread.
+///
+/// The requested niceness is clamped to `bounds`. Without bounds, the guest may only query the
+/// niceness and changes fail with `-EPERM`.
+#[cfg(target_os = "linux")]
+pub fn set_priority(
+	syssetpriority: &mut SetPriorityParams,
+	bounds: Option<&std::ops::RangeInclusive<i32>>,
+) {
+	let tid = unsafe { libc::gettid() } as libc::id_t;
+	if syssetpriority.set != 0 {
+		let Some(bounds) = bounds else {
+			syssetpriority.ret = -libc::EPERM;
+			return;
+		};
+		let nice = syssetpriority.nice.clamp(*bounds.start(), *bounds.end());
+		if unsafe { libc::setpriority(libc::PRIO_PROCESS, tid, nice) } < 0 {
+			syssetpriority.ret = -io::Error::last_os_error().raw_os_error().unwrap();
+			return;
+		}
+	}
+	// getpriority may legitimately return -1, so errors can only be told apart via errno.
+	unsafe { *libc::__errno_location() = 0 };
+	let nice = unsafe { libc::getpriority(libc::PRIO_PROCESS, tid) };
+	let errno = io::Error::last_os_error().raw_os_error().unwrap();
+	if nice == -1 && errno != 0 {
+		syssetpriority.ret = -errno;
+		return;
+	}
+	syssetpriority.nice = nice;
+	syssetpriority.ret = 0;
+}
+
+/// Handles a NanoSleep hypercall by sleeping the calling vCPU thread.
+///
+/// The sleep ends early if the thread is kicked, in which case the remaining time is reported to
+/// the guest and `false` is returned.
+pub fn nanosleep(sysnanosleep: &mut NanoSleepParams) -> bool {
+	let duration = Duration::from_nanos(sysnanosleep.duration_ns);
+	let request = libc::timespec {
+		tv_sec: duration.as_secs() as libc::time_t,
+		tv_nsec: duration.subsec_nanos().into(),
+	};
+	let mut remaining = libc::timespec {
+		tv_sec: 0,
+		tv_nsec: 0,
+	};
+	if unsafe { libc::nanosleep(&request, &mut remaining) } == 0 {
+		sysnanosleep.remaining_ns = 0;
+		sysnanosleep.ret = 0;
+		return true;
+	}
+
+	let errno = io::Error::last_os_error().raw_os_error().unwrap();
+	sysnanosleep.remaining_ns = remaining.tv_sec as u64 * 1_000_000_000 + remaining.tv_nsec as u64;
+	sysnanosleep.ret = -errno;
+	errno != libc::EINTR
+}
+
 /// Handles an close syscall by closing the file on the host.
-pub fn close(sysclose: &mut CloseParams) {
-	unsafe {
-		sysclose.ret = libc::close(sysclose.fd);
+///
+/// The standard streams are shared with uhyve and are therefore left open.
+pub fn close(sysclose: &mut CloseParams, fdmap: &FdMap) {
+	if let Some(host_fd) = fdmap.remove(sysclose.fd) {
+		if unsafe { libc::close(host_fd) } < 0 {
+			sysclose.ret = -io::Error::last_os_error().raw_os_error().unwrap();
+		} else {
+			sysclose.ret = 0;
+		}
+	} else if let Some(HostFd::Shared(_)) = fdmap.get(sysclose.fd) {
+		sysclose.ret = 0;
+	} else {
+		sysclose.ret = -libc::EBADF;
 	}
 }
 
 /// Handles an read syscall on the host.
-pub fn read(mem: &MmapMemory, sysread: &mut ReadPrams) {
-	unsafe {
-		let bytes_read = libc::read(
-			sysread.fd,
-			mem.host_address(virt_to_phys(sysread.buf, mem, BOOT_PML4).unwrap())
-				.unwrap() as *mut libc::c_void,
-			sysread.len,
-		);
-		if bytes_read >= 0 {
-			sysread.ret = bytes_read;
+///
+/// Fails with `-EFAULT` if the buffer is not entirely mapped, without reading anything.
+pub fn read(mem: &MmapMemory, sysread: &mut ReadPrams, fdmap: &FdMap) {
+	read_pages(mem, sysread, fdmap, false);
+}
+
+/// Handles a FileReadExact hypercall, which reads until the guest's buffer is full or the end of
+/// the file is reached.
+///
+/// Otherwise, it behaves like [`read`].
+pub fn read_exact(mem: &MmapMemory, sysread: &mut ReadPrams, fdmap: &FdMap) {
+	read_pages(mem, sysread, fdmap, true);
+}
+
+/// The maximum number of buffers of a single `readv` on Linux and macOS.
+const IOV_MAX: usize = 1024;
+
+/// Reads into the guest's buffer page by page. Unless `exact` is set, the pages are filled by a
+/// single host read.
+fn read_pages(mem: &MmapMemory, sysread: &mut ReadPrams, fdmap: &FdMap, exact: bool) {
+	let Some(fd) = fdmap.host_fd(sysread.fd) else {
+		sysread.ret = -(libc::EBADF as isize);
+		return;
+	};
+	let Some(mut pages) = (unsafe { guest_virt_slices(mem, sysread.buf, sysread.len).ok() }) else {
+		sysread.ret = -(libc::EFAULT as isize);
+		return;
+	};
+
+	if !exact {
+		// Reading the pages one by one could block on a pipe or socket after data was already
+		// returned, so they are read at once, which returns what is available.
+		let iovecs = pages
+			.iter_mut()
+			.take(IOV_MAX)
+			.map(|page| libc::iovec {
+				iov_base: page.as_mut_ptr().cast(),
+				iov_len: page.len(),
+			})
+			.collect::<Vec<_>>();
+		let ret = unsafe { libc::readv(fd, iovecs.as_ptr(), iovecs.len() as libc::c_int) };
+		sysread.ret = if ret < 0 {
+			-(io::Error::last_os_error().raw_os_error().unwrap() as isize)
 		} else {
-			sysread.ret = -1;
+			ret
+		};
+		return;
+	}
+
+	let mut bytes_read = 0;
+	'pages: for page in pages {
+		let mut offset = 0;
+		while offset < page.len() {
+			let rest = &mut page[offset..];
+			let step = unsafe { libc::read(fd, rest.as_mut_ptr().cast(), rest.len()) };
+			if step < 0 {
+				let err = io::Error::last_os_error();
+				if err.kind() == io::ErrorKind::Interrupted {
+					continue;
+				}
+				// Report the bytes already read, like a short read.
+				if bytes_read == 0 {
+					sysread.ret = -(err.raw_os_error().unwrap() as isize);
+					return;
+				}
+				break 'pages;
+			}
+			if step == 0 {
+				break 'pages;
+			}
+			bytes_read += step;
+			offset += step as usize;
 		}
 	}
+	sysread.ret = bytes_read;
 }
 
 /// Handles an write syscall on the host.
-pub fn write(mem: &MmapMemory, syswrite: &WriteParams) -> io::Result<()> {
-	let mut bytes_written: usize = 0;
-	while bytes_written != syswrite.len {
-		unsafe {
-			let step = libc::write(
-				syswrite.fd,
-				mem.host_address(
-					virt_to_phys(syswrite.buf + bytes_written as u64, mem, BOOT_PML4).unwrap(),
+///
+/// A write longer than the maximum write size of `fdmap` is truncated to it and reported to the
+/// guest as a short write. Writes to files count against the write quota of `fdmap`. A write
+/// exceeding the quota is refused as a whole and fails with `-EDQUOT`. Fails with `-EFAULT` if the
+/// buffer is not entirely mapped, without writing anything, and with `-EBADF` for unknown
+/// descriptors and the shared stdin. A write that fails after writing some bytes is reported as a
+/// short write, and only the bytes actually written count against the quota.
+///
+/// The buffer is written page by page. On descriptors opened with `O_APPEND`, the host appends
+/// each page atomically at the current end of the file, regardless of the file offset, so
+/// concurrent appends through several descriptors never overwrite each other. Only writes that
+/// fit into a single guest page are guaranteed not to be interleaved with other appends.
+///
+/// Writes to the shared stdout and stderr go to `serial`, together with the kernel's own output,
+/// so that the application's output is logged, collected or streamed like the rest of it.
+///
+/// `syswrite` has no room for an error, so a failed write is only reported as `0` bytes written.
+/// Guests that need the error use [`write2`].
+pub fn write(mem: &MmapMemory, syswrite: &mut WriteParams, fdmap: &FdMap, serial: &SerialOutput) {
+	syswrite.len =
+		write_pages(mem, syswrite.fd, syswrite.buf, syswrite.len, fdmap, serial).unwrap_or(0);
+}
+
+/// Handles a write syscall like [`write`], but reports errors to the guest in `syswrite.ret`.
+pub fn write2(mem: &MmapMemory, syswrite: &mut Write2Params, fdmap: &FdMap, serial: &SerialOutput) {
+	(syswrite.len, syswrite.ret) =
+		match write_pages(mem, syswrite.fd, syswrite.buf, syswrite.len, fdmap, serial) {
+			Ok(written) => (written, 0),
+			Err(errno) => (0, -errno),
+		};
+}
+
+/// Writes `len` bytes at `buf` to the guest descriptor `fd` for [`write`] and [`write2`] and
+/// returns the number of bytes written or the errno on failure.
+fn write_pages(
+	mem: &MmapMemory,
+	fd: i32,
+	buf: GuestVirtAddr,
+	len: usize,
+	fdmap: &FdMap,
+	serial: &SerialOutput,
+) -> Result<usize, i32> {
+	let host_fd = fdmap
+		.get(fd)
+		.filter(|fd| fd.is_writable())
+		.ok_or(libc::EBADF)?;
+	let len = fdmap.write_size(len);
+	// Validate the whole buffer up front to avoid partial writes.
+	let pages = unsafe { guest_virt_slices(mem, buf, len) }.map_err(|_| libc::EFAULT)?;
+	if let HostFd::Shared(_) = host_fd {
+		for page in pages {
+			serial
+				.write(page)
+				.map_err(|err| err.raw_os_error().unwrap_or(libc::EIO))?;
+		}
+		return Ok(len);
+	}
+	if !fdmap.reserve_write(len as u64) {
+		warn!("Refusing guest write of {len} bytes: write quota exhausted");
+		return Err(libc::EDQUOT);
+	}
+	let fd = host_fd.raw();
+	let mut written: usize = 0;
+	for page in pages {
+		let mut bytes_written: usize = 0;
+		while bytes_written != page.len() {
+			let step = unsafe {
+				libc::write(
+					fd,
+					page[bytes_written..].as_ptr().cast(),
+					page.len() - bytes_written,
 				)
-				.map_err(|e| match e {
-					MemoryError::BoundsViolation => {
-						unreachable!("Bounds violation after host_address function")
-					}
-					MemoryError::WrongMemoryError => {
-						Error::new(ErrorKind::AddrNotAvailable, e.to_string())
-					}
-				})? as *const libc::c_void,
-				syswrite.len - bytes_written,
-			);
+			};
 			if step >= 0 {
 				bytes_written += step as usize;
 			} else {
-				return Err(io::Error::last_os_error());
+				let errno = io::Error::last_os_error().raw_os_error().unwrap();
+				written += bytes_written;
+				fdmap.release_write((len - written) as u64);
+				return if written > 0 { Ok(written) } else { Err(errno) };
 			}
 		}
+		written += page.len();
 	}
 
-	Ok(())
+	Ok(written)
 }
 
-/// Handles an write syscall on the host.
-pub fn lseek(syslseek: &mut LseekParams) {
-	unsafe {
-		syslseek.offset =
-			libc::lseek(syslseek.fd, syslseek.offset as i64, syslseek.whence) as isize;
+/// Handles a Sendfile hypercall by copying between two host descriptors of the guest.
+///
+/// Like [`write`], the transfer counts against the write quota of `fdmap` and is refused as a
+/// whole with `-EDQUOT` if `count` exceeds the quota. Only the bytes actually transferred are
+/// charged. Only regular files are supported as source, other sources fail with `-EINVAL`. Hosts
+/// other than Linux fail with `-ENOSYS`.
+pub fn sendfile(syssendfile: &mut SendfileParams, fdmap: &FdMap) {
+	let (Some(out_fd), Some(in_fd)) = (
+		fdmap.get(syssendfile.out_fd).filter(|fd| fd.is_writable()),
+		fdmap.host_fd(syssendfile.in_fd),
+	) else {
+		syssendfile.ret = -(libc::EBADF as isize);
+		return;
+	};
+
+	#[cfg(target_os = "linux")]
+	sendfile_file(out_fd, in_fd, syssendfile, fdmap);
+	#[cfg(not(target_os = "linux"))]
+	{
+		let _ = (out_fd, in_fd);
+		syssendfile.ret = -(libc::ENOSYS as isize);
 	}
 }
 
-/// Handles an UART syscall by writing to stdout.
-pub fn uart(buf: &[u8]) -> io::Result<()> {
-	io::stdout().write_all(buf)
-}
+/// Copies from the regular file `in_fd` to `out_fd` for [`sendfile`].
+#[cfg(target_os = "linux")]
+fn sendfile_file(out_fd: HostFd, in_fd: RawFd, syssendfile: &mut SendfileParams, fdmap: &FdMap) {
+	let mut stat = MaybeUninit::<libc::stat>::uninit();
+	if unsafe { libc::fstat(in_fd, stat.as_mut_ptr()) } < 0 {
+		syssendfile.ret = -(io::Error::last_os_error().raw_os_error().unwrap() as isize);
+		return;
+	}
+	if unsafe { stat.assume_init() }.st_mode & libc::S_IFMT != libc::S_IFREG {
+		syssendfile.ret = -(libc::EINVAL as isize);
+		return;
+	}
+	// Like the serial output of `write`, the shared standard streams don't count against the quota.
+	let quota = !matches!(out_fd, HostFd::Shared(_));
+	if quota && !fdmap.reserve_write(syssendfile.count as u64) {
+		warn!(
+			"Refusing guest sendfile of {} bytes: write quota exhausted",
+			{ syssendfile.count }
+		);
+		syssendfile.ret = -(libc::EDQUOT as isize);
+		return;
+	}
 
-/// Copies the arguments of the application into the VM's memory to the destinations specified in `syscmdval`.
-pub fn copy_argv(path: &OsStr, argv: &[OsString], syscmdval: &CmdvalParams, mem: &MmapMemory) {
-	// copy kernel path as first argument
-	let argvp = mem
-		.host_address(syscmdval.argv)
-		.expect("Systemcall parameters for Cmdval are invalid") as *const GuestPhysAddr;
-	let arg_addrs = unsafe { std::slice::from_raw_parts(argvp, argv.len() + 1) };
+	let mut offset = syssendfile.offset;
+	let offset_ptr = if offset < 0 {
+		std::ptr::null_mut()
+	} else {
+		&mut offset as *mut i64
+	};
+	let ret = unsafe { libc::sendfile(out_fd.raw(), in_fd, offset_ptr, syssendfile.count) };
+	let transferred = if ret < 0 {
+		syssendfile.ret = -(io::Error::last_os_error().raw_os_error().unwrap() as isize);
+		0
+	} else {
+		syssendfile.offset = offset;
+		syssendfile.ret = ret;
+		ret as usize
+	};
+	if quota {
+		fdmap.release_write((syssendfile.count - transferred) as u64);
+	}
+}
 
-	{
-		let len = path.len();
-		// Safety: we drop path_dest before anything else is done with mem
-		let path_dest = unsafe {
-			mem.slice_at_mut(arg_addrs[0], len + 1)
-				.expect("Systemcall parameters for Cmdval are invalid")
-		};
+/// Handles an lseek syscall on the host.
+///
+/// Like all file hypercalls, it accepts the descriptors of [`FdMap::get`], which include the
+/// standard streams shared with uhyve. Seeking on them fails like on the host, e.g. with
+/// `-ESPIPE` for a terminal or pipe.
+///
+/// `syslseek` has no room for an error, so a failed seek is only reported as offset `-1`. Guests
+/// that need the error use [`lseek2`].
+pub fn lseek(syslseek: &mut LseekParams, fdmap: &FdMap) {
+	syslseek.offset = seek(syslseek.fd, syslseek.offset, syslseek.whence, fdmap).unwrap_or(-1);
+}
 
-		path_dest[0..len].copy_from_slice(path.as_bytes());
-		path_dest[len] = 0; // argv strings are zero terminated
+/// Handles an lseek syscall like [`lseek`], but reports errors to the guest in `syslseek.ret`.
+pub fn lseek2(syslseek: &mut Lseek2Params, fdmap: &FdMap) {
+	match seek(syslseek.fd, syslseek.offset, syslseek.whence, fdmap) {
+		Ok(offset) => {
+			syslseek.offset = offset;
+			syslseek.ret = 0;
+		}
+		Err(errno) => syslseek.ret = -errno,
 	}
+}
 
-	// Copy the application arguments into the vm memory
-	for (counter, argument) in argv.iter().enumerate() {
-		let len = argument.as_bytes().len();
-		let arg_dest = unsafe {
-			mem.slice_at_mut(arg_addrs[counter], len + 1)
-				.expect("Systemcall parameters for Cmdval are invalid")
-		};
-		arg_dest[0..len].copy_from_slice(argument.as_bytes());
-		arg_dest[len] = 0;
+/// Seeks in the guest descriptor `fd` for [`lseek`] and [`lseek2`] and returns the resulting
+/// offset or the errno on failure.
+fn seek(fd: i32, offset: isize, whence: i32, fdmap: &FdMap) -> Result<isize, i32> {
+	let fd = fdmap.host_fd(fd).ok_or(libc::EBADF)?;
+	if ![libc::SEEK_SET, libc::SEEK_CUR, libc::SEEK_END].contains(&whence) {
+		return Err(libc::EINVAL);
+	}
+	let offset = unsafe { libc::lseek(fd, offset as i64, whence) };
+	if offset >= 0 {
+		Ok(offset as isize)
+	} else {
+		Err(io::Error::last_os_error().raw_os_error().unwrap())
 	}
 }
 
-/// Copies the environment variables into the VM's memory to the destinations specified in `syscmdval`.
-pub fn copy_env(syscmdval: &CmdvalParams, mem: &MmapMemory) {
-	let env_len = std::env::vars_os().count();
-	let envp = mem
-		.host_address(syscmdval.envp)
+/// Handles an fadvise syscall by passing the advice on to the host.
+///
+/// The advice is purely advisory, so hosts without `posix_fadvise` only validate it.
+pub fn fadvise(sysfadvise: &mut FadviseParams, fdmap: &FdMap) {
+	let Some(fd) = fdmap.host_fd(sysfadvise.fd) else {
+		sysfadvise.ret = -libc::EBADF;
+		return;
+	};
+	#[cfg(target_os = "linux")]
+	let advice = match sysfadvise.advice {
+		FADVISE_NORMAL => libc::POSIX_FADV_NORMAL,
+		FADVISE_RANDOM => libc::POSIX_FADV_RANDOM,
+		FADVISE_SEQUENTIAL => libc::POSIX_FADV_SEQUENTIAL,
+		FADVISE_WILLNEED => libc::POSIX_FADV_WILLNEED,
+		FADVISE_DONTNEED => libc::POSIX_FADV_DONTNEED,
+		FADVISE_NOREUSE => libc::POSIX_FADV_NOREUSE,
+		_ => {
+			sysfadvise.ret = -libc::EINVAL;
+			return;
+		}
+	};
+	#[cfg(target_os = "linux")]
+	{
+		sysfadvise.ret =
+			-unsafe { libc::posix_fadvise(fd, sysfadvise.offset, sysfadvise.len, advice) };
+	}
+	#[cfg(not(target_os = "linux"))]
+	{
+		let _ = fd;
+		sysfadvise.ret = if (FADVISE_NORMAL..=FADVISE_NOREUSE).contains(&{ sysfadvise.advice }) {
+			0
+		} else {
+			-libc::EINVAL
+		};
+	}
+}
+
+/// Handles a MemBalloon hypercall by growing or shrinking the `committed` part of the guest
+/// memory.
+///
+/// The memory can't shrink below the `boot_memory_size`, as the kernel was loaded into it, or grow
+/// beyond `mem`. Committed memory is faulted in, released memory is returned to the host.
+pub fn mem_balloon(
+	mem: &MmapMemory,
+	sysballoon: &mut MemBalloonParams,
+	committed: &Mutex<usize>,
+	boot_memory_size: usize,
+) {
+	let mut committed = committed.lock().unwrap();
+	let requested = sysballoon.size;
+	// Unless the memory is resized, the guest learns the current size.
+	sysballoon.size = *committed;
+	let Some(size) = requested
+		.checked_next_multiple_of(GUEST_PAGE_SIZE as usize)
+		.filter(|size| (boot_memory_size..=mem.memory_size).contains(size))
+	else {
+		sysballoon.ret = -libc::EINVAL;
+		return;
+	};
+	let result = if size > *committed {
+		mem.populate(mem.guest_address + *committed as u64, size - *committed)
+	} else {
+		mem.discard(mem.guest_address + size as u64, *committed - size)
+	};
+	if let Err(err) = result {
+		warn!("Unable to resize the guest memory to {size:#x}: {err}");
+		sysballoon.ret = match err {
+			MemoryError::Advice(errno) => -(errno as i32),
+			_ => -libc::EINVAL,
+		};
+		return;
+	}
+	debug!("Guest memory resized from {:#x} to {size:#x}", *committed);
+	*committed = size;
+	sysballoon.size = size;
+	sysballoon.ret = 0;
+}
+
+/// Handles a TestResult hypercall by turning it into a [`TestReport`].
+///
+/// A name at an invalid guest address is dropped, the counts are reported in any case.
+pub fn test_result(mem: &MmapMemory, systestresult: &TestResultParams) -> TestReport {
+	let name = (systestresult.name_len > 0)
+		.then(|| {
+			let buf = guest_buf(mem, systestresult.name, systestresult.name_len);
+			if buf.is_none() {
+				warn!("Ignoring test suite name at invalid guest address {:#x}", {
+					systestresult.name
+				});
+			}
+			buf
+		})
+		.flatten()
+		.map(|name| String::from_utf8_lossy(&name).into_owned());
+	TestReport {
+		name,
+		passed: systestresult.passed,
+		failed: systestresult.failed,
+	}
+}
+
+/// Handles a ReportMetric hypercall.
+///
+/// Returns the name of the metric with the reported value. Fails with `-EFAULT` if the name is
+/// at an invalid guest address and with `-EINVAL` if it is empty, longer than
+/// [`MAX_METRIC_NAME_LEN`] or not valid UTF-8.
+pub fn report_metric(
+	mem: &MmapMemory,
+	sysmetric: &mut ReportMetricParams,
+) -> Option<(String, f64)> {
+	if sysmetric.name_len == 0 || sysmetric.name_len > MAX_METRIC_NAME_LEN {
+		sysmetric.ret = -libc::EINVAL;
+		return None;
+	}
+	let Some(buf) = guest_buf(mem, sysmetric.name, sysmetric.name_len) else {
+		sysmetric.ret = -libc::EFAULT;
+		return None;
+	};
+	let Ok(name) = std::str::from_utf8(&buf) else {
+		sysmetric.ret = -libc::EINVAL;
+		return None;
+	};
+	sysmetric.ret = 0;
+	Some((name.to_string(), sysmetric.value))
+}
+
+/// Handles a SetAppName hypercall by naming the calling host thread after the application.
+///
+/// Returns the name, if the guest provided a valid one.
+pub fn set_app_name(mem: &MmapMemory, sysappname: &SetAppNameParams) -> Option<String> {
+	let len = sysappname.len.min(MAX_APP_NAME_LEN);
+	let Some(buf) = guest_buf(mem, sysappname.name, len) else {
+		warn!("Ignoring app name at invalid guest address {:#x}", {
+			sysappname.name
+		});
+		return None;
+	};
+	let name = match std::str::from_utf8(&buf) {
+		Ok(name) => name,
+		// The name may have been truncated in the middle of a character
+		Err(err) if err.error_len().is_none() => {
+			std::str::from_utf8(&buf[..err.valid_up_to()]).unwrap()
+		}
+		Err(err) => {
+			warn!("Ignoring app name that is not valid UTF-8: {err}");
+			return None;
+		}
+	};
+	debug!("Guest application is called {name:?}");
+
+	#[cfg(target_os = "linux")]
+	{
+		// Linux limits thread names to 15 bytes plus the terminating zero.
+		let mut thread_name = name.as_bytes()[..name.len().min(15)].to_vec();
+		thread_name.retain(|&b| b != 0);
+		let thread_name = CString::new(thread_name).unwrap();
+		unsafe {
+			libc::pthread_setname_np(libc::pthread_self(), thread_name.as_ptr());
+		}
+	}
+
+	Some(name.to_owned())
+}
+
+/// Handles a LogMessage hypercall by forwarding the guest's message to the host's logger.
+///
+/// The records are emitted with the target `guest`. Messages above `max_level`, the level the
+/// guest set for its VM, are dropped.
+pub fn log_message(mem: &MmapMemory, syslog: &LogMessageParams, max_level: LevelFilter) {
+	let level = match syslog.level {
+		1 => log::Level::Error,
+		2 => log::Level::Warn,
+		3 => log::Level::Info,
+		4 => log::Level::Debug,
+		5 => log::Level::Trace,
+		level => {
+			warn!("Ignoring guest log message with invalid level {level}");
+			return;
+		}
+	};
+	if level > max_level {
+		return;
+	}
+	let Some(buf) = guest_buf(mem, syslog.buf, syslog.len) else {
+		warn!(
+			"Ignoring guest log message at invalid guest address {:#x}",
+			{ syslog.buf }
+		);
+		return;
+	};
+	log!(target: "guest", level, "{}", String::from_utf8_lossy(&buf));
+}
+
+/// Returns the log level the guest requested, capped to `ceiling`, or the error number to report.
+fn guest_log_level(level: u8, ceiling: Option<LevelFilter>) -> Result<LevelFilter, i32> {
+	let ceiling = ceiling.ok_or(libc::EPERM)?;
+	let level = match level {
+		0 => LevelFilter::Off,
+		1 => LevelFilter::Error,
+		2 => LevelFilter::Warn,
+		3 => LevelFilter::Info,
+		4 => LevelFilter::Debug,
+		5 => LevelFilter::Trace,
+		_ => return Err(libc::EINVAL),
+	};
+	Ok(level.min(ceiling))
+}
+
+/// Handles a SetLogLevel hypercall by changing the log level of the guest's VM, which is stored in
+/// `vm_level` and filters the guest's log messages.
+///
+/// The guest can't raise the level above `ceiling` and can't change it at all without one.
+/// Uhyve's own records are not affected, as they are filtered by the process-wide maximum level,
+/// which is shared by all VMs of the process.
+pub fn set_log_level(
+	syssetloglevel: &mut SetLogLevelParams,
+	ceiling: Option<LevelFilter>,
+	vm_level: &Mutex<LevelFilter>,
+) {
+	syssetloglevel.ret = match guest_log_level(syssetloglevel.level, ceiling) {
+		Ok(level) => {
+			info!("Guest set the log level to {level}");
+			*vm_level.lock().unwrap() = level;
+			level as i32
+		}
+		Err(errno) => -errno,
+	};
+}
+
+/// Handles a Panic hypercall by logging the guest's panic. Returns the panic message.
+///
+/// Invalid message or location buffers are replaced by placeholders, as the panic has to be
+/// reported in any case.
+pub fn panic(mem: &MmapMemory, syspanic: &PanicParams) -> String {
+	let message = match guest_buf(mem, syspanic.message, syspanic.message_len) {
+		Some(buf) => String::from_utf8_lossy(&buf).into_owned(),
+		None => String::from("<invalid panic message>"),
+	};
+	let file = (syspanic.file_len > 0)
+		.then(|| guest_buf(mem, syspanic.file, syspanic.file_len))
+		.flatten();
+	match file {
+		Some(file) => error!(
+			target: "guest",
+			"Guest panicked at {}:{}: {message}",
+			String::from_utf8_lossy(&file),
+			{ syspanic.line }
+		),
+		None => error!(target: "guest", "Guest panicked: {message}"),
+	}
+	message
+}
+
+/// Handles a Madvise hypercall by applying the advice to the host memory backing the guest range.
+///
+/// The whole range is translated before any memory is touched, so an invalid range leaves the
+/// guest memory unchanged.
+pub fn madvise(mem: &MmapMemory, sysmadvise: &mut MadviseParams) {
+	let advice = match sysmadvise.advice {
+		MADVISE_DONTNEED => libc::MADV_DONTNEED,
+		MADVISE_FREE => libc::MADV_FREE,
+		_ => {
+			sysmadvise.ret = -libc::EINVAL;
+			return;
+		}
+	};
+	let (addr, len) = (sysmadvise.addr, sysmadvise.len);
+	if addr.as_u64() % PAGE_SIZE as u64 != 0 || len % PAGE_SIZE != 0 {
+		sysmadvise.ret = -libc::EINVAL;
+		return;
+	}
+
+	let Some(pages) = (unsafe { guest_virt_slices(mem, addr, len).ok() }) else {
+		sysmadvise.ret = -libc::ENOMEM;
+		return;
+	};
+
+	for page in pages {
+		let res = unsafe { libc::madvise(page.as_mut_ptr().cast(), page.len(), advice) };
+		// Only Linux guarantees that private anonymous memory reads back as zero after
+		// `MADV_DONTNEED`. The advice may also fail if the host uses larger pages.
+		if advice == libc::MADV_DONTNEED && (res != 0 || cfg!(not(target_os = "linux"))) {
+			page.fill(0);
+		}
+	}
+	sysmadvise.ret = 0;
+}
+
+/// Handles an UART syscall by writing to the guest's serial output.
+pub fn uart(serial: &SerialOutput, buf: &[u8]) -> io::Result<()> {
+	serial.write(buf)
+}
+
+/// Handles a SetSerialMode hypercall by switching the forwarding of the serial output.
+pub fn set_serial_mode(serial: &SerialOutput, sysserialmode: &mut SetSerialModeParams) {
+	sysserialmode.ret = match sysserialmode.mode {
+		SERIAL_MODE_BUFFERED => {
+			serial.set_unbuffered(false);
+			0
+		}
+		SERIAL_MODE_UNBUFFERED => {
+			serial.set_unbuffered(true);
+			0
+		}
+		_ => -libc::EINVAL,
+	};
+}
+
+/// Copies the arguments of the application into the VM's memory to the destinations specified in `syscmdval`.
+pub fn copy_argv(path: &OsStr, argv: &[OsString], syscmdval: &CmdvalParams, mem: &MmapMemory) {
+	// copy kernel path as first argument
+	let argvp = mem
+		.host_address(syscmdval.argv)
+		.expect("Systemcall parameters for Cmdval are invalid") as *const GuestPhysAddr;
+	let arg_addrs = unsafe { std::slice::from_raw_parts(argvp, argv.len() + 1) };
+
+	{
+		let len = path.len();
+		// Safety: we drop path_dest before anything else is done with mem
+		let path_dest = unsafe {
+			mem.slice_at_mut(arg_addrs[0], len + 1)
+				.expect("Systemcall parameters for Cmdval are invalid")
+		};
+
+		path_dest[0..len].copy_from_slice(path.as_bytes());
+		path_dest[len] = 0; // argv strings are zero terminated
+	}
+
+	// Copy the application arguments into the vm memory
+	for (counter, argument) in argv.iter().enumerate() {
+		let len = argument.as_bytes().len();
+		let arg_dest = unsafe {
+			mem.slice_at_mut(arg_addrs[counter], len + 1)
+				.expect("Systemcall parameters for Cmdval are invalid")
+		};
+		arg_dest[0..len].copy_from_slice(argument.as_bytes());
+		arg_dest[len] = 0;
+	}
+}
+
+/// Copies the environment variables into the VM's memory to the destinations specified in `syscmdval`.
+pub fn copy_env(syscmdval: &CmdvalParams, mem: &MmapMemory, env: &[(OsString, OsString)]) {
+	let env_len = env.len().min(MAX_ARGC_ENVC);
+	let envp = mem
+		.host_address(syscmdval.envp)
 		.expect("Systemcall parameters for Cmdval are invalid") as *const GuestPhysAddr;
 	let env_addrs = unsafe { std::slice::from_raw_parts(envp, env_len) };
 
 	// Copy the environment variables into the vm memory
-	for (counter, (key, value)) in std::env::vars_os().enumerate() {
+	for (counter, (key, value)) in env.iter().enumerate() {
 		if counter >= MAX_ARGC_ENVC.try_into().unwrap() {
 			warn!("Environment is larger than the maximum that can be copied to the VM. Remaining environment is ignored");
 			break;
@@ -216,3 +1542,2258 @@ pub fn copy_env(syscmdval: &CmdvalParams, mem: &MmapMemory) {
 		env_dest[len] = 0;
 	}
 }
+
+/// Handles a notification of the guest by passing the event to the `hook`.
+///
+/// Events with a payload outside of the guest memory are dropped.
+pub fn notify(mem: &MmapMemory, sysnotify: &NotifyParams, hook: Option<&EventHook>) {
+	let event = sysnotify.event;
+	let payload = if sysnotify.payload_len == 0 {
+		Vec::new()
+	} else {
+		match guest_buf(mem, sysnotify.payload, sysnotify.payload_len) {
+			Some(payload) => payload,
+			None => {
+				warn!("Dropping guest event {event}: invalid payload");
+				return;
+			}
+		}
+	};
+	debug!(
+		"Guest event {event} with {} bytes of payload",
+		payload.len()
+	);
+	if let Some(hook) = hook {
+		hook.call(event, &payload);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{
+		io::{Read, Write},
+		net::TcpStream,
+		os::fd::AsRawFd,
+		sync::{Arc, Mutex},
+		time::Instant,
+	};
+
+	use super::*;
+	use crate::{
+		arch::init_guest_mem,
+		consts::{GUEST_PAGE_SIZE, MAX_IDENTITY_MAP_LENGTH},
+		serial::TcpOutput,
+		test_utils::{capture_logs, captured_logs},
+	};
+
+	/// Creates a guest memory with initialized pagetables.
+	fn guest_mem() -> MmapMemory {
+		let mem = MmapMemory::new(
+			0,
+			4 * GUEST_PAGE_SIZE as usize,
+			GuestPhysAddr::new(0),
+			false,
+			false,
+		);
+		init_guest_mem(unsafe { mem.as_slice_mut() }, MAX_IDENTITY_MAP_LENGTH, &[]);
+		mem
+	}
+
+	#[test]
+	fn test_guest_buf() {
+		let mem = MmapMemory::new(
+			0,
+			4 * GUEST_PAGE_SIZE as usize,
+			GuestPhysAddr::new(0),
+			false,
+			false,
+		);
+		// Only the first 2 MiB page is mapped
+		init_guest_mem(unsafe { mem.as_slice_mut() }, GUEST_PAGE_SIZE, &[]);
+		let end = GUEST_PAGE_SIZE - 4;
+		unsafe { mem.slice_at_mut(GuestPhysAddr::new(end), 8) }
+			.unwrap()
+			.copy_from_slice(b"pagepage");
+
+		// Buffers may cross page boundaries
+		let addr = GuestVirtAddr::new(end - PAGE_SIZE as u64);
+		let buf = guest_buf(&mem, addr, PAGE_SIZE + 4).unwrap();
+		assert_eq!(&buf[PAGE_SIZE..], b"page");
+		// Every page of the buffer has to be mapped
+		assert_eq!(guest_buf(&mem, GuestVirtAddr::new(end), 8), None);
+	}
+
+	#[test]
+	fn test_set_app_name() {
+		let mem = guest_mem();
+		let addr = GuestVirtAddr::new(GUEST_PAGE_SIZE);
+		let name = "uhyve-app";
+		unsafe { mem.slice_at_mut(GuestPhysAddr::new(GUEST_PAGE_SIZE), name.len()) }
+			.unwrap()
+			.copy_from_slice(name.as_bytes());
+
+		let sysappname = SetAppNameParams {
+			name: addr,
+			len: name.len(),
+		};
+		assert_eq!(set_app_name(&mem, &sysappname).as_deref(), Some(name));
+
+		// Overlong names are truncated
+		let sysappname = SetAppNameParams {
+			name: addr,
+			len: MAX_APP_NAME_LEN + 1,
+		};
+		assert_eq!(
+			set_app_name(&mem, &sysappname).unwrap().len(),
+			MAX_APP_NAME_LEN
+		);
+
+		// Names outside of the guest memory are rejected
+		let sysappname = SetAppNameParams {
+			name: GuestVirtAddr::new(3 * GUEST_PAGE_SIZE + GUEST_PAGE_SIZE - 1),
+			len: name.len(),
+		};
+		assert_eq!(set_app_name(&mem, &sysappname), None);
+	}
+
+	#[test]
+	fn test_madvise() {
+		let mem = guest_mem();
+		let phys = GuestPhysAddr::new(GUEST_PAGE_SIZE);
+		let len = 4 * PAGE_SIZE;
+		unsafe { mem.slice_at_mut(phys, len) }.unwrap().fill(0xAB);
+
+		let mut sysmadvise = MadviseParams {
+			addr: GuestVirtAddr::new(GUEST_PAGE_SIZE),
+			len,
+			advice: MADVISE_DONTNEED,
+			ret: -1,
+		};
+		madvise(&mem, &mut sysmadvise);
+		assert_eq!({ sysmadvise.ret }, 0);
+		assert!(unsafe { mem.slice_at(phys, len) }
+			.unwrap()
+			.iter()
+			.all(|&byte| byte == 0));
+
+		// Unaligned ranges are rejected
+		let mut sysmadvise = MadviseParams {
+			addr: GuestVirtAddr::new(GUEST_PAGE_SIZE + 1),
+			len,
+			advice: MADVISE_DONTNEED,
+			ret: 0,
+		};
+		madvise(&mem, &mut sysmadvise);
+		assert_eq!({ sysmadvise.ret }, -libc::EINVAL);
+
+		// Ranges exceeding the guest memory are rejected without touching the valid part
+		let page = GuestPhysAddr::new(4 * GUEST_PAGE_SIZE - 2 * PAGE_SIZE as u64);
+		unsafe { mem.slice_at_mut(page, PAGE_SIZE) }
+			.unwrap()
+			.fill(0xAB);
+		let mut sysmadvise = MadviseParams {
+			addr: GuestVirtAddr::new(page.as_u64()),
+			len: 3 * PAGE_SIZE,
+			advice: MADVISE_DONTNEED,
+			ret: 0,
+		};
+		madvise(&mem, &mut sysmadvise);
+		assert_eq!({ sysmadvise.ret }, -libc::ENOMEM);
+		assert!(unsafe { mem.slice_at(page, PAGE_SIZE) }
+			.unwrap()
+			.iter()
+			.all(|&byte| byte == 0xAB));
+	}
+
+	#[test]
+	fn test_unmapped_buffer() {
+		let mem = guest_mem();
+		// The buffer starts on the last page of the guest memory and extends beyond it
+		let buf = GuestVirtAddr::new(4 * GUEST_PAGE_SIZE - PAGE_SIZE as u64 - 8);
+		let len = PAGE_SIZE + 16;
+
+		let dir = assert_fs::TempDir::new().unwrap();
+		let path = dir.path().join("efault.txt");
+		std::fs::write(&path, [b'a'; 16]).unwrap();
+		let file = std::fs::OpenOptions::new()
+			.read(true)
+			.write(true)
+			.open(&path)
+			.unwrap();
+		let fdmap = FdMap::new();
+		fdmap.insert(file.as_raw_fd()).unwrap();
+
+		let mut syswrite = Write2Params {
+			fd: file.as_raw_fd(),
+			buf,
+			len,
+			ret: 0,
+		};
+		write2(
+			&mem,
+			&mut syswrite,
+			&fdmap,
+			&SerialOutput::new(None, false, false),
+		);
+		assert_eq!({ syswrite.ret }, -libc::EFAULT);
+		assert_eq!({ syswrite.len }, 0);
+		assert_eq!(std::fs::read(&path).unwrap(), [b'a'; 16]);
+
+		let mut sysread = ReadPrams {
+			fd: file.as_raw_fd(),
+			buf,
+			len,
+			ret: 0,
+		};
+		read(&mem, &mut sysread, &fdmap);
+		assert_eq!({ sysread.ret }, -(libc::EFAULT as isize));
+		assert_eq!(
+			unsafe { libc::lseek(file.as_raw_fd(), 0, libc::SEEK_CUR) },
+			0
+		);
+	}
+
+	#[test]
+	fn test_read_available() {
+		let mem = guest_mem();
+		let buf = GuestVirtAddr::new(GUEST_PAGE_SIZE);
+		let data = (0..PAGE_SIZE).map(|i| i as u8).collect::<Vec<_>>();
+
+		// A pipe holding a single page doesn't block a read into a larger buffer
+		let (reader, mut writer) = std::io::pipe().unwrap();
+		writer.write_all(&data).unwrap();
+		let fdmap = FdMap::new();
+		fdmap.insert(reader.as_raw_fd()).unwrap();
+		let mut sysread = ReadPrams {
+			fd: reader.as_raw_fd(),
+			buf,
+			len: 2 * PAGE_SIZE,
+			ret: 0,
+		};
+		read(&mem, &mut sysread, &fdmap);
+		assert_eq!({ sysread.ret }, PAGE_SIZE as isize);
+		assert_eq!(guest_buf(&mem, buf, PAGE_SIZE).unwrap(), data);
+	}
+
+	#[test]
+	fn test_read_exact() {
+		let mem = guest_mem();
+		let buf = GuestVirtAddr::new(GUEST_PAGE_SIZE);
+		let len = 2 * PAGE_SIZE;
+		let data = (0..len).map(|i| i as u8).collect::<Vec<_>>();
+
+		// A pipe that is filled in chunks delivers short reads
+		let (reader, mut writer) = std::io::pipe().unwrap();
+		let fdmap = FdMap::new();
+		fdmap.insert(reader.as_raw_fd()).unwrap();
+		let writer_thread = {
+			let data = data.clone();
+			std::thread::spawn(move || {
+				for chunk in data.chunks(1000) {
+					writer.write_all(chunk).unwrap();
+					std::thread::sleep(Duration::from_millis(10));
+				}
+			})
+		};
+
+		let mut sysread = ReadPrams {
+			fd: reader.as_raw_fd(),
+			buf,
+			len,
+			ret: 0,
+		};
+		read_exact(&mem, &mut sysread, &fdmap);
+		assert_eq!({ sysread.ret }, len as isize);
+		assert_eq!(guest_buf(&mem, buf, len).unwrap(), data);
+		writer_thread.join().unwrap();
+
+		// The end of the file ends the read early
+		let path = assert_fs::NamedTempFile::new("short.txt").unwrap();
+		std::fs::write(path.path(), &data[..100]).unwrap();
+		let file = std::fs::File::open(path.path()).unwrap();
+		fdmap.insert(file.as_raw_fd()).unwrap();
+		sysread.fd = file.as_raw_fd();
+		read_exact(&mem, &mut sysread, &fdmap);
+		assert_eq!({ sysread.ret }, 100);
+		assert_eq!(guest_buf(&mem, buf, 100).unwrap(), &data[..100]);
+	}
+
+	#[test]
+	#[cfg(target_os = "linux")]
+	fn test_sendfile() {
+		let dir = assert_fs::TempDir::new().unwrap();
+		let src_path = dir.path().join("src.txt");
+		std::fs::write(&src_path, b"hello sendfile").unwrap();
+		let src = std::fs::File::open(&src_path).unwrap();
+		let dst_path = dir.path().join("dst.txt");
+		let dst = std::fs::File::create(&dst_path).unwrap();
+		let fdmap = FdMap::new();
+		fdmap.insert(src.as_raw_fd()).unwrap();
+		fdmap.insert(dst.as_raw_fd()).unwrap();
+
+		let mut syssendfile = SendfileParams {
+			out_fd: dst.as_raw_fd(),
+			in_fd: src.as_raw_fd(),
+			offset: 6,
+			count: 64,
+			ret: 0,
+		};
+		sendfile(&mut syssendfile, &fdmap);
+		assert_eq!({ syssendfile.ret }, 8);
+		assert_eq!({ syssendfile.offset }, 14);
+		// A negative offset uses the file offset of the source
+		syssendfile.offset = -1;
+		syssendfile.count = 5;
+		sendfile(&mut syssendfile, &fdmap);
+		assert_eq!({ syssendfile.ret }, 5);
+		assert_eq!(std::fs::read(&dst_path).unwrap(), b"sendfilehello");
+
+		// Transfers exceeding the write quota are refused as a whole
+		let quota_fdmap = FdMap::with_write_quota(Some(4));
+		quota_fdmap.insert(src.as_raw_fd()).unwrap();
+		quota_fdmap.insert(dst.as_raw_fd()).unwrap();
+		sendfile(&mut syssendfile, &quota_fdmap);
+		assert_eq!({ syssendfile.ret }, -(libc::EDQUOT as isize));
+		assert_eq!(std::fs::read(&dst_path).unwrap(), b"sendfilehello");
+
+		// Only the transferred bytes count against the quota
+		let quota_fdmap = FdMap::with_write_quota(Some(16));
+		quota_fdmap.insert(src.as_raw_fd()).unwrap();
+		quota_fdmap.insert(dst.as_raw_fd()).unwrap();
+		syssendfile.offset = 10;
+		syssendfile.count = 10;
+		sendfile(&mut syssendfile, &quota_fdmap);
+		assert_eq!({ syssendfile.ret }, 4);
+		syssendfile.offset = 0;
+		sendfile(&mut syssendfile, &quota_fdmap);
+		assert_eq!({ syssendfile.ret }, 10);
+
+		// Pipes are not supported as source
+		let (reader, _writer) = std::io::pipe().unwrap();
+		fdmap.insert(reader.as_raw_fd()).unwrap();
+		syssendfile.in_fd = reader.as_raw_fd();
+		sendfile(&mut syssendfile, &fdmap);
+		assert_eq!({ syssendfile.ret }, -(libc::EINVAL as isize));
+
+		syssendfile.in_fd = src.as_raw_fd();
+		syssendfile.out_fd = 1000;
+		sendfile(&mut syssendfile, &fdmap);
+		assert_eq!({ syssendfile.ret }, -(libc::EBADF as isize));
+		syssendfile.out_fd = libc::STDIN_FILENO;
+		sendfile(&mut syssendfile, &fdmap);
+		assert_eq!({ syssendfile.ret }, -(libc::EBADF as isize));
+	}
+
+	#[test]
+	#[cfg(target_os = "linux")]
+	fn test_set_priority() {
+		// Run on a separate thread, as the niceness of the test thread can't be restored
+		// without privileges.
+		std::thread::spawn(|| {
+			let niceness = || unsafe { libc::getpriority(libc::PRIO_PROCESS, libc::gettid() as _) };
+			let base = niceness();
+			let bounds = base..=(base + 4).min(19);
+
+			let mut syssetpriority = SetPriorityParams {
+				set: 1,
+				nice: base + 2,
+				ret: -1,
+			};
+			set_priority(&mut syssetpriority, Some(&bounds));
+			assert_eq!({ syssetpriority.ret }, 0);
+			assert_eq!({ syssetpriority.nice }, base + 2);
+			assert_eq!(niceness(), base + 2);
+
+			// Out of range requests are clamped
+			let mut syssetpriority = SetPriorityParams {
+				set: 1,
+				nice: -20,
+				ret: -1,
+			};
+			set_priority(&mut syssetpriority, Some(&bounds));
+			assert_eq!({ syssetpriority.ret }, 0);
+			assert_eq!({ syssetpriority.nice }, base);
+			assert_eq!(niceness(), base);
+
+			let mut syssetpriority = SetPriorityParams {
+				set: 1,
+				nice: base + 1,
+				ret: -1,
+			};
+			set_priority(&mut syssetpriority, None);
+			assert_eq!({ syssetpriority.ret }, -libc::EPERM);
+			assert_eq!(niceness(), base);
+
+			// Queries are allowed without bounds
+			let mut syssetpriority = SetPriorityParams {
+				set: 0,
+				nice: 0,
+				ret: -1,
+			};
+			set_priority(&mut syssetpriority, None);
+			assert_eq!({ syssetpriority.ret }, 0);
+			assert_eq!({ syssetpriority.nice }, base);
+		})
+		.join()
+		.unwrap();
+	}
+
+	#[test]
+	fn test_sync_all() {
+		let dir = assert_fs::TempDir::new().unwrap();
+		let fdmap = FdMap::new();
+		let files = (0..3)
+			.map(|i| {
+				let mut file = std::fs::File::create(dir.path().join(format!("{i}.txt"))).unwrap();
+				file.write_all(b"durable").unwrap();
+				fdmap.insert(file.as_raw_fd()).unwrap();
+				file
+			})
+			.collect::<Vec<_>>();
+		// Read-only descriptors and descriptors that can't be synchronized are skipped
+		let read_only = std::fs::File::open(dir.path().join("0.txt")).unwrap();
+		fdmap.insert(read_only.as_raw_fd()).unwrap();
+		let (_reader, writer) = std::io::pipe().unwrap();
+		fdmap.insert(writer.as_raw_fd()).unwrap();
+
+		let mut syssyncall = SyncAllParams {
+			sync_host: 0,
+			synced: 0,
+			ret: -1,
+		};
+		sync_all(&mut syssyncall, &fdmap);
+		assert_eq!({ syssyncall.ret }, 0);
+		assert_eq!({ syssyncall.synced }, files.len() as u32);
+	}
+
+	#[test]
+	fn test_unix_socket() {
+		let mem = guest_mem();
+		let name = GuestPhysAddr::new(GUEST_PAGE_SIZE);
+		unsafe { mem.slice_at_mut(name, 7) }
+			.unwrap()
+			.copy_from_slice(b"daemon\0");
+		let dir = assert_fs::TempDir::new().unwrap();
+		let socket = dir.path().join("daemon.sock");
+		let listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+		let unix_sockets = [(PathBuf::from("daemon"), socket)];
+		let proc_info = ProcInfo {
+			num_cpus: 1,
+			memory_size: 1 << 20,
+			cpu_freq: None,
+			uptime: Default::default(),
+			virtual_files: &[],
+			unix_sockets: &unix_sockets,
+		};
+		let fdmap = FdMap::new();
+		let mut sysopen = OpenParams {
+			name,
+			flags: libc::O_RDWR,
+			mode: 0,
+			ret: -1,
+		};
+		open(
+			&mem,
+			&mut sysopen,
+			// The socket lies outside of the host root
+			Some(&dir.path().join("root")),
+			Path::new(""),
+			&fdmap,
+			Some(&proc_info),
+			None,
+		);
+		let fd = sysopen.ret;
+		assert!(fd > 2);
+		let (mut daemon, _) = listener.accept().unwrap();
+
+		let buf = GuestVirtAddr::new(2 * GUEST_PAGE_SIZE);
+		let guest_buf = GuestPhysAddr::new(buf.as_u64());
+		unsafe { mem.slice_at_mut(guest_buf, 4) }
+			.unwrap()
+			.copy_from_slice(b"ping");
+		write(
+			&mem,
+			&mut WriteParams { fd, buf, len: 4 },
+			&fdmap,
+			&SerialOutput::new(None, false, false),
+		);
+		let mut request = [0; 4];
+		daemon.read_exact(&mut request).unwrap();
+		assert_eq!(&request, b"ping");
+
+		daemon.write_all(b"pong").unwrap();
+		let mut sysread = ReadPrams {
+			fd,
+			buf,
+			len: 4,
+			ret: 0,
+		};
+		read(&mem, &mut sysread, &fdmap);
+		assert_eq!({ sysread.ret }, 4);
+		assert_eq!(unsafe { mem.slice_at(guest_buf, 4) }.unwrap(), b"pong");
+	}
+
+	#[test]
+	fn test_independent_offsets() {
+		let mem = guest_mem();
+		let dir = assert_fs::TempDir::new().unwrap();
+		std::fs::write(dir.path().join("shared.txt"), b"0123456789").unwrap();
+		let fdmap = FdMap::new();
+		let name = GuestPhysAddr::new(2 * GUEST_PAGE_SIZE);
+		unsafe { mem.slice_at_mut(name, 11) }
+			.unwrap()
+			.copy_from_slice(b"shared.txt\0");
+		let open_shared = || {
+			let mut sysopen = OpenParams {
+				name,
+				flags: libc::O_RDONLY,
+				mode: 0,
+				ret: -1,
+			};
+			open(
+				&mem,
+				&mut sysopen,
+				Some(dir.path()),
+				Path::new(""),
+				&fdmap,
+				None,
+				None,
+			);
+			assert!(sysopen.ret > 2);
+			sysopen.ret
+		};
+		let (first, second) = (open_shared(), open_shared());
+		assert_ne!(first, second);
+
+		let buf = GuestVirtAddr::new(GUEST_PAGE_SIZE);
+		let read_from = |fd, len| {
+			let mut sysread = ReadPrams {
+				fd,
+				buf,
+				len,
+				ret: -1,
+			};
+			read(&mem, &mut sysread, &fdmap);
+			unsafe { mem.slice_at(GuestPhysAddr::new(buf.as_u64()), sysread.ret as usize) }
+				.unwrap()
+				.to_vec()
+		};
+		// Each descriptor advances its own offset
+		assert_eq!(read_from(first, 4), b"0123");
+		assert_eq!(read_from(second, 2), b"01");
+		assert_eq!(read_from(first, 3), b"456");
+		assert_eq!(read_from(second, 4), b"2345");
+		assert_eq!(read_from(first, 10), b"789");
+
+		for fd in [first, second] {
+			close(&mut CloseParams { fd, ret: -1 }, &fdmap);
+		}
+	}
+
+	#[test]
+	fn test_guest_devices() {
+		let mem = guest_mem();
+		let dir = assert_fs::TempDir::new().unwrap();
+		let fdmap = FdMap::new();
+		let buf = GuestVirtAddr::new(3 * GUEST_PAGE_SIZE);
+		let guest_buf = GuestPhysAddr::new(buf.as_u64());
+		let open_device = |path: &[u8], flags| {
+			let name = GuestPhysAddr::new(GUEST_PAGE_SIZE);
+			unsafe { mem.slice_at_mut(name, path.len()) }
+				.unwrap()
+				.copy_from_slice(path);
+			let mut sysopen = OpenParams {
+				name,
+				flags,
+				mode: 0,
+				ret: -1,
+			};
+			// The devices lie outside of the host root
+			open(
+				&mem,
+				&mut sysopen,
+				Some(dir.path()),
+				Path::new(""),
+				&fdmap,
+				None,
+				None,
+			);
+			sysopen.ret
+		};
+
+		let zero = open_device(b"/dev/zero\0", libc::O_RDWR);
+		assert!(zero > 2);
+		unsafe { mem.slice_at_mut(guest_buf, 64) }
+			.unwrap()
+			.fill(0xff);
+		let mut sysread = ReadPrams {
+			fd: zero,
+			buf,
+			len: 64,
+			ret: 0,
+		};
+		read(&mem, &mut sysread, &fdmap);
+		assert_eq!({ sysread.ret }, 64);
+		assert_eq!(unsafe { mem.slice_at(guest_buf, 64) }.unwrap(), [0; 64]);
+
+		// Seeking succeeds without effect
+		let mut syslseek = Lseek2Params {
+			fd: zero,
+			offset: 16,
+			whence: libc::SEEK_SET,
+			ret: 1,
+		};
+		lseek2(&mut syslseek, &fdmap);
+		assert_eq!({ syslseek.ret }, 0);
+
+		let null = open_device(b"/dev/null\0", libc::O_RDWR);
+		assert!(null > 2);
+		let mut syswrite = WriteParams {
+			fd: null,
+			buf,
+			len: 64,
+		};
+		write(
+			&mem,
+			&mut syswrite,
+			&fdmap,
+			&SerialOutput::new(None, false, false),
+		);
+		assert_eq!({ syswrite.len }, 64);
+		let mut sysread = ReadPrams {
+			fd: null,
+			buf,
+			len: 64,
+			ret: -1,
+		};
+		read(&mem, &mut sysread, &fdmap);
+		assert_eq!({ sysread.ret }, 0);
+
+		// The host's entropy pool can't be written to
+		assert_eq!(open_device(b"/dev/urandom\0", libc::O_RDWR), -libc::EACCES);
+		let urandom = open_device(b"/dev/urandom\0", libc::O_RDONLY);
+		assert!(urandom > 2);
+		let mut sysread = ReadPrams {
+			fd: urandom,
+			buf,
+			len: 64,
+			ret: 0,
+		};
+		read(&mem, &mut sysread, &fdmap);
+		assert_eq!({ sysread.ret }, 64);
+	}
+
+	#[test]
+	fn test_proc_files() {
+		let mem = guest_mem();
+		let name = GuestPhysAddr::new(GUEST_PAGE_SIZE);
+		unsafe { mem.slice_at_mut(name, 14) }
+			.unwrap()
+			.copy_from_slice(b"/proc/meminfo\0");
+		let proc_info = ProcInfo {
+			num_cpus: 1,
+			memory_size: 1 << 20,
+			cpu_freq: None,
+			uptime: Default::default(),
+			virtual_files: &[],
+			unix_sockets: &[],
+		};
+		let fdmap = FdMap::new();
+		// The host root doesn't matter for synthetic files
+		let root = assert_fs::TempDir::new().unwrap();
+		let open_proc = |flags| {
+			let mut sysopen = OpenParams {
+				name,
+				flags,
+				mode: 0,
+				ret: -1,
+			};
+			open(
+				&mem,
+				&mut sysopen,
+				Some(root.path()),
+				Path::new(""),
+				&fdmap,
+				Some(&proc_info),
+				None,
+			);
+			sysopen.ret
+		};
+
+		assert_eq!(open_proc(libc::O_RDWR), -libc::EACCES);
+		let fd = open_proc(libc::O_RDONLY);
+		assert!(fd > 2);
+
+		let buf = GuestVirtAddr::new(2 * GUEST_PAGE_SIZE);
+		let mut sysread = ReadPrams {
+			fd,
+			buf,
+			len: 64,
+			ret: 0,
+		};
+		read(&mem, &mut sysread, &fdmap);
+		assert_eq!(
+			unsafe { mem.slice_at(GuestPhysAddr::new(buf.as_u64()), sysread.ret as usize) }
+				.unwrap(),
+			b"MemTotal:\t1024 kB\n"
+		);
+		// The file is sealed
+		let mut syswrite = Write2Params {
+			fd,
+			buf,
+			len: 4,
+			ret: 0,
+		};
+		write2(
+			&mem,
+			&mut syswrite,
+			&fdmap,
+			&SerialOutput::new(None, false, false),
+		);
+		assert!({ syswrite.ret } < 0);
+		fdmap.close_all();
+	}
+
+	#[test]
+	fn test_inherited_fd() {
+		let mem = guest_mem();
+		let (reader, mut writer) = std::io::pipe().unwrap();
+		writer.write_all(b"inherited").unwrap();
+		let fdmap = FdMap::new();
+		fdmap.inherit(10, reader.as_raw_fd()).unwrap();
+
+		let mut sysread = ReadPrams {
+			fd: 10,
+			buf: GuestVirtAddr::new(GUEST_PAGE_SIZE),
+			len: 64,
+			ret: 0,
+		};
+		read(&mem, &mut sysread, &fdmap);
+		assert_eq!({ sysread.ret }, 9);
+		assert_eq!(
+			unsafe { mem.slice_at(GuestPhysAddr::new(GUEST_PAGE_SIZE), 9) }.unwrap(),
+			b"inherited"
+		);
+
+		// Closing the guest fd leaves the host's end of the pipe open
+		let mut sysclose = CloseParams { fd: 10, ret: -1 };
+		close(&mut sysclose, &fdmap);
+		assert_eq!({ sysclose.ret }, 0);
+		read(&mem, &mut sysread, &fdmap);
+		assert_eq!({ sysread.ret }, -(libc::EBADF as isize));
+		writer.write_all(b"!").unwrap();
+		let mut buf = [0; 1];
+		assert_eq!((&reader).read(&mut buf).unwrap(), 1);
+	}
+
+	#[test]
+	fn test_notify() {
+		let mem = guest_mem();
+		let payload = GuestVirtAddr::new(GUEST_PAGE_SIZE);
+		unsafe { mem.slice_at_mut(GuestPhysAddr::new(GUEST_PAGE_SIZE), 5) }
+			.unwrap()
+			.copy_from_slice(b"ready");
+
+		let events = Arc::new(Mutex::new(Vec::new()));
+		let hook = {
+			let events = events.clone();
+			EventHook::new(move |event, payload| {
+				events.lock().unwrap().push((event, payload.to_vec()))
+			})
+		};
+
+		let sysnotify = NotifyParams {
+			event: 1,
+			payload,
+			payload_len: 5,
+		};
+		notify(&mem, &sysnotify, Some(&hook));
+		let sysnotify = NotifyParams {
+			event: 2,
+			payload: GuestVirtAddr::zero(),
+			payload_len: 0,
+		};
+		notify(&mem, &sysnotify, Some(&hook));
+
+		// Events with a payload outside of the guest memory are dropped
+		let sysnotify = NotifyParams {
+			event: 3,
+			payload: GuestVirtAddr::new(4 * GUEST_PAGE_SIZE),
+			payload_len: 5,
+		};
+		notify(&mem, &sysnotify, Some(&hook));
+
+		assert_eq!(
+			*events.lock().unwrap(),
+			[(1, b"ready".to_vec()), (2, Vec::new())]
+		);
+	}
+
+	#[test]
+	fn test_lseek() {
+		let dir = assert_fs::TempDir::new().unwrap();
+		let path = dir.path().join("lseek.txt");
+		std::fs::write(&path, [b'a'; 16]).unwrap();
+		let file = std::fs::File::open(&path).unwrap();
+		let fdmap = FdMap::new();
+		fdmap.insert(file.as_raw_fd()).unwrap();
+
+		let seek = |fd, offset, whence| {
+			let mut syslseek = Lseek2Params {
+				fd,
+				offset,
+				whence,
+				ret: 1,
+			};
+			lseek2(&mut syslseek, &fdmap);
+			({ syslseek.ret }, { syslseek.offset })
+		};
+
+		assert_eq!(seek(file.as_raw_fd(), -1, libc::SEEK_END), (0, 15));
+		assert_eq!(seek(file.as_raw_fd(), 4, libc::SEEK_SET), (0, 4));
+		assert_eq!(
+			seek(file.as_raw_fd(), -1, libc::SEEK_SET),
+			(-libc::EINVAL, -1)
+		);
+		assert_eq!(seek(file.as_raw_fd(), 0, 42), (-libc::EINVAL, 0));
+		// Unknown file descriptors are distinct from a seek to offset -1
+		assert_eq!(seek(1234, -1, libc::SEEK_CUR), (-libc::EBADF, -1));
+		// The standard streams are accepted like by read and write
+		assert_ne!(seek(libc::STDERR_FILENO, 0, libc::SEEK_CUR).0, -libc::EBADF);
+
+		// The old hypercall reports errors as offset -1
+		let mut syslseek = LseekParams {
+			fd: 1234,
+			offset: 0,
+			whence: libc::SEEK_SET,
+		};
+		lseek(&mut syslseek, &fdmap);
+		assert_eq!({ syslseek.offset }, -1);
+		let mut syslseek = LseekParams {
+			fd: file.as_raw_fd(),
+			offset: 8,
+			whence: libc::SEEK_SET,
+		};
+		lseek(&mut syslseek, &fdmap);
+		assert_eq!({ syslseek.offset }, 8);
+	}
+
+	#[test]
+	fn test_write_stdin() {
+		let mem = guest_mem();
+		let write_to = |fd| {
+			let mut syswrite = Write2Params {
+				fd,
+				buf: GuestVirtAddr::new(GUEST_PAGE_SIZE),
+				len: 1,
+				ret: 0,
+			};
+			write2(
+				&mem,
+				&mut syswrite,
+				&FdMap::new(),
+				&SerialOutput::new(None, false, false),
+			);
+			syswrite.ret
+		};
+		assert_eq!(write_to(libc::STDIN_FILENO), -libc::EBADF);
+		assert_eq!(write_to(1234), -libc::EBADF);
+
+		// The old hypercall only reports that nothing was written
+		let mut syswrite = WriteParams {
+			fd: libc::STDIN_FILENO,
+			buf: GuestVirtAddr::new(GUEST_PAGE_SIZE),
+			len: 1,
+		};
+		write(
+			&mem,
+			&mut syswrite,
+			&FdMap::new(),
+			&SerialOutput::new(None, false, false),
+		);
+		assert_eq!({ syswrite.len }, 0);
+	}
+
+	#[test]
+	fn test_write_stdout_to_serial() {
+		let mem = guest_mem();
+		let buf = GuestVirtAddr::new(GUEST_PAGE_SIZE);
+		unsafe { mem.slice_at_mut(GuestPhysAddr::new(GUEST_PAGE_SIZE), 13) }
+			.unwrap()
+			.copy_from_slice(b"stdout\nstderr");
+		let serial = SerialOutput::new(Some("[write] ".to_string()), true, false);
+		let fdmap = FdMap::new();
+
+		let mut syswrite = WriteParams {
+			fd: libc::STDOUT_FILENO,
+			buf,
+			len: 7,
+		};
+		write(&mem, &mut syswrite, &fdmap, &serial);
+		assert_eq!({ syswrite.len }, 7);
+		let mut syswrite = WriteParams {
+			fd: libc::STDERR_FILENO,
+			buf: buf + 7,
+			len: 6,
+		};
+		write(&mem, &mut syswrite, &fdmap, &serial);
+		assert_eq!({ syswrite.len }, 6);
+
+		serial.flush();
+		assert_eq!(serial.take_lines(), ["stdout", "stderr"]);
+	}
+
+	#[test]
+	fn test_flush_stdout() {
+		let mem = guest_mem();
+		let buf = GuestVirtAddr::new(GUEST_PAGE_SIZE);
+		unsafe { mem.slice_at_mut(GuestPhysAddr::new(GUEST_PAGE_SIZE), 10) }
+			.unwrap()
+			.copy_from_slice(b"first\nlast");
+		let serial = SerialOutput::new(None, true, false);
+		let mut syswrite = WriteParams {
+			fd: libc::STDOUT_FILENO,
+			buf,
+			len: 10,
+		};
+		write(&mem, &mut syswrite, &FdMap::new(), &serial);
+
+		// The last line has no line ending and is only complete once the VM flushes the output
+		assert_eq!(serial.take_lines(), ["first"]);
+		serial.flush();
+		assert_eq!(serial.take_lines(), ["last"]);
+	}
+
+	#[test]
+	fn test_write_stdout_to_tcp() {
+		let mem = guest_mem();
+		let buf = GuestVirtAddr::new(GUEST_PAGE_SIZE);
+		unsafe { mem.slice_at_mut(GuestPhysAddr::new(GUEST_PAGE_SIZE), 4) }
+			.unwrap()
+			.copy_from_slice(b"tcp\n");
+		let tcp = TcpOutput::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+		let mut client = TcpStream::connect(tcp.local_addr()).unwrap();
+		client
+			.set_read_timeout(Some(Duration::from_secs(5)))
+			.unwrap();
+		let serial = SerialOutput::new(None, false, false).with_tcp(tcp);
+
+		let mut syswrite = WriteParams {
+			fd: libc::STDOUT_FILENO,
+			buf,
+			len: 4,
+		};
+		write(&mem, &mut syswrite, &FdMap::new(), &serial);
+		assert_eq!({ syswrite.len }, 4);
+		let mut received = [0; 4];
+		client.read_exact(&mut received).unwrap();
+		assert_eq!(&received, b"tcp\n");
+	}
+
+	#[test]
+	fn test_write_quota() {
+		let mem = guest_mem();
+		let buf = GuestVirtAddr::new(GUEST_PAGE_SIZE);
+		unsafe { mem.slice_at_mut(GuestPhysAddr::new(GUEST_PAGE_SIZE), 16) }
+			.unwrap()
+			.fill(b'a');
+
+		let dir = assert_fs::TempDir::new().unwrap();
+		let fdmap = FdMap::with_write_quota(Some(24));
+		let name = GuestPhysAddr::new(2 * GUEST_PAGE_SIZE);
+		unsafe { mem.slice_at_mut(name, 10) }
+			.unwrap()
+			.copy_from_slice(b"quota.txt\0");
+		let mut sysopen = OpenParams {
+			name,
+			flags: libc::O_CREAT | libc::O_WRONLY,
+			mode: 0o644,
+			ret: -1,
+		};
+		open(
+			&mem,
+			&mut sysopen,
+			Some(dir.path()),
+			Path::new(""),
+			&fdmap,
+			None,
+			None,
+		);
+		assert!(sysopen.ret > 2);
+
+		let mut syswrite = WriteParams {
+			fd: sysopen.ret,
+			buf,
+			len: 16,
+		};
+		write(
+			&mem,
+			&mut syswrite,
+			&fdmap,
+			&SerialOutput::new(None, false, false),
+		);
+		assert_eq!({ syswrite.len }, 16);
+
+		// The second write would exceed the quota and is refused as a whole
+		let mut syswrite = Write2Params {
+			fd: sysopen.ret,
+			buf,
+			len: 16,
+			ret: 0,
+		};
+		write2(
+			&mem,
+			&mut syswrite,
+			&fdmap,
+			&SerialOutput::new(None, false, false),
+		);
+		assert_eq!({ syswrite.ret }, -libc::EDQUOT);
+		assert_eq!(
+			std::fs::read(dir.path().join("quota.txt")).unwrap(),
+			[b'a'; 16]
+		);
+
+		close(
+			&mut CloseParams {
+				fd: sysopen.ret,
+				ret: -1,
+			},
+			&fdmap,
+		);
+	}
+
+	#[test]
+	fn test_write_quota_partial_write() {
+		let mem = guest_mem();
+		let buf = GuestVirtAddr::new(GUEST_PAGE_SIZE);
+
+		// A non-blocking pipe holding a single page fails the write of the second page
+		let (_reader, writer) = std::io::pipe().unwrap();
+		unsafe {
+			assert!(
+				libc::fcntl(
+					writer.as_raw_fd(),
+					libc::F_SETPIPE_SZ,
+					PAGE_SIZE as libc::c_int
+				) >= 0
+			);
+			assert!(libc::fcntl(writer.as_raw_fd(), libc::F_SETFL, libc::O_NONBLOCK) >= 0);
+		}
+		let fdmap = FdMap::with_write_quota(Some(3 * PAGE_SIZE as u64));
+		fdmap.insert(writer.as_raw_fd()).unwrap();
+		let mut syswrite = WriteParams {
+			fd: writer.as_raw_fd(),
+			buf,
+			len: 2 * PAGE_SIZE,
+		};
+		write(
+			&mem,
+			&mut syswrite,
+			&fdmap,
+			&SerialOutput::new(None, false, false),
+		);
+		assert_eq!({ syswrite.len }, PAGE_SIZE);
+
+		// Only the written page counts against the quota
+		assert!(fdmap.reserve_write(2 * PAGE_SIZE as u64));
+		assert!(!fdmap.reserve_write(1));
+	}
+
+	#[test]
+	fn test_append() {
+		let mem = guest_mem();
+		let dir = assert_fs::TempDir::new().unwrap();
+		let path = dir.path().join("append.txt");
+		std::fs::write(&path, b"head").unwrap();
+		let fdmap = FdMap::new();
+		let name = GuestPhysAddr::new(2 * GUEST_PAGE_SIZE);
+		unsafe { mem.slice_at_mut(name, 11) }
+			.unwrap()
+			.copy_from_slice(b"append.txt\0");
+		let open_append = || {
+			let mut sysopen = OpenParams {
+				name,
+				flags: libc::O_WRONLY | libc::O_APPEND,
+				mode: 0,
+				ret: -1,
+			};
+			open(
+				&mem,
+				&mut sysopen,
+				Some(dir.path()),
+				Path::new(""),
+				&fdmap,
+				None,
+				None,
+			);
+			assert!(sysopen.ret > 2);
+			sysopen.ret
+		};
+		let fds = [open_append(), open_append()];
+
+		const CHUNK: usize = 64;
+		const CHUNKS: usize = 100;
+		std::thread::scope(|scope| {
+			for (fd, byte) in fds.into_iter().zip(*b"ab") {
+				let fdmap = &fdmap;
+				scope.spawn(move || {
+					// Each writer has a guest memory of its own, like a vCPU of its own.
+					let mem = guest_mem();
+					let buf = GuestVirtAddr::new(GUEST_PAGE_SIZE);
+					unsafe { mem.slice_at_mut(GuestPhysAddr::new(buf.as_u64()), CHUNK) }
+						.unwrap()
+						.fill(byte);
+					for _ in 0..CHUNKS {
+						let mut syswrite = WriteParams {
+							fd,
+							buf,
+							len: CHUNK,
+						};
+						write(
+							&mem,
+							&mut syswrite,
+							fdmap,
+							&SerialOutput::new(None, false, false),
+						);
+						assert_eq!({ syswrite.len }, CHUNK);
+					}
+				});
+			}
+		});
+
+		// Both descriptors appended all of their chunks without overwriting each other
+		let content = std::fs::read(&path).unwrap();
+		assert_eq!(content.len(), 4 + 2 * CHUNKS * CHUNK);
+		assert_eq!(&content[..4], b"head");
+		let chunks = content[4..].chunks(CHUNK).collect::<Vec<_>>();
+		for byte in *b"ab" {
+			assert_eq!(
+				chunks
+					.iter()
+					.filter(|chunk| **chunk == [byte; CHUNK])
+					.count(),
+				CHUNKS
+			);
+		}
+
+		for fd in fds {
+			close(&mut CloseParams { fd, ret: -1 }, &fdmap);
+		}
+	}
+
+	#[test]
+	fn test_max_write_size() {
+		let mem = guest_mem();
+		let buf = GuestVirtAddr::new(GUEST_PAGE_SIZE);
+		unsafe { mem.slice_at_mut(GuestPhysAddr::new(GUEST_PAGE_SIZE), 64) }
+			.unwrap()
+			.fill(b'a');
+
+		let dir = assert_fs::TempDir::new().unwrap();
+		let fdmap = FdMap::new().with_max_write_size(Some(24));
+		let name = GuestPhysAddr::new(2 * GUEST_PAGE_SIZE);
+		unsafe { mem.slice_at_mut(name, 10) }
+			.unwrap()
+			.copy_from_slice(b"chunk.txt\0");
+		let mut sysopen = OpenParams {
+			name,
+			flags: libc::O_CREAT | libc::O_WRONLY,
+			mode: 0o644,
+			ret: -1,
+		};
+		open(
+			&mem,
+			&mut sysopen,
+			Some(dir.path()),
+			Path::new(""),
+			&fdmap,
+			None,
+			None,
+		);
+		assert!(sysopen.ret > 2);
+
+		// The oversized write is truncated to the maximum write size
+		let mut syswrite = WriteParams {
+			fd: sysopen.ret,
+			buf,
+			len: 64,
+		};
+		write(
+			&mem,
+			&mut syswrite,
+			&fdmap,
+			&SerialOutput::new(None, false, false),
+		);
+		assert_eq!({ syswrite.len }, 24);
+
+		let mut syswrite = WriteParams {
+			fd: sysopen.ret,
+			buf,
+			len: 16,
+		};
+		write(
+			&mem,
+			&mut syswrite,
+			&fdmap,
+			&SerialOutput::new(None, false, false),
+		);
+		assert_eq!({ syswrite.len }, 16);
+		assert_eq!(
+			std::fs::read(dir.path().join("chunk.txt")).unwrap(),
+			[b'a'; 40]
+		);
+
+		close(
+			&mut CloseParams {
+				fd: sysopen.ret,
+				ret: -1,
+			},
+			&fdmap,
+		);
+	}
+
+	#[test]
+	fn test_copy_env() {
+		let mem = guest_mem();
+		let env = [
+			(OsString::from("HOME"), OsString::from("/root")),
+			(OsString::from("EMPTY"), OsString::new()),
+		];
+
+		let mut syssize = CmdsizeParams {
+			argc: 0,
+			argsz: [0; MAX_ARGC_ENVC],
+			envc: 0,
+			envsz: [0; MAX_ARGC_ENVC],
+		};
+		syssize.update(Path::new("app"), &[], &env);
+		assert_eq!({ syssize.envc }, 2);
+		assert_eq!({ syssize.envsz }[..2], [11, 7]);
+
+		// The guest allocates the pointer array and the strings with the sizes from above
+		let envp = GuestPhysAddr::new(GUEST_PAGE_SIZE);
+		let dests = [
+			GuestPhysAddr::new(GUEST_PAGE_SIZE + 0x100),
+			GuestPhysAddr::new(GUEST_PAGE_SIZE + 0x200),
+		];
+		unsafe { *mem.get_ref_mut::<[GuestPhysAddr; 2]>(envp).unwrap() = dests };
+		copy_env(
+			&CmdvalParams {
+				argv: GuestPhysAddr::new(0),
+				envp,
+			},
+			&mem,
+			&env,
+		);
+
+		assert_eq!(
+			unsafe { mem.slice_at(dests[0], 11) }.unwrap(),
+			b"HOME=/root\0"
+		);
+		assert_eq!(unsafe { mem.slice_at(dests[1], 7) }.unwrap(), b"EMPTY=\0");
+	}
+
+	#[test]
+	fn test_panic() {
+		capture_logs();
+		let mem = guest_mem();
+		let (msg, file) = ("index out of bounds", "src/main.rs");
+		unsafe { mem.slice_at_mut(GuestPhysAddr::new(GUEST_PAGE_SIZE), msg.len()) }
+			.unwrap()
+			.copy_from_slice(msg.as_bytes());
+		unsafe { mem.slice_at_mut(GuestPhysAddr::new(2 * GUEST_PAGE_SIZE), file.len()) }
+			.unwrap()
+			.copy_from_slice(file.as_bytes());
+
+		let message = panic(
+			&mem,
+			&PanicParams {
+				message: GuestVirtAddr::new(GUEST_PAGE_SIZE),
+				message_len: msg.len(),
+				file: GuestVirtAddr::new(2 * GUEST_PAGE_SIZE),
+				file_len: file.len(),
+				line: 42,
+			},
+		);
+		assert_eq!(message, msg);
+		assert!(captured_logs().iter().any(|record| record.target == "guest"
+			&& record.message == "Guest panicked at src/main.rs:42: index out of bounds"));
+
+		// Invalid buffers don't prevent the panic from being reported
+		let message = panic(
+			&mem,
+			&PanicParams {
+				message: GuestVirtAddr::new(4 * GUEST_PAGE_SIZE),
+				message_len: msg.len(),
+				file: GuestVirtAddr::new(0),
+				file_len: 0,
+				line: 0,
+			},
+		);
+		assert_eq!(message, "<invalid panic message>");
+	}
+
+	#[test]
+	fn test_host_info() {
+		let mem = guest_mem();
+		let version = GuestVirtAddr::new(GUEST_PAGE_SIZE);
+		let backend = GuestVirtAddr::new(GUEST_PAGE_SIZE + 64);
+		let mut syshostinfo = HostInfoParams {
+			version,
+			version_len: 64,
+			backend,
+			backend_len: 64,
+			ret: -1,
+		};
+		host_info(&mem, &mut syshostinfo, "kvm");
+		assert_eq!({ syshostinfo.ret }, 0);
+		let string_at = |addr: GuestVirtAddr| {
+			let buf = guest_buf(&mem, addr, 64).unwrap();
+			CStr::from_bytes_until_nul(&buf)
+				.unwrap()
+				.to_str()
+				.unwrap()
+				.to_owned()
+		};
+		assert_eq!(string_at(version), env!("CARGO_PKG_VERSION"));
+		assert_eq!(string_at(backend), "kvm");
+
+		// The terminating zero has to fit into the buffer
+		syshostinfo.backend_len = 3;
+		host_info(&mem, &mut syshostinfo, "kvm");
+		assert_eq!({ syshostinfo.ret }, -libc::ERANGE);
+
+		syshostinfo.backend_len = 64;
+		syshostinfo.backend = GuestVirtAddr::new(4 * GUEST_PAGE_SIZE);
+		host_info(&mem, &mut syshostinfo, "kvm");
+		assert_eq!({ syshostinfo.ret }, -libc::EFAULT);
+	}
+
+	#[test]
+	fn test_getenv() {
+		let mem = guest_mem();
+		let write_name = |name: &str| {
+			unsafe { mem.slice_at_mut(GuestPhysAddr::new(GUEST_PAGE_SIZE), name.len() + 1) }
+				.unwrap()
+				.copy_from_slice(CString::new(name).unwrap().as_bytes_with_nul());
+		};
+		let buf = GuestVirtAddr::new(2 * GUEST_PAGE_SIZE);
+		let mut sysgetenv = GetEnvParams {
+			name: GuestPhysAddr::new(GUEST_PAGE_SIZE),
+			buf,
+			len: 64,
+			ret: -1,
+		};
+		let allowlist = ["UHYVE_TEST_ALLOWED".to_string()];
+		unsafe {
+			env::set_var("UHYVE_TEST_ALLOWED", "allowed");
+			env::set_var("UHYVE_TEST_DENIED", "denied");
+		}
+
+		write_name("UHYVE_TEST_ALLOWED");
+		getenv(&mem, &mut sysgetenv, &allowlist);
+		assert_eq!({ sysgetenv.ret }, 7);
+		let value = guest_buf(&mem, buf, 8).unwrap();
+		assert_eq!(value, b"allowed\0");
+
+		write_name("UHYVE_TEST_DENIED");
+		getenv(&mem, &mut sysgetenv, &allowlist);
+		assert_eq!({ sysgetenv.ret }, -libc::ENOENT as isize);
+
+		write_name("UHYVE_TEST_ALLOWED");
+		sysgetenv.len = 7;
+		getenv(&mem, &mut sysgetenv, &allowlist);
+		assert_eq!({ sysgetenv.ret }, -libc::ERANGE as isize);
+	}
+
+	#[test]
+	fn test_gethostname() {
+		let mem = guest_mem();
+		let buf = GuestVirtAddr::new(GUEST_PAGE_SIZE);
+		let mut sysgethostname = GetHostnameParams {
+			buf,
+			len: 64,
+			ret: -1,
+		};
+		gethostname(&mem, &mut sysgethostname, c"hermit");
+		assert_eq!({ sysgethostname.ret }, 6);
+		assert_eq!(guest_buf(&mem, buf, 7).unwrap(), b"hermit\0");
+
+		sysgethostname.len = 6;
+		gethostname(&mem, &mut sysgethostname, c"hermit");
+		assert_eq!({ sysgethostname.ret }, -libc::ERANGE as isize);
+	}
+
+	#[test]
+	fn test_readdir() {
+		let mem = guest_mem();
+		let virtual_files = [
+			(PathBuf::from("/fixtures/a.txt"), b"a".to_vec()),
+			(PathBuf::from("/fixtures/sub/b.txt"), b"b".to_vec()),
+		];
+		let proc_info = ProcInfo {
+			num_cpus: 1,
+			memory_size: 0,
+			cpu_freq: None,
+			uptime: Default::default(),
+			virtual_files: &virtual_files,
+			unix_sockets: &[],
+		};
+		let name = GuestPhysAddr::new(GUEST_PAGE_SIZE);
+		let buf = GuestVirtAddr::new(2 * GUEST_PAGE_SIZE);
+		let readdir_at = |path: &[u8], cwd: &str, len| {
+			unsafe { mem.slice_at_mut(name, path.len()) }
+				.unwrap()
+				.copy_from_slice(path);
+			let mut sysreaddir = ReaddirParams {
+				name,
+				buf,
+				len,
+				ret: 0,
+			};
+			readdir(&mem, &mut sysreaddir, Path::new(cwd), &proc_info);
+			sysreaddir.ret
+		};
+
+		assert_eq!(readdir_at(b"/fixtures\0", "", 64), 11);
+		assert_eq!(guest_buf(&mem, buf, 11).unwrap(), b"a.txt\0sub/\0");
+		// Relative paths are resolved against the working directory
+		assert_eq!(readdir_at(b"sub\0", "/fixtures", 64), 6);
+		assert_eq!(guest_buf(&mem, buf, 6).unwrap(), b"b.txt\0");
+		// The needed size is reported if the buffer is too small
+		assert_eq!(readdir_at(b"/\0", "", 4), 10);
+		assert_eq!(guest_buf(&mem, buf, 6).unwrap(), b"b.txt\0");
+		assert_eq!(
+			readdir_at(b"/fixtures/a.txt\0", "", 64),
+			-libc::ENOTDIR as isize
+		);
+		assert_eq!(readdir_at(b"/etc\0", "", 64), -libc::ENOENT as isize);
+	}
+
+	#[test]
+	fn test_sched_yield() {
+		let mem = guest_mem();
+		// The value written to the port is not dereferenced
+		let hypercall = unsafe {
+			address_to_hypercall(
+				&mem,
+				HypercallAddress::SchedYield as u16,
+				GuestPhysAddr::new(0xdead_0000),
+			)
+		};
+		assert!(matches!(hypercall, Some(Hypercall::SchedYield)));
+		sched_yield();
+	}
+
+	#[test]
+	fn test_nanosleep() {
+		let duration = Duration::from_millis(50);
+		let mut sysnanosleep = NanoSleepParams {
+			duration_ns: duration.as_nanos() as u64,
+			remaining_ns: u64::MAX,
+			ret: -1,
+		};
+		let start = Instant::now();
+		assert!(nanosleep(&mut sysnanosleep));
+		let elapsed = start.elapsed();
+		assert_eq!({ sysnanosleep.ret }, 0);
+		assert_eq!({ sysnanosleep.remaining_ns }, 0);
+		assert!(elapsed >= duration);
+		assert!(elapsed < duration + Duration::from_millis(200));
+	}
+
+	#[test]
+	fn test_guest_log_level() {
+		let ceiling = Some(LevelFilter::Debug);
+		assert_eq!(guest_log_level(2, ceiling), Ok(LevelFilter::Warn));
+		assert_eq!(guest_log_level(4, ceiling), Ok(LevelFilter::Debug));
+		assert_eq!(guest_log_level(5, ceiling), Ok(LevelFilter::Debug));
+		assert_eq!(guest_log_level(0, ceiling), Ok(LevelFilter::Off));
+		assert_eq!(guest_log_level(6, ceiling), Err(libc::EINVAL));
+		assert_eq!(guest_log_level(1, None), Err(libc::EPERM));
+
+		// Rejected requests don't change the VM's level
+		let vm_level = Mutex::new(LevelFilter::Trace);
+		let mut syssetloglevel = SetLogLevelParams { level: 4, ret: 0 };
+		set_log_level(&mut syssetloglevel, None, &vm_level);
+		assert_eq!({ syssetloglevel.ret }, -libc::EPERM);
+		assert_eq!(*vm_level.lock().unwrap(), LevelFilter::Trace);
+
+		// Accepted requests are capped to the ceiling
+		syssetloglevel.level = 5;
+		set_log_level(&mut syssetloglevel, ceiling, &vm_level);
+		assert_eq!({ syssetloglevel.ret }, LevelFilter::Debug as i32);
+		assert_eq!(*vm_level.lock().unwrap(), LevelFilter::Debug);
+		syssetloglevel.level = 0;
+		set_log_level(&mut syssetloglevel, ceiling, &vm_level);
+		assert_eq!({ syssetloglevel.ret }, 0);
+		assert_eq!(*vm_level.lock().unwrap(), LevelFilter::Off);
+	}
+
+	#[test]
+	fn test_set_serial_mode() {
+		let serial = SerialOutput::new(Some("[mode] ".to_string()), false, false);
+		let mut sysserialmode = SetSerialModeParams {
+			mode: SERIAL_MODE_UNBUFFERED,
+			ret: -1,
+		};
+		set_serial_mode(&serial, &mut sysserialmode);
+		assert_eq!({ sysserialmode.ret }, 0);
+		assert!(serial.is_unbuffered());
+
+		sysserialmode.mode = SERIAL_MODE_BUFFERED;
+		set_serial_mode(&serial, &mut sysserialmode);
+		assert_eq!({ sysserialmode.ret }, 0);
+		assert!(!serial.is_unbuffered());
+
+		sysserialmode.mode = 2;
+		set_serial_mode(&serial, &mut sysserialmode);
+		assert_eq!({ sysserialmode.ret }, -libc::EINVAL);
+		assert!(!serial.is_unbuffered());
+	}
+
+	#[test]
+	fn test_serial_mode_stdout() {
+		capture_logs();
+		let mem = guest_mem();
+		let buf = GuestVirtAddr::new(GUEST_PAGE_SIZE);
+		unsafe { mem.slice_at_mut(GuestPhysAddr::new(GUEST_PAGE_SIZE), 16) }
+			.unwrap()
+			.copy_from_slice(b"stdout-mode-test");
+		let serial = SerialOutput::new(Some("[stdout-mode] ".to_string()), false, false);
+		let lines = || {
+			captured_logs()
+				.into_iter()
+				.filter(|record| record.message.starts_with("[stdout-mode] "))
+				.count()
+		};
+		let write_stdout = || {
+			let mut syswrite = WriteParams {
+				fd: libc::STDOUT_FILENO,
+				buf,
+				len: 16,
+			};
+			write(&mem, &mut syswrite, &FdMap::new(), &serial);
+			assert_eq!({ syswrite.len }, 16);
+		};
+
+		// Buffered, the incomplete line isn't logged yet
+		write_stdout();
+		assert_eq!(lines(), 0);
+
+		// Switching the mode ends the line, and unbuffered writes are logged right away
+		let mut sysserialmode = SetSerialModeParams {
+			mode: SERIAL_MODE_UNBUFFERED,
+			ret: -1,
+		};
+		set_serial_mode(&serial, &mut sysserialmode);
+		assert_eq!(lines(), 1);
+		write_stdout();
+		assert_eq!(lines(), 2);
+	}
+
+	#[test]
+	fn test_report_metric() {
+		let mem = guest_mem();
+		let name = GuestVirtAddr::new(GUEST_PAGE_SIZE);
+		unsafe { mem.slice_at_mut(GuestPhysAddr::new(name.as_u64()), 7) }
+			.unwrap()
+			.copy_from_slice(b"latency");
+		let mut sysmetric = ReportMetricParams {
+			name,
+			name_len: 7,
+			value: 1.5,
+			ret: -1,
+		};
+		assert_eq!(
+			report_metric(&mem, &mut sysmetric),
+			Some(("latency".to_string(), 1.5))
+		);
+		assert_eq!({ sysmetric.ret }, 0);
+
+		for name_len in [0, MAX_METRIC_NAME_LEN + 1] {
+			let mut sysmetric = ReportMetricParams {
+				name,
+				name_len,
+				value: 1.0,
+				ret: 0,
+			};
+			assert_eq!(report_metric(&mem, &mut sysmetric), None);
+			assert_eq!({ sysmetric.ret }, -libc::EINVAL);
+		}
+
+		let mut sysmetric = ReportMetricParams {
+			name: GuestVirtAddr::new(1 << 40),
+			name_len: 7,
+			value: 1.0,
+			ret: 0,
+		};
+		assert_eq!(report_metric(&mem, &mut sysmetric), None);
+		assert_eq!({ sysmetric.ret }, -libc::EFAULT);
+	}
+
+	#[test]
+	fn test_test_result() {
+		let mem = guest_mem();
+		let name = "frobnicator";
+		unsafe { mem.slice_at_mut(GuestPhysAddr::new(GUEST_PAGE_SIZE), name.len()) }
+			.unwrap()
+			.copy_from_slice(name.as_bytes());
+
+		let report = test_result(
+			&mem,
+			&TestResultParams {
+				passed: 3,
+				failed: 1,
+				name: GuestVirtAddr::new(GUEST_PAGE_SIZE),
+				name_len: name.len(),
+			},
+		);
+		assert_eq!(
+			report,
+			TestReport {
+				name: Some(name.to_string()),
+				passed: 3,
+				failed: 1
+			}
+		);
+
+		// Invalid names don't prevent the results from being reported
+		let report = test_result(
+			&mem,
+			&TestResultParams {
+				passed: 0,
+				failed: 2,
+				name: GuestVirtAddr::new(4 * GUEST_PAGE_SIZE),
+				name_len: name.len(),
+			},
+		);
+		assert_eq!(
+			report,
+			TestReport {
+				name: None,
+				passed: 0,
+				failed: 2
+			}
+		);
+	}
+
+	#[test]
+	fn test_fadvise() {
+		let dir = assert_fs::TempDir::new().unwrap();
+		let path = dir.path().join("fadvise.txt");
+		std::fs::write(&path, [b'a'; 4096]).unwrap();
+		let file = std::fs::File::open(&path).unwrap();
+		let fdmap = FdMap::new();
+		fdmap.insert(file.as_raw_fd()).unwrap();
+
+		let advise = |fd, advice| {
+			let mut sysfadvise = FadviseParams {
+				fd,
+				offset: 0,
+				len: 0,
+				advice,
+				ret: 1,
+			};
+			fadvise(&mut sysfadvise, &fdmap);
+			sysfadvise.ret
+		};
+		assert_eq!(advise(file.as_raw_fd(), FADVISE_SEQUENTIAL), 0);
+		assert_eq!(advise(file.as_raw_fd(), 42), -libc::EINVAL);
+		assert_eq!(advise(1234, FADVISE_SEQUENTIAL), -libc::EBADF);
+	}
+
+	#[test]
+	fn test_max_open_files() {
+		let mem = guest_mem();
+		let dir = assert_fs::TempDir::new().unwrap();
+		std::fs::write(dir.path().join("file.txt"), "file").unwrap();
+		unsafe { mem.slice_at_mut(GuestPhysAddr::new(GUEST_PAGE_SIZE), 9) }
+			.unwrap()
+			.copy_from_slice(b"file.txt\0");
+		let fdmap = FdMap::new().with_max_open_files(Some(2));
+
+		let open_file = || {
+			let mut sysopen = OpenParams {
+				name: GuestPhysAddr::new(GUEST_PAGE_SIZE),
+				flags: libc::O_RDONLY,
+				mode: 0,
+				ret: -1,
+			};
+			open(
+				&mem,
+				&mut sysopen,
+				Some(dir.path()),
+				Path::new(""),
+				&fdmap,
+				None,
+				None,
+			);
+			sysopen.ret
+		};
+
+		let first = open_file();
+		assert!(first > 2);
+		assert!(open_file() > 2);
+		assert_eq!(open_file(), -libc::EMFILE);
+
+		let mut sysclose = CloseParams { fd: first, ret: -1 };
+		close(&mut sysclose, &fdmap);
+		assert_eq!({ sysclose.ret }, 0);
+		assert!(open_file() > 2);
+		fdmap.close_all();
+	}
+
+	#[test]
+	fn test_open2_created() {
+		let mem = guest_mem();
+		let dir = assert_fs::TempDir::new().unwrap();
+		std::fs::write(dir.path().join("existing.txt"), "existing").unwrap();
+		let fdmap = FdMap::new();
+
+		let open_name = |name: &str, flags| {
+			let addr = GuestPhysAddr::new(GUEST_PAGE_SIZE);
+			let dest = unsafe { mem.slice_at_mut(addr, name.len() + 1) }.unwrap();
+			dest[..name.len()].copy_from_slice(name.as_bytes());
+			dest[name.len()] = 0;
+			let mut sysopen2 = Open2Params {
+				name: addr,
+				flags,
+				mode: 0o644,
+				ret: -1,
+				created: 0xff,
+			};
+			open2(
+				&mem,
+				&mut sysopen2,
+				Some(dir.path()),
+				Path::new(""),
+				&fdmap,
+				None,
+				None,
+			);
+			assert!({ sysopen2.ret } > 2);
+			sysopen2.created
+		};
+
+		assert_eq!(open_name("existing.txt", libc::O_CREAT | libc::O_WRONLY), 0);
+		assert_eq!(open_name("new.txt", libc::O_CREAT | libc::O_WRONLY), 1);
+		assert!(dir.path().join("new.txt").exists());
+		assert_eq!(open_name("new.txt", libc::O_CREAT | libc::O_WRONLY), 0);
+		assert_eq!(open_name("existing.txt", libc::O_RDONLY), 0);
+		fdmap.close_all();
+	}
+
+	#[test]
+	fn test_file_creation_limits() {
+		let mem = guest_mem();
+		let dir = assert_fs::TempDir::new().unwrap();
+		std::fs::write(dir.path().join("existing.txt"), "existing").unwrap();
+
+		let create = |fdmap: &FdMap, name: &str| {
+			let addr = GuestPhysAddr::new(GUEST_PAGE_SIZE);
+			let dest = unsafe { mem.slice_at_mut(addr, name.len() + 1) }.unwrap();
+			dest[..name.len()].copy_from_slice(name.as_bytes());
+			dest[name.len()] = 0;
+			let mut sysopen = OpenParams {
+				name: addr,
+				flags: libc::O_CREAT | libc::O_WRONLY,
+				mode: 0o644,
+				ret: -1,
+			};
+			open(
+				&mem,
+				&mut sysopen,
+				Some(dir.path()),
+				Path::new(""),
+				fdmap,
+				None,
+				None,
+			);
+			sysopen.ret
+		};
+
+		let fdmap = FdMap::new().with_max_created_files(Some(2));
+		assert!(create(&fdmap, "first.txt") > 2);
+		assert!(create(&fdmap, "second.txt") > 2);
+		assert_eq!(create(&fdmap, "third.txt"), -libc::ENOSPC);
+		assert!(!dir.path().join("third.txt").exists());
+		// Opening existing files with `O_CREAT` doesn't create anything
+		assert!(create(&fdmap, "first.txt") > 2);
+		assert!(create(&fdmap, "existing.txt") > 2);
+		fdmap.close_all();
+
+		let fdmap = FdMap::new().with_file_creation_disabled(true);
+		assert_eq!(create(&fdmap, "fourth.txt"), -libc::ENOENT);
+		assert!(!dir.path().join("fourth.txt").exists());
+		assert!(create(&fdmap, "existing.txt") > 2);
+		fdmap.close_all();
+	}
+
+	#[test]
+	fn test_open_beneath_host_root() {
+		let mem = guest_mem();
+		let root = assert_fs::TempDir::new().unwrap();
+		std::fs::create_dir_all(root.path().join("data/sub")).unwrap();
+		std::fs::write(root.path().join("data/file.txt"), "file").unwrap();
+		let fdmap = FdMap::new();
+
+		let open_name = |name: &str| {
+			let addr = GuestPhysAddr::new(GUEST_PAGE_SIZE);
+			let dest = unsafe { mem.slice_at_mut(addr, name.len() + 1) }.unwrap();
+			dest[..name.len()].copy_from_slice(name.as_bytes());
+			dest[name.len()] = 0;
+			let mut sysopen = OpenParams {
+				name: addr,
+				flags: libc::O_RDONLY,
+				mode: 0,
+				ret: -1,
+			};
+			open(
+				&mem,
+				&mut sysopen,
+				Some(root.path()),
+				Path::new(""),
+				&fdmap,
+				None,
+				None,
+			);
+			sysopen.ret
+		};
+
+		assert!(open_name("data/sub/../file.txt") > 2);
+		assert_eq!(open_name("data/../../escape.txt"), -libc::EACCES);
+		fdmap.close_all();
+	}
+
+	#[test]
+	fn test_openat() {
+		let mem = guest_mem();
+		let dir = assert_fs::TempDir::new().unwrap();
+		std::fs::create_dir_all(dir.path().join("dir/sub")).unwrap();
+		std::fs::write(dir.path().join("dir/child.txt"), "child").unwrap();
+		std::fs::write(dir.path().join("escape"), "escape").unwrap();
+		let fdmap = FdMap::new();
+
+		let write_name = |name: &str| {
+			let addr = GuestPhysAddr::new(GUEST_PAGE_SIZE);
+			let dest = unsafe { mem.slice_at_mut(addr, name.len() + 1) }.unwrap();
+			dest[..name.len()].copy_from_slice(name.as_bytes());
+			dest[name.len()] = 0;
+			addr
+		};
+
+		let mut sysopen = OpenParams {
+			name: write_name("dir"),
+			flags: libc::O_RDONLY | libc::O_DIRECTORY,
+			mode: 0,
+			ret: -1,
+		};
+		open(
+			&mem,
+			&mut sysopen,
+			Some(dir.path()),
+			Path::new(""),
+			&fdmap,
+			None,
+			None,
+		);
+		let dirfd = sysopen.ret;
+		assert!(dirfd > 2);
+
+		let openat_name = |dirfd, name| {
+			let mut sysopenat = OpenatParams {
+				dirfd,
+				name: write_name(name),
+				flags: libc::O_RDONLY,
+				mode: 0,
+				ret: 0,
+			};
+			openat(&mem, &mut sysopenat, &fdmap, None);
+			sysopenat.ret
+		};
+
+		let fd = openat_name(dirfd, "child.txt");
+		assert!(fd > 2);
+		assert!(fdmap.contains(fd));
+		assert!(openat_name(dirfd, "./sub/../child.txt") > 2);
+		assert_eq!(openat_name(dirfd, "../escape"), -libc::EACCES);
+		assert_eq!(openat_name(dirfd, "/etc/passwd"), -libc::EACCES);
+		assert_eq!(openat_name(dirfd, "missing.txt"), -libc::ENOENT);
+		// Only directories opened by the guest can be used
+		assert_eq!(openat_name(libc::AT_FDCWD, "child.txt"), -libc::EBADF);
+	}
+
+	#[test]
+	fn test_audit_log() {
+		let mem = guest_mem();
+		let root = assert_fs::TempDir::new().unwrap();
+		let log_path = root.path().join("audit.log");
+		let audit = AuditLog::open(&log_path).unwrap();
+		let fdmap = FdMap::new().with_file_creation_disabled(true);
+		std::fs::write(root.path().join("data.txt"), "data").unwrap();
+
+		let write_name = |name: &str| {
+			let addr = GuestPhysAddr::new(GUEST_PAGE_SIZE);
+			let dest = unsafe { mem.slice_at_mut(addr, name.len() + 1) }.unwrap();
+			dest[..name.len()].copy_from_slice(name.as_bytes());
+			dest[name.len()] = 0;
+			addr
+		};
+		let open_name = |name: &str, flags| {
+			let mut sysopen = OpenParams {
+				name: write_name(name),
+				flags,
+				mode: 0o644,
+				ret: -1,
+			};
+			open(
+				&mem,
+				&mut sysopen,
+				Some(root.path()),
+				Path::new(""),
+				&fdmap,
+				None,
+				Some(&audit),
+			);
+			sysopen.ret
+		};
+		let unlink_name = |name: &str| {
+			let mut sysunlink = UnlinkParams {
+				name: write_name(name),
+				ret: -1,
+			};
+			unlink(
+				&mem,
+				&mut sysunlink,
+				Some(root.path()),
+				Path::new(""),
+				Some(&audit),
+			);
+			sysunlink.ret
+		};
+
+		let fd = open_name("data.txt", libc::O_RDONLY);
+		assert!(fd > 2);
+		assert_eq!(open_name("../escape.txt", libc::O_RDONLY), -libc::EACCES);
+		assert!(open_name("new.txt", libc::O_CREAT | libc::O_WRONLY) < 0);
+		assert_eq!(open_name("missing.txt", libc::O_RDONLY), -libc::ENOENT);
+		assert_eq!(unlink_name("data.txt"), 0);
+		assert_eq!(unlink_name("data.txt"), -libc::ENOENT);
+		fdmap.close_all();
+
+		let log = std::fs::read_to_string(&log_path).unwrap();
+		let records = log
+			.lines()
+			.map(|line| line.split_once(' ').unwrap().1)
+			.collect::<Vec<_>>();
+		let host = |name: &str| format!("{:?}", root.path().join(name));
+		assert_eq!(
+			records,
+			[
+				format!(
+					r#"op=open guest="data.txt" host={} result={fd}"#,
+					host("data.txt")
+				),
+				format!(
+					r#"op=open guest="../escape.txt" host=- result={}"#,
+					-libc::EACCES
+				),
+				format!(
+					r#"op=open guest="new.txt" host={} result={}"#,
+					host("new.txt"),
+					-libc::ENOENT
+				),
+				format!(
+					r#"op=open guest="missing.txt" host={} result={}"#,
+					host("missing.txt"),
+					-libc::ENOENT
+				),
+				format!(
+					r#"op=unlink guest="data.txt" host={} result=0"#,
+					host("data.txt")
+				),
+				format!(
+					r#"op=unlink guest="data.txt" host={} result={}"#,
+					host("data.txt"),
+					-libc::ENOENT
+				),
+			]
+		);
+	}
+
+	#[test]
+	fn test_mem_balloon() {
+		let mem = MmapMemory::new(
+			0,
+			4 * GUEST_PAGE_SIZE as usize,
+			GuestPhysAddr::new(0),
+			false,
+			false,
+		);
+		let boot_memory_size = 2 * GUEST_PAGE_SIZE as usize;
+		let committed = Mutex::new(boot_memory_size);
+		let balloon = |size| {
+			let mut sysballoon = MemBalloonParams { size, ret: 1 };
+			mem_balloon(&mem, &mut sysballoon, &committed, boot_memory_size);
+			({ sysballoon.ret }, { sysballoon.size })
+		};
+
+		// The size is rounded up to whole guest pages
+		assert_eq!(
+			balloon(3 * GUEST_PAGE_SIZE as usize + 1),
+			(0, mem.memory_size)
+		);
+		assert_eq!(*committed.lock().unwrap(), mem.memory_size);
+		let addr = GuestPhysAddr::new(3 * GUEST_PAGE_SIZE + 0x100);
+		unsafe { mem.slice_at_mut(addr, 4) }
+			.unwrap()
+			.copy_from_slice(b"grow");
+		assert_eq!(unsafe { mem.slice_at(addr, 4) }.unwrap(), b"grow");
+
+		assert_eq!(
+			balloon(5 * GUEST_PAGE_SIZE as usize),
+			(-libc::EINVAL, mem.memory_size)
+		);
+		assert_eq!(
+			balloon(GUEST_PAGE_SIZE as usize),
+			(-libc::EINVAL, mem.memory_size)
+		);
+		assert_eq!(balloon(usize::MAX), (-libc::EINVAL, mem.memory_size));
+
+		// Released memory is zeroed
+		assert_eq!(balloon(boot_memory_size), (0, boot_memory_size));
+		assert_eq!(unsafe { mem.slice_at(addr, 4) }.unwrap(), [0; 4]);
+	}
+
+	#[test]
+	fn test_statvfs() {
+		let mem = guest_mem();
+		let dir = assert_fs::TempDir::new().unwrap();
+		std::fs::create_dir(dir.path().join("data")).unwrap();
+		let statvfs_of = |path: &str| {
+			let addr = GuestPhysAddr::new(GUEST_PAGE_SIZE);
+			let dest = unsafe { mem.slice_at_mut(addr, path.len() + 1) }.unwrap();
+			dest[..path.len()].copy_from_slice(path.as_bytes());
+			dest[path.len()] = 0;
+			let mut sysstatvfs = StatvfsParams {
+				name: addr,
+				block_size: 0,
+				blocks: 0,
+				blocks_free: 0,
+				blocks_available: 0,
+				ret: 1,
+			};
+			statvfs(&mem, &mut sysstatvfs, Some(dir.path()), Path::new(""));
+			sysstatvfs
+		};
+
+		let stat = statvfs_of("data");
+		assert_eq!({ stat.ret }, 0);
+		assert_ne!({ stat.block_size }, 0);
+		assert!({ stat.blocks_available } <= { stat.blocks_free });
+		assert!({ stat.blocks_free } <= { stat.blocks });
+
+		assert_eq!({ statvfs_of("missing").ret }, -libc::ENOENT);
+		assert_eq!({ statvfs_of("../outside").ret }, -libc::ENOENT);
+	}
+
+	#[test]
+	fn test_chdir() {
+		let mem = guest_mem();
+		let dir = assert_fs::TempDir::new().unwrap();
+		std::fs::create_dir_all(dir.path().join("data/sub")).unwrap();
+		std::fs::write(dir.path().join("data/sub/file.txt"), "file").unwrap();
+		let cwd = RwLock::new(PathBuf::new());
+
+		let chdir_to = |path: &str| {
+			let addr = GuestPhysAddr::new(GUEST_PAGE_SIZE);
+			let dest = unsafe { mem.slice_at_mut(addr, path.len() + 1) }.unwrap();
+			dest[..path.len()].copy_from_slice(path.as_bytes());
+			dest[path.len()] = 0;
+			let mut syschdir = ChdirParams { path: addr, ret: 1 };
+			chdir(&mem, &mut syschdir, Some(dir.path()), &cwd);
+			syschdir.ret
+		};
+
+		assert_eq!(chdir_to("data"), 0);
+		assert_eq!(chdir_to("sub"), 0);
+		assert_eq!(*cwd.read().unwrap(), Path::new("data/sub"));
+		assert_eq!(chdir_to("missing"), -libc::ENOENT);
+		assert_eq!(chdir_to("file.txt"), -libc::ENOTDIR);
+		assert_eq!(chdir_to("../../.."), -libc::EACCES);
+		assert_eq!(*cwd.read().unwrap(), Path::new("data/sub"));
+
+		// Relative paths are opened in the working directory
+		let name = GuestPhysAddr::new(2 * GUEST_PAGE_SIZE);
+		unsafe { mem.slice_at_mut(name, 9) }
+			.unwrap()
+			.copy_from_slice(b"file.txt\0");
+		let mut sysopen = OpenParams {
+			name,
+			flags: libc::O_RDONLY,
+			mode: 0,
+			ret: -1,
+		};
+		let fdmap = FdMap::new();
+		open(
+			&mem,
+			&mut sysopen,
+			Some(dir.path()),
+			&cwd.read().unwrap(),
+			&fdmap,
+			None,
+			None,
+		);
+		assert!(sysopen.ret > 2);
+		close(
+			&mut CloseParams {
+				fd: sysopen.ret,
+				ret: -1,
+			},
+			&fdmap,
+		);
+
+		let buf = GuestVirtAddr::new(3 * GUEST_PAGE_SIZE);
+		let mut sysgetcwd = GetCwdParams {
+			buf,
+			len: 16,
+			ret: 0,
+		};
+		getcwd(&mem, &mut sysgetcwd, &cwd.read().unwrap());
+		assert_eq!({ sysgetcwd.ret }, 8);
+		assert_eq!(
+			unsafe { mem.slice_at(GuestPhysAddr::new(3 * GUEST_PAGE_SIZE), 9) }.unwrap(),
+			b"data/sub\0"
+		);
+
+		let mut sysgetcwd = GetCwdParams {
+			buf,
+			len: 8,
+			ret: 0,
+		};
+		getcwd(&mem, &mut sysgetcwd, &cwd.read().unwrap());
+		assert_eq!({ sysgetcwd.ret }, -(libc::ERANGE as isize));
+
+		// Absolute working directories are resolved relative to the host root and kept relative
+		// to it, so the guest can't change into host directories outside of it
+		assert_eq!(chdir_to("/.."), -libc::EACCES);
+		assert_eq!(chdir_to("/tmp/../.."), -libc::EACCES);
+		assert_eq!(chdir_to(dir.path().to_str().unwrap()), -libc::ENOENT);
+		assert_eq!(*cwd.read().unwrap(), Path::new("data/sub"));
+		assert_eq!(chdir_to("/data"), 0);
+		assert_eq!(*cwd.read().unwrap(), Path::new("data"));
+		assert_eq!(chdir_to("sub"), 0);
+		assert_eq!(*cwd.read().unwrap(), Path::new("data/sub"));
+	}
+
+	#[test]
+	fn test_log_message() {
+		capture_logs();
+		let mem = guest_mem();
+		let msg = "guest failed to frobnicate";
+		unsafe { mem.slice_at_mut(GuestPhysAddr::new(GUEST_PAGE_SIZE), msg.len()) }
+			.unwrap()
+			.copy_from_slice(msg.as_bytes());
+
+		log_message(
+			&mem,
+			&LogMessageParams {
+				level: 1,
+				buf: GuestVirtAddr::new(GUEST_PAGE_SIZE),
+				len: msg.len(),
+			},
+			LevelFilter::Trace,
+		);
+
+		assert!(captured_logs()
+			.iter()
+			.any(|record| record.level == log::Level::Error
+				&& record.target == "guest"
+				&& record.message == msg));
+
+		// Messages above the VM's level are dropped
+		let filtered = "guest frobnicates quietly";
+		unsafe { mem.slice_at_mut(GuestPhysAddr::new(GUEST_PAGE_SIZE), filtered.len()) }
+			.unwrap()
+			.copy_from_slice(filtered.as_bytes());
+		log_message(
+			&mem,
+			&LogMessageParams {
+				level: 3,
+				buf: GuestVirtAddr::new(GUEST_PAGE_SIZE),
+				len: filtered.len(),
+			},
+			LevelFilter::Warn,
+		);
+		assert!(!captured_logs()
+			.iter()
+			.any(|record| record.message == filtered));
+
+		// Messages extending beyond the guest memory are dropped
+		log_message(
+			&mem,
+			&LogMessageParams {
+				level: 1,
+				buf: GuestVirtAddr::new(GUEST_PAGE_SIZE),
+				len: usize::MAX - GUEST_PAGE_SIZE as usize + 1,
+			},
+			LevelFilter::Trace,
+		);
+		assert!(captured_logs()
+			.iter()
+			.any(|record| record.level == log::Level::Warn
+				&& record.message
+					== format!(
+						"Ignoring guest log message at invalid guest address {GUEST_PAGE_SIZE:#x}"
+					)));
+	}
+
+	#[test]
+	fn test_resolve_host_path() {
+		let root = Path::new("/srv/uhyve");
+
+		let resolved = resolve_host_path(Some(root), Path::new(""), c"data/foo.txt").unwrap();
+		assert_eq!(resolved.as_c_str(), c"/srv/uhyve/data/foo.txt");
+
+		// Paths beneath the host root are normalized and must not escape it
+		let resolved =
+			resolve_host_path(Some(root), Path::new(""), c"./data/sub/../foo.txt").unwrap();
+		assert_eq!(resolved.as_c_str(), c"/srv/uhyve/data/foo.txt");
+		assert_eq!(
+			resolve_host_path(Some(root), Path::new(""), c"data/../../etc/passwd"),
+			None
+		);
+		assert_eq!(
+			resolve_host_path(Some(root), Path::new("data"), c"../../etc/passwd"),
+			None
+		);
+
+		// Absolute paths are resolved relative to the host root and must not escape it either
+		let resolved = resolve_host_path(Some(root), Path::new("data"), c"/tmp/foo.txt").unwrap();
+		assert_eq!(resolved.as_c_str(), c"/srv/uhyve/tmp/foo.txt");
+		assert_eq!(
+			resolve_host_path(Some(root), Path::new(""), c"/../etc/passwd"),
+			None
+		);
+
+		// Relative paths are resolved against the guest's working directory first
+		let resolved = resolve_host_path(Some(root), Path::new("data"), c"foo.txt").unwrap();
+		assert_eq!(resolved.as_c_str(), c"/srv/uhyve/data/foo.txt");
+		// The working directory is relative to the host root, an absolute one escapes it
+		assert_eq!(
+			resolve_host_path(Some(root), Path::new("/tmp"), c"foo.txt"),
+			None
+		);
+
+		// Without a host root, relative paths are left to the working directory
+		let resolved = resolve_host_path(None, Path::new(""), c"foo.txt").unwrap();
+		assert_eq!(resolved.as_c_str(), c"foo.txt");
+
+		// Host paths with a nul byte can't be passed to the host
+		assert_eq!(
+			resolve_host_path(Some(Path::new("/srv/u\0hyve")), Path::new(""), c"foo.txt"),
+			None
+		);
+	}
+}
@@ -10,7 +10,12 @@ mod macros;
 extern crate log;
 
 mod arch;
+mod archive;
+mod audit;
+mod barrier;
 pub mod consts;
+pub mod fdmap;
+pub mod fdt;
 #[cfg(target_os = "linux")]
 pub mod linux;
 #[cfg(target_os = "linux")]
@@ -23,8 +28,17 @@ mod hypercall;
 pub mod mem;
 pub mod paging;
 pub mod params;
+mod procfs;
+pub mod rate_limiter;
+mod rlimit;
+pub mod runner;
+mod serial;
 #[cfg(target_os = "linux")]
 pub mod shared_queue;
+pub mod stats;
+mod sysv_stack;
+#[cfg(test)]
+mod test_utils;
 mod vcpu;
 pub mod virtio;
 pub mod virtqueue;
@@ -29,8 +29,7 @@ use x86_64::registers::debug::Dr6Flags;
 use self::breakpoints::SwBreakpoints;
 use super::HypervisorError;
 use crate::{
-	arch::x86_64::{registers::debug::HwBreakpoints, virt_to_phys},
-	consts::BOOT_PML4,
+	arch::x86_64::registers::debug::HwBreakpoints,
 	linux::{x86_64::kvm_cpu::KvmCpu, KickSignal},
 	vcpu::{VcpuStopReason, VirtualCPU},
 	vm::UhyveVm,
@@ -128,30 +127,19 @@ impl SingleThreadBase for GdbUhyve {
 
 	fn read_addrs(&mut self, start_addr: u64, data: &mut [u8]) -> TargetResult<usize, Self> {
 		let guest_addr = GuestVirtAddr::try_new(start_addr).map_err(|_e| TargetError::NonFatal)?;
-		// Safety: mem is copied to data before mem can be modified.
-		let src = unsafe {
-			self.vm.mem.slice_at(
-				virt_to_phys(guest_addr, &self.vm.mem, BOOT_PML4).map_err(|_err| ())?,
-				data.len(),
-			)
-		}
-		.unwrap();
-		data.copy_from_slice(src);
+		let src = self
+			.vm
+			.read_guest_virt(guest_addr, data.len())
+			.map_err(|_err| ())?;
+		data.copy_from_slice(&src);
 		Ok(data.len())
 	}
 
 	fn write_addrs(&mut self, start_addr: u64, data: &[u8]) -> TargetResult<(), Self> {
-		// Safety: self.vm.mem is not altered during the lifetime of mem.
-		let mem = unsafe {
-			self.vm.mem.slice_at_mut(
-				virt_to_phys(GuestVirtAddr::new(start_addr), &self.vm.mem, BOOT_PML4)
-					.map_err(|_err| ())?,
-				data.len(),
-			)
-		}
-		.unwrap();
-
-		mem.copy_from_slice(data);
+		let guest_addr = GuestVirtAddr::try_new(start_addr).map_err(|_e| TargetError::NonFatal)?;
+		self.vm
+			.write_guest_virt(guest_addr, data)
+			.map_err(|_err| ())?;
 		Ok(())
 	}
 
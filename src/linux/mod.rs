@@ -6,16 +6,29 @@ pub mod gdb;
 pub type HypervisorError = kvm_ioctls::Error;
 pub type DebugExitInfo = kvm_bindings::kvm_debug_exit_arch;
 
+/// Returns the [`HypervisorError`] for the error number `errno`.
+pub(crate) fn errno_error(errno: i32) -> HypervisorError {
+	HypervisorError::new(errno)
+}
+
 use std::{
+	cell::Cell,
 	io,
 	net::{TcpListener, TcpStream},
 	os::unix::prelude::JoinHandleExt,
-	sync::{Arc, Barrier},
-	thread,
+	ptr,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		mpsc::{self, Receiver, Sender},
+		Arc,
+	},
+	thread::{self, JoinHandle},
+	time::Instant,
 };
 
 use core_affinity::CoreId;
 use gdbstub::stub::{DisconnectReason, GdbStub};
+use kvm_bindings::{kvm_irqchip, kvm_run, KVM_IRQCHIP_IOAPIC};
 use kvm_ioctls::Kvm;
 use lazy_static::lazy_static;
 use libc::{SIGRTMAX, SIGRTMIN};
@@ -23,20 +36,29 @@ use nix::sys::{
 	pthread::{pthread_kill, Pthread},
 	signal::{signal, SigHandler, Signal},
 };
+use uhyve_interface::GuestPhysAddr;
 
 use crate::{
+	barrier::GuestBarrier,
 	linux::{
 		gdb::{GdbUhyve, UhyveGdbEventLoop},
 		x86_64::kvm_cpu::KvmCpu,
 	},
+	rlimit::RlimitGuard,
 	vcpu::VirtualCPU,
-	vm::UhyveVm,
+	vm::{UhyveVm, VmResult},
+	HypervisorResult,
 };
 
 lazy_static! {
 	static ref KVM: Kvm = Kvm::new().unwrap();
 }
 
+thread_local! {
+	/// The `kvm_run` structure of the vCPU running on this thread, see [`KickSignal::set_kvm_run`].
+	static KVM_RUN: Cell<*mut kvm_run> = const { Cell::new(ptr::null_mut()) };
+}
+
 /// The signal for kicking vCPUs out of KVM_RUN.
 ///
 /// It is used to stop a vCPU from another thread.
@@ -54,7 +76,14 @@ impl KickSignal {
 	}
 
 	fn register_handler() -> nix::Result<()> {
-		extern "C" fn handle_signal(_signal: libc::c_int) {}
+		extern "C" fn handle_signal(_signal: libc::c_int) {
+			// Also stop a vCPU that is about to enter KVM_RUN or is handling an exit.
+			let run = KVM_RUN.get();
+			if !run.is_null() {
+				// SAFETY: The pointer was set by this thread and the vCPU outlives the kick.
+				unsafe { ptr::addr_of_mut!((*run).immediate_exit).write_volatile(1) };
+			}
+		}
 		// SAFETY: We don't use the `signal`'s return value.
 		unsafe {
 			signal(Self::get(), SigHandler::Handler(handle_signal))?;
@@ -68,33 +97,166 @@ impl KickSignal {
 	fn pthread_kill(pthread: Pthread) -> nix::Result<()> {
 		pthread_kill(pthread, Self::get())
 	}
+
+	/// Makes kicks of the current thread set `immediate_exit` in `run`.
+	///
+	/// Afterwards, a kick stops the vCPU even if it isn't inside KVM_RUN yet. `run` must be reset
+	/// to null before the vCPU is dropped.
+	fn set_kvm_run(run: *mut kvm_run) {
+		KVM_RUN.set(run);
+	}
+}
+
+/// The state shared between a VM running in the background and its [`VmHandle`].
+#[derive(Debug)]
+struct Cancellation {
+	cancelled: AtomicBool,
+	/// Wakes up the thread waiting for the vCPUs to exit.
+	exit_tx: Sender<()>,
+}
+
+/// A handle to a VM running on a background thread.
+///
+/// Created by [`UhyveVm::spawn`].
+#[derive(Debug)]
+pub struct VmHandle {
+	thread: Option<JoinHandle<HypervisorResult<VmResult>>>,
+	cancellation: Arc<Cancellation>,
+	barrier: Arc<GuestBarrier>,
+}
+
+impl VmHandle {
+	/// Returns the result of the VM, if it has finished.
+	///
+	/// The result is only returned once, subsequent calls return `None`.
+	pub fn try_join(&mut self) -> Option<HypervisorResult<VmResult>> {
+		if self.thread.as_ref()?.is_finished() {
+			Some(self.thread.take().unwrap().join().unwrap())
+		} else {
+			None
+		}
+	}
+
+	/// Blocks until the VM has finished execution and returns its result, see [`UhyveVm::run`].
+	///
+	/// Panics if the result was already returned by [`VmHandle::try_join`].
+	pub fn join(mut self) -> HypervisorResult<VmResult> {
+		self.thread
+			.take()
+			.expect("the result was already taken")
+			.join()
+			.unwrap()
+	}
+
+	/// Stops the VM by kicking its vCPUs.
+	///
+	/// The result of a cancelled VM has [`VmResult::cancelled`] set. Cancelling a VM that already
+	/// finished has no effect. Cancellation is not supported while a debugger is attached.
+	pub fn cancel(&self) {
+		self.cancellation.cancelled.store(true, Ordering::Relaxed);
+		self.cancellation.exit_tx.send(()).ok();
+	}
+
+	/// Returns whether the guest is waiting at the barrier of the `Barrier` hypercall.
+	pub fn is_at_barrier(&self) -> bool {
+		self.barrier.is_waiting()
+	}
+
+	/// Releases the guest from the barrier of the `Barrier` hypercall.
+	///
+	/// The barrier stays open afterwards, also if the guest didn't reach it yet.
+	pub fn release_barrier(&self) {
+		self.barrier.release();
+	}
 }
 
 impl UhyveVm<KvmCpu> {
 	/// Runs the VM.
 	///
-	/// Blocks until the VM has finished execution.
-	pub fn run(mut self, cpu_affinity: Option<Vec<CoreId>>) -> i32 {
+	/// Blocks until the VM has finished execution. Returns an error if the hypervisor failed,
+	/// e.g., if a vCPU couldn't be created. The guest's exit code, including the one of a guest
+	/// that faulted, timed out or was cancelled, is reported in the [`VmResult`].
+	pub fn run(self, cpu_affinity: Option<Vec<CoreId>>) -> HypervisorResult<VmResult> {
+		let (exit_tx, exit_rx) = mpsc::channel();
+		let cancellation = Cancellation {
+			cancelled: AtomicBool::new(false),
+			exit_tx,
+		};
+		self.run_cancellable(cpu_affinity, &cancellation, exit_rx)
+	}
+
+	/// Runs the VM on a background thread.
+	///
+	/// The returned [`VmHandle`] can be used to wait for or to cancel the VM.
+	pub fn spawn(self, cpu_affinity: Option<Vec<CoreId>>) -> VmHandle {
+		let (exit_tx, exit_rx) = mpsc::channel();
+		let cancellation = Arc::new(Cancellation {
+			cancelled: AtomicBool::new(false),
+			exit_tx,
+		});
+		let barrier = self.barrier.clone();
+		let thread = {
+			let cancellation = cancellation.clone();
+			thread::spawn(move || self.run_cancellable(cpu_affinity, &cancellation, exit_rx))
+		};
+		VmHandle {
+			thread: Some(thread),
+			cancellation,
+			barrier,
+		}
+	}
+
+	/// Returns the base address of the IOAPIC as it is programmed into KVM.
+	pub fn programmed_ioapic_base(&self) -> Result<GuestPhysAddr, HypervisorError> {
+		let mut irqchip = kvm_irqchip {
+			chip_id: KVM_IRQCHIP_IOAPIC,
+			..Default::default()
+		};
+		self.vm_fd.get_irqchip(&mut irqchip)?;
+		Ok(GuestPhysAddr::new(unsafe {
+			irqchip.chip.ioapic.base_address
+		}))
+	}
+
+	fn run_cancellable(
+		mut self,
+		cpu_affinity: Option<Vec<CoreId>>,
+		cancellation: &Cancellation,
+		exit_rx: Receiver<()>,
+	) -> HypervisorResult<VmResult> {
 		KickSignal::register_handler().unwrap();
+		let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
 
-		self.load_kernel().expect("Unabled to load the kernel");
+		self.load_kernel().map_err(|err| {
+			error!("Unable to load the kernel: {err}");
+			errno_error(err.errno())
+		})?;
+		let _rlimits = RlimitGuard::apply(&self.rlimits).map_err(|err| {
+			error!("Unable to apply the resource limits: {err}");
+			errno_error(err as i32)
+		})?;
 
 		if self.gdb_port.is_none() {
-			self.run_no_gdb(cpu_affinity)
+			self.run_no_gdb(cpu_affinity, cancellation, exit_rx, deadline)
 		} else {
 			self.run_gdb(cpu_affinity)
 		}
 	}
 
-	fn run_no_gdb(self, cpu_affinity: Option<Vec<CoreId>>) -> i32 {
-		// After spinning up all vCPU threads, the main thread waits for any vCPU to end execution.
-		let barrier = Arc::new(Barrier::new(2));
-
+	fn run_no_gdb(
+		self,
+		cpu_affinity: Option<Vec<CoreId>>,
+		cancellation: &Cancellation,
+		exit_rx: Receiver<()>,
+		deadline: Option<Instant>,
+	) -> HypervisorResult<VmResult> {
+		// After spinning up all vCPU threads, the main thread waits for any vCPU to end execution,
+		// for the cancellation of the VM or for the deadline.
 		let this = Arc::new(self);
 		let threads = (0..this.num_cpus())
 			.map(|cpu_id| {
 				let parent_vm = this.clone();
-				let barrier = barrier.clone();
+				let exit_tx = cancellation.exit_tx.clone();
 				let local_cpu_affinity = cpu_affinity
 					.as_ref()
 					.and_then(|core_ids| core_ids.get(cpu_id as usize).copied());
@@ -109,23 +271,41 @@ impl UhyveVm<KvmCpu> {
 						None => debug!("No affinity specified, not binding thread"),
 					}
 
-					let mut cpu = KvmCpu::new(cpu_id, parent_vm.clone()).unwrap();
+					let mut cpu = KvmCpu::new(cpu_id, parent_vm.clone()).inspect_err(|err| {
+						error!("Unable to create CPU {cpu_id}: {err}");
+						exit_tx.send(()).unwrap();
+					})?;
 
 					thread::sleep(std::time::Duration::from_millis(cpu_id as u64 * 50));
 
-					// jump into the VM and execute code of the guest
-					match cpu.run() {
+					// The VM is stopped by aborting the barrier and kicking the vCPUs. A kick that
+					// arrived before this point was lost, so check whether the VM is stopping.
+					KickSignal::set_kvm_run(cpu.get_vcpu_mut().get_kvm_run());
+					let result = if parent_vm.barrier.is_aborted() {
+						Ok(None)
+					} else {
+						// jump into the VM and execute code of the guest
+						cpu.run()
+					};
+					KickSignal::set_kvm_run(ptr::null_mut());
+
+					match result {
 						Ok(code) => {
 							if code.is_some() {
 								// Let the main thread continue with kicking the other vCPUs
-								barrier.wait();
+								exit_tx.send(()).unwrap();
 							}
-							code
+							Ok(code)
 						}
 						Err(err) => {
 							error!("CPU {} crashed with {:?}", cpu_id, err);
-							barrier.wait();
-							Some(err.errno())
+							exit_tx.send(()).unwrap();
+							// A fault of the guest is part of its result.
+							if cpu.has_faulted() {
+								Ok(Some(err.errno()))
+							} else {
+								Err(err)
+							}
 						}
 					}
 				})
@@ -133,24 +313,67 @@ impl UhyveVm<KvmCpu> {
 			.collect::<Vec<_>>();
 
 		// Wait for one vCPU to return with an exit code.
-		barrier.wait();
+		let timed_out = match deadline {
+			Some(mut deadline) => loop {
+				if exit_rx
+					.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+					.is_ok()
+				{
+					break false;
+				}
+				// Heartbeats of the guest push the deadline back.
+				match this.extended_deadline(deadline) {
+					Some(extended) => deadline = extended,
+					None => break true,
+				}
+			},
+			None => {
+				exit_rx.recv().unwrap();
+				false
+			}
+		};
+		if timed_out {
+			warn!("Stopping the VM after exceeding its timeout");
+		}
 
+		// vCPUs waiting at the barrier can't be kicked
+		this.barrier.abort();
 		for thread in &threads {
 			KickSignal::pthread_kill(thread.as_pthread_t()).unwrap();
 		}
 
 		let code = threads
 			.into_iter()
-			.filter_map(|thread| thread.join().unwrap())
+			.map(|thread| thread.join().unwrap())
+			.collect::<HypervisorResult<Vec<_>>>()?
+			.into_iter()
+			.flatten()
 			.collect::<Vec<_>>();
-		match code.len() {
+		let code = match code.len() {
+			0 if timed_out => {
+				return Ok(VmResult {
+					timed_out: true,
+					..this.result(libc::ETIMEDOUT)
+				});
+			}
+			0 if cancellation.cancelled.load(Ordering::Relaxed) => {
+				return Ok(VmResult {
+					cancelled: true,
+					..this.result(libc::ECANCELED)
+				});
+			}
 			0 => panic!("No return code from any CPU? Maybe all have been kicked?"),
 			1 => code[0],
-			_ => panic!("more than one thread finished with an exit code (codes: {code:?})"),
-		}
+			_ => {
+				// E.g., several vCPUs faulted at the same time.
+				warn!("More than one vCPU finished with an exit code (codes: {code:?})");
+				code[0]
+			}
+		};
+		Ok(this.result(code))
 	}
 
-	fn run_gdb(self, cpu_affinity: Option<Vec<CoreId>>) -> i32 {
+	fn run_gdb(self, cpu_affinity: Option<Vec<CoreId>>) -> HypervisorResult<VmResult> {
 		let cpu_id = 0;
 
 		let local_cpu_affinity = cpu_affinity
@@ -166,13 +389,13 @@ impl UhyveVm<KvmCpu> {
 		}
 
 		let this = Arc::new(self);
-		let cpu = KvmCpu::new(cpu_id, this.clone()).unwrap();
+		let cpu = KvmCpu::new(cpu_id, this.clone())?;
 
 		let connection = wait_for_gdb_connection(this.gdb_port.unwrap()).unwrap();
 		let debugger = GdbStub::new(connection);
-		let mut debuggable_vcpu = GdbUhyve::new(this, cpu);
+		let mut debuggable_vcpu = GdbUhyve::new(this.clone(), cpu);
 
-		match debugger
+		let code = match debugger
 			.run_blocking::<UhyveGdbEventLoop>(&mut debuggable_vcpu)
 			.unwrap()
 		{
@@ -186,7 +409,10 @@ impl UhyveVm<KvmCpu> {
 				eprintln!("Kill command received.");
 				0
 			}
-		}
+		};
+		// Close the guest's files when the debug session ends, so that they don't outlive it.
+		this.fdmap.close_all();
+		Ok(this.result(code))
 	}
 }
 
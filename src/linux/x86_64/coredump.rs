@@ -0,0 +1,194 @@
+//! ELF core dumps of crashed guests.
+//!
+//! A dump contains the registers of the faulting vCPU as an `NT_PRSTATUS` note and the guest
+//! RAM as a single `PT_LOAD` segment at its guest-physical address, so that it can be inspected
+//! with the usual ELF tools, e.g., `gdb <kernel> <dump>`.
+
+use std::{
+	fs::File,
+	io::{self, BufWriter, Write},
+	path::Path,
+};
+
+use kvm_bindings::{kvm_regs, kvm_sregs};
+
+use crate::{consts::PAGE_SIZE, mem::MmapMemory};
+
+const ELF_HEADER_SIZE: u64 = 64;
+const PROGRAM_HEADER_SIZE: u64 = 56;
+const ET_CORE: u16 = 4;
+const EM_X86_64: u16 = 62;
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+const NT_PRSTATUS: u32 = 1;
+/// Size of `struct elf_prstatus` on x86_64.
+const PRSTATUS_SIZE: usize = 336;
+/// Offset of `pr_pid` in `struct elf_prstatus`.
+const PRSTATUS_PID: usize = 32;
+/// Offset of `pr_reg` in `struct elf_prstatus`.
+const PRSTATUS_REGS: usize = 112;
+
+/// Writes a core dump of the guest RAM `mem` and the registers of the vCPU `cpu_id` to `path`.
+pub fn write_core_dump(
+	path: &Path,
+	mem: &MmapMemory,
+	cpu_id: u32,
+	regs: &kvm_regs,
+	sregs: &kvm_sregs,
+) -> io::Result<()> {
+	let note = prstatus_note(cpu_id, regs, sregs);
+	let note_offset = ELF_HEADER_SIZE + 2 * PROGRAM_HEADER_SIZE;
+	let ram_offset = (note_offset + note.len() as u64).next_multiple_of(PAGE_SIZE as u64);
+	// The vCPUs are stopped or don't touch the RAM anymore, as the guest crashed.
+	let ram = unsafe { mem.slice_at(mem.guest_address, mem.memory_size) }.unwrap();
+
+	let mut out = BufWriter::new(File::create(path)?);
+	out.write_all(&elf_header())?;
+	out.write_all(&program_header(
+		PT_NOTE,
+		0,
+		note_offset,
+		0,
+		note.len() as u64,
+		1,
+	))?;
+	out.write_all(&program_header(
+		PT_LOAD,
+		PF_R | PF_W | PF_X,
+		ram_offset,
+		mem.guest_address.as_u64(),
+		ram.len() as u64,
+		PAGE_SIZE as u64,
+	))?;
+	out.write_all(&note)?;
+	out.write_all(&vec![0; (ram_offset - note_offset) as usize - note.len()])?;
+	out.write_all(ram)?;
+	out.flush()
+}
+
+fn elf_header() -> Vec<u8> {
+	let mut header = Vec::with_capacity(ELF_HEADER_SIZE as usize);
+	// 64 bit, little endian, ELF version 1, System V ABI
+	header.extend_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0]);
+	header.extend_from_slice(&[0; 8]);
+	header.extend_from_slice(&ET_CORE.to_le_bytes());
+	header.extend_from_slice(&EM_X86_64.to_le_bytes());
+	header.extend_from_slice(&1u32.to_le_bytes());
+	// entry point
+	header.extend_from_slice(&0u64.to_le_bytes());
+	// program and section header offsets
+	header.extend_from_slice(&ELF_HEADER_SIZE.to_le_bytes());
+	header.extend_from_slice(&0u64.to_le_bytes());
+	// flags
+	header.extend_from_slice(&0u32.to_le_bytes());
+	header.extend_from_slice(&(ELF_HEADER_SIZE as u16).to_le_bytes());
+	header.extend_from_slice(&(PROGRAM_HEADER_SIZE as u16).to_le_bytes());
+	header.extend_from_slice(&2u16.to_le_bytes());
+	// no section headers
+	header.extend_from_slice(&[0; 6]);
+	header
+}
+
+fn program_header(kind: u32, flags: u32, offset: u64, addr: u64, size: u64, align: u64) -> Vec<u8> {
+	let mut header = Vec::with_capacity(PROGRAM_HEADER_SIZE as usize);
+	header.extend_from_slice(&kind.to_le_bytes());
+	header.extend_from_slice(&flags.to_le_bytes());
+	for field in [offset, addr, addr, size, size, align] {
+		header.extend_from_slice(&field.to_le_bytes());
+	}
+	header
+}
+
+/// Returns an `NT_PRSTATUS` note with the registers in the layout of `struct user_regs_struct`.
+fn prstatus_note(cpu_id: u32, regs: &kvm_regs, sregs: &kvm_sregs) -> Vec<u8> {
+	let mut prstatus = vec![0; PRSTATUS_SIZE];
+	// gdb uses the pid to tell threads apart, so vCPU 0 becomes thread 1
+	prstatus[PRSTATUS_PID..][..4].copy_from_slice(&(cpu_id + 1).to_le_bytes());
+	let user_regs = [
+		regs.r15,
+		regs.r14,
+		regs.r13,
+		regs.r12,
+		regs.rbp,
+		regs.rbx,
+		regs.r11,
+		regs.r10,
+		regs.r9,
+		regs.r8,
+		regs.rax,
+		regs.rcx,
+		regs.rdx,
+		regs.rsi,
+		regs.rdi,
+		// orig_rax
+		u64::MAX,
+		regs.rip,
+		sregs.cs.selector.into(),
+		regs.rflags,
+		regs.rsp,
+		sregs.ss.selector.into(),
+		sregs.fs.base,
+		sregs.gs.base,
+		sregs.ds.selector.into(),
+		sregs.es.selector.into(),
+		sregs.fs.selector.into(),
+		sregs.gs.selector.into(),
+	];
+	for (i, reg) in user_regs.iter().enumerate() {
+		prstatus[PRSTATUS_REGS + i * 8..][..8].copy_from_slice(&reg.to_le_bytes());
+	}
+
+	let mut note = Vec::new();
+	note.extend_from_slice(&5u32.to_le_bytes());
+	note.extend_from_slice(&(PRSTATUS_SIZE as u32).to_le_bytes());
+	note.extend_from_slice(&NT_PRSTATUS.to_le_bytes());
+	// the name is padded to 4 bytes
+	note.extend_from_slice(b"CORE\0\0\0\0");
+	note.extend_from_slice(&prstatus);
+	note
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+
+	use uhyve_interface::GuestPhysAddr;
+
+	use super::*;
+
+	#[test]
+	fn test_core_dump() {
+		let mem = MmapMemory::new(0, 0x10_0000, GuestPhysAddr::new(0), false, false);
+		let signature = b"uhyve core dump";
+		unsafe { mem.slice_at_mut(GuestPhysAddr::new(0x2000), signature.len()) }
+			.unwrap()
+			.copy_from_slice(signature);
+		let regs = kvm_regs {
+			rip: 0xdead_beef,
+			rsp: 0x8000,
+			..Default::default()
+		};
+
+		let dir = assert_fs::TempDir::new().unwrap();
+		let path = dir.path().join("core");
+		write_core_dump(&path, &mem, 0, &regs, &Default::default()).unwrap();
+		let dump = fs::read(path).unwrap();
+
+		assert_eq!(&dump[..4], b"\x7fELF");
+		assert_eq!(u16::from_le_bytes([dump[16], dump[17]]), ET_CORE);
+		let note_offset = (ELF_HEADER_SIZE + 2 * PROGRAM_HEADER_SIZE) as usize;
+		let prstatus = &dump[note_offset + 20..][..PRSTATUS_SIZE];
+		let reg = |i: usize| {
+			u64::from_le_bytes(prstatus[PRSTATUS_REGS + i * 8..][..8].try_into().unwrap())
+		};
+		assert_eq!(reg(16), 0xdead_beef);
+		assert_eq!(reg(19), 0x8000);
+
+		let ram_offset = dump.len() - mem.memory_size;
+		assert_eq!(ram_offset % PAGE_SIZE, 0);
+		assert_eq!(&dump[ram_offset + 0x2000..][..signature.len()], signature);
+	}
+}
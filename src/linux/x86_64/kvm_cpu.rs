@@ -1,19 +1,21 @@
-use std::sync::{Arc, Mutex};
+use std::{ops::Range, sync::Arc};
 
 use kvm_bindings::*;
-use kvm_ioctls::{VcpuExit, VcpuFd, VmFd};
-use uhyve_interface::{GuestPhysAddr, Hypercall};
+use kvm_ioctls::{Cap, VcpuExit, VcpuFd, VmFd};
+use uhyve_interface::{GuestPhysAddr, GuestVirtAddr, Hypercall};
 use vmm_sys_util::eventfd::EventFd;
 use x86_64::registers::control::{Cr0Flags, Cr4Flags};
 
 use crate::{
 	consts::*,
 	hypercall,
-	linux::KVM,
+	linux::{x86_64::coredump::write_core_dump, KVM},
 	mem::MmapMemory,
+	stats::PerfCounters,
 	vcpu::{VcpuStopReason, VirtualCPU},
+	virt_to_phys,
 	virtio::*,
-	vm::UhyveVm,
+	vm::{GuestFault, UhyveVm},
 	HypervisorError, HypervisorResult,
 };
 
@@ -21,6 +23,10 @@ const CPUID_EXT_HYPERVISOR: u32 = 1 << 31;
 const CPUID_TSC_DEADLINE: u32 = 1 << 24;
 const CPUID_ENABLE_MSR: u32 = 1 << 5;
 const MSR_IA32_MISC_ENABLE: u32 = 0x000001a0;
+const MSR_CORE_PERF_FIXED_CTR0: u32 = 0x00000309;
+const MSR_CORE_PERF_FIXED_CTR1: u32 = 0x0000030a;
+const MSR_CORE_PERF_FIXED_CTR_CTRL: u32 = 0x0000038d;
+const MSR_CORE_PERF_GLOBAL_CTRL: u32 = 0x0000038f;
 const PCI_CONFIG_DATA_PORT: u16 = 0xCFC;
 const PCI_CONFIG_ADDRESS_PORT: u16 = 0xCF8;
 
@@ -28,9 +34,23 @@ const KVM_32BIT_MAX_MEM_SIZE: usize = 1 << 32;
 const KVM_32BIT_GAP_SIZE: usize = 768 << 20;
 const KVM_32BIT_GAP_START: usize = KVM_32BIT_MAX_MEM_SIZE - KVM_32BIT_GAP_SIZE;
 
-static KVM_ACCESS: Mutex<Option<VmFd>> = Mutex::new(None);
+/// Returns whether `range` overlaps the guest RAM as it is mapped by [`initialize_kvm`].
+pub fn overlaps_guest_ram(mem: &MmapMemory, range: Range<u64>) -> bool {
+	let start = mem.guest_address.as_u64();
+	let below_gap = start..start + std::cmp::min(mem.memory_size, KVM_32BIT_GAP_START) as u64;
+	let above_gap =
+		start + (KVM_32BIT_GAP_START + KVM_32BIT_GAP_SIZE) as u64..start + mem.memory_size as u64;
+	[below_gap, above_gap]
+		.iter()
+		.any(|ram| range.start < ram.end && ram.start < range.end)
+}
 
-pub fn initialize_kvm(mem: &MmapMemory, use_pit: bool) -> HypervisorResult<()> {
+/// Creates a KVM VM backed by `mem`, whose IOAPIC is located at `ioapic_base`.
+pub fn initialize_kvm(
+	mem: &MmapMemory,
+	use_pit: bool,
+	ioapic_base: GuestPhysAddr,
+) -> HypervisorResult<VmFd> {
 	let sz = std::cmp::min(mem.memory_size, KVM_32BIT_GAP_START);
 
 	let kvm_mem = kvm_userspace_memory_region {
@@ -41,7 +61,6 @@ pub fn initialize_kvm(mem: &MmapMemory, use_pit: bool) -> HypervisorResult<()> {
 		userspace_addr: mem.host_address as u64,
 	};
 
-	// TODO: make vm a global struct in linux blah
 	let vm = KVM.create_vm()?;
 	unsafe { vm.set_user_memory_region(kvm_mem) }?;
 
@@ -64,6 +83,14 @@ pub fn initialize_kvm(mem: &MmapMemory, use_pit: bool) -> HypervisorResult<()> {
 	// create basic interrupt controller
 	vm.create_irq_chip()?;
 
+	let mut irqchip = kvm_irqchip {
+		chip_id: KVM_IRQCHIP_IOAPIC,
+		..Default::default()
+	};
+	vm.get_irqchip(&mut irqchip)?;
+	irqchip.chip.ioapic.base_address = ioapic_base.as_u64();
+	vm.set_irqchip(&irqchip)?;
+
 	if use_pit {
 		vm.create_pit2(kvm_pit_config::default()).unwrap();
 	}
@@ -109,8 +136,7 @@ pub fn initialize_kvm(mem: &MmapMemory, use_pit: bool) -> HypervisorResult<()> {
 	let evtfd = EventFd::new(0).unwrap();
 	vm.register_irqfd(&evtfd, UHYVE_IRQ_NET)?;
 
-	*KVM_ACCESS.lock().unwrap() = Some(vm);
-	Ok(())
+	Ok(vm)
 }
 
 pub struct KvmCpu {
@@ -118,9 +144,61 @@ pub struct KvmCpu {
 	vcpu: VcpuFd,
 	parent_vm: Arc<UhyveVm<Self>>,
 	pci_addr: Option<u32>,
+	/// Whether the fixed performance counters are enabled.
+	perf_counters: bool,
+	/// Whether this vCPU recorded a [`GuestFault`] in the VM.
+	faulted: bool,
 }
 
 impl KvmCpu {
+	/// Records the state of the vCPU, which stopped abnormally because of `reason`, in the VM
+	/// and returns the error to stop the vCPU with.
+	fn fault(&mut self, reason: String) -> HypervisorError {
+		let (Ok(regs), Ok(sregs)) = (self.vcpu.get_regs(), self.vcpu.get_sregs()) else {
+			error!("CPU {} stopped with {reason}", self.id);
+			return HypervisorError::new(libc::EFAULT);
+		};
+
+		let mem = &self.parent_vm.mem;
+		let pml4 = GuestPhysAddr::new(sregs.cr3 & !0xfff);
+		let read_code = |start: u64| {
+			if mem.host_address(pml4).is_err() {
+				return None;
+			}
+			(start..regs.rip.saturating_add(GuestFault::CODE_WINDOW))
+				.map(|addr| {
+					let addr = GuestVirtAddr::try_new(addr).ok()?;
+					let phys = virt_to_phys(addr, mem, pml4).ok()?;
+					unsafe { mem.slice_at(phys, 1) }.ok().map(|byte| byte[0])
+				})
+				.collect::<Option<Vec<_>>>()
+				.map(|code| (start, code))
+		};
+		let (code_start, code) = read_code(regs.rip.saturating_sub(GuestFault::CODE_WINDOW))
+			.or_else(|| read_code(regs.rip))
+			.unwrap_or((regs.rip, Vec::new()));
+
+		let fault = GuestFault {
+			cpu_id: self.id,
+			reason,
+			rip: regs.rip,
+			rsp: regs.rsp,
+			cr2: sregs.cr2,
+			code,
+			code_start,
+		};
+		error!("{fault}");
+		if let Some(path) = &self.parent_vm.core_dump {
+			match write_core_dump(path, mem, self.id, &regs, &sregs) {
+				Ok(()) => info!("Wrote a core dump to {}", path.display()),
+				Err(err) => error!("Unable to write a core dump to {}: {err}", path.display()),
+			}
+		}
+		*self.parent_vm.fault.lock().unwrap() = Some(fault);
+		self.faulted = true;
+		HypervisorError::new(libc::EFAULT)
+	}
+
 	fn setup_cpuid(&self) -> Result<(), kvm_ioctls::Error> {
 		//debug!("Setup cpuid");
 
@@ -191,8 +269,25 @@ impl KvmCpu {
 			.position(|&r| r.function == 0x0A)
 			.unwrap();
 
-		// disable performance monitor
-		kvm_cpuid_entries[i].eax = 0x00;
+		// disable performance monitor, unless the guest's fixed counters are requested
+		if !self.parent_vm.perf_counters {
+			kvm_cpuid_entries[i].eax = 0x00;
+		}
+
+		if let Some(cpu_features) = &self.parent_vm.cpu_features {
+			for entry in kvm_cpuid_entries.iter_mut() {
+				cpu_features.apply(
+					entry.function,
+					entry.index,
+					[
+						&mut entry.eax,
+						&mut entry.ebx,
+						&mut entry.ecx,
+						&mut entry.edx,
+					],
+				);
+			}
+		}
 
 		self.vcpu.set_cpuid2(&kvm_cpuid)?;
 
@@ -235,10 +330,14 @@ impl KvmCpu {
 
 		let mut sregs = self.vcpu.get_sregs()?;
 
-		let cr0 = Cr0Flags::PROTECTED_MODE_ENABLE
+		let mut cr0 = Cr0Flags::PROTECTED_MODE_ENABLE
 			| Cr0Flags::EXTENSION_TYPE
 			| Cr0Flags::NUMERIC_ERROR
 			| Cr0Flags::PAGING;
+		// Write protection makes read-only pages trap on kernel writes as well.
+		if self.parent_vm.write_protect {
+			cr0 |= Cr0Flags::WRITE_PROTECT;
+		}
 		sregs.cr0 = cr0.bits();
 
 		sregs.cr3 = BOOT_PML4.as_u64();
@@ -248,6 +347,9 @@ impl KvmCpu {
 
 		sregs.efer = EFER_LME | EFER_LMA | EFER_NXE;
 
+		// Keep the enable and BSP flags, only relocate the local APIC.
+		sregs.apic_base = self.parent_vm.apic_base().as_u64() | (sregs.apic_base & 0xfff);
+
 		let mut seg = kvm_segment {
 			base: 0,
 			limit: 0xffffffff,
@@ -297,6 +399,11 @@ impl KvmCpu {
 		println!("{name}       {seg:?}");
 	}
 
+	/// Returns whether the vCPU stopped because of a fault of the guest.
+	pub fn has_faulted(&self) -> bool {
+		self.faulted
+	}
+
 	pub fn get_vcpu(&self) -> &VcpuFd {
 		&self.vcpu
 	}
@@ -317,25 +424,109 @@ impl KvmCpu {
 
 		self.setup_msrs()?;
 
+		if let Some(scale) = self.parent_vm.tsc_scale {
+			self.setup_tsc_scale(scale)?;
+		}
+
+		if self.parent_vm.perf_counters {
+			self.perf_counters = self.setup_perf_counters()?;
+			if !self.perf_counters {
+				warn!("The virtual PMU doesn't support fixed counters, not counting instructions");
+			}
+		}
+
 		Ok(())
 	}
+
+	/// Runs the TSC of this vCPU at `scale` times the host's TSC frequency.
+	fn setup_tsc_scale(&self, scale: f64) -> Result<(), kvm_ioctls::Error> {
+		if !KVM.check_extension(Cap::TscControl) {
+			error!("KVM doesn't support TSC scaling on this host");
+			return Err(kvm_ioctls::Error::new(libc::ENOTSUP));
+		}
+		let host_khz = self.vcpu.get_tsc_khz()?;
+		let guest_khz = (f64::from(host_khz) * scale).round();
+		if !(1.0..=f64::from(u32::MAX)).contains(&guest_khz) {
+			error!("The TSC scale {scale} is out of range");
+			return Err(kvm_ioctls::Error::new(libc::EINVAL));
+		}
+		self.vcpu.set_tsc_khz(guest_khz as u32).inspect_err(|err| {
+			error!(
+				"KVM can't scale the TSC from {host_khz} kHz to {guest_khz} kHz (scale {scale}): {err}"
+			)
+		})
+	}
+
+	/// Resets and enables the fixed counters of the virtual PMU for retired instructions and
+	/// unhalted core cycles.
+	///
+	/// Returns whether the virtual PMU supports them.
+	fn setup_perf_counters(&self) -> Result<bool, kvm_ioctls::Error> {
+		let msr = |index, data| kvm_msr_entry {
+			index,
+			data,
+			..Default::default()
+		};
+		let msrs = Msrs::from_entries(&[
+			msr(MSR_CORE_PERF_FIXED_CTR0, 0),
+			msr(MSR_CORE_PERF_FIXED_CTR1, 0),
+			// count in all privilege levels
+			msr(MSR_CORE_PERF_FIXED_CTR_CTRL, 0x33),
+			msr(MSR_CORE_PERF_GLOBAL_CTRL, (1 << 32) | (1 << 33)),
+		])
+		.unwrap();
+		// KVM stops at the first MSR it doesn't support
+		Ok(self.vcpu.set_msrs(&msrs)? == msrs.as_slice().len())
+	}
+
+	/// Adds the fixed performance counters of this vCPU to the VM's statistics, if they are
+	/// enabled.
+	fn record_perf_counters(&self) {
+		if !self.perf_counters {
+			return;
+		}
+		let mut msrs = Msrs::from_entries(&[
+			kvm_msr_entry {
+				index: MSR_CORE_PERF_FIXED_CTR0,
+				..Default::default()
+			},
+			kvm_msr_entry {
+				index: MSR_CORE_PERF_FIXED_CTR1,
+				..Default::default()
+			},
+		])
+		.unwrap();
+		match self.vcpu.get_msrs(&mut msrs) {
+			Ok(2) => {
+				let [instructions, cycles] = [0, 1].map(|i| msrs.as_slice()[i].data);
+				self.parent_vm.stats.add_perf_counters(PerfCounters {
+					instructions,
+					cycles,
+				});
+			}
+			Ok(_) => warn!("Unable to read the performance counters of CPU {}", self.id),
+			Err(err) => warn!(
+				"Unable to read the performance counters of CPU {}: {err}",
+				self.id
+			),
+		}
+	}
 }
 
 impl VirtualCPU for KvmCpu {
+	const NAME: &'static str = "kvm";
+
 	fn new(id: u32, parent_vm: Arc<UhyveVm<KvmCpu>>) -> HypervisorResult<KvmCpu> {
-		let vcpu = KVM_ACCESS
-			.lock()
-			.unwrap()
-			.as_mut()
-			.expect("KVM is not initialized yet")
-			.create_vcpu(id as u64)?;
+		let vcpu = parent_vm.vm_fd.create_vcpu(id as u64)?;
 		let mut kvcpu = KvmCpu {
 			id,
 			vcpu,
 			parent_vm: parent_vm.clone(),
 			pci_addr: None,
+			perf_counters: false,
+			faulted: false,
 		};
-		kvcpu.init(parent_vm.get_entry_point(), parent_vm.stack_address(), id)?;
+		kvcpu.init(parent_vm.get_entry_point(), parent_vm.stack_pointer(), id)?;
 
 		Ok(kvcpu)
 	}
@@ -349,7 +540,8 @@ impl VirtualCPU for KvmCpu {
 						debug!("{:?}", VcpuExit::Hlt);
 					}
 					VcpuExit::Shutdown => {
-						return Ok(VcpuStopReason::Exit(0));
+						// The guest triple faulted
+						return Err(self.fault("Shutdown".to_string()));
 					}
 					VcpuExit::IoIn(port, addr) => match port {
 						PCI_CONFIG_DATA_PORT => {
@@ -401,9 +593,16 @@ impl VirtualCPU for KvmCpu {
 						if let Some(hypercall) = unsafe {
 							hypercall::address_to_hypercall(&self.parent_vm.mem, port, data_addr)
 						} {
+							if let Some(limiter) = &self.parent_vm.hypercall_limiter {
+								limiter.acquire();
+							}
+							self.parent_vm.stats.count_hypercall(port);
 							match hypercall {
-								Hypercall::Cmdsize(syssize) => syssize
-									.update(self.parent_vm.kernel_path(), self.parent_vm.args()),
+								Hypercall::Cmdsize(syssize) => syssize.update(
+									self.parent_vm.kernel_path(),
+									self.parent_vm.args(),
+									self.parent_vm.guest_env(),
+								),
 								Hypercall::Cmdval(syscmdval) => {
 									hypercall::copy_argv(
 										self.parent_vm.kernel_path().as_os_str(),
@@ -411,27 +610,219 @@ impl VirtualCPU for KvmCpu {
 										syscmdval,
 										&self.parent_vm.mem,
 									);
-									hypercall::copy_env(syscmdval, &self.parent_vm.mem);
+									hypercall::copy_env(
+										syscmdval,
+										&self.parent_vm.mem,
+										self.parent_vm.guest_env(),
+									);
 								}
 								Hypercall::Exit(sysexit) => {
 									return Ok(VcpuStopReason::Exit(sysexit.arg));
 								}
-								Hypercall::FileClose(sysclose) => hypercall::close(sysclose),
-								Hypercall::FileLseek(syslseek) => hypercall::lseek(syslseek),
+								Hypercall::FileClose(sysclose) => {
+									hypercall::close(sysclose, &self.parent_vm.fdmap)
+								}
+								Hypercall::FileLseek(syslseek) => {
+									hypercall::lseek(syslseek, &self.parent_vm.fdmap)
+								}
+								Hypercall::FileLseek2(syslseek) => {
+									hypercall::lseek2(syslseek, &self.parent_vm.fdmap)
+								}
 								Hypercall::FileOpen(sysopen) => {
-									hypercall::open(&self.parent_vm.mem, sysopen)
+									hypercall::open(
+										&self.parent_vm.mem,
+										sysopen,
+										self.parent_vm.host_root(),
+										&self.parent_vm.cwd.read().unwrap(),
+										&self.parent_vm.fdmap,
+										Some(&self.parent_vm.proc_info()),
+										self.parent_vm.audit_log.as_ref(),
+									);
+								}
+								Hypercall::FileOpen2(sysopen2) => hypercall::open2(
+									&self.parent_vm.mem,
+									sysopen2,
+									self.parent_vm.host_root(),
+									&self.parent_vm.cwd.read().unwrap(),
+									&self.parent_vm.fdmap,
+									Some(&self.parent_vm.proc_info()),
+									self.parent_vm.audit_log.as_ref(),
+								),
+								Hypercall::FileOpenat(sysopenat) => hypercall::openat(
+									&self.parent_vm.mem,
+									sysopenat,
+									&self.parent_vm.fdmap,
+									self.parent_vm.audit_log.as_ref(),
+								),
+								Hypercall::FileRead(sysread) => hypercall::read(
+									&self.parent_vm.mem,
+									sysread,
+									&self.parent_vm.fdmap,
+								),
+								Hypercall::FileReadExact(sysread) => hypercall::read_exact(
+									&self.parent_vm.mem,
+									sysread,
+									&self.parent_vm.fdmap,
+								),
+								Hypercall::MemBalloon(sysballoon) => hypercall::mem_balloon(
+									&self.parent_vm.mem,
+									sysballoon,
+									&self.parent_vm.committed_memory,
+									self.parent_vm.boot_memory_size,
+								),
+								Hypercall::Sendfile(syssendfile) => {
+									hypercall::sendfile(syssendfile, &self.parent_vm.fdmap)
 								}
-								Hypercall::FileRead(sysread) => {
-									hypercall::read(&self.parent_vm.mem, sysread)
+								Hypercall::FileWrite(syswrite) => hypercall::write(
+									&self.parent_vm.mem,
+									syswrite,
+									&self.parent_vm.fdmap,
+									&self.parent_vm.serial,
+								),
+								Hypercall::FileWrite2(syswrite) => hypercall::write2(
+									&self.parent_vm.mem,
+									syswrite,
+									&self.parent_vm.fdmap,
+									&self.parent_vm.serial,
+								),
+								Hypercall::FileUnlink(sysunlink) => hypercall::unlink(
+									&self.parent_vm.mem,
+									sysunlink,
+									self.parent_vm.host_root(),
+									&self.parent_vm.cwd.read().unwrap(),
+									self.parent_vm.audit_log.as_ref(),
+								),
+								Hypercall::SchedYield => hypercall::sched_yield(),
+								Hypercall::SyncAll(syssyncall) => {
+									hypercall::sync_all(syssyncall, &self.parent_vm.fdmap)
 								}
-								Hypercall::FileWrite(syswrite) => {
-									hypercall::write(&self.parent_vm.mem, syswrite)
-										.map_err(|_e| HypervisorError::new(libc::EFAULT))?
+								Hypercall::SerialWriteByte(buf) => {
+									hypercall::uart(&self.parent_vm.serial, &[buf])?
 								}
-								Hypercall::FileUnlink(sysunlink) => {
-									hypercall::unlink(&self.parent_vm.mem, sysunlink)
+								Hypercall::SetAppName(sysappname) => {
+									if let Some(name) =
+										hypercall::set_app_name(&self.parent_vm.mem, sysappname)
+									{
+										*self.parent_vm.app_name.lock().unwrap() = Some(name);
+									}
+								}
+								Hypercall::LogMessage(syslog) => hypercall::log_message(
+									&self.parent_vm.mem,
+									syslog,
+									*self.parent_vm.guest_log_level.lock().unwrap(),
+								),
+								Hypercall::Madvise(sysmadvise) => {
+									hypercall::madvise(&self.parent_vm.mem, sysmadvise)
+								}
+								Hypercall::Panic(syspanic) => {
+									let message = hypercall::panic(&self.parent_vm.mem, syspanic);
+									*self.parent_vm.panic_message.lock().unwrap() = Some(message);
+									return Ok(VcpuStopReason::Exit(GUEST_PANIC_EXIT_CODE));
+								}
+								Hypercall::Chdir(syschdir) => hypercall::chdir(
+									&self.parent_vm.mem,
+									syschdir,
+									self.parent_vm.host_root(),
+									&self.parent_vm.cwd,
+								),
+								Hypercall::GetCwd(sysgetcwd) => hypercall::getcwd(
+									&self.parent_vm.mem,
+									sysgetcwd,
+									&self.parent_vm.cwd.read().unwrap(),
+								),
+								Hypercall::Notify(sysnotify) => hypercall::notify(
+									&self.parent_vm.mem,
+									sysnotify,
+									self.parent_vm.event_hook.as_ref(),
+								),
+								Hypercall::Brk(sysbrk) => {
+									self.parent_vm.stats.record_brk(sysbrk.brk)
+								}
+								Hypercall::OutOfMemory(sysoom) => {
+									warn!("Guest failed to allocate {} bytes", { sysoom.size });
+									self.parent_vm
+										.stats
+										.record_out_of_memory(sysoom.size as u64)
+								}
+								Hypercall::Heartbeat(sysheartbeat) => {
+									self.parent_vm.heartbeat(sysheartbeat.progress)
+								}
+								Hypercall::SetPriority(syssetpriority) => hypercall::set_priority(
+									syssetpriority,
+									self.parent_vm.priority_bounds.as_ref(),
+								),
+								Hypercall::FileFadvise(sysfadvise) => {
+									hypercall::fadvise(sysfadvise, &self.parent_vm.fdmap)
+								}
+								Hypercall::Barrier(sysbarrier) => {
+									if !self.parent_vm.barrier.wait() {
+										sysbarrier.ret = -libc::ECANCELED;
+										return Ok(VcpuStopReason::Kick);
+									}
+									sysbarrier.ret = 0;
+								}
+								Hypercall::SetLogLevel(syssetloglevel) => hypercall::set_log_level(
+									syssetloglevel,
+									self.parent_vm.max_guest_log_level,
+									&self.parent_vm.guest_log_level,
+								),
+								Hypercall::FileReaddir(sysreaddir) => hypercall::readdir(
+									&self.parent_vm.mem,
+									sysreaddir,
+									&self.parent_vm.cwd.read().unwrap(),
+									&self.parent_vm.proc_info(),
+								),
+								Hypercall::FileStatvfs(sysstatvfs) => hypercall::statvfs(
+									&self.parent_vm.mem,
+									sysstatvfs,
+									self.parent_vm.host_root(),
+									&self.parent_vm.cwd.read().unwrap(),
+								),
+								Hypercall::NanoSleep(sysnanosleep) => {
+									if !hypercall::nanosleep(sysnanosleep) {
+										return Ok(VcpuStopReason::Kick);
+									}
+								}
+								Hypercall::GetEnv(sysgetenv) => hypercall::getenv(
+									&self.parent_vm.mem,
+									sysgetenv,
+									&self.parent_vm.env_allowlist,
+								),
+								Hypercall::GetHostname(sysgethostname) => hypercall::gethostname(
+									&self.parent_vm.mem,
+									sysgethostname,
+									&self.parent_vm.guest_hostname,
+								),
+								Hypercall::HostInfo(syshostinfo) => hypercall::host_info(
+									&self.parent_vm.mem,
+									syshostinfo,
+									Self::NAME,
+								),
+								Hypercall::SetSerialMode(sysserialmode) => {
+									hypercall::set_serial_mode(
+										&self.parent_vm.serial,
+										sysserialmode,
+									)
+								}
+								Hypercall::ClockCompare(sysclock) => {
+									let (host_ns, drift_ns) =
+										self.parent_vm.stats.record_clock(sysclock.guest_ns);
+									sysclock.host_ns = host_ns;
+									sysclock.drift_ns = drift_ns;
+								}
+								Hypercall::ReportMetric(sysmetric) => {
+									if let Some((name, value)) =
+										hypercall::report_metric(&self.parent_vm.mem, sysmetric)
+									{
+										let mut metrics = self.parent_vm.metrics.lock().unwrap();
+										metrics.entry(name).or_default().push(value);
+									}
+								}
+								Hypercall::TestResult(systestresult) => {
+									let report =
+										hypercall::test_result(&self.parent_vm.mem, systestresult);
+									self.parent_vm.test_reports.lock().unwrap().push(report);
 								}
-								Hypercall::SerialWriteByte(buf) => hypercall::uart(&[buf])?,
 								_ => panic!("Got unknown hypercall {:?}", hypercall),
 							};
 						} else {
@@ -484,11 +875,9 @@ impl VirtualCPU for KvmCpu {
 						info!("Caught Debug Interrupt!");
 						return Ok(VcpuStopReason::Debug(debug));
 					}
-					VcpuExit::InternalError => {
-						panic!("{:?}", VcpuExit::InternalError)
-					}
 					vcpu_exit => {
-						unimplemented!("{:?}", vcpu_exit)
+						let reason = format!("{vcpu_exit:?}");
+						return Err(self.fault(reason));
 					}
 				},
 				Err(err) => match err.errno() {
@@ -500,7 +889,9 @@ impl VirtualCPU for KvmCpu {
 	}
 
 	fn run(&mut self) -> HypervisorResult<Option<i32>> {
-		match self.r#continue()? {
+		let stop_reason = self.r#continue();
+		self.record_perf_counters();
+		match stop_reason? {
 			VcpuStopReason::Debug(_) => {
 				unreachable!("reached debug exit without running in debugging mode")
 			}
@@ -1 +1,2 @@
+pub mod coredump;
 pub mod kvm_cpu;
@@ -136,13 +136,15 @@ impl XhyveCpu {
 }
 
 impl VirtualCPU for XhyveCpu {
+	const NAME: &'static str = "xhyve";
+
 	fn new(id: u32, parent_vm: Arc<UhyveVm<Self>>) -> HypervisorResult<Self> {
 		let mut vcpu = XhyveCpu {
 			id,
 			parent_vm: parent_vm.clone(),
 			vcpu: xhypervisor::VirtualCpu::new().unwrap(),
 		};
-		vcpu.init(parent_vm.get_entry_point(), parent_vm.stack_address(), id)?;
+		vcpu.init(parent_vm.get_entry_point(), parent_vm.stack_pointer(), id)?;
 
 		Ok(vcpu)
 	}
@@ -165,17 +167,24 @@ impl VirtualCPU for XhyveCpu {
 						if let Some(hypercall) = unsafe {
 							hypercall::address_to_hypercall(&self.parent_vm.mem, addr, data_addr)
 						} {
+							if let Some(limiter) = &self.parent_vm.hypercall_limiter {
+								limiter.acquire();
+							}
+							self.parent_vm.stats.count_hypercall(addr);
 							match hypercall {
 								Hypercall::SerialWriteByte(_char) => {
 									let x8 = (self.vcpu.read_register(Register::X8)? & 0xFF) as u8;
 
-									hypercall::uart(&[x8]).unwrap();
+									hypercall::uart(&self.parent_vm.serial, &[x8]).unwrap();
 								}
 								Hypercall::Exit(sysexit) => {
 									return Ok(VcpuStopReason::Exit(sysexit.arg));
 								}
-								Hypercall::Cmdsize(syssize) => syssize
-									.update(self.parent_vm.kernel_path(), self.parent_vm.args()),
+								Hypercall::Cmdsize(syssize) => syssize.update(
+									self.parent_vm.kernel_path(),
+									self.parent_vm.args(),
+									self.parent_vm.guest_env(),
+								),
 								Hypercall::Cmdval(syscmdval) => {
 									copy_argv(
 										self.parent_vm.kernel_path().as_os_str(),
@@ -183,24 +192,212 @@ impl VirtualCPU for XhyveCpu {
 										syscmdval,
 										&self.parent_vm.mem,
 									);
-									copy_env(syscmdval, &self.parent_vm.mem);
+									copy_env(
+										syscmdval,
+										&self.parent_vm.mem,
+										self.parent_vm.guest_env(),
+									);
+								}
+								Hypercall::FileClose(sysclose) => {
+									hypercall::close(sysclose, &self.parent_vm.fdmap)
+								}
+								Hypercall::FileLseek(syslseek) => {
+									hypercall::lseek(syslseek, &self.parent_vm.fdmap)
+								}
+								Hypercall::FileLseek2(syslseek) => {
+									hypercall::lseek2(syslseek, &self.parent_vm.fdmap)
 								}
-								Hypercall::FileClose(sysclose) => hypercall::close(sysclose),
-								Hypercall::FileLseek(syslseek) => hypercall::lseek(syslseek),
 								Hypercall::FileOpen(sysopen) => {
-									hypercall::open(&self.parent_vm.mem, sysopen)
+									hypercall::open(
+										&self.parent_vm.mem,
+										sysopen,
+										self.parent_vm.host_root(),
+										&self.parent_vm.cwd.read().unwrap(),
+										&self.parent_vm.fdmap,
+										Some(&self.parent_vm.proc_info()),
+										self.parent_vm.audit_log.as_ref(),
+									);
+								}
+								Hypercall::FileReaddir(sysreaddir) => hypercall::readdir(
+									&self.parent_vm.mem,
+									sysreaddir,
+									&self.parent_vm.cwd.read().unwrap(),
+									&self.parent_vm.proc_info(),
+								),
+								Hypercall::FileRead(sysread) => hypercall::read(
+									&self.parent_vm.mem,
+									sysread,
+									&self.parent_vm.fdmap,
+								),
+								Hypercall::FileWrite(syswrite) => hypercall::write(
+									&self.parent_vm.mem,
+									syswrite,
+									&self.parent_vm.fdmap,
+									&self.parent_vm.serial,
+								),
+								Hypercall::FileWrite2(syswrite) => hypercall::write2(
+									&self.parent_vm.mem,
+									syswrite,
+									&self.parent_vm.fdmap,
+									&self.parent_vm.serial,
+								),
+								Hypercall::FileUnlink(sysunlink) => hypercall::unlink(
+									&self.parent_vm.mem,
+									sysunlink,
+									self.parent_vm.host_root(),
+									&self.parent_vm.cwd.read().unwrap(),
+									self.parent_vm.audit_log.as_ref(),
+								),
+								Hypercall::SetAppName(sysappname) => {
+									if let Some(name) =
+										hypercall::set_app_name(&self.parent_vm.mem, sysappname)
+									{
+										*self.parent_vm.app_name.lock().unwrap() = Some(name);
+									}
+								}
+								Hypercall::LogMessage(syslog) => hypercall::log_message(
+									&self.parent_vm.mem,
+									syslog,
+									*self.parent_vm.guest_log_level.lock().unwrap(),
+								),
+								Hypercall::Panic(syspanic) => {
+									let message = hypercall::panic(&self.parent_vm.mem, syspanic);
+									*self.parent_vm.panic_message.lock().unwrap() = Some(message);
+									return Ok(VcpuStopReason::Exit(GUEST_PANIC_EXIT_CODE));
 								}
-								Hypercall::FileRead(sysread) => {
-									hypercall::read(&self.parent_vm.mem, sysread)
+								Hypercall::Notify(sysnotify) => hypercall::notify(
+									&self.parent_vm.mem,
+									sysnotify,
+									self.parent_vm.event_hook.as_ref(),
+								),
+								Hypercall::ReportMetric(sysmetric) => {
+									if let Some((name, value)) =
+										hypercall::report_metric(&self.parent_vm.mem, sysmetric)
+									{
+										let mut metrics = self.parent_vm.metrics.lock().unwrap();
+										metrics.entry(name).or_default().push(value);
+									}
 								}
-								Hypercall::FileWrite(syswrite) => {
-									hypercall::write(&self.parent_vm.mem, syswrite).unwrap()
+								Hypercall::TestResult(systestresult) => {
+									let report =
+										hypercall::test_result(&self.parent_vm.mem, systestresult);
+									self.parent_vm.test_reports.lock().unwrap().push(report);
 								}
-								Hypercall::FileUnlink(sysunlink) => {
-									hypercall::unlink(&self.parent_vm.mem, sysunlink)
+								Hypercall::FileOpen2(sysopen2) => hypercall::open2(
+									&self.parent_vm.mem,
+									sysopen2,
+									self.parent_vm.host_root(),
+									&self.parent_vm.cwd.read().unwrap(),
+									&self.parent_vm.fdmap,
+									Some(&self.parent_vm.proc_info()),
+									self.parent_vm.audit_log.as_ref(),
+								),
+								Hypercall::FileOpenat(sysopenat) => hypercall::openat(
+									&self.parent_vm.mem,
+									sysopenat,
+									&self.parent_vm.fdmap,
+									self.parent_vm.audit_log.as_ref(),
+								),
+								Hypercall::FileReadExact(sysread) => hypercall::read_exact(
+									&self.parent_vm.mem,
+									sysread,
+									&self.parent_vm.fdmap,
+								),
+								Hypercall::FileStatvfs(sysstatvfs) => hypercall::statvfs(
+									&self.parent_vm.mem,
+									sysstatvfs,
+									self.parent_vm.host_root(),
+									&self.parent_vm.cwd.read().unwrap(),
+								),
+								Hypercall::SyncAll(syssyncall) => {
+									hypercall::sync_all(syssyncall, &self.parent_vm.fdmap)
+								}
+								Hypercall::Chdir(syschdir) => hypercall::chdir(
+									&self.parent_vm.mem,
+									syschdir,
+									self.parent_vm.host_root(),
+									&self.parent_vm.cwd,
+								),
+								Hypercall::GetCwd(sysgetcwd) => hypercall::getcwd(
+									&self.parent_vm.mem,
+									sysgetcwd,
+									&self.parent_vm.cwd.read().unwrap(),
+								),
+								Hypercall::MemBalloon(sysballoon) => hypercall::mem_balloon(
+									&self.parent_vm.mem,
+									sysballoon,
+									&self.parent_vm.committed_memory,
+									self.parent_vm.boot_memory_size,
+								),
+								Hypercall::SchedYield => hypercall::sched_yield(),
+								Hypercall::NanoSleep(sysnanosleep) => {
+									hypercall::nanosleep(sysnanosleep);
+								}
+								Hypercall::Barrier(sysbarrier) => {
+									if !self.parent_vm.barrier.wait() {
+										sysbarrier.ret = -libc::ECANCELED;
+										return Ok(VcpuStopReason::Kick);
+									}
+									sysbarrier.ret = 0;
+								}
+								Hypercall::Heartbeat(sysheartbeat) => {
+									self.parent_vm.heartbeat(sysheartbeat.progress)
+								}
+								Hypercall::Brk(sysbrk) => {
+									self.parent_vm.stats.record_brk(sysbrk.brk)
+								}
+								Hypercall::OutOfMemory(sysoom) => {
+									warn!("Guest failed to allocate {} bytes", { sysoom.size });
+									self.parent_vm
+										.stats
+										.record_out_of_memory(sysoom.size as u64)
+								}
+								Hypercall::ClockCompare(sysclock) => {
+									let (host_ns, drift_ns) =
+										self.parent_vm.stats.record_clock(sysclock.guest_ns);
+									sysclock.host_ns = host_ns;
+									sysclock.drift_ns = drift_ns;
+								}
+								Hypercall::SetLogLevel(syssetloglevel) => hypercall::set_log_level(
+									syssetloglevel,
+									self.parent_vm.max_guest_log_level,
+									&self.parent_vm.guest_log_level,
+								),
+								Hypercall::SetSerialMode(sysserialmode) => {
+									hypercall::set_serial_mode(
+										&self.parent_vm.serial,
+										sysserialmode,
+									)
+								}
+								Hypercall::GetEnv(sysgetenv) => hypercall::getenv(
+									&self.parent_vm.mem,
+									sysgetenv,
+									&self.parent_vm.env_allowlist,
+								),
+								Hypercall::GetHostname(sysgethostname) => hypercall::gethostname(
+									&self.parent_vm.mem,
+									sysgethostname,
+									&self.parent_vm.guest_hostname,
+								),
+								Hypercall::HostInfo(syshostinfo) => hypercall::host_info(
+									&self.parent_vm.mem,
+									syshostinfo,
+									Self::NAME,
+								),
+								Hypercall::Sendfile(syssendfile) => {
+									hypercall::sendfile(syssendfile, &self.parent_vm.fdmap)
+								}
+								Hypercall::FileFadvise(sysfadvise) => {
+									hypercall::fadvise(sysfadvise, &self.parent_vm.fdmap)
+								}
+								Hypercall::Madvise(sysmadvise) => {
+									hypercall::madvise(&self.parent_vm.mem, sysmadvise)
 								}
 								_ => {
-									panic! {"Hypercall {hypercall:?} not implemented on macos-aarch64"}
+									error!(
+										"Hypercall {hypercall:?} not implemented on macos-aarch64"
+									);
+									return Err(xhypervisor::Error::Unsupp);
 								}
 							}
 							// increase the pc to the instruction after the exception to continue execution
@@ -7,6 +7,7 @@ pub mod xhyve;
 use std::{
 	sync::{mpsc, Arc},
 	thread,
+	time::Instant,
 };
 
 use core_affinity::CoreId;
@@ -15,17 +16,43 @@ use core_affinity::CoreId;
 pub use crate::macos::aarch64::vcpu::XhyveCpu;
 #[cfg(target_arch = "x86_64")]
 pub use crate::macos::x86_64::vcpu::XhyveCpu;
-use crate::{vcpu::VirtualCPU, vm::UhyveVm};
+use crate::{
+	rlimit::RlimitGuard,
+	vcpu::VirtualCPU,
+	vm::{UhyveVm, VmResult},
+	HypervisorResult,
+};
 
 pub type HypervisorError = xhypervisor::Error;
 pub type DebugExitInfo = ();
 
+/// Returns the [`HypervisorError`] that is closest to the error number `errno`.
+pub(crate) fn errno_error(errno: i32) -> HypervisorError {
+	match errno {
+		libc::EINVAL => HypervisorError::BadArg,
+		libc::ENOMEM => HypervisorError::NoRes,
+		libc::EBUSY => HypervisorError::Busy,
+		libc::ENOTSUP => HypervisorError::Unsupp,
+		_ => HypervisorError::Error,
+	}
+}
+
 impl UhyveVm<XhyveCpu> {
 	/// Runs the VM.
 	///
-	/// Blocks until the VM has finished execution.
-	pub fn run(mut self, cpu_affinity: Option<Vec<CoreId>>) -> i32 {
-		self.load_kernel().expect("Unabled to load the kernel");
+	/// Blocks until the VM has finished execution. Returns an error if the hypervisor failed,
+	/// e.g., if a vCPU couldn't be created. The guest's exit code, including the one of a guest
+	/// that timed out, is reported in the [`VmResult`].
+	pub fn run(mut self, cpu_affinity: Option<Vec<CoreId>>) -> HypervisorResult<VmResult> {
+		let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+		self.load_kernel().map_err(|err| {
+			error!("Unable to load the kernel: {err}");
+			errno_error(err.errno())
+		})?;
+		let _rlimits = RlimitGuard::apply(&self.rlimits).map_err(|err| {
+			error!("Unable to apply the resource limits: {err}");
+			errno_error(err as i32)
+		})?;
 
 		// For communication of the exit code from one vcpu to this thread as return
 		// value.
@@ -53,14 +80,24 @@ impl UhyveVm<XhyveCpu> {
 					None => debug!("No affinity specified, not binding thread"),
 				}
 
-				let mut cpu = XhyveCpu::new(cpu_id, parent_vm.clone()).unwrap();
+				let mut cpu = match XhyveCpu::new(cpu_id, parent_vm.clone()) {
+					Ok(cpu) => cpu,
+					Err(err) => {
+						error!("Unable to create CPU {} with {:?}", cpu_id, err);
+						exit_tx.send(Err(err)).unwrap();
+						return;
+					}
+				};
 
 				// jump into the VM and execute code of the guest
 				let result = cpu.run();
 				match result {
-					Ok(Some(exit_code)) => exit_tx.send(exit_code).unwrap(),
+					Ok(Some(exit_code)) => exit_tx.send(Ok(exit_code)).unwrap(),
 					Ok(None) => {}
-					Err(err) => error!("CPU {} crashed with {:?}", cpu_id, err),
+					Err(err) => {
+						error!("CPU {} crashed with {:?}", cpu_id, err);
+						exit_tx.send(Err(err)).unwrap();
+					}
 				}
 			});
 		});
@@ -70,6 +107,25 @@ impl UhyveVm<XhyveCpu> {
 		// ignore the remaining running threads. A better design would be to force
 		// the VCPUs externally to stop, so that the other threads don't block and
 		// can be terminated correctly.
-		exit_rx.recv().unwrap()
+		let code = match deadline {
+			Some(mut deadline) => loop {
+				match exit_rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+					Ok(code) => break code?,
+					// Heartbeats of the guest push the deadline back.
+					Err(_) => match this.extended_deadline(deadline) {
+						Some(extended) => deadline = extended,
+						None => {
+							warn!("Stopping the VM after exceeding its timeout");
+							return Ok(VmResult {
+								timed_out: true,
+								..this.result(libc::ETIMEDOUT)
+							});
+						}
+					},
+				}
+			},
+			None => exit_rx.recv().unwrap()?,
+		};
+		Ok(this.result(code))
 	}
 }
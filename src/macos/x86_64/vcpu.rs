@@ -540,7 +540,7 @@ impl XhyveCpu {
 							.unwrap()
 							.as_mut()
 							.expect("IOAPIC not initialized")
-							.write(address - IOAPIC_BASE, val);
+							.write(address - self.parent_vm.ioapic_base().as_u64(), val);
 					}
 
 					if read {
@@ -549,7 +549,7 @@ impl XhyveCpu {
 							.unwrap()
 							.as_mut()
 							.expect("IOAPIC not initialized")
-							.read(address - IOAPIC_BASE);
+							.read(address - self.parent_vm.ioapic_base().as_u64());
 
 						match instr.operands[0].operand {
 							OperandType::REG_EDI => {
@@ -632,14 +632,17 @@ impl XhyveCpu {
 }
 
 impl VirtualCPU for XhyveCpu {
+	const NAME: &'static str = "xhyve";
+
 	fn new(id: u32, parent_vm: Arc<UhyveVm<Self>>) -> HypervisorResult<Self> {
 		let mut vcpu = XhyveCpu {
 			id,
 			parent_vm: parent_vm.clone(),
 			vcpu: xhypervisor::VirtualCpu::new().unwrap(),
-			apic_base: APIC_DEFAULT_BASE,
+			apic_base: parent_vm.apic_base().as_u64(),
 		};
-		vcpu.init(parent_vm.get_entry_point(), parent_vm.stack_address(), id)?;
+		vcpu.init(parent_vm.get_entry_point(), parent_vm.stack_pointer(), id)?;
+		vcpu.vcpu.set_apic_addr(vcpu.apic_base)?;
 
 		Ok(vcpu)
 	}
@@ -702,7 +705,8 @@ impl VirtualCPU for XhyveCpu {
 					let gpa = self.vcpu.read_vmcs(VMCS_GUEST_PHYSICAL_ADDRESS)?;
 					trace!("Exit reason {} - EPT violation at 0x{:x}", reason, gpa);
 
-					if (IOAPIC_BASE..IOAPIC_BASE + IOAPIC_SIZE).contains(&gpa) {
+					let ioapic_base = self.parent_vm.ioapic_base().as_u64();
+					if (ioapic_base..ioapic_base + IOAPIC_SIZE).contains(&gpa) {
 						self.emulate_ioapic(rip, gpa)?;
 					}
 				}
@@ -722,10 +726,16 @@ impl VirtualCPU for XhyveCpu {
 					if let Some(hypercall) = unsafe {
 						hypercall::address_to_hypercall(&self.parent_vm.mem, port, data_addr)
 					} {
+						if let Some(limiter) = &self.parent_vm.hypercall_limiter {
+							limiter.acquire();
+						}
+						self.parent_vm.stats.count_hypercall(port);
 						match hypercall {
-							Hypercall::Cmdsize(syssize) => {
-								syssize.update(self.parent_vm.kernel_path(), self.parent_vm.args())
-							}
+							Hypercall::Cmdsize(syssize) => syssize.update(
+								self.parent_vm.kernel_path(),
+								self.parent_vm.args(),
+								self.parent_vm.guest_env(),
+							),
 							Hypercall::Cmdval(syscmdval) => {
 								copy_argv(
 									self.parent_vm.kernel_path().as_os_str(),
@@ -733,27 +743,208 @@ impl VirtualCPU for XhyveCpu {
 									syscmdval,
 									&self.parent_vm.mem,
 								);
-								copy_env(syscmdval, &self.parent_vm.mem);
+								copy_env(
+									syscmdval,
+									&self.parent_vm.mem,
+									self.parent_vm.guest_env(),
+								);
 							}
 							Hypercall::Exit(sysexit) => {
 								return Ok(VcpuStopReason::Exit(sysexit.arg));
 							}
-							Hypercall::FileClose(sysclose) => hypercall::close(sysclose),
-							Hypercall::FileLseek(syslseek) => hypercall::lseek(syslseek),
+							Hypercall::FileClose(sysclose) => {
+								hypercall::close(sysclose, &self.parent_vm.fdmap)
+							}
+							Hypercall::FileLseek(syslseek) => {
+								hypercall::lseek(syslseek, &self.parent_vm.fdmap)
+							}
+							Hypercall::FileLseek2(syslseek) => {
+								hypercall::lseek2(syslseek, &self.parent_vm.fdmap)
+							}
 							Hypercall::FileOpen(sysopen) => {
-								hypercall::open(&self.parent_vm.mem, sysopen)
+								hypercall::open(
+									&self.parent_vm.mem,
+									sysopen,
+									self.parent_vm.host_root(),
+									&self.parent_vm.cwd.read().unwrap(),
+									&self.parent_vm.fdmap,
+									Some(&self.parent_vm.proc_info()),
+									self.parent_vm.audit_log.as_ref(),
+								);
 							}
+							Hypercall::FileReaddir(sysreaddir) => hypercall::readdir(
+								&self.parent_vm.mem,
+								sysreaddir,
+								&self.parent_vm.cwd.read().unwrap(),
+								&self.parent_vm.proc_info(),
+							),
 							Hypercall::FileRead(sysread) => {
-								hypercall::read(&self.parent_vm.mem, sysread)
+								hypercall::read(&self.parent_vm.mem, sysread, &self.parent_vm.fdmap)
+							}
+							Hypercall::FileWrite(syswrite) => hypercall::write(
+								&self.parent_vm.mem,
+								syswrite,
+								&self.parent_vm.fdmap,
+								&self.parent_vm.serial,
+							),
+							Hypercall::FileWrite2(syswrite) => hypercall::write2(
+								&self.parent_vm.mem,
+								syswrite,
+								&self.parent_vm.fdmap,
+								&self.parent_vm.serial,
+							),
+							Hypercall::FileUnlink(sysunlink) => hypercall::unlink(
+								&self.parent_vm.mem,
+								sysunlink,
+								self.parent_vm.host_root(),
+								&self.parent_vm.cwd.read().unwrap(),
+								self.parent_vm.audit_log.as_ref(),
+							),
+							Hypercall::SerialWriteByte(buf) => {
+								hypercall::uart(&self.parent_vm.serial, &[buf]).unwrap()
+							}
+							Hypercall::SetAppName(sysappname) => {
+								if let Some(name) =
+									hypercall::set_app_name(&self.parent_vm.mem, sysappname)
+								{
+									*self.parent_vm.app_name.lock().unwrap() = Some(name);
+								}
+							}
+							Hypercall::LogMessage(syslog) => hypercall::log_message(
+								&self.parent_vm.mem,
+								syslog,
+								*self.parent_vm.guest_log_level.lock().unwrap(),
+							),
+							Hypercall::Panic(syspanic) => {
+								let message = hypercall::panic(&self.parent_vm.mem, syspanic);
+								*self.parent_vm.panic_message.lock().unwrap() = Some(message);
+								return Ok(VcpuStopReason::Exit(GUEST_PANIC_EXIT_CODE));
 							}
-							Hypercall::FileWrite(syswrite) => {
-								hypercall::write(&self.parent_vm.mem, syswrite).unwrap()
+							Hypercall::Notify(sysnotify) => hypercall::notify(
+								&self.parent_vm.mem,
+								sysnotify,
+								self.parent_vm.event_hook.as_ref(),
+							),
+							Hypercall::ReportMetric(sysmetric) => {
+								if let Some((name, value)) =
+									hypercall::report_metric(&self.parent_vm.mem, sysmetric)
+								{
+									let mut metrics = self.parent_vm.metrics.lock().unwrap();
+									metrics.entry(name).or_default().push(value);
+								}
 							}
-							Hypercall::FileUnlink(sysunlink) => {
-								hypercall::unlink(&self.parent_vm.mem, sysunlink)
+							Hypercall::TestResult(systestresult) => {
+								let report =
+									hypercall::test_result(&self.parent_vm.mem, systestresult);
+								self.parent_vm.test_reports.lock().unwrap().push(report);
+							}
+							Hypercall::FileOpen2(sysopen2) => hypercall::open2(
+								&self.parent_vm.mem,
+								sysopen2,
+								self.parent_vm.host_root(),
+								&self.parent_vm.cwd.read().unwrap(),
+								&self.parent_vm.fdmap,
+								Some(&self.parent_vm.proc_info()),
+								self.parent_vm.audit_log.as_ref(),
+							),
+							Hypercall::FileOpenat(sysopenat) => hypercall::openat(
+								&self.parent_vm.mem,
+								sysopenat,
+								&self.parent_vm.fdmap,
+								self.parent_vm.audit_log.as_ref(),
+							),
+							Hypercall::FileReadExact(sysread) => hypercall::read_exact(
+								&self.parent_vm.mem,
+								sysread,
+								&self.parent_vm.fdmap,
+							),
+							Hypercall::FileStatvfs(sysstatvfs) => hypercall::statvfs(
+								&self.parent_vm.mem,
+								sysstatvfs,
+								self.parent_vm.host_root(),
+								&self.parent_vm.cwd.read().unwrap(),
+							),
+							Hypercall::SyncAll(syssyncall) => {
+								hypercall::sync_all(syssyncall, &self.parent_vm.fdmap)
+							}
+							Hypercall::Chdir(syschdir) => hypercall::chdir(
+								&self.parent_vm.mem,
+								syschdir,
+								self.parent_vm.host_root(),
+								&self.parent_vm.cwd,
+							),
+							Hypercall::GetCwd(sysgetcwd) => hypercall::getcwd(
+								&self.parent_vm.mem,
+								sysgetcwd,
+								&self.parent_vm.cwd.read().unwrap(),
+							),
+							Hypercall::MemBalloon(sysballoon) => hypercall::mem_balloon(
+								&self.parent_vm.mem,
+								sysballoon,
+								&self.parent_vm.committed_memory,
+								self.parent_vm.boot_memory_size,
+							),
+							Hypercall::SchedYield => hypercall::sched_yield(),
+							Hypercall::NanoSleep(sysnanosleep) => {
+								hypercall::nanosleep(sysnanosleep);
+							}
+							Hypercall::Barrier(sysbarrier) => {
+								if !self.parent_vm.barrier.wait() {
+									sysbarrier.ret = -libc::ECANCELED;
+									return Ok(VcpuStopReason::Kick);
+								}
+								sysbarrier.ret = 0;
+							}
+							Hypercall::Heartbeat(sysheartbeat) => {
+								self.parent_vm.heartbeat(sysheartbeat.progress)
+							}
+							Hypercall::Brk(sysbrk) => self.parent_vm.stats.record_brk(sysbrk.brk),
+							Hypercall::OutOfMemory(sysoom) => {
+								warn!("Guest failed to allocate {} bytes", { sysoom.size });
+								self.parent_vm
+									.stats
+									.record_out_of_memory(sysoom.size as u64)
+							}
+							Hypercall::ClockCompare(sysclock) => {
+								let (host_ns, drift_ns) =
+									self.parent_vm.stats.record_clock(sysclock.guest_ns);
+								sysclock.host_ns = host_ns;
+								sysclock.drift_ns = drift_ns;
+							}
+							Hypercall::SetLogLevel(syssetloglevel) => hypercall::set_log_level(
+								syssetloglevel,
+								self.parent_vm.max_guest_log_level,
+								&self.parent_vm.guest_log_level,
+							),
+							Hypercall::SetSerialMode(sysserialmode) => {
+								hypercall::set_serial_mode(&self.parent_vm.serial, sysserialmode)
+							}
+							Hypercall::GetEnv(sysgetenv) => hypercall::getenv(
+								&self.parent_vm.mem,
+								sysgetenv,
+								&self.parent_vm.env_allowlist,
+							),
+							Hypercall::GetHostname(sysgethostname) => hypercall::gethostname(
+								&self.parent_vm.mem,
+								sysgethostname,
+								&self.parent_vm.guest_hostname,
+							),
+							Hypercall::HostInfo(syshostinfo) => {
+								hypercall::host_info(&self.parent_vm.mem, syshostinfo, Self::NAME)
+							}
+							Hypercall::Sendfile(syssendfile) => {
+								hypercall::sendfile(syssendfile, &self.parent_vm.fdmap)
+							}
+							Hypercall::FileFadvise(sysfadvise) => {
+								hypercall::fadvise(sysfadvise, &self.parent_vm.fdmap)
+							}
+							Hypercall::Madvise(sysmadvise) => {
+								hypercall::madvise(&self.parent_vm.mem, sysmadvise)
+							}
+							_ => {
+								error!("Hypercall {hypercall:?} not implemented on macos-x86_64");
+								return Err(xhypervisor::Error::Unsupp);
 							}
-							Hypercall::SerialWriteByte(buf) => hypercall::uart(&[buf]).unwrap(),
-							_ => panic!("Got unknown hypercall {:?}", hypercall),
 						}
 						self.vcpu.write_register(&Register::RIP, rip + len)?;
 					} else {
@@ -1,15 +1,77 @@
-use std::{mem::MaybeUninit, ops::Index, os::raw::c_void, ptr::NonNull};
+use std::{
+	mem::{size_of, MaybeUninit},
+	ops::{Index, Range},
+	os::raw::c_void,
+	ptr::NonNull,
+};
 
 use nix::sys::mman::*;
 use thiserror::Error;
 use uhyve_interface::GuestPhysAddr;
 
+use crate::consts::GUEST_PAGE_SIZE;
+#[cfg(target_os = "linux")]
+use crate::{params::HugePageSize, stats::MemoryAdviceStatus};
+
 #[derive(Error, Debug)]
 pub enum MemoryError {
 	#[error("Memory bounds exceeded")]
 	BoundsViolation,
 	#[error("The desired guest location is not part of this memory")]
 	WrongMemoryError,
+	#[error("Unable to advise the host about the memory: {0}")]
+	Advice(nix::errno::Errno),
+}
+
+#[cfg(target_os = "linux")]
+#[derive(Error, Debug)]
+pub enum HugePageError {
+	#[error("Memory size {memory_size:#x} is not a multiple of the huge page size ({page_size})")]
+	UnalignedSize {
+		memory_size: usize,
+		page_size: HugePageSize,
+	},
+	#[error("Failed to allocate {0} huge pages: {1}")]
+	Allocation(HugePageSize, nix::Error),
+}
+
+#[cfg(target_os = "linux")]
+#[derive(Error, Debug)]
+pub enum LockError {
+	#[error(
+		"Unable to lock {memory_size:#x} bytes of memory, the limit of locked memory is {limit:#x} bytes (raise it via RLIMIT_MEMLOCK, e.g., `ulimit -l`)"
+	)]
+	Limit { memory_size: usize, limit: u64 },
+	#[error("Failed to lock memory: {0}")]
+	Other(nix::Error),
+}
+
+#[cfg(target_os = "linux")]
+impl LockError {
+	fn new(err: nix::Error, memory_size: usize) -> Self {
+		let mut limit = libc::rlimit {
+			rlim_cur: 0,
+			rlim_max: 0,
+		};
+		if err == nix::Error::ENOMEM
+			&& unsafe { libc::getrlimit(libc::RLIMIT_MEMLOCK, &mut limit) } == 0
+		{
+			Self::Limit {
+				memory_size,
+				limit: limit.rlim_cur,
+			}
+		} else {
+			Self::Other(err)
+		}
+	}
+
+	/// Returns the error number of the failed lock.
+	pub fn errno(&self) -> i32 {
+		match self {
+			Self::Limit { .. } => libc::ENOMEM,
+			Self::Other(err) => *err as i32,
+		}
+	}
 }
 
 /// A general purpose VM memory section that can exploit some Linux Kernel features.
@@ -20,9 +82,15 @@ pub struct MmapMemory {
 	pub memory_size: usize,
 	pub guest_address: GuestPhysAddr,
 	pub host_address: *mut u8,
+	/// Whether the memory is locked into RAM.
+	locked: bool,
 }
 
 impl MmapMemory {
+	/// Creates a memory section of at least `memory_size` bytes.
+	///
+	/// The size is rounded up to a multiple of [`GUEST_PAGE_SIZE`], so that the memory can be
+	/// mapped with large pages. The actual size is available as `memory_size`.
 	pub fn new(
 		flags: u32,
 		memory_size: usize,
@@ -30,6 +98,12 @@ impl MmapMemory {
 		huge_pages: bool,
 		mergeable: bool,
 	) -> MmapMemory {
+		let requested_size = memory_size;
+		let memory_size = requested_size.next_multiple_of(GUEST_PAGE_SIZE as usize);
+		if memory_size != requested_size {
+			info!("Rounding the guest memory size {requested_size:#x} up to {memory_size:#x}");
+		}
+
 		let host_address = unsafe {
 			mmap_anonymous(
 				None,
@@ -73,7 +147,103 @@ impl MmapMemory {
 			memory_size,
 			guest_address,
 			host_address: host_address.as_ptr() as *mut u8,
+			locked: false,
+		}
+	}
+
+	/// Creates a memory section backed by explicitly allocated huge pages of `page_size`.
+	///
+	/// The huge pages have to be reserved on the host beforehand.
+	#[cfg(target_os = "linux")]
+	pub fn new_hugetlb(
+		flags: u32,
+		memory_size: usize,
+		guest_address: GuestPhysAddr,
+		page_size: HugePageSize,
+	) -> Result<MmapMemory, HugePageError> {
+		if !memory_size.is_multiple_of(page_size.get()) {
+			return Err(HugePageError::UnalignedSize {
+				memory_size,
+				page_size,
+			});
+		}
+
+		let page_size_flag = match page_size {
+			HugePageSize::Size2MiB => MapFlags::MAP_HUGE_2MB,
+			HugePageSize::Size1GiB => MapFlags::MAP_HUGE_1GB,
+		};
+		let host_address = unsafe {
+			mmap_anonymous(
+				None,
+				memory_size.try_into().unwrap(),
+				ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+				MapFlags::MAP_PRIVATE
+					| MapFlags::MAP_NORESERVE
+					| MapFlags::MAP_HUGETLB
+					| page_size_flag,
+			)
+			.map_err(|err| HugePageError::Allocation(page_size, err))?
+		};
+		debug!("Uhyve uses {page_size} huge pages");
+
+		Ok(MmapMemory {
+			flags,
+			memory_size,
+			guest_address,
+			host_address: host_address.as_ptr() as *mut u8,
+			locked: false,
+		})
+	}
+
+	/// Locks the memory into RAM, so that it is never swapped out.
+	///
+	/// This fails if the memory exceeds the limit of locked memory of the process.
+	#[cfg(target_os = "linux")]
+	pub fn lock(&mut self) -> Result<(), LockError> {
+		let host_addr = NonNull::new(self.host_address as *mut c_void).unwrap();
+		unsafe { mlock(host_addr, self.memory_size) }
+			.map_err(|err| LockError::new(err, self.memory_size))?;
+		self.locked = true;
+		Ok(())
+	}
+
+	/// Faults in the `len` bytes at `addr`, e.g., when the guest commits them via ballooning.
+	///
+	/// Populating is an optimization, so hosts that don't support it leave the pages to be
+	/// faulted in on the first access.
+	pub fn populate(&self, addr: GuestPhysAddr, len: usize) -> Result<(), MemoryError> {
+		self.check_bounds(addr, len)?;
+		#[cfg(target_os = "linux")]
+		{
+			let host_addr = self.host_address(addr)? as *mut c_void;
+			if unsafe { libc::madvise(host_addr, len, libc::MADV_POPULATE_WRITE) } != 0 {
+				debug!(
+					"Unable to populate guest memory: {}",
+					std::io::Error::last_os_error()
+				);
+			}
 		}
+		Ok(())
+	}
+
+	/// Returns the `len` bytes at `addr` to the host, which zeroes them.
+	pub fn discard(&self, addr: GuestPhysAddr, len: usize) -> Result<(), MemoryError> {
+		self.check_bounds(addr, len)?;
+		let host_addr = NonNull::new(self.host_address(addr)? as *mut c_void).unwrap();
+		unsafe { madvise(host_addr, len, MmapAdvise::MADV_DONTNEED) }.map_err(MemoryError::Advice)
+	}
+
+	/// Reads the effectiveness of the memory advice for this memory from `/proc/self/smaps`.
+	#[cfg(target_os = "linux")]
+	pub fn advice_status(&self) -> std::io::Result<MemoryAdviceStatus> {
+		let smaps = std::fs::read_to_string("/proc/self/smaps")?;
+		let start = self.host_address as usize;
+		Ok(parse_smaps(&smaps, start..start + self.memory_size))
+	}
+
+	/// Returns the guest physical addresses covered by this memory.
+	pub fn guest_range(&self) -> Range<u64> {
+		self.guest_address.as_u64()..self.guest_address.as_u64() + self.memory_size as u64
 	}
 
 	/// This can create multiple aliasing. During the lifetime of the returned slice, the memory must not be altered, dropped or simmilar.
@@ -88,6 +258,20 @@ impl MmapMemory {
 		std::slice::from_raw_parts_mut(self.host_address as *mut MaybeUninit<u8>, self.memory_size)
 	}
 
+	/// Checks that the `len` bytes at `addr` lie entirely within the memory.
+	///
+	/// The length is supplied by the guest in many cases, so it must not overflow the address.
+	fn check_bounds(&self, addr: GuestPhysAddr, len: usize) -> Result<(), MemoryError> {
+		let offset = addr
+			.as_u64()
+			.checked_sub(self.guest_address.as_u64())
+			.ok_or(MemoryError::WrongMemoryError)?;
+		match (offset as usize).checked_add(len) {
+			Some(end) if end <= self.memory_size => Ok(()),
+			_ => Err(MemoryError::BoundsViolation),
+		}
+	}
+
 	/// Read a section of the memory.
 	///
 	/// # Safety
@@ -96,11 +280,8 @@ impl MmapMemory {
 	/// the returned slice, the memory must not be altered to prevent undfined
 	/// behaviour.
 	pub unsafe fn slice_at(&self, addr: GuestPhysAddr, len: usize) -> Result<&[u8], MemoryError> {
-		if addr.as_u64() as usize + len >= self.memory_size - self.guest_address.as_u64() as usize {
-			Err(MemoryError::BoundsViolation)
-		} else {
-			Ok(unsafe { std::slice::from_raw_parts(self.host_address(addr)?, len) })
-		}
+		self.check_bounds(addr, len)?;
+		Ok(unsafe { std::slice::from_raw_parts(self.host_address(addr)?, len) })
 	}
 
 	/// Writeable access to a section of the memory.
@@ -110,16 +291,45 @@ impl MmapMemory {
 	/// This is unsafe, as it can create multiple aliasing. During the lifetime of
 	/// the returned slice, the memory must not be altered to prevent undfined
 	/// behavior.
+	#[allow(clippy::mut_from_ref)]
 	pub unsafe fn slice_at_mut(
 		&self,
 		addr: GuestPhysAddr,
 		len: usize,
 	) -> Result<&mut [u8], MemoryError> {
-		if addr.as_u64() as usize + len >= self.memory_size - self.guest_address.as_u64() as usize {
-			Err(MemoryError::BoundsViolation)
-		} else {
-			Ok(unsafe { std::slice::from_raw_parts_mut(self.host_address(addr)? as *mut u8, len) })
-		}
+		self.check_bounds(addr, len)?;
+		Ok(unsafe { std::slice::from_raw_parts_mut(self.host_address(addr)? as *mut u8, len) })
+	}
+
+	/// Copies `data` into the memory at `addr`.
+	///
+	/// Fails without writing anything if `data` doesn't fit into the memory.
+	pub fn write_at(&self, addr: GuestPhysAddr, data: &[u8]) -> Result<(), MemoryError> {
+		self.check_bounds(addr, data.len())?;
+		// Safety: The destination lies within the memory, as checked above. `data` may itself be
+		// part of the memory, so the ranges may overlap.
+		unsafe {
+			std::ptr::copy(
+				data.as_ptr(),
+				self.host_address(addr)? as *mut u8,
+				data.len(),
+			)
+		};
+		Ok(())
+	}
+
+	/// Writes `value` into the memory at `addr`, which doesn't need to be aligned.
+	///
+	/// Fails without writing anything if `value` doesn't fit into the memory.
+	pub fn write_struct_at<T: Copy>(
+		&self,
+		addr: GuestPhysAddr,
+		value: T,
+	) -> Result<(), MemoryError> {
+		self.check_bounds(addr, size_of::<T>())?;
+		// Safety: The destination lies within the memory, as checked above.
+		unsafe { (self.host_address(addr)? as *mut T).write_unaligned(value) };
+		Ok(())
 	}
 
 	/// Returns the host address of the given internal physical address in the
@@ -150,6 +360,7 @@ impl MmapMemory {
 	}
 
 	/// Get a mutable reference to the type at the given address in the memory.
+	#[allow(clippy::mut_from_ref)]
 	pub unsafe fn get_ref_mut<T>(&self, addr: GuestPhysAddr) -> Result<&mut T, MemoryError> {
 		Ok(unsafe { &mut *(self.host_address(addr)? as *mut T) })
 	}
@@ -160,6 +371,9 @@ impl Drop for MmapMemory {
 		if self.memory_size > 0 {
 			let host_addr = NonNull::new(self.host_address as *mut c_void).unwrap();
 			unsafe {
+				if self.locked {
+					munlock(host_addr, self.memory_size).unwrap();
+				}
 				munmap(host_addr, self.memory_size).unwrap();
 			}
 		}
@@ -180,6 +394,51 @@ impl Index<usize> for MmapMemory {
 	}
 }
 
+/// Sums up the memory advice status of the mappings in `smaps` that overlap `range`.
+///
+/// The memory may be split into several mappings, e.g., if parts of it are advised differently.
+#[cfg(target_os = "linux")]
+fn parse_smaps(smaps: &str, range: Range<usize>) -> MemoryAdviceStatus {
+	let kib = |value: Option<&str>| {
+		value
+			.and_then(|value| value.parse::<u64>().ok())
+			.unwrap_or(0)
+			* 1024
+	};
+	let mut status = MemoryAdviceStatus::default();
+	let mut in_range = false;
+	for line in smaps.lines() {
+		let mut fields = line.split_whitespace();
+		let Some(first) = fields.next() else {
+			continue;
+		};
+		let Some(key) = first.strip_suffix(':') else {
+			// A mapping header of the form `<start>-<end> <perms> ...`
+			in_range = first
+				.split_once('-')
+				.and_then(|(start, end)| {
+					Some((
+						usize::from_str_radix(start, 16).ok()?,
+						usize::from_str_radix(end, 16).ok()?,
+					))
+				})
+				.is_some_and(|(start, end)| start < range.end && range.start < end);
+			continue;
+		};
+		if !in_range {
+			continue;
+		}
+		match key {
+			"AnonHugePages" => status.thp_bytes += kib(fields.next()),
+			"THPeligible" => status.thp_eligible |= fields.next() == Some("1"),
+			"KSM" => *status.ksm_bytes.get_or_insert(0) += kib(fields.next()),
+			"VmFlags" => status.ksm_mergeable |= fields.any(|flag| flag == "mg"),
+			_ => {}
+		}
+	}
+	status
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -206,4 +465,233 @@ mod tests {
 			0x12345678bbaa
 		);
 	}
+
+	#[test]
+	fn test_slice_bounds() {
+		let mem = MmapMemory::new(0, 40 * PAGE_SIZE, GuestPhysAddr::new(0x1000), true, true);
+		let end = 0x1000 + GUEST_PAGE_SIZE;
+		unsafe {
+			assert_eq!(
+				mem.slice_at(GuestPhysAddr::new(end - 8), 8).unwrap().len(),
+				8
+			);
+			assert_eq!(mem.slice_at(GuestPhysAddr::new(end), 0).unwrap().len(), 0);
+			assert!(matches!(
+				mem.slice_at(GuestPhysAddr::new(end - 8), 9),
+				Err(MemoryError::BoundsViolation)
+			));
+			assert!(matches!(
+				mem.slice_at_mut(GuestPhysAddr::new(0x1000), usize::MAX),
+				Err(MemoryError::BoundsViolation)
+			));
+			assert!(matches!(
+				mem.slice_at(GuestPhysAddr::new(0x800), 8),
+				Err(MemoryError::WrongMemoryError)
+			));
+		}
+	}
+
+	#[test]
+	fn test_write_at() {
+		let mem = MmapMemory::new(
+			0,
+			GUEST_PAGE_SIZE as usize,
+			GuestPhysAddr::new(0x1000),
+			false,
+			false,
+		);
+		let end = mem.guest_range().end;
+
+		mem.write_at(GuestPhysAddr::new(0x2000), b"uhyve").unwrap();
+		assert_eq!(
+			unsafe { mem.slice_at(GuestPhysAddr::new(0x2000), 5) }.unwrap(),
+			b"uhyve"
+		);
+		mem.write_at(GuestPhysAddr::new(end - 5), b"uhyve").unwrap();
+		assert!(matches!(
+			mem.write_at(GuestPhysAddr::new(end - 4), b"uhyve"),
+			Err(MemoryError::BoundsViolation)
+		));
+		assert!(matches!(
+			mem.write_at(GuestPhysAddr::new(0x800), b"uhyve"),
+			Err(MemoryError::WrongMemoryError)
+		));
+
+		// unaligned write
+		mem.write_struct_at(GuestPhysAddr::new(0x3001), 0x1234_5678u64)
+			.unwrap();
+		assert_eq!(
+			mem.read::<u64>(GuestPhysAddr::new(0x3001)).unwrap(),
+			0x1234_5678
+		);
+		assert!(matches!(
+			mem.write_struct_at(GuestPhysAddr::new(end - 4), 0u64),
+			Err(MemoryError::BoundsViolation)
+		));
+		// Failed writes leave the memory untouched
+		assert_eq!(
+			mem.read::<u32>(GuestPhysAddr::new(end - 4)).unwrap(),
+			u32::from_le_bytes(*b"hyve")
+		);
+	}
+
+	#[test]
+	fn test_memory_rounding() {
+		let mem = MmapMemory::new(0, 0x20_1234, GuestPhysAddr::new(0x1000), false, false);
+		assert_eq!(mem.memory_size, 2 * GUEST_PAGE_SIZE as usize);
+		assert_eq!(mem.guest_range(), 0x1000..0x1000 + 2 * GUEST_PAGE_SIZE);
+		unsafe {
+			assert_eq!(mem.as_slice_mut().len(), mem.memory_size);
+			mem.as_slice_mut()[mem.memory_size - 1] = 0xaa;
+		}
+		assert_eq!(
+			mem.read::<u8>(GuestPhysAddr::new(mem.guest_range().end - 1))
+				.unwrap(),
+			0xaa
+		);
+
+		let mem = MmapMemory::new(
+			0,
+			GUEST_PAGE_SIZE as usize,
+			GuestPhysAddr::new(0),
+			false,
+			false,
+		);
+		assert_eq!(mem.memory_size, GUEST_PAGE_SIZE as usize);
+	}
+
+	#[test]
+	#[cfg(target_os = "linux")]
+	fn test_hugetlb_memory() {
+		let page_size = HugePageSize::Size2MiB;
+		assert!(matches!(
+			MmapMemory::new_hugetlb(0, 3 * PAGE_SIZE, GuestPhysAddr::new(0), page_size),
+			Err(HugePageError::UnalignedSize { .. })
+		));
+
+		// Explicit huge pages are only available if the administrator reserved some.
+		let free_hugepages =
+			std::fs::read_to_string("/sys/kernel/mm/hugepages/hugepages-2048kB/free_hugepages")
+				.ok()
+				.and_then(|free| free.trim().parse::<usize>().ok())
+				.unwrap_or(0);
+		if free_hugepages < 2 {
+			eprintln!("Skipping test: no 2 MiB huge pages available");
+			return;
+		}
+
+		let mem = MmapMemory::new_hugetlb(0, 2 * page_size.get(), GuestPhysAddr::new(0), page_size)
+			.unwrap();
+		unsafe {
+			mem.as_slice_mut()[page_size.get()] = 0xaa;
+			assert_eq!(mem.as_slice_mut()[page_size.get()], 0xaa);
+		}
+	}
+
+	#[test]
+	#[cfg(target_os = "linux")]
+	fn test_lock_memory() {
+		let mut mem = MmapMemory::new(0, 16 * PAGE_SIZE, GuestPhysAddr::new(0), false, false);
+		mem.lock().unwrap();
+		unsafe {
+			mem.as_slice_mut()[PAGE_SIZE] = 0xaa;
+		}
+		assert_eq!(mem[PAGE_SIZE], 0xaa);
+	}
+
+	#[test]
+	#[cfg(target_os = "linux")]
+	fn test_lock_memory_limit() {
+		let mut limit = libc::rlimit {
+			rlim_cur: 0,
+			rlim_max: 0,
+		};
+		unsafe { libc::getrlimit(libc::RLIMIT_MEMLOCK, &mut limit) };
+		let low_limit = libc::rlimit {
+			rlim_cur: PAGE_SIZE as u64,
+			..limit
+		};
+		assert_eq!(
+			unsafe { libc::setrlimit(libc::RLIMIT_MEMLOCK, &low_limit) },
+			0
+		);
+
+		let err = LockError::new(nix::Error::ENOMEM, 16 * PAGE_SIZE);
+		assert!(matches!(
+			err,
+			LockError::Limit {
+				memory_size: 0x10000,
+				limit: 0x1000
+			}
+		));
+		assert!(err.to_string().contains("RLIMIT_MEMLOCK"));
+		assert_eq!(err.errno(), libc::ENOMEM);
+
+		// Processes with CAP_IPC_LOCK may exceed the limit
+		let mut mem = MmapMemory::new(0, 16 * PAGE_SIZE, GuestPhysAddr::new(0), false, false);
+		let result = mem.lock();
+		unsafe { libc::setrlimit(libc::RLIMIT_MEMLOCK, &limit) };
+		if let Err(err) = result {
+			assert!(matches!(err, LockError::Limit { .. }));
+		}
+	}
+
+	#[test]
+	#[cfg(target_os = "linux")]
+	fn test_parse_smaps() {
+		let smaps = "\
+00400000-00401000 r-xp 00000000 08:01 42 /usr/bin/uhyve
+AnonHugePages:      2048 kB
+THPeligible:    1
+VmFlags: rd ex mr mw me mg
+7f0000000000-7f0000400000 rw-p 00000000 00:00 0
+Size:               4096 kB
+AnonHugePages:      2048 kB
+KSM:                   8 kB
+THPeligible:    1
+VmFlags: rd wr mr mw me ac hg mg
+7f0000400000-7f0000600000 rw-p 00000000 00:00 0
+AnonHugePages:         0 kB
+THPeligible:    0
+VmFlags: rd wr mr mw me ac
+";
+		let status = parse_smaps(smaps, 0x7f00_0000_0000..0x7f00_0060_0000);
+		assert_eq!(
+			status,
+			MemoryAdviceStatus {
+				thp_eligible: true,
+				thp_bytes: 2 * 1024 * 1024,
+				ksm_mergeable: true,
+				ksm_bytes: Some(8 * 1024),
+			}
+		);
+		assert!(status.thp_active());
+		assert!(status.ksm_active());
+
+		let status = parse_smaps(smaps, 0x7f00_0040_0000..0x7f00_0060_0000);
+		assert_eq!(status, MemoryAdviceStatus::default());
+	}
+
+	#[test]
+	#[cfg(target_os = "linux")]
+	fn test_thp_status() {
+		let thp_mode = std::fs::read_to_string("/sys/kernel/mm/transparent_hugepage/enabled")
+			.unwrap_or_default();
+		if thp_mode.is_empty() || thp_mode.contains("[never]") {
+			eprintln!("Skipping test: transparent huge pages are disabled");
+			return;
+		}
+
+		let mem = MmapMemory::new(
+			0,
+			4 * GUEST_PAGE_SIZE as usize,
+			GuestPhysAddr::new(0),
+			true,
+			false,
+		);
+		unsafe { mem.as_slice_mut() }.fill(0xaa);
+		let status = mem.advice_status().unwrap();
+		assert!(status.thp_eligible);
+		assert!(!status.ksm_mergeable);
+	}
 }
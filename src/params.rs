@@ -1,12 +1,22 @@
 use std::{
 	ffi::OsString,
-	fmt,
+	fmt, fs, io,
+	net::SocketAddr,
 	num::{NonZeroU32, ParseIntError, TryFromIntError},
+	ops::{Range, RangeInclusive},
+	os::fd::RawFd,
+	path::{Path, PathBuf},
 	str::FromStr,
+	sync::Arc,
+	time::Duration,
 };
 
 use byte_unit::{Byte, Unit};
+use log::LevelFilter;
+pub use nix::sys::resource::Resource;
+use serde::Deserialize;
 use thiserror::Error;
+use uhyve_interface::GuestPhysAddr;
 
 #[derive(Debug, Clone)]
 pub struct Params {
@@ -16,6 +26,13 @@ pub struct Params {
 	/// Guest RAM size
 	pub memory_size: GuestMemorySize,
 
+	/// Maximum guest RAM size
+	///
+	/// The guest boots with `memory_size` and can commit further memory up to this size via the
+	/// `MemBalloon` hypercall. The memory is mapped in full from the start, but only backed by
+	/// host memory once the guest uses it.
+	pub max_memory_size: Option<GuestMemorySize>,
+
 	/// Advise Transparent Hugepages
 	#[cfg(target_os = "linux")]
 	pub thp: bool,
@@ -24,18 +41,279 @@ pub struct Params {
 	#[cfg(target_os = "linux")]
 	pub ksm: bool,
 
+	/// Back the guest RAM with explicitly allocated huge pages of this size
+	#[cfg(target_os = "linux")]
+	pub hugepages: Option<HugePageSize>,
+
+	/// Lock the guest RAM into host memory, so that it is never swapped out
+	///
+	/// The guest memory size must not exceed the limit of locked memory (`RLIMIT_MEMLOCK`).
+	#[cfg(target_os = "linux")]
+	pub lock_memory: bool,
+
 	/// Number of guest CPUs
 	pub cpu_count: CpuCount,
 
+	/// Length of the identity mapped region of the boot pagetables
+	///
+	/// It must be a multiple of 2 MiB and must not exceed the guest memory size. If unset, the
+	/// first [`MAX_IDENTITY_MAP_LENGTH`](crate::consts::MAX_IDENTITY_MAP_LENGTH) bytes are mapped.
+	/// A shorter map lets stray accesses above it fault.
+	pub identity_map_length: Option<u64>,
+
 	/// Create a PIT
 	#[cfg(target_os = "linux")]
 	pub pit: bool,
 
+	/// Guest-physical base address of the local APICs
+	///
+	/// It must be page-aligned and must not overlap the guest RAM. If unset,
+	/// [`APIC_DEFAULT_BASE`](crate::consts::APIC_DEFAULT_BASE) is used. Only supported on x86_64.
+	pub apic_base: Option<GuestPhysAddr>,
+
+	/// Guest-physical base address of the IOAPIC
+	///
+	/// It must be page-aligned and must not overlap the guest RAM. If unset,
+	/// [`IOAPIC_BASE`](crate::consts::IOAPIC_BASE) is used. Only supported on x86_64.
+	pub ioapic_base: Option<GuestPhysAddr>,
+
+	/// Scale of the guest's TSC frequency relative to the host's
+	///
+	/// A scale of `2.0` makes the guest's clock run twice as fast as real time, as the guest is
+	/// still told the unscaled frequency. This tests the guest's timekeeping. The scaled frequency
+	/// has to be supported by KVM, otherwise creating the vCPUs fails.
+	#[cfg(target_os = "linux")]
+	pub tsc_scale: Option<f64>,
+
+	/// CPU features to hide from the guest
+	///
+	/// Hiding the features that are missing on some hosts presents the same CPU to the guest on
+	/// all of them.
+	#[cfg(target_os = "linux")]
+	pub cpu_features: Option<CpuFeatureMask>,
+
+	/// Count the instructions and cycles the guest executes
+	///
+	/// The counts are read from the fixed counters of the virtual PMU and reported in
+	/// [`VmStats::perf_counters`](crate::stats::VmStats::perf_counters). Hosts without fixed
+	/// counters, e.g., with AMD CPUs, don't report any.
+	#[cfg(target_os = "linux")]
+	pub perf_counters: bool,
+
+	/// Write an ELF core dump of the guest to this path if a vCPU stops abnormally
+	///
+	/// The dump contains the guest RAM and the registers of the faulting vCPU. It is not written
+	/// if the guest exits or panics.
+	#[cfg(target_os = "linux")]
+	pub core_dump: Option<PathBuf>,
+
 	/// GDB server port
 	pub gdb_port: Option<u16>,
 
+	/// Host directory to which the guest's file system is confined
+	///
+	/// Guest paths, including absolute ones, are resolved beneath it and must not escape it. If
+	/// unset, relative paths are resolved against uhyve's current working directory.
+	pub host_root: Option<PathBuf>,
+
+	/// Maximum number of bytes the guest may write to host files
+	///
+	/// Output to stdout and stderr does not count against the quota.
+	pub write_quota_bytes: Option<u64>,
+
+	/// Maximum number of bytes a single write hypercall may write
+	///
+	/// Longer writes are truncated and reported to the guest as short writes, so that the guest
+	/// has to write large buffers in chunks.
+	pub max_write_size: Option<usize>,
+
+	/// Maximum number of host files the guest may have open at the same time
+	///
+	/// Inherited file descriptors count against the limit, the standard streams don't.
+	pub max_open_files: Option<usize>,
+
+	/// Maximum number of host files the guest may create
+	///
+	/// Opening a nonexisting file with `O_CREAT` past the limit fails with `ENOSPC`.
+	pub max_created_files: Option<usize>,
+
+	/// Forbid the guest to create host files
+	///
+	/// Opening a nonexisting file with `O_CREAT` fails with `ENOENT`, as if the file couldn't be
+	/// created. This is the safest choice for sandboxing.
+	pub disable_file_creation: bool,
+
+	/// Append a record of each file system operation of the guest to this file
+	///
+	/// Opens and unlinks are recorded with the guest path, the resolved host path and the result,
+	/// including operations that were rejected.
+	pub audit_log: Option<PathBuf>,
+
+	/// Maximum number of hypercalls per second
+	///
+	/// vCPUs exceeding the limit are put to sleep until the next second.
+	pub hypercall_rate_limit: Option<NonZeroU32>,
+
+	/// Range of niceness values the guest may select for its vCPU threads
+	///
+	/// Requests outside of the range are clamped to it. If unset, the guest can't change the
+	/// priority of its vCPU threads.
+	pub priority_bounds: Option<RangeInclusive<i32>>,
+
+	/// Environment variables of the guest
+	///
+	/// If unset, the guest inherits uhyve's environment.
+	pub guest_env: Option<Vec<(String, String)>>,
+
+	/// Host environment variables the guest may read via the `GetEnv` hypercall
+	///
+	/// Reading any other variable fails as if it wasn't set.
+	pub env_allowlist: Vec<String>,
+
+	/// Hostname reported to the guest via the `GetHostname` hypercall
+	///
+	/// If unset, [`DEFAULT_GUEST_HOSTNAME`](crate::consts::DEFAULT_GUEST_HOSTNAME) is used.
+	pub guest_hostname: Option<String>,
+
+	/// Read-only files served to the guest from memory as `(guest_path, contents)`
+	///
+	/// They shadow the host file system at their guest paths, which have to match the paths the
+	/// guest opens exactly. The guest can read and seek in them, but not write to them.
+	pub virtual_files: Vec<(String, Vec<u8>)>,
+
+	/// Host UNIX sockets the guest connects to by opening their guest paths
+	///
+	/// Like virtual files, the guest paths shadow the host file system and have to match the
+	/// paths the guest opens exactly. The connection bypasses the host root, so the guest can
+	/// talk to the socket's daemon, and thus act outside of the host root, through it. Only map
+	/// sockets of daemons the guest may use.
+	pub unix_sockets: Vec<UnixSocketMapping>,
+
+	/// Tar archives whose files are served to the guest as read-only virtual files
+	///
+	/// The files appear below the guest directory of their mount, following the directory
+	/// structure of the archive. The archives are read into memory when the VM is created.
+	pub archives: Vec<ArchiveMount>,
+
+	/// Expected SHA-256 digest of the kernel file as hex string
+	///
+	/// If set, the kernel is read and verified when the VM is created, which fails if the digest
+	/// doesn't match.
+	pub kernel_sha256: Option<String>,
+
+	/// Size of the boot stack in bytes
+	///
+	/// If unset, [`KERNEL_STACK_SIZE`](crate::consts::KERNEL_STACK_SIZE) is used.
+	pub boot_stack_size: Option<u64>,
+
+	/// Put the arguments and the environment onto the boot stack
+	///
+	/// The boot stack pointer then points to `argc`, followed by `argv`, `envp` and the auxiliary
+	/// vector as in the System V ABI, for kernels that don't query them via hypercalls.
+	pub classic_stack_abi: bool,
+
+	/// Host files to copy verbatim into the guest memory at the given guest-physical addresses
+	pub raw_mappings: Vec<(GuestPhysAddr, PathBuf)>,
+
+	/// Guest-physical regions that are mapped read-only in the boot pagetables
+	///
+	/// The regions must be aligned to 2 MiB and lie within the identity map.
+	/// Guest writes to them fault, e.g., to protect data of [`Params::raw_mappings`]. Only
+	/// supported on x86_64.
+	pub read_only_regions: Vec<Range<GuestPhysAddr>>,
+
+	/// Log the guest's serial output line by line with this prefix instead of printing it to stdout
+	pub log_serial_prefix: Option<String>,
+
+	/// Stream the guest's serial output to a TCP client connecting to this address instead of
+	/// printing it to stdout
+	///
+	/// Like the kernel's log, the application's stdout and stderr are streamed to the client. The
+	/// output is buffered while no client is connected. Can't be combined with
+	/// [`Params::log_serial_prefix`].
+	pub serial_tcp: Option<SocketAddr>,
+
+	/// Collect the guest's serial output line by line into
+	/// [`VmResult::output_lines`](crate::vm::VmResult::output_lines)
+	///
+	/// This includes the kernel's log as well as the application's stdout and stderr. The output
+	/// is still printed or logged as usual.
+	pub structured_output: bool,
+
+	/// Forward each write of the guest to its serial port right away instead of line by line
+	///
+	/// The guest can switch the mode at runtime via the `SetSerialMode` hypercall.
+	pub unbuffered_serial: bool,
+
+	/// Flush the guest's output and synchronize the host files it has open for writing before
+	/// the result of the run is returned
+	///
+	/// This makes sure that the output is complete and the guest's files are on disk once
+	/// [`UhyveVm::run`](crate::vm::UhyveVm::run) returns, even if the guest exits right after
+	/// writing. Enabled by default.
+	pub flush_on_exit: bool,
+
+	/// Callback for events the guest reports via the `Notify` hypercall
+	pub event_hook: Option<EventHook>,
+
+	/// Host file descriptors passed into the guest as `(guest_fd, host_fd)`
+	///
+	/// The guest can use them without opening them first. It operates on duplicates, so closing
+	/// them doesn't close the host descriptors.
+	pub inherited_fds: Vec<(RawFd, RawFd)>,
+
+	/// Resource limits applied to the uhyve process while the VM runs
+	///
+	/// They bound the host file operations performed on the guest's behalf, e.g., `RLIMIT_FSIZE`
+	/// makes writes beyond the given file size fail with `EFBIG`. Only the soft limits are lowered
+	/// and restored after the run. The limits apply to the whole process, so running the VM fails
+	/// with `EBUSY` while another VM of the process runs with resource limits. VMs without resource
+	/// limits running at the same time are still subject to them.
+	pub rlimits: Vec<(Resource, u64)>,
+
+	/// Make runs reproducible
+	///
+	/// The guest is booted with a fixed boot time and CPU frequency instead of the host's, and its
+	/// network device reports a fixed MAC address, so that a deterministic kernel behaves
+	/// identically across runs and hosts.
+	pub deterministic: bool,
+
+	/// Delay after which the guest is released from the `Barrier` hypercall
+	///
+	/// If unset, the guest waits until the host releases it via
+	/// [`VmHandle::release_barrier`](crate::linux::VmHandle::release_barrier).
+	pub barrier_timeout: Option<Duration>,
+
+	/// Maximum wall-clock time the VM may run
+	///
+	/// Once it is exceeded, the VM is stopped and its result has
+	/// [`VmResult::timed_out`](crate::vm::VmResult::timed_out) set. Each `Heartbeat` hypercall of
+	/// the guest restarts the timeout, so that long-running guests can keep the VM alive as long
+	/// as they make progress. The timeout is not enforced while a debugger is attached.
+	pub timeout: Option<Duration>,
+
+	/// Highest log level the guest may set via the `SetLogLevel` hypercall
+	///
+	/// Higher requested levels are capped to it. If unset, the guest can't change the log level.
+	/// The level only filters the log messages of this VM's guest. Uhyve's own records are
+	/// filtered by the process-wide maximum level, which the guest can't change.
+	pub max_guest_log_level: Option<LevelFilter>,
+
+	/// Exit code by which the guest signals a panic
+	///
+	/// If the guest exits with this code, [`VmResult::panicked`](crate::vm::VmResult::panicked)
+	/// is set, like for panics reported via the `Panic` hypercall.
+	pub panic_exit_code: Option<i32>,
+
 	/// Arguments to forward to the kernel
+	///
+	/// Application arguments separated by `--` are deprecated, use [`Params::app_args`] instead.
 	pub kernel_args: Vec<OsString>,
+
+	/// Arguments to forward to the application
+	///
+	/// They are passed to the guest after the kernel arguments, separated by `--`.
+	pub app_args: Vec<OsString>,
 }
 
 #[allow(clippy::derivable_impls)]
@@ -44,19 +322,269 @@ impl Default for Params {
 		Self {
 			verbose: Default::default(),
 			memory_size: Default::default(),
+			max_memory_size: None,
 			#[cfg(target_os = "linux")]
 			thp: false,
 			#[cfg(target_os = "linux")]
 			ksm: false,
 			#[cfg(target_os = "linux")]
+			hugepages: None,
+			#[cfg(target_os = "linux")]
+			lock_memory: false,
+			#[cfg(target_os = "linux")]
 			pit: false,
+			apic_base: None,
+			ioapic_base: None,
+			#[cfg(target_os = "linux")]
+			tsc_scale: None,
+			#[cfg(target_os = "linux")]
+			cpu_features: None,
+			#[cfg(target_os = "linux")]
+			perf_counters: false,
+			#[cfg(target_os = "linux")]
+			core_dump: None,
 			cpu_count: Default::default(),
+			identity_map_length: None,
 			gdb_port: Default::default(),
+			host_root: Default::default(),
+			write_quota_bytes: Default::default(),
+			max_write_size: Default::default(),
+			max_open_files: Default::default(),
+			max_created_files: Default::default(),
+			disable_file_creation: false,
+			audit_log: Default::default(),
+			hypercall_rate_limit: Default::default(),
+			priority_bounds: None,
+			guest_env: Default::default(),
+			env_allowlist: Default::default(),
+			guest_hostname: Default::default(),
+			virtual_files: Default::default(),
+			unix_sockets: Default::default(),
+			archives: Default::default(),
+			kernel_sha256: Default::default(),
+			boot_stack_size: Default::default(),
+			classic_stack_abi: false,
+			raw_mappings: Default::default(),
+			read_only_regions: Default::default(),
+			log_serial_prefix: Default::default(),
+			serial_tcp: None,
+			structured_output: false,
+			unbuffered_serial: false,
+			flush_on_exit: true,
+			event_hook: Default::default(),
+			inherited_fds: Default::default(),
+			rlimits: Default::default(),
+			deterministic: false,
+			barrier_timeout: Default::default(),
+			timeout: Default::default(),
+			max_guest_log_level: Default::default(),
+			panic_exit_code: Default::default(),
 			kernel_args: Default::default(),
+			app_args: Default::default(),
 		}
 	}
 }
 
+impl Params {
+	/// Loads the parameters from the TOML file at `path`.
+	///
+	/// Sizes are given as strings with units, e.g., `memory_size = "64 MiB"`. Parameters
+	/// missing from the file keep their default values.
+	pub fn from_toml(path: &Path) -> Result<Self, ConfigError> {
+		let config: ConfigFile = toml::from_str(&fs::read_to_string(path)?)?;
+		let params = Self {
+			verbose: config.verbose,
+			memory_size: config
+				.memory_size
+				.as_deref()
+				.map(str::parse)
+				.transpose()?
+				.unwrap_or_default(),
+			max_memory_size: config
+				.max_memory_size
+				.as_deref()
+				.map(str::parse)
+				.transpose()?,
+			#[cfg(target_os = "linux")]
+			thp: config.thp,
+			#[cfg(target_os = "linux")]
+			ksm: config.ksm,
+			#[cfg(target_os = "linux")]
+			hugepages: config.hugepages.as_deref().map(str::parse).transpose()?,
+			#[cfg(target_os = "linux")]
+			lock_memory: config.lock_memory,
+			cpu_count: config
+				.cpu_count
+				.map(CpuCount::try_from)
+				.transpose()
+				.map_err(|_| ConfigError::NoCpus)?
+				.unwrap_or_default(),
+			#[cfg(target_os = "linux")]
+			pit: config.pit,
+			#[cfg(target_os = "linux")]
+			tsc_scale: config.tsc_scale,
+			#[cfg(target_os = "linux")]
+			perf_counters: config.perf_counters,
+			#[cfg(target_os = "linux")]
+			core_dump: config.core_dump,
+			gdb_port: config.gdb_port,
+			host_root: config.host_root,
+			write_quota_bytes: config
+				.write_quota
+				.as_deref()
+				.map(Byte::from_str)
+				.transpose()?
+				.map(|quota| quota.as_u64()),
+			max_write_size: config
+				.max_write_size
+				.as_deref()
+				.map(Byte::from_str)
+				.transpose()?
+				.map(|size| size.as_u64() as usize),
+			max_open_files: config.max_open_files,
+			max_created_files: config.max_created_files,
+			disable_file_creation: config.disable_file_creation,
+			audit_log: config.audit_log,
+			hypercall_rate_limit: config.hypercall_rate_limit,
+			env_allowlist: config.env_allowlist,
+			guest_hostname: config.guest_hostname,
+			kernel_sha256: config.kernel_sha256,
+			boot_stack_size: config
+				.boot_stack_size
+				.as_deref()
+				.map(Byte::from_str)
+				.transpose()?
+				.map(|size| size.as_u64()),
+			classic_stack_abi: config.classic_stack_abi,
+			raw_mappings: config
+				.raw_mappings
+				.into_iter()
+				.map(|mapping| (GuestPhysAddr::new(mapping.address), mapping.path))
+				.collect(),
+			log_serial_prefix: config.log_serial_prefix,
+			serial_tcp: config.serial_tcp,
+			deterministic: config.deterministic,
+			panic_exit_code: config.panic_exit_code,
+			kernel_args: config.kernel_args.into_iter().map(OsString::from).collect(),
+			app_args: config.app_args.into_iter().map(OsString::from).collect(),
+			..Default::default()
+		};
+		if params.gdb_port.is_some() && params.cpu_count.get() != 1 {
+			return Err(ConfigError::GdbMultipleCpus);
+		}
+		if params.serial_tcp.is_some() && params.log_serial_prefix.is_some() {
+			return Err(ConfigError::SerialTcpWithLogPrefix);
+		}
+		Ok(params)
+	}
+}
+
+/// The content of a configuration file, see [`Params::from_toml`].
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+	verbose: bool,
+	memory_size: Option<String>,
+	max_memory_size: Option<String>,
+	#[cfg(target_os = "linux")]
+	thp: bool,
+	#[cfg(target_os = "linux")]
+	ksm: bool,
+	#[cfg(target_os = "linux")]
+	hugepages: Option<String>,
+	#[cfg(target_os = "linux")]
+	lock_memory: bool,
+	cpu_count: Option<u32>,
+	#[cfg(target_os = "linux")]
+	pit: bool,
+	#[cfg(target_os = "linux")]
+	tsc_scale: Option<f64>,
+	#[cfg(target_os = "linux")]
+	perf_counters: bool,
+	#[cfg(target_os = "linux")]
+	core_dump: Option<PathBuf>,
+	gdb_port: Option<u16>,
+	host_root: Option<PathBuf>,
+	write_quota: Option<String>,
+	max_write_size: Option<String>,
+	max_open_files: Option<usize>,
+	max_created_files: Option<usize>,
+	disable_file_creation: bool,
+	audit_log: Option<PathBuf>,
+	hypercall_rate_limit: Option<NonZeroU32>,
+	env_allowlist: Vec<String>,
+	guest_hostname: Option<String>,
+	kernel_sha256: Option<String>,
+	boot_stack_size: Option<String>,
+	classic_stack_abi: bool,
+	raw_mappings: Vec<RawMapping>,
+	log_serial_prefix: Option<String>,
+	serial_tcp: Option<SocketAddr>,
+	deterministic: bool,
+	panic_exit_code: Option<i32>,
+	kernel_args: Vec<String>,
+	app_args: Vec<String>,
+}
+
+/// A `[[raw_mappings]]` entry of a configuration file.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct RawMapping {
+	address: u64,
+	path: PathBuf,
+}
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+	#[error("Unable to read the configuration file: {0}")]
+	Io(#[from] io::Error),
+
+	#[error("Invalid configuration file: {0}")]
+	Toml(#[from] toml::de::Error),
+
+	#[error(transparent)]
+	MemorySize(#[from] ParseByteError),
+
+	#[error(transparent)]
+	HugePageSize(#[from] ParseHugePageSizeError),
+
+	#[error(transparent)]
+	Size(#[from] byte_unit::ParseError),
+
+	#[error("The number of CPUs must be at least 1")]
+	NoCpus,
+
+	#[error("gdbstub is only supported with one CPU")]
+	GdbMultipleCpus,
+
+	#[error("The serial output can't be both logged and streamed over TCP")]
+	SerialTcpWithLogPrefix,
+}
+
+/// A callback receiving the id and the payload of guest events.
+///
+/// The hook is called on the vCPU thread that issued the event and should therefore return
+/// quickly.
+#[derive(Clone)]
+#[allow(clippy::type_complexity)]
+pub struct EventHook(Arc<dyn Fn(u32, &[u8]) + Send + Sync>);
+
+impl EventHook {
+	pub fn new(hook: impl Fn(u32, &[u8]) + Send + Sync + 'static) -> Self {
+		Self(Arc::new(hook))
+	}
+
+	pub fn call(&self, event: u32, payload: &[u8]) {
+		(self.0)(event, payload)
+	}
+}
+
+impl fmt::Debug for EventHook {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_tuple("EventHook").finish_non_exhaustive()
+	}
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct CpuCount(NonZeroU32);
 
@@ -96,6 +624,55 @@ impl FromStr for CpuCount {
 	}
 }
 
+/// A register of a CPUID leaf.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuidRegister {
+	Eax,
+	Ebx,
+	Ecx,
+	Edx,
+}
+
+/// Feature bits that are cleared from the CPUID leaves reported to the guest.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CpuFeatureMask {
+	/// The hidden bits as `(function, index, register, bits)`.
+	hidden: Vec<(u32, u32, CpuidRegister, u32)>,
+}
+
+impl CpuFeatureMask {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Hides the `bits` of `register` in the CPUID leaf `function` with the subleaf `index`.
+	///
+	/// `index` is `0` for leaves without subleaves.
+	pub fn hide(mut self, function: u32, index: u32, register: CpuidRegister, bits: u32) -> Self {
+		self.hidden.push((function, index, register, bits));
+		self
+	}
+
+	/// Clears the hidden bits from the registers `[eax, ebx, ecx, edx]` of the CPUID leaf
+	/// `function` with the subleaf `index`.
+	pub fn apply(&self, function: u32, index: u32, registers: [&mut u32; 4]) {
+		let [eax, ebx, ecx, edx] = registers;
+		for &(_, _, register, bits) in self
+			.hidden
+			.iter()
+			.filter(|hidden| hidden.0 == function && hidden.1 == index)
+		{
+			let value = match register {
+				CpuidRegister::Eax => &mut *eax,
+				CpuidRegister::Ebx => &mut *ebx,
+				CpuidRegister::Ecx => &mut *ecx,
+				CpuidRegister::Edx => &mut *edx,
+			};
+			*value &= !bits;
+		}
+	}
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct GuestMemorySize(Byte);
 
@@ -141,7 +718,10 @@ impl TryFrom<Byte> for GuestMemorySize {
 	fn try_from(value: Byte) -> Result<Self, Self::Error> {
 		if value < Self::minimum() {
 			Err(InvalidGuestMemorySizeError::MemoryTooSmall(value))
-		} else if value.as_u64() % Byte::from_u64_with_unit(2, Unit::MiB).unwrap().as_u64() != 0 {
+		} else if !value
+			.as_u64()
+			.is_multiple_of(Byte::from_u64_with_unit(2, Unit::MiB).unwrap().as_u64())
+		{
 			Err(InvalidGuestMemorySizeError::NotAHugepage(value))
 		} else {
 			Ok(Self(value))
@@ -167,3 +747,253 @@ impl FromStr for GuestMemorySize {
 		Ok(memory_size)
 	}
 }
+
+/// The size of the huge pages backing the guest memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HugePageSize {
+	Size2MiB,
+	Size1GiB,
+}
+
+impl HugePageSize {
+	/// Returns the page size in bytes.
+	pub fn get(self) -> usize {
+		match self {
+			Self::Size2MiB => 2 * 1024 * 1024,
+			Self::Size1GiB => 1024 * 1024 * 1024,
+		}
+	}
+}
+
+impl fmt::Display for HugePageSize {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Size2MiB => f.write_str("2 MiB"),
+			Self::Size1GiB => f.write_str("1 GiB"),
+		}
+	}
+}
+
+#[derive(Error, Debug)]
+pub enum ParseHugePageSizeError {
+	#[error(transparent)]
+	Parse(#[from] byte_unit::ParseError),
+
+	#[error("Unsupported huge page size. Must be either 2 MiB or 1 GiB (is {0})")]
+	Unsupported(Byte),
+}
+
+impl FromStr for HugePageSize {
+	type Err = ParseHugePageSizeError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let requested = Byte::from_str(s)?;
+		[Self::Size2MiB, Self::Size1GiB]
+			.into_iter()
+			.find(|size| size.get() as u64 == requested.as_u64())
+			.ok_or(ParseHugePageSizeError::Unsupported(requested))
+	}
+}
+
+/// A host UNIX socket that is mapped into the guest, see [`Params::unix_sockets`].
+///
+/// Parsed from `unix:<host path>:<guest path>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnixSocketMapping {
+	pub host_path: PathBuf,
+	pub guest_path: String,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ParseUnixSocketMappingError {
+	#[error("Invalid UNIX socket mapping. Must be of the form `unix:<host path>:<guest path>` (is {0:?})")]
+	Invalid(String),
+}
+
+impl FromStr for UnixSocketMapping {
+	type Err = ParseUnixSocketMappingError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let invalid = || ParseUnixSocketMappingError::Invalid(s.to_string());
+		let (host_path, guest_path) = s
+			.strip_prefix("unix:")
+			.and_then(|mapping| mapping.rsplit_once(':'))
+			.ok_or_else(invalid)?;
+		if host_path.is_empty() || guest_path.is_empty() {
+			return Err(invalid());
+		}
+		Ok(Self {
+			host_path: host_path.into(),
+			guest_path: guest_path.to_string(),
+		})
+	}
+}
+
+/// A tar archive whose files are mounted into the guest, see [`Params::archives`].
+///
+/// Parsed from `<archive>:<guest directory>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveMount {
+	pub archive: PathBuf,
+	pub guest_dir: String,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ParseArchiveMountError {
+	#[error("Invalid archive mount. Must be of the form `<archive>:<guest directory>` (is {0:?})")]
+	Invalid(String),
+}
+
+impl FromStr for ArchiveMount {
+	type Err = ParseArchiveMountError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let invalid = || ParseArchiveMountError::Invalid(s.to_string());
+		let (archive, guest_dir) = s.rsplit_once(':').ok_or_else(invalid)?;
+		if archive.is_empty() || guest_dir.is_empty() {
+			return Err(invalid());
+		}
+		Ok(Self {
+			archive: archive.into(),
+			guest_dir: guest_dir.to_string(),
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_from_toml() {
+		let dir = assert_fs::TempDir::new().unwrap();
+		let path = dir.path().join("uhyve.toml");
+		fs::write(
+			&path,
+			r#"
+			verbose = true
+			memory_size = "128 MiB"
+			cpu_count = 2
+			host_root = "/srv/guest"
+			write_quota = "1 MiB"
+			max_write_size = "4 KiB"
+			max_open_files = 16
+			boot_stack_size = "64 KiB"
+			log_serial_prefix = "[vm] "
+			kernel_args = ["-freq", "1000"]
+			app_args = ["hello"]
+
+			[[raw_mappings]]
+			address = 0x40000000
+			path = "data.bin"
+			"#,
+		)
+		.unwrap();
+
+		let params = Params::from_toml(&path).unwrap();
+		assert!(params.verbose);
+		assert_eq!(params.memory_size.get(), 128 * 1024 * 1024);
+		assert_eq!(params.cpu_count.get(), 2);
+		assert_eq!(params.gdb_port, None);
+		assert_eq!(params.host_root.as_deref(), Some(Path::new("/srv/guest")));
+		assert_eq!(params.write_quota_bytes, Some(1024 * 1024));
+		assert_eq!(params.max_write_size, Some(4096));
+		assert_eq!(params.max_open_files, Some(16));
+		assert_eq!(params.max_created_files, None);
+		assert!(!params.disable_file_creation);
+		assert_eq!(params.hypercall_rate_limit, None);
+		assert_eq!(params.boot_stack_size, Some(64 * 1024));
+		assert!(!params.classic_stack_abi);
+		assert_eq!(
+			params.raw_mappings,
+			[(GuestPhysAddr::new(0x4000_0000), PathBuf::from("data.bin"))]
+		);
+		assert_eq!(params.log_serial_prefix.as_deref(), Some("[vm] "));
+		assert_eq!(params.serial_tcp, None);
+		assert!(!params.deterministic);
+		assert_eq!(params.kernel_args, ["-freq", "1000"]);
+		assert_eq!(params.app_args, ["hello"]);
+
+		let invalid = |content: &str| {
+			fs::write(&path, content).unwrap();
+			Params::from_toml(&path).unwrap_err()
+		};
+		assert!(matches!(
+			invalid(r#"memory_size = "8 MiB""#),
+			ConfigError::MemorySize(ParseByteError::InvalidMemorySize(
+				InvalidGuestMemorySizeError::MemoryTooSmall(_)
+			))
+		));
+		assert!(matches!(
+			invalid("gdb_port = 6677\ncpu_count = 2"),
+			ConfigError::GdbMultipleCpus
+		));
+		assert!(matches!(
+			invalid("serial_tcp = \"127.0.0.1:4000\"\nlog_serial_prefix = \"[vm] \""),
+			ConfigError::SerialTcpWithLogPrefix
+		));
+		assert!(matches!(invalid("cpus = 2"), ConfigError::Toml(_)));
+
+		fs::write(&path, r#"serial_tcp = "127.0.0.1:4000""#).unwrap();
+		let params = Params::from_toml(&path).unwrap();
+		assert_eq!(params.serial_tcp, Some("127.0.0.1:4000".parse().unwrap()));
+	}
+
+	#[test]
+	fn test_cpu_feature_mask() {
+		let mask = CpuFeatureMask::new()
+			.hide(1, 0, CpuidRegister::Ecx, 1 << 30)
+			.hide(1, 0, CpuidRegister::Edx, 0b11)
+			.hide(7, 0, CpuidRegister::Ebx, 1 << 5);
+
+		let mut regs = [u32::MAX; 4];
+		let [eax, ebx, ecx, edx] = &mut regs;
+		mask.apply(1, 0, [eax, ebx, ecx, edx]);
+		assert_eq!(regs, [u32::MAX, u32::MAX, !(1 << 30), !0b11]);
+
+		// Other leaves and subleaves are left alone
+		let mut regs = [u32::MAX; 4];
+		let [eax, ebx, ecx, edx] = &mut regs;
+		mask.apply(7, 1, [eax, ebx, ecx, edx]);
+		mask.apply(0x8000_0001, 0, [eax, ebx, ecx, edx]);
+		assert_eq!(regs, [u32::MAX; 4]);
+	}
+
+	#[test]
+	fn test_archive_mount() {
+		assert_eq!(
+			"fixtures.tar:/srv/fixtures".parse(),
+			Ok(ArchiveMount {
+				archive: "fixtures.tar".into(),
+				guest_dir: "/srv/fixtures".to_string(),
+			})
+		);
+		assert_eq!(
+			"fixtures.tar".parse::<ArchiveMount>(),
+			Err(ParseArchiveMountError::Invalid("fixtures.tar".to_string()))
+		);
+		assert!(":/srv".parse::<ArchiveMount>().is_err());
+		assert!("fixtures.tar:".parse::<ArchiveMount>().is_err());
+	}
+
+	#[test]
+	fn test_unix_socket_mapping() {
+		assert_eq!(
+			"unix:/run/daemon.sock:daemon".parse(),
+			Ok(UnixSocketMapping {
+				host_path: "/run/daemon.sock".into(),
+				guest_path: "daemon".to_string(),
+			})
+		);
+		for invalid in [
+			"/run/daemon.sock:daemon",
+			"unix:/run/daemon.sock",
+			"unix::daemon",
+		] {
+			assert_eq!(
+				invalid.parse::<UnixSocketMapping>(),
+				Err(ParseUnixSocketMappingError::Invalid(invalid.to_string()))
+			);
+		}
+	}
+}
@@ -0,0 +1,209 @@
+//! Synthetic, read-only files that let the guest inspect its own VM.
+//!
+//! The files are generated from the host state when the guest opens them and shadow the host
+//! file system (including the host root) at their reserved paths. Similarly, the virtual files
+//! configured for the VM are served from memory and the mapped host UNIX sockets are connected
+//! to instead of opening the host file system at their paths.
+
+use std::{
+	collections::BTreeSet,
+	ffi::OsString,
+	fmt::Write,
+	fs::File,
+	io::{self, Seek, Write as _},
+	num::NonZeroU32,
+	os::fd::OwnedFd,
+	path::{Path, PathBuf},
+	time::Duration,
+};
+
+/// A snapshot of the VM's state the synthetic files are generated from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcInfo<'a> {
+	pub num_cpus: u32,
+	/// The guest memory size in bytes.
+	pub memory_size: usize,
+	/// The CPU frequency in kHz, if it could be detected.
+	pub cpu_freq: Option<NonZeroU32>,
+	/// Time since the start of the guest.
+	pub uptime: Duration,
+	/// The guest paths and contents of the VM's virtual files.
+	pub virtual_files: &'a [(PathBuf, Vec<u8>)],
+	/// The guest paths of the host UNIX sockets mapped into the guest with their host paths.
+	pub unix_sockets: &'a [(PathBuf, PathBuf)],
+}
+
+impl<'a> ProcInfo<'a> {
+	/// Returns the content of the synthetic file at `path`, or `None` if `path` is not reserved.
+	pub fn file(&self, path: &Path) -> Option<String> {
+		let mut content = String::new();
+		match path.to_str()? {
+			"/proc/cpuinfo" => {
+				for cpu in 0..self.num_cpus {
+					writeln!(content, "processor\t: {cpu}").unwrap();
+					if let Some(freq) = self.cpu_freq {
+						let freq = freq.get();
+						writeln!(content, "cpu MHz\t\t: {}.{:03}", freq / 1000, freq % 1000)
+							.unwrap();
+					}
+					writeln!(content).unwrap();
+				}
+			}
+			"/proc/meminfo" => {
+				writeln!(content, "MemTotal:\t{} kB", self.memory_size / 1024).unwrap();
+			}
+			"/proc/uptime" => {
+				let uptime = self.uptime;
+				writeln!(
+					content,
+					"{}.{:02}",
+					uptime.as_secs(),
+					uptime.subsec_millis() / 10
+				)
+				.unwrap();
+			}
+			_ => return None,
+		}
+		Some(content)
+	}
+
+	/// Returns the content of the virtual file at `path`, or `None` if there is none.
+	pub fn virtual_file(&self, path: &Path) -> Option<&'a [u8]> {
+		self.virtual_files
+			.iter()
+			.find(|(virtual_path, _)| virtual_path == path)
+			.map(|(_, content)| content.as_slice())
+	}
+
+	/// Returns the names of the entries of the virtual directory at `path`, or `None` if there is
+	/// no virtual file beneath it.
+	///
+	/// The directories are implied by the paths of the virtual files, e.g., of the entries of a
+	/// mounted archive. The names of subdirectories end with a `/`.
+	pub fn virtual_dir(&self, path: &Path) -> Option<BTreeSet<OsString>> {
+		let entries: BTreeSet<_> = self
+			.virtual_files
+			.iter()
+			.filter_map(|(virtual_path, _)| {
+				let mut components = virtual_path.strip_prefix(path).ok()?.iter();
+				let mut name = components.next()?.to_owned();
+				if components.next().is_some() {
+					name.push("/");
+				}
+				Some(name)
+			})
+			.collect();
+		(!entries.is_empty()).then_some(entries)
+	}
+
+	/// Returns the host path of the UNIX socket mapped to `path`, or `None` if there is none.
+	pub fn unix_socket(&self, path: &Path) -> Option<&'a Path> {
+		self.unix_sockets
+			.iter()
+			.find(|(guest_path, _)| guest_path == path)
+			.map(|(_, host_path)| host_path.as_path())
+	}
+}
+
+/// Creates a sealed, read-only in-memory file containing `content`.
+#[cfg(target_os = "linux")]
+pub fn sealed_file(content: &[u8]) -> io::Result<OwnedFd> {
+	use std::os::fd::{AsRawFd, FromRawFd};
+
+	let fd = unsafe {
+		libc::memfd_create(
+			c"uhyve-proc".as_ptr(),
+			libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING,
+		)
+	};
+	if fd < 0 {
+		return Err(io::Error::last_os_error());
+	}
+	let mut file = unsafe { File::from_raw_fd(fd) };
+	file.write_all(content)?;
+	file.rewind()?;
+	let seals = libc::F_SEAL_WRITE | libc::F_SEAL_GROW | libc::F_SEAL_SHRINK | libc::F_SEAL_SEAL;
+	if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_ADD_SEALS, seals) } < 0 {
+		return Err(io::Error::last_os_error());
+	}
+	Ok(file.into())
+}
+
+/// Creates a sealed, read-only in-memory file containing `content`.
+#[cfg(not(target_os = "linux"))]
+pub fn sealed_file(_content: &[u8]) -> io::Result<OwnedFd> {
+	Err(io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_proc_files() {
+		let info = ProcInfo {
+			num_cpus: 2,
+			memory_size: 64 * 1024 * 1024,
+			cpu_freq: NonZeroU32::new(2_400_500),
+			uptime: Duration::from_millis(3_456),
+			virtual_files: &[],
+			unix_sockets: &[],
+		};
+		assert_eq!(
+			info.file(Path::new("/proc/cpuinfo")).unwrap(),
+			"processor\t: 0\ncpu MHz\t\t: 2400.500\n\nprocessor\t: 1\ncpu MHz\t\t: 2400.500\n\n"
+		);
+		assert_eq!(
+			info.file(Path::new("/proc/meminfo")).unwrap(),
+			"MemTotal:\t65536 kB\n"
+		);
+		assert_eq!(info.file(Path::new("/proc/uptime")).unwrap(), "3.45\n");
+		assert_eq!(info.file(Path::new("/proc/stat")), None);
+		assert_eq!(info.file(Path::new("proc/cpuinfo")), None);
+	}
+
+	#[test]
+	fn test_virtual_files() {
+		let virtual_files = [(PathBuf::from("/etc/motd"), b"hello".to_vec())];
+		let info = ProcInfo {
+			num_cpus: 1,
+			memory_size: 0,
+			cpu_freq: None,
+			uptime: Duration::ZERO,
+			virtual_files: &virtual_files,
+			unix_sockets: &[],
+		};
+		assert_eq!(
+			info.virtual_file(Path::new("/etc/motd")),
+			Some(b"hello".as_slice())
+		);
+		assert_eq!(info.virtual_file(Path::new("/etc/passwd")), None);
+		assert_eq!(info.virtual_file(Path::new("etc/motd")), None);
+	}
+
+	#[test]
+	fn test_virtual_dir() {
+		let virtual_files = [
+			(PathBuf::from("/fixtures/a.txt"), b"a".to_vec()),
+			(PathBuf::from("/fixtures/sub/b.txt"), b"b".to_vec()),
+			(PathBuf::from("/fixtures/sub/c.txt"), b"c".to_vec()),
+		];
+		let info = ProcInfo {
+			num_cpus: 1,
+			memory_size: 0,
+			cpu_freq: None,
+			uptime: Duration::ZERO,
+			virtual_files: &virtual_files,
+			unix_sockets: &[],
+		};
+		let names = |path| {
+			info.virtual_dir(Path::new(path))
+				.map(|entries| entries.into_iter().collect::<Vec<_>>())
+		};
+		assert_eq!(names("/").unwrap(), ["fixtures/"]);
+		assert_eq!(names("/fixtures").unwrap(), ["a.txt", "sub/"]);
+		assert_eq!(names("/fixtures/sub/").unwrap(), ["b.txt", "c.txt"]);
+		assert_eq!(names("/fixtures/a.txt"), None);
+		assert_eq!(names("/etc"), None);
+	}
+}
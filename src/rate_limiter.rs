@@ -0,0 +1,97 @@
+//! Throttling of guest requests.
+
+use std::{
+	num::NonZeroU32,
+	sync::Mutex,
+	thread,
+	time::{Duration, Instant},
+};
+
+/// The interval in which at most `limit` calls are admitted.
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// Limits the number of calls per second, e.g., of hypercalls issued by the guest.
+///
+/// The limiter is shared by all vCPUs, so the limit applies to the VM as a whole.
+#[derive(Debug)]
+pub struct RateLimiter {
+	limit: NonZeroU32,
+	window: Mutex<Window>,
+}
+
+#[derive(Debug)]
+struct Window {
+	start: Instant,
+	calls: u32,
+}
+
+impl RateLimiter {
+	pub fn new(calls_per_second: NonZeroU32) -> Self {
+		Self {
+			limit: calls_per_second,
+			window: Mutex::new(Window {
+				start: Instant::now(),
+				calls: 0,
+			}),
+		}
+	}
+
+	/// Accounts a call. If the limit is exhausted, the calling thread sleeps until the next window.
+	pub fn acquire(&self) {
+		loop {
+			let mut window = self.window.lock().unwrap();
+			let now = Instant::now();
+			let elapsed = now.duration_since(window.start);
+			if elapsed >= WINDOW {
+				window.start = now;
+				window.calls = 0;
+			}
+			if window.calls < self.limit.get() {
+				window.calls += 1;
+				return;
+			}
+			drop(window);
+			thread::sleep(WINDOW - elapsed);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+
+	use super::*;
+
+	#[test]
+	fn test_rate_limit() {
+		const LIMIT: u32 = 20;
+
+		let limiter = Arc::new(RateLimiter::new(NonZeroU32::new(LIMIT).unwrap()));
+		let start = Instant::now();
+		let threads = (0..4)
+			.map(|_| {
+				let limiter = limiter.clone();
+				thread::spawn(move || {
+					(0..3 * LIMIT / 4)
+						.map(|_| {
+							limiter.acquire();
+							start.elapsed()
+						})
+						.collect::<Vec<_>>()
+				})
+			})
+			.collect::<Vec<_>>();
+		let mut calls = threads
+			.into_iter()
+			.flat_map(|thread| thread.join().unwrap())
+			.collect::<Vec<_>>();
+		calls.sort();
+
+		// 3 * LIMIT calls need at least three windows
+		assert!(start.elapsed() >= 2 * WINDOW);
+		// As every window admits at most LIMIT calls, any 2 * LIMIT + 1 calls span three windows
+		for (i, call) in calls.iter().enumerate().skip(2 * LIMIT as usize) {
+			assert!(*call - calls[i - 2 * LIMIT as usize] >= WINDOW);
+		}
+	}
+}
@@ -0,0 +1,101 @@
+//! Resource limits that bound the host impact of a guest.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use nix::{
+	errno::Errno,
+	sys::{
+		resource::{getrlimit, setrlimit, Resource},
+		signal::{signal, SigHandler, Signal},
+	},
+};
+
+/// Whether an [`RlimitGuard`] currently holds the limits of the process.
+static LIMITED: AtomicBool = AtomicBool::new(false);
+
+/// Resource limits of the uhyve process for the duration of a VM run.
+///
+/// Only the soft limits are lowered, so the previous limits can be restored when the guard is
+/// dropped. The limits apply to the whole process, so only one guard can hold limits at a time.
+/// Otherwise, guards dropped in a different order than they were created would restore each
+/// other's limits.
+#[derive(Debug)]
+pub(crate) struct RlimitGuard {
+	/// The resources with their previous soft and hard limits.
+	previous: Vec<(Resource, u64, u64)>,
+	/// The previous disposition of `SIGXFSZ`, if it was changed.
+	previous_sigxfsz: Option<SigHandler>,
+	/// Whether this guard holds the limits of the process.
+	limited: bool,
+}
+
+impl RlimitGuard {
+	/// Applies `rlimits`, clamping them to the hard limits.
+	///
+	/// Fails with `EBUSY` if `rlimits` isn't empty and another guard holds limits.
+	pub(crate) fn apply(rlimits: &[(Resource, u64)]) -> nix::Result<Self> {
+		let limited = !rlimits.is_empty();
+		if limited && LIMITED.swap(true, Ordering::AcqRel) {
+			return Err(Errno::EBUSY);
+		}
+		let mut guard = Self {
+			previous: Vec::with_capacity(rlimits.len()),
+			previous_sigxfsz: None,
+			limited,
+		};
+		for &(resource, limit) in rlimits {
+			let (soft, hard) = getrlimit(resource)?;
+			debug!("Limiting {resource:?} to {limit}");
+			setrlimit(resource, limit.min(hard), hard)?;
+			guard.previous.push((resource, soft, hard));
+			// Exceeding the file size limit should fail the write instead of killing uhyve.
+			if resource == Resource::RLIMIT_FSIZE && guard.previous_sigxfsz.is_none() {
+				guard.previous_sigxfsz =
+					Some(unsafe { signal(Signal::SIGXFSZ, SigHandler::SigIgn) }?);
+			}
+		}
+		Ok(guard)
+	}
+}
+
+impl Drop for RlimitGuard {
+	fn drop(&mut self) {
+		for &(resource, soft, hard) in self.previous.iter().rev() {
+			if let Err(err) = setrlimit(resource, soft, hard) {
+				warn!("Unable to restore the limit of {resource:?}: {err}");
+			}
+		}
+		if let Some(handler) = self.previous_sigxfsz {
+			unsafe { signal(Signal::SIGXFSZ, handler) }.unwrap();
+		}
+		if self.limited {
+			LIMITED.store(false, Ordering::Release);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_restore_limits() {
+		// Core dumps are the only resource the other tests don't depend on
+		let previous = getrlimit(Resource::RLIMIT_CORE).unwrap();
+		{
+			let _guard = RlimitGuard::apply(&[(Resource::RLIMIT_CORE, 0)]).unwrap();
+			assert_eq!(getrlimit(Resource::RLIMIT_CORE).unwrap(), (0, previous.1));
+
+			// Only one guard can hold limits at a time
+			assert_eq!(
+				RlimitGuard::apply(&[(Resource::RLIMIT_CORE, 1)]).unwrap_err(),
+				Errno::EBUSY
+			);
+			assert!(RlimitGuard::apply(&[]).is_ok());
+			assert_eq!(getrlimit(Resource::RLIMIT_CORE).unwrap(), (0, previous.1));
+		}
+		assert_eq!(getrlimit(Resource::RLIMIT_CORE).unwrap(), previous);
+		drop(RlimitGuard::apply(&[(Resource::RLIMIT_CORE, 0)]).unwrap());
+		assert_eq!(getrlimit(Resource::RLIMIT_CORE).unwrap(), previous);
+	}
+}
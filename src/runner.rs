@@ -0,0 +1,67 @@
+//! Running several VMs in one host process.
+
+use std::{num::NonZeroUsize, sync::Mutex, thread};
+
+use crate::{
+	vm::{UhyveVm, VmResult},
+	HypervisorResult,
+};
+
+/// Runs several VMs concurrently.
+///
+/// Every VM keeps its own guest memory, host files and output settings, only the host process
+/// and thus its logger are shared.
+#[derive(Debug, Default)]
+pub struct UhyveRunner {
+	vms: Vec<UhyveVm>,
+	max_concurrency: Option<NonZeroUsize>,
+}
+
+impl UhyveRunner {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Limits the number of VMs that run at the same time to `max_concurrency`.
+	///
+	/// By default, all VMs run at the same time.
+	pub fn with_max_concurrency(self, max_concurrency: Option<NonZeroUsize>) -> Self {
+		Self {
+			max_concurrency,
+			..self
+		}
+	}
+
+	/// Adds `vm` to the VMs to run.
+	pub fn add(&mut self, vm: UhyveVm) -> &mut Self {
+		self.vms.push(vm);
+		self
+	}
+
+	/// Runs all VMs and blocks until all of them have finished.
+	///
+	/// The results are returned in the order in which the VMs were added, see [`UhyveVm::run`].
+	pub fn run(self) -> Vec<HypervisorResult<VmResult>> {
+		let workers = self
+			.max_concurrency
+			.map_or(self.vms.len(), |max| max.get().min(self.vms.len()));
+		let queue = Mutex::new(self.vms.into_iter().enumerate());
+		let results = Mutex::new(Vec::new());
+
+		thread::scope(|scope| {
+			for _ in 0..workers {
+				scope.spawn(|| loop {
+					let Some((i, vm)) = queue.lock().unwrap().next() else {
+						break;
+					};
+					let result = vm.run(None);
+					results.lock().unwrap().push((i, result));
+				});
+			}
+		});
+
+		let mut results = results.into_inner().unwrap();
+		results.sort_by_key(|&(i, _)| i);
+		results.into_iter().map(|(_, result)| result).collect()
+	}
+}
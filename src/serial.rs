@@ -0,0 +1,357 @@
+//! Output of the guest's serial port.
+
+use std::{
+	io::{self, Write},
+	mem,
+	net::{SocketAddr, TcpListener, TcpStream},
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Mutex,
+	},
+	time::Duration,
+};
+
+/// Maximum number of bytes buffered for a TCP client. Older output is discarded.
+const TCP_BUFFER_SIZE: usize = 1024 * 1024;
+/// How long a write to a TCP client may block before the client is disconnected.
+const TCP_WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Forwards the guest's serial output either to stdout, to a TCP client or, line by line, to the
+/// host log. The application's writes to stdout and stderr are part of the serial output.
+///
+/// The output can additionally be collected line by line.
+///
+/// In unbuffered mode, each write is forwarded right away: stdout is flushed after every write
+/// and logged output isn't combined into lines. Collected lines are unaffected by the mode.
+#[derive(Debug)]
+pub(crate) struct SerialOutput {
+	log_prefix: Option<String>,
+	/// Whether complete lines are collected.
+	collect_lines: bool,
+	unbuffered: AtomicBool,
+	/// Receives the output instead of stdout. Never set together with `log_prefix`.
+	tcp: Option<TcpOutput>,
+	/// The current, incomplete line, if the output is logged or collected.
+	line: Mutex<Vec<u8>>,
+	/// The collected lines.
+	lines: Mutex<Vec<String>>,
+}
+
+impl SerialOutput {
+	/// Creates a serial output that writes to stdout or, if a `log_prefix` is given, logs each
+	/// line prefixed with it. If `collect_lines` is set, the lines are also collected.
+	pub(crate) fn new(log_prefix: Option<String>, collect_lines: bool, unbuffered: bool) -> Self {
+		Self {
+			log_prefix,
+			collect_lines,
+			unbuffered: AtomicBool::new(unbuffered),
+			tcp: None,
+			line: Mutex::new(Vec::new()),
+			lines: Mutex::new(Vec::new()),
+		}
+	}
+
+	/// Streams the output to a client of `tcp` instead of printing it to stdout.
+	pub(crate) fn with_tcp(mut self, tcp: TcpOutput) -> Self {
+		self.tcp = Some(tcp);
+		self
+	}
+
+	/// Switches between buffered and unbuffered mode. Switching the mode ends the current line.
+	pub(crate) fn set_unbuffered(&self, unbuffered: bool) {
+		if self.is_unbuffered() != unbuffered {
+			// Unbuffered output has already been logged, buffered output is logged now.
+			self.flush();
+			self.unbuffered.store(unbuffered, Ordering::Relaxed);
+		}
+	}
+
+	pub(crate) fn is_unbuffered(&self) -> bool {
+		self.unbuffered.load(Ordering::Relaxed)
+	}
+
+	pub(crate) fn write(&self, buf: &[u8]) -> io::Result<()> {
+		let unbuffered = self.is_unbuffered();
+		match &self.log_prefix {
+			None if self.tcp.is_some() => self.tcp.as_ref().unwrap().write(buf),
+			None => {
+				let mut stdout = io::stdout().lock();
+				stdout.write_all(buf)?;
+				if unbuffered {
+					stdout.flush()?;
+				}
+			}
+			Some(prefix) if unbuffered => {
+				for fragment in buf.split(|&byte| byte == b'\n') {
+					let fragment = fragment.strip_suffix(b"\r").unwrap_or(fragment);
+					if !fragment.is_empty() {
+						info!(target: "guest", "{prefix}{}", String::from_utf8_lossy(fragment));
+					}
+				}
+			}
+			Some(_) => {}
+		}
+		// Without logging or collecting, there is no need to split the output into lines.
+		if !self.collect_lines && (self.log_prefix.is_none() || unbuffered) {
+			return Ok(());
+		}
+
+		let mut line = self.line.lock().unwrap();
+		for &byte in buf {
+			if byte == b'\n' {
+				self.end_line(&mem::take(&mut *line));
+			} else {
+				line.push(byte);
+			}
+		}
+		Ok(())
+	}
+
+	/// Flushes stdout or the TCP client and logs and collects the last line, even if it is
+	/// incomplete.
+	pub(crate) fn flush(&self) {
+		if let (None, Some(tcp)) = (&self.log_prefix, &self.tcp) {
+			tcp.flush();
+		} else if self.log_prefix.is_none() {
+			if let Err(err) = io::stdout().flush() {
+				warn!("Unable to flush the guest's output: {err}");
+			}
+		}
+		let line = mem::take(&mut *self.line.lock().unwrap());
+		if !line.is_empty() {
+			self.end_line(&line);
+		}
+	}
+
+	/// Returns the lines collected so far.
+	pub(crate) fn take_lines(&self) -> Vec<String> {
+		mem::take(&mut *self.lines.lock().unwrap())
+	}
+
+	fn end_line(&self, line: &[u8]) {
+		let line = line.strip_suffix(b"\r").unwrap_or(line);
+		// Lines end at a newline, so they can't split a UTF-8 sequence.
+		let line = String::from_utf8_lossy(line);
+		if let Some(prefix) = self.log_prefix.as_ref().filter(|_| !self.is_unbuffered()) {
+			info!(target: "guest", "{prefix}{line}");
+		}
+		if self.collect_lines {
+			self.lines.lock().unwrap().push(line.into_owned());
+		}
+	}
+}
+
+/// Streams output to a TCP client.
+///
+/// Clients are accepted without blocking whenever output is written, one at a time. Until a
+/// client is connected, and after it disconnected, the output is buffered for the next client.
+/// A client that doesn't receive the output within [`TCP_WRITE_TIMEOUT`] is disconnected, so
+/// that it can't stall the vCPUs.
+#[derive(Debug)]
+pub(crate) struct TcpOutput {
+	listener: TcpListener,
+	state: Mutex<TcpState>,
+}
+
+#[derive(Debug, Default)]
+struct TcpState {
+	client: Option<TcpStream>,
+	/// The output that hasn't been sent yet.
+	pending: Vec<u8>,
+}
+
+impl TcpOutput {
+	/// Listens for clients on `addr`.
+	pub(crate) fn bind(addr: SocketAddr) -> io::Result<Self> {
+		let listener = TcpListener::bind(addr)?;
+		listener.set_nonblocking(true)?;
+		Ok(Self {
+			listener,
+			state: Default::default(),
+		})
+	}
+
+	#[cfg(test)]
+	pub(crate) fn local_addr(&self) -> SocketAddr {
+		self.listener.local_addr().unwrap()
+	}
+
+	fn write(&self, buf: &[u8]) {
+		let mut state = self.state.lock().unwrap();
+		state.pending.extend_from_slice(buf);
+		self.send(&mut state);
+		let excess = state.pending.len().saturating_sub(TCP_BUFFER_SIZE);
+		state.pending.drain(..excess);
+	}
+
+	/// Sends the buffered output, if a client is connected.
+	fn flush(&self) {
+		self.send(&mut self.state.lock().unwrap());
+	}
+
+	fn send(&self, state: &mut TcpState) {
+		let TcpState { client, pending } = state;
+		if client.is_none() {
+			match self.listener.accept() {
+				Ok((stream, addr)) => {
+					// Depending on the platform, the stream inherits the listener's non-blocking mode.
+					if let Err(err) = stream
+						.set_nonblocking(false)
+						.and_then(|_| stream.set_write_timeout(Some(TCP_WRITE_TIMEOUT)))
+					{
+						warn!("Unable to set up the serial output client {addr}: {err}");
+						return;
+					}
+					debug!("Streaming the serial output to {addr}");
+					*client = Some(stream);
+				}
+				Err(err) if err.kind() == io::ErrorKind::WouldBlock => return,
+				Err(err) => {
+					warn!("Unable to accept a serial output client: {err}");
+					return;
+				}
+			}
+		}
+
+		let stream = client.as_mut().unwrap();
+		let mut sent = 0;
+		while sent < pending.len() {
+			match stream.write(&pending[sent..]) {
+				Ok(0) => {
+					*client = None;
+					break;
+				}
+				Ok(len) => sent += len,
+				Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+				Err(err) => {
+					debug!("Disconnecting the serial output client: {err}");
+					*client = None;
+					break;
+				}
+			}
+		}
+		pending.drain(..sent);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{io::Read, thread};
+
+	use super::*;
+	use crate::test_utils::{capture_logs, captured_logs};
+
+	#[test]
+	fn test_log_prefix() {
+		capture_logs();
+		let serial = SerialOutput::new(Some("[vm-0] ".to_string()), false, false);
+		serial.write(b"serial-test first\r\nserial-test ").unwrap();
+		serial.write(b"second\nserial-test third").unwrap();
+
+		let lines = || {
+			captured_logs()
+				.into_iter()
+				.filter(|record| record.target == "guest" && record.message.contains("serial-test"))
+				.map(|record| record.message)
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(
+			lines(),
+			["[vm-0] serial-test first", "[vm-0] serial-test second"]
+		);
+
+		serial.flush();
+		let lines = lines();
+		assert_eq!(lines.len(), 3);
+		assert!(lines.iter().all(|line| line.starts_with("[vm-0] ")));
+		assert_eq!(lines[2], "[vm-0] serial-test third");
+	}
+
+	#[test]
+	fn test_unbuffered() {
+		capture_logs();
+		let serial = SerialOutput::new(Some("[unbuffered] ".to_string()), true, false);
+		let lines = || {
+			captured_logs()
+				.into_iter()
+				.filter(|record| record.target == "guest" && record.message.contains("unbuffered"))
+				.map(|record| record.message)
+				.collect::<Vec<_>>()
+		};
+
+		// Buffered output only appears once the line is complete
+		serial.write(b"unbuffered-test a").unwrap();
+		serial.write(b"b").unwrap();
+		assert!(lines().is_empty());
+		serial.write(b"\n").unwrap();
+		assert_eq!(lines(), ["[unbuffered] unbuffered-test ab"]);
+
+		// Unbuffered output appears with every write
+		serial.set_unbuffered(true);
+		serial.write(b"unbuffered-test c").unwrap();
+		assert_eq!(lines().len(), 2);
+		serial.write(b"d\n").unwrap();
+		assert_eq!(
+			lines()[1..],
+			["[unbuffered] unbuffered-test c", "[unbuffered] d"]
+		);
+
+		serial.set_unbuffered(false);
+		serial.flush();
+		assert_eq!(lines().len(), 3);
+		assert_eq!(
+			serial.take_lines(),
+			["unbuffered-test ab", "unbuffered-test cd"]
+		);
+	}
+
+	#[test]
+	fn test_tcp_output() {
+		let tcp = TcpOutput::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+		let addr = tcp.local_addr();
+		let serial = SerialOutput::new(None, true, false).with_tcp(tcp);
+		let read = |client: &mut TcpStream, len: usize| {
+			let mut buf = vec![0; len];
+			client.read_exact(&mut buf).unwrap();
+			String::from_utf8(buf).unwrap()
+		};
+
+		// Output is buffered until a client connects
+		serial.write(b"before\n").unwrap();
+		let mut client = TcpStream::connect(addr).unwrap();
+		client
+			.set_read_timeout(Some(Duration::from_secs(5)))
+			.unwrap();
+		serial.write(b"after\n").unwrap();
+		assert_eq!(read(&mut client, 13), "before\nafter\n");
+
+		// Writing doesn't fail once the client is gone, and the next client receives the output
+		drop(client);
+		let mut client = TcpStream::connect(addr).unwrap();
+		client
+			.set_read_timeout(Some(Duration::from_secs(5)))
+			.unwrap();
+		for _ in 0..3 {
+			serial.write(b"x").unwrap();
+			thread::sleep(Duration::from_millis(10));
+		}
+		serial.flush();
+		assert_eq!(read(&mut client, 1), "x");
+		assert_eq!(serial.take_lines(), ["before", "after", "xxx"]);
+	}
+
+	#[test]
+	fn test_collect_lines() {
+		let serial = SerialOutput::new(Some("[collect] ".to_string()), true, false);
+		serial.write(b"first\r\nsecond\n\n").unwrap();
+		serial.write(b"fourth \xf0\x9f\xa6").unwrap();
+		serial.write(b"\x80\n").unwrap();
+		serial.flush();
+		assert_eq!(serial.take_lines(), ["first", "second", "", "fourth 🦀"]);
+
+		serial.write(b"partial").unwrap();
+		serial.flush();
+		assert_eq!(serial.take_lines(), ["partial"]);
+		assert!(serial.take_lines().is_empty());
+	}
+}
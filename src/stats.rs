@@ -0,0 +1,452 @@
+//! Statistics of VM runs, e.g., for microbenchmarking.
+
+use std::{
+	collections::HashMap,
+	fmt, mem,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Mutex,
+	},
+	time::{Duration, Instant},
+};
+
+use uhyve_interface::{GuestVirtAddr, HypercallAddress};
+
+/// Statistics of a single VM run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VmStats {
+	/// Time from the start of the guest until its exit.
+	pub duration: Duration,
+	/// Number of hypercalls the guest issued per hypercall.
+	pub hypercalls: HashMap<HypercallAddress, u64>,
+	/// Highest end of the heap the guest reported via the `Brk` hypercall.
+	pub peak_brk: Option<GuestVirtAddr>,
+	/// Performance counters of the guest, summed over all vCPUs, if they were enabled and are
+	/// supported by the host.
+	pub perf_counters: Option<PerfCounters>,
+	/// Effectiveness of the transparent huge pages and same page merging of the guest memory at
+	/// the end of the run, if either was requested and the host reports it.
+	pub memory_advice: Option<MemoryAdviceStatus>,
+	/// Allocation failures the guest reported via the `OutOfMemory` hypercall, if any.
+	pub out_of_memory: Option<OutOfMemoryStats>,
+	/// Last progress in percent the guest reported via the `Heartbeat` hypercall, if any.
+	pub progress: Option<u8>,
+	/// Drift of the guest's clock the guest measured via the `ClockCompare` hypercall, if any.
+	pub clock_drift: Option<ClockDriftStats>,
+}
+
+/// Drift of the guest's monotonic clock against the host's.
+///
+/// The drift is measured relative to the first comparison and is positive if the guest's clock
+/// runs slow.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClockDriftStats {
+	/// Number of comparisons.
+	pub samples: u64,
+	/// Drift in nanoseconds at the last comparison.
+	pub last_drift_ns: i64,
+	/// Largest absolute drift in nanoseconds over all comparisons.
+	pub max_drift_ns: u64,
+}
+
+/// Allocation failures of the guest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OutOfMemoryStats {
+	/// Number of failed allocations.
+	pub failures: u64,
+	/// Largest size in bytes the guest failed to allocate.
+	pub peak_size: u64,
+}
+
+/// Hardware performance counters of the guest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PerfCounters {
+	/// Number of retired instructions.
+	pub instructions: u64,
+	/// Number of unhalted core cycles.
+	pub cycles: u64,
+}
+
+/// Effectiveness of the memory advice for the guest memory, as reported by the host kernel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryAdviceStatus {
+	/// Whether the kernel may back the guest memory with transparent huge pages.
+	pub thp_eligible: bool,
+	/// Bytes of guest memory backed by transparent huge pages.
+	pub thp_bytes: u64,
+	/// Whether the guest memory is registered for same page merging (KSM).
+	pub ksm_mergeable: bool,
+	/// Bytes of guest memory merged with identical pages, if the host kernel reports it.
+	pub ksm_bytes: Option<u64>,
+}
+
+impl MemoryAdviceStatus {
+	/// Returns whether the guest memory is actually backed by transparent huge pages.
+	pub fn thp_active(&self) -> bool {
+		self.thp_bytes > 0
+	}
+
+	/// Returns whether pages of the guest memory were actually merged.
+	pub fn ksm_active(&self) -> bool {
+		self.ksm_bytes.is_some_and(|bytes| bytes > 0)
+	}
+}
+
+/// Collects the [`VmStats`] while the VM is running.
+#[derive(Debug, Default)]
+pub(crate) struct StatsCollector {
+	start: Mutex<Option<Instant>>,
+	hypercalls: Mutex<HashMap<HypercallAddress, u64>>,
+	peak_brk: AtomicU64,
+	perf_counters: Mutex<Option<PerfCounters>>,
+	out_of_memory: Mutex<Option<OutOfMemoryStats>>,
+	progress: Mutex<Option<u8>>,
+	/// The guest's and host's clocks at the first comparison with the drift since then.
+	clock: Mutex<Option<(u64, u64, ClockDriftStats)>>,
+}
+
+impl StatsCollector {
+	/// Marks the start of the guest.
+	pub(crate) fn start(&self) {
+		*self.start.lock().unwrap() = Some(Instant::now());
+	}
+
+	/// Counts a hypercall issued on `port`.
+	pub(crate) fn count_hypercall(&self, port: u16) {
+		if let Ok(hypercall) = HypercallAddress::try_from(port) {
+			*self
+				.hypercalls
+				.lock()
+				.unwrap()
+				.entry(hypercall)
+				.or_default() += 1;
+		}
+	}
+
+	/// Records the end of the guest's heap, keeping the highest one.
+	pub(crate) fn record_brk(&self, brk: GuestVirtAddr) {
+		self.peak_brk.fetch_max(brk.as_u64(), Ordering::Relaxed);
+	}
+
+	/// Adds the performance counters of a vCPU to the ones of the other vCPUs.
+	pub(crate) fn add_perf_counters(&self, counters: PerfCounters) {
+		let mut total = self.perf_counters.lock().unwrap();
+		let total = total.get_or_insert_default();
+		total.instructions += counters.instructions;
+		total.cycles += counters.cycles;
+	}
+
+	/// Records an allocation of `size` bytes the guest failed to make.
+	pub(crate) fn record_out_of_memory(&self, size: u64) {
+		let mut stats = self.out_of_memory.lock().unwrap();
+		let stats = stats.get_or_insert_default();
+		stats.failures += 1;
+		stats.peak_size = stats.peak_size.max(size);
+	}
+
+	/// Records the progress in percent the guest reported, replacing the previous one.
+	pub(crate) fn record_progress(&self, progress: u8) {
+		*self.progress.lock().unwrap() = Some(progress);
+	}
+
+	/// Compares the guest's clock `guest_ns` with the host's and records the drift.
+	///
+	/// Returns the host's clock in nanoseconds since the start of the guest and the drift in
+	/// nanoseconds since the first comparison.
+	pub(crate) fn record_clock(&self, guest_ns: u64) -> (u64, i64) {
+		let host_ns = self.elapsed().as_nanos() as u64;
+		let mut clock = self.clock.lock().unwrap();
+		let (guest_start, host_start, stats) =
+			clock.get_or_insert((guest_ns, host_ns, Default::default()));
+		let drift =
+			host_ns.wrapping_sub(*host_start) as i64 - guest_ns.wrapping_sub(*guest_start) as i64;
+		stats.samples += 1;
+		stats.last_drift_ns = drift;
+		stats.max_drift_ns = stats.max_drift_ns.max(drift.unsigned_abs());
+		(host_ns, drift)
+	}
+
+	/// Returns the time since the start of the guest.
+	pub(crate) fn elapsed(&self) -> Duration {
+		self.start
+			.lock()
+			.unwrap()
+			.map_or(Duration::ZERO, |start| start.elapsed())
+	}
+
+	/// Returns the statistics collected since the start of the guest.
+	pub(crate) fn finish(&self) -> VmStats {
+		VmStats {
+			duration: self.elapsed(),
+			hypercalls: mem::take(&mut self.hypercalls.lock().unwrap()),
+			peak_brk: match self.peak_brk.swap(0, Ordering::Relaxed) {
+				0 => None,
+				brk => Some(GuestVirtAddr::new(brk)),
+			},
+			perf_counters: self.perf_counters.lock().unwrap().take(),
+			memory_advice: None,
+			out_of_memory: self.out_of_memory.lock().unwrap().take(),
+			progress: self.progress.lock().unwrap().take(),
+			clock_drift: self.clock.lock().unwrap().take().map(|(_, _, stats)| stats),
+		}
+	}
+}
+
+/// Minimum, maximum and mean of a value over several runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Summary<T> {
+	pub min: T,
+	pub max: T,
+	/// The mean, rounded down.
+	pub mean: T,
+}
+
+impl Summary<u64> {
+	fn new(values: impl ExactSizeIterator<Item = u64> + Clone) -> Self {
+		let len = values.len() as u64;
+		Self {
+			min: values.clone().min().unwrap_or_default(),
+			max: values.clone().max().unwrap_or_default(),
+			mean: values.sum::<u64>().checked_div(len).unwrap_or_default(),
+		}
+	}
+}
+
+impl Summary<Duration> {
+	fn new(values: impl ExactSizeIterator<Item = Duration> + Clone) -> Self {
+		let len = values.len() as u32;
+		Self {
+			min: values.clone().min().unwrap_or_default(),
+			max: values.clone().max().unwrap_or_default(),
+			mean: values
+				.sum::<Duration>()
+				.checked_div(len)
+				.unwrap_or_default(),
+		}
+	}
+}
+
+/// Statistics aggregated over several VM runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AggregatedStats {
+	/// Number of runs.
+	pub runs: usize,
+	pub duration: Summary<Duration>,
+	/// Hypercall counts per hypercall. Runs that did not issue a hypercall count as zero.
+	pub hypercalls: HashMap<HypercallAddress, Summary<u64>>,
+	/// Retired instructions of the runs with performance counters, if there are any.
+	pub instructions: Option<Summary<u64>>,
+	/// Unhalted core cycles of the runs with performance counters, if there are any.
+	pub cycles: Option<Summary<u64>>,
+}
+
+impl AggregatedStats {
+	pub fn new(stats: &[VmStats]) -> Self {
+		let hypercalls = stats
+			.iter()
+			.flat_map(|stats| stats.hypercalls.keys())
+			.map(|&hypercall| {
+				let counts = stats
+					.iter()
+					.map(|stats| stats.hypercalls.get(&hypercall).copied().unwrap_or(0));
+				(hypercall, Summary::<u64>::new(counts))
+			})
+			.collect();
+		let perf_counters = stats
+			.iter()
+			.filter_map(|stats| stats.perf_counters)
+			.collect::<Vec<_>>();
+		let perf_summary = |counter: fn(&PerfCounters) -> u64| {
+			(!perf_counters.is_empty())
+				.then(|| Summary::<u64>::new(perf_counters.iter().map(counter)))
+		};
+		Self {
+			runs: stats.len(),
+			duration: Summary::<Duration>::new(stats.iter().map(|stats| stats.duration)),
+			hypercalls,
+			instructions: perf_summary(|counters| counters.instructions),
+			cycles: perf_summary(|counters| counters.cycles),
+		}
+	}
+}
+
+impl fmt::Display for AggregatedStats {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "runs: {}", self.runs)?;
+		let Summary { min, max, mean } = self.duration;
+		writeln!(f, "duration: min {min:?}, max {max:?}, mean {mean:?}")?;
+		let mut hypercalls = self.hypercalls.iter().collect::<Vec<_>>();
+		hypercalls.sort_by_key(|(&hypercall, _)| hypercall as u16);
+		for (hypercall, Summary { min, max, mean }) in hypercalls {
+			writeln!(f, "{hypercall:?}: min {min}, max {max}, mean {mean}")?;
+		}
+		for (name, summary) in [("instructions", self.instructions), ("cycles", self.cycles)] {
+			if let Some(Summary { min, max, mean }) = summary {
+				writeln!(f, "{name}: min {min}, max {max}, mean {mean}")?;
+			}
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_out_of_memory() {
+		let collector = StatsCollector::default();
+		assert_eq!(collector.finish().out_of_memory, None);
+
+		collector.record_out_of_memory(0x1000);
+		collector.record_out_of_memory(1 << 40);
+		collector.record_out_of_memory(0x2000);
+		assert_eq!(
+			collector.finish().out_of_memory,
+			Some(OutOfMemoryStats {
+				failures: 3,
+				peak_size: 1 << 40,
+			})
+		);
+		assert_eq!(collector.finish().out_of_memory, None);
+	}
+
+	#[test]
+	fn test_progress() {
+		let collector = StatsCollector::default();
+		assert_eq!(collector.finish().progress, None);
+
+		collector.record_progress(20);
+		collector.record_progress(60);
+		assert_eq!(collector.finish().progress, Some(60));
+		assert_eq!(collector.finish().progress, None);
+	}
+
+	#[test]
+	fn test_clock_drift() {
+		let collector = StatsCollector::default();
+		collector.start();
+		let (first, drift) = collector.record_clock(1_000_000);
+		assert_eq!(drift, 0);
+
+		std::thread::sleep(Duration::from_millis(20));
+		// The guest's clock claims that only 10 ms passed
+		let (second, drift) = collector.record_clock(11_000_000);
+		let elapsed = second - first;
+		assert!(elapsed >= 20_000_000);
+		assert_eq!(drift, elapsed as i64 - 10_000_000);
+
+		assert_eq!(
+			collector.finish().clock_drift,
+			Some(ClockDriftStats {
+				samples: 2,
+				last_drift_ns: drift,
+				max_drift_ns: drift as u64,
+			})
+		);
+		assert_eq!(collector.finish().clock_drift, None);
+	}
+
+	#[test]
+	fn test_peak_brk() {
+		let collector = StatsCollector::default();
+		assert_eq!(collector.finish().peak_brk, None);
+
+		collector.record_brk(GuestVirtAddr::new(0x20_0000));
+		collector.record_brk(GuestVirtAddr::new(0x80_0000));
+		collector.record_brk(GuestVirtAddr::new(0x40_0000));
+		assert_eq!(
+			collector.finish().peak_brk,
+			Some(GuestVirtAddr::new(0x80_0000))
+		);
+		assert_eq!(collector.finish().peak_brk, None);
+	}
+
+	#[test]
+	fn test_aggregated_stats() {
+		let stats = [
+			VmStats {
+				duration: Duration::from_millis(10),
+				hypercalls: HashMap::from([
+					(HypercallAddress::FileWrite, 4),
+					(HypercallAddress::Exit, 1),
+				]),
+				peak_brk: None,
+				perf_counters: Some(PerfCounters {
+					instructions: 1000,
+					cycles: 3000,
+				}),
+				memory_advice: None,
+				out_of_memory: None,
+				progress: None,
+				clock_drift: None,
+			},
+			VmStats {
+				duration: Duration::from_millis(30),
+				hypercalls: HashMap::from([(HypercallAddress::Exit, 1)]),
+				peak_brk: None,
+				perf_counters: None,
+				memory_advice: None,
+				out_of_memory: None,
+				progress: None,
+				clock_drift: None,
+			},
+		];
+		let aggregated = AggregatedStats::new(&stats);
+		assert_eq!(aggregated.runs, 2);
+		assert_eq!(
+			aggregated.duration,
+			Summary {
+				min: Duration::from_millis(10),
+				max: Duration::from_millis(30),
+				mean: Duration::from_millis(20),
+			}
+		);
+		assert_eq!(
+			aggregated.hypercalls[&HypercallAddress::FileWrite],
+			Summary {
+				min: 0,
+				max: 4,
+				mean: 2
+			}
+		);
+		assert_eq!(
+			aggregated.hypercalls[&HypercallAddress::Exit],
+			Summary {
+				min: 1,
+				max: 1,
+				mean: 1
+			}
+		);
+		assert_eq!(
+			aggregated.instructions,
+			Some(Summary {
+				min: 1000,
+				max: 1000,
+				mean: 1000
+			})
+		);
+		assert_eq!(AggregatedStats::new(&stats[1..]).cycles, None);
+	}
+
+	#[test]
+	fn test_perf_counters() {
+		let collector = StatsCollector::default();
+		assert_eq!(collector.finish().perf_counters, None);
+
+		for _ in 0..2 {
+			collector.add_perf_counters(PerfCounters {
+				instructions: 10,
+				cycles: 20,
+			});
+		}
+		assert_eq!(
+			collector.finish().perf_counters,
+			Some(PerfCounters {
+				instructions: 20,
+				cycles: 40
+			})
+		);
+		assert_eq!(collector.finish().perf_counters, None);
+	}
+}
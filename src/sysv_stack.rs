@@ -0,0 +1,106 @@
+//! The initial process stack of the System V ABI, for kernels that don't query their arguments
+//! and environment via hypercalls.
+//!
+//! From the stack pointer upwards, the stack contains `argc`, the null-terminated `argv` and
+//! `envp` pointer arrays and the auxiliary vector, followed by the strings they point to.
+
+use std::{ffi::OsStr, mem::size_of, os::unix::ffi::OsStrExt};
+
+/// End of the auxiliary vector.
+const AT_NULL: u64 = 0;
+/// Page size of the system.
+const AT_PAGESZ: u64 = 6;
+
+/// Writes the initial stack for `args` and `env` into `stack`, which starts at the guest
+/// address `base`.
+///
+/// Returns the stack pointer, which points to `argc` and is 16-byte aligned, or `None` if the
+/// stack is too small.
+pub fn write_initial_stack<'a>(
+	stack: &mut [u8],
+	base: u64,
+	args: impl IntoIterator<Item = &'a OsStr>,
+	env: impl IntoIterator<Item = (&'a OsStr, &'a OsStr)>,
+) -> Option<u64> {
+	let mut top = stack.len();
+	let mut push_str = |parts: &[&[u8]]| {
+		let len = parts.iter().map(|part| part.len()).sum::<usize>() + 1;
+		top = top.checked_sub(len)?;
+		let mut offset = top;
+		for part in parts {
+			stack[offset..][..part.len()].copy_from_slice(part);
+			offset += part.len();
+		}
+		stack[offset] = 0;
+		Some(base + top as u64)
+	};
+
+	let argv = args
+		.into_iter()
+		.map(|arg| push_str(&[arg.as_bytes()]))
+		.collect::<Option<Vec<_>>>()?;
+	let envp = env
+		.into_iter()
+		.map(|(key, value)| push_str(&[key.as_bytes(), b"=", value.as_bytes()]))
+		.collect::<Option<Vec<_>>>()?;
+
+	let words = [argv.len() as u64]
+		.into_iter()
+		.chain(argv)
+		.chain([0])
+		.chain(envp)
+		.chain([0, AT_PAGESZ, 4096, AT_NULL, 0])
+		.collect::<Vec<_>>();
+	let start = top.checked_sub(words.len() * size_of::<u64>())? & !0xf;
+	for (i, word) in words.iter().enumerate() {
+		stack[start + i * size_of::<u64>()..][..size_of::<u64>()]
+			.copy_from_slice(&word.to_ne_bytes());
+	}
+	Some(base + start as u64)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::ffi::CStr;
+
+	use super::*;
+
+	#[test]
+	fn test_initial_stack() {
+		const BASE: u64 = 0x10_0000;
+		let mut stack = vec![0xffu8; 512];
+		let rsp = write_initial_stack(
+			&mut stack,
+			BASE,
+			["/kernel", "hello"].map(OsStr::new),
+			[(OsStr::new("KEY"), OsStr::new("value"))],
+		)
+		.unwrap();
+		assert_eq!(rsp % 16, 0);
+
+		let word = |addr: u64| {
+			let offset = (addr - BASE) as usize;
+			u64::from_ne_bytes(stack[offset..][..8].try_into().unwrap())
+		};
+		let str_at = |addr: u64| {
+			CStr::from_bytes_until_nul(&stack[(addr - BASE) as usize..])
+				.unwrap()
+				.to_bytes()
+		};
+		assert_eq!(word(rsp), 2);
+		assert_eq!(str_at(word(rsp + 8)), b"/kernel");
+		assert_eq!(str_at(word(rsp + 16)), b"hello");
+		assert_eq!(word(rsp + 24), 0);
+		assert_eq!(str_at(word(rsp + 32)), b"KEY=value");
+		assert_eq!(word(rsp + 40), 0);
+		assert_eq!(
+			[rsp + 48, rsp + 56, rsp + 64, rsp + 72].map(word),
+			[AT_PAGESZ, 4096, AT_NULL, 0]
+		);
+
+		assert_eq!(
+			write_initial_stack(&mut [0; 64], BASE, [OsStr::new("/kernel")], []),
+			None
+		);
+	}
+}
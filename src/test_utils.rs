@@ -0,0 +1,54 @@
+//! Helpers for unit tests.
+
+use std::sync::{Mutex, Once};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// A log record as seen by the [`CapturingLogger`].
+#[derive(Debug, Clone)]
+pub struct CapturedRecord {
+	pub level: Level,
+	pub target: String,
+	pub message: String,
+}
+
+/// A logger that keeps all records in memory.
+struct CapturingLogger {
+	records: Mutex<Vec<CapturedRecord>>,
+}
+
+impl Log for CapturingLogger {
+	fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+		true
+	}
+
+	fn log(&self, record: &Record<'_>) {
+		self.records.lock().unwrap().push(CapturedRecord {
+			level: record.level(),
+			target: record.target().to_owned(),
+			message: record.args().to_string(),
+		});
+	}
+
+	fn flush(&self) {}
+}
+
+static LOGGER: CapturingLogger = CapturingLogger {
+	records: Mutex::new(Vec::new()),
+};
+
+/// Installs the capturing logger, if that didn't happen yet.
+pub fn capture_logs() {
+	static INIT: Once = Once::new();
+	INIT.call_once(|| {
+		log::set_logger(&LOGGER).unwrap();
+		log::set_max_level(LevelFilter::Trace);
+	});
+}
+
+/// Returns all log records emitted since [`capture_logs`] was first called.
+///
+/// As the logger is global, the records of concurrently running tests are included as well.
+pub fn captured_logs() -> Vec<CapturedRecord> {
+	LOGGER.records.lock().unwrap().clone()
+}
@@ -18,6 +18,9 @@ pub enum VcpuStopReason {
 
 /// Functionality a virtual CPU backend must provide to be used by uhyve
 pub trait VirtualCPU: Sized {
+	/// The name of the virtualization backend, e.g., `kvm`.
+	const NAME: &'static str;
+
 	/// Create a new CPU object
 	fn new(id: u32, vm: Arc<UhyveVm<Self>>) -> HypervisorResult<Self>;
 
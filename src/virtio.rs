@@ -57,6 +57,7 @@ pub struct VirtioNetPciDevice {
 	virt_queues: Vec<Virtqueue>,
 	iface: Option<Mutex<Iface>>,
 	mac_addr: [u8; 6],
+	fixed_mac_addr: bool,
 }
 
 impl fmt::Debug for VirtioNetPciDevice {
@@ -117,6 +118,16 @@ impl VirtioNetPciDevice {
 			virt_queues,
 			iface: None,
 			mac_addr: [0; 6],
+			fixed_mac_addr: false,
+		}
+	}
+
+	/// Creates a device that reports `mac_addr` instead of the MAC address of its TAP device.
+	pub fn with_mac_addr(mac_addr: [u8; 6]) -> VirtioNetPciDevice {
+		VirtioNetPciDevice {
+			mac_addr,
+			fixed_mac_addr: true,
+			..Self::new()
 		}
 	}
 
@@ -192,22 +203,22 @@ impl VirtioNetPciDevice {
 	// This function is reliant on tap devices as the underlying packet sending mechanism
 	// Gets the tap device by name then gets its mac address
 	fn get_mac_addr(&mut self) {
-		match &self.iface {
-			Some(tap) => {
-				let locked_dev = tap.lock().unwrap();
-				match mac_address_by_name(locked_dev.name()) {
-					Ok(Some(ma)) => self.mac_addr = ma.bytes(),
-					Ok(None) => {
-						info!("No MAC address found.");
-						self.registers[STATUS_REGISTER as usize] |= STATUS_DRIVER_NEEDS_RESET;
-					}
-					Err(e) => {
-						info!("{:?}", e);
-						self.registers[STATUS_REGISTER as usize] |= STATUS_DRIVER_NEEDS_RESET;
-					}
+		if self.fixed_mac_addr {
+			return;
+		}
+		if let Some(tap) = &self.iface {
+			let locked_dev = tap.lock().unwrap();
+			match mac_address_by_name(locked_dev.name()) {
+				Ok(Some(ma)) => self.mac_addr = ma.bytes(),
+				Ok(None) => {
+					info!("No MAC address found.");
+					self.registers[STATUS_REGISTER as usize] |= STATUS_DRIVER_NEEDS_RESET;
+				}
+				Err(e) => {
+					info!("{:?}", e);
+					self.registers[STATUS_REGISTER as usize] |= STATUS_DRIVER_NEEDS_RESET;
 				}
 			}
-			None => {}
 		}
 	}
 
@@ -1,31 +1,56 @@
 use std::{
-	ffi::OsString,
+	collections::HashMap,
+	env,
+	ffi::{CString, OsString},
 	fmt, fs, io,
 	marker::PhantomData,
-	num::NonZeroU32,
-	path::PathBuf,
+	mem::MaybeUninit,
+	num::{NonZeroU32, NonZeroU64},
+	ops::{Range, RangeInclusive},
+	path::{Path, PathBuf},
 	ptr,
-	sync::{Arc, Mutex},
-	time::SystemTime,
+	sync::{Arc, Mutex, RwLock},
+	time::{Duration, Instant, SystemTime},
 };
 
+use core_affinity::CoreId;
 use hermit_entry::{
-	boot_info::{BootInfo, HardwareInfo, PlatformInfo, RawBootInfo, SerialPortBase},
+	boot_info::{BootInfo, HardwareInfo, LoadInfo, PlatformInfo, RawBootInfo, SerialPortBase},
 	elf::{KernelObject, LoadedKernel, ParseKernelError},
 };
-use log::{error, warn};
+use log::{warn, LevelFilter};
+use sha2::{Digest, Sha256};
 use thiserror::Error;
+use uhyve_interface::{GuestPhysAddr, GuestVirtAddr};
 
 #[cfg(target_arch = "x86_64")]
-use crate::arch::x86_64::{
-	detect_freq_from_cpuid, detect_freq_from_cpuid_hypervisor_info, get_cpu_frequency_from_os,
-};
+use crate::arch::x86_64::detect_cpu_freq as detect_x86_64_cpu_freq;
 #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
 use crate::linux::x86_64::kvm_cpu::initialize_kvm;
 use crate::{
-	arch, consts::*, mem::MmapMemory, os::HypervisorError, params::Params, vcpu::VirtualCPU,
+	arch,
+	archive::archive_files,
+	audit::AuditLog,
+	barrier::GuestBarrier,
+	consts::*,
+	fdmap::FdMap,
+	fdt::{self, DeviceTree},
+	hypercall,
+	mem::{MemoryError, MmapMemory},
+	os::{errno_error, HypervisorError},
+	paging::PagetableError,
+	params::{EventHook, Params, Resource},
+	procfs::ProcInfo,
+	rate_limiter::RateLimiter,
+	serial::{SerialOutput, TcpOutput},
+	stats::{AggregatedStats, MemoryAdviceStatus, StatsCollector, VmStats},
+	sysv_stack,
+	vcpu::VirtualCPU,
+	virt_to_phys,
 	virtio::*,
 };
+#[cfg(target_os = "linux")]
+use crate::{mem::HugePageError, params::CpuFeatureMask};
 
 pub type HypervisorResult<T> = Result<T, HypervisorError>;
 
@@ -35,26 +60,328 @@ pub enum LoadKernelError {
 	Io(#[from] io::Error),
 	#[error("{0}")]
 	ParseKernelError(ParseKernelError),
+	#[error("kernel SHA-256 digest {found} does not match the expected digest {expected}")]
+	IntegrityMismatch { expected: String, found: String },
 	#[error("guest memory size is not large enough")]
 	InsufficientMemory,
+	#[error("boot stack of {stack_size:#x} bytes does not fit between the boot structures and the kernel at {kernel_start:#x}")]
+	InsufficientStackSpace { stack_size: u64, kernel_start: u64 },
+	#[error("arguments and environment don't fit on the boot stack of {stack_size:#x} bytes")]
+	ArgumentsExceedStack { stack_size: u64 },
+	#[error("raw mapping of {} at {addr:#x} exceeds the guest memory", .path.display())]
+	MappingOutOfRange { path: PathBuf, addr: GuestPhysAddr },
+	#[error("raw mapping of {} at {addr:#x} overlaps {region}", .path.display())]
+	MappingOverlap {
+		path: PathBuf,
+		addr: GuestPhysAddr,
+		region: String,
+	},
+}
+
+impl LoadKernelError {
+	/// Returns the error number that describes the error best.
+	pub fn errno(&self) -> i32 {
+		match self {
+			Self::Io(err) => err.raw_os_error().unwrap_or(libc::EIO),
+			Self::ParseKernelError(_) => libc::ENOEXEC,
+			Self::IntegrityMismatch { .. } => libc::EBADMSG,
+			Self::InsufficientMemory | Self::InsufficientStackSpace { .. } => libc::ENOMEM,
+			Self::ArgumentsExceedStack { .. } => libc::E2BIG,
+			Self::MappingOutOfRange { .. } | Self::MappingOverlap { .. } => libc::EINVAL,
+		}
+	}
 }
 
 pub type LoadKernelResult<T> = Result<T, LoadKernelError>;
 
+/// An error accessing the guest memory by guest virtual addresses.
+#[derive(Error, Debug)]
+pub enum GuestMemoryError {
+	#[error(transparent)]
+	Pagetable(#[from] PagetableError),
+	#[error(transparent)]
+	Memory(#[from] MemoryError),
+}
+
+/// Translates the guest virtual range `addr..addr + len` page by page, as it may be backed by
+/// discontiguous host memory.
+///
+/// # Safety
+///
+/// The returned slices alias the guest memory and are only valid as long as the guest is halted.
+#[allow(clippy::mut_from_ref)]
+pub(crate) unsafe fn guest_virt_slices(
+	mem: &MmapMemory,
+	addr: GuestVirtAddr,
+	len: usize,
+) -> Result<Vec<&mut [u8]>, GuestMemoryError> {
+	let mut slices = Vec::new();
+	let mut offset = 0;
+	while offset < len {
+		let page_addr = addr + offset as u64;
+		let page_offset = (page_addr.as_u64() % PAGE_SIZE as u64) as usize;
+		let chunk = (PAGE_SIZE - page_offset).min(len - offset);
+		let phys = virt_to_phys(page_addr, mem, BOOT_PML4)?;
+		slices.push(unsafe { mem.slice_at_mut(phys, chunk) }?);
+		offset += chunk;
+	}
+	Ok(slices)
+}
+
+/// The result of running a VM.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VmResult {
+	/// The exit code of the guest.
+	pub code: i32,
+	/// The application name the guest reported via the `SetAppName` hypercall.
+	pub app_name: Option<String>,
+	/// The message of the panic the guest reported via the `Panic` hypercall.
+	pub panic_message: Option<String>,
+	/// Whether the guest panicked, i.e., reported a panic via the `Panic` hypercall or exited
+	/// with [`Params::panic_exit_code`].
+	pub panicked: bool,
+	/// Whether the VM was cancelled before the guest exited.
+	pub cancelled: bool,
+	/// Whether the VM was stopped because it exceeded [`Params::timeout`].
+	pub timed_out: bool,
+	/// Statistics of the run.
+	pub stats: VmStats,
+	/// The platform information that was handed to the guest, if it was booted.
+	pub boot_metadata: Option<BootMetadata>,
+	/// The test results the guest reported via the `TestResult` hypercall, in order.
+	pub test_reports: Vec<TestReport>,
+	/// The state of the vCPU that stopped abnormally, e.g., because of a triple fault.
+	pub fault: Option<GuestFault>,
+	/// The guest's serial output, including the application's stdout and stderr, split into lines,
+	/// without the line endings. Only collected if [`Params::structured_output`] is set.
+	pub output_lines: Vec<String>,
+	/// The values the guest reported via the `ReportMetric` hypercall by metric name, in order.
+	pub metrics: HashMap<String, Vec<f64>>,
+}
+
+impl VmResult {
+	/// Returns the number of passed tests over all [`VmResult::test_reports`].
+	pub fn tests_passed(&self) -> u64 {
+		self.test_reports
+			.iter()
+			.map(|report| u64::from(report.passed))
+			.sum()
+	}
+
+	/// Returns the number of failed tests over all [`VmResult::test_reports`].
+	pub fn tests_failed(&self) -> u64 {
+		self.test_reports
+			.iter()
+			.map(|report| u64::from(report.failed))
+			.sum()
+	}
+}
+
+/// The results of a test suite the guest reported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestReport {
+	/// The name of the test suite, if the guest provided a valid one.
+	pub name: Option<String>,
+	pub passed: u32,
+	pub failed: u32,
+}
+
+/// The state of a vCPU that stopped abnormally.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GuestFault {
+	pub cpu_id: u32,
+	/// Why the vCPU stopped, e.g., `Shutdown` for a triple fault.
+	pub reason: String,
+	/// The instruction pointer.
+	pub rip: u64,
+	/// The stack pointer.
+	pub rsp: u64,
+	/// The address of the last page fault.
+	pub cr2: u64,
+	/// The guest memory around the instruction pointer, starting at `code_start`.
+	///
+	/// Empty if the memory is not mapped.
+	pub code: Vec<u8>,
+	pub code_start: u64,
+}
+
+impl GuestFault {
+	/// Number of bytes captured before and after the instruction pointer.
+	pub(crate) const CODE_WINDOW: u64 = 16;
+}
+
+impl fmt::Display for GuestFault {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"CPU {} stopped with {} at rip={:#x} rsp={:#x} cr2={:#x}",
+			self.cpu_id, self.reason, self.rip, self.rsp, self.cr2
+		)?;
+		if !self.code.is_empty() {
+			write!(f, "\ncode at {:#x}:", self.code_start)?;
+			for (addr, byte) in (self.code_start..).zip(&self.code) {
+				if addr == self.rip {
+					write!(f, " [{byte:02x}]")?;
+				} else {
+					write!(f, " {byte:02x}")?;
+				}
+			}
+		}
+		Ok(())
+	}
+}
+
+/// A kernel that was parsed and loaded once and can be copied into the memory of several VMs.
+#[derive(Debug)]
+pub struct KernelImage {
+	start_address: u64,
+	entry_point: u64,
+	load_info: LoadInfo,
+	data: Vec<u8>,
+}
+
+impl KernelImage {
+	/// Loads the kernel at `path`, if its SHA-256 digest matches `expected_sha256`.
+	pub fn load(path: &Path, expected_sha256: Option<&str>) -> LoadKernelResult<Self> {
+		let elf = fs::read(path)?;
+		if let Some(expected) = expected_sha256 {
+			let found = Sha256::digest(&elf)
+				.iter()
+				.map(|byte| format!("{byte:02x}"))
+				.collect::<String>();
+			if !found.eq_ignore_ascii_case(expected.trim()) {
+				return Err(LoadKernelError::IntegrityMismatch {
+					expected: expected.to_string(),
+					found,
+				});
+			}
+		}
+		let object = KernelObject::parse(&elf).map_err(LoadKernelError::ParseKernelError)?;
+
+		// TODO: should be a random start address, if we have a relocatable executable
+		let start_address = object.start_addr().unwrap_or(0x400000);
+		let mut data = vec![0; object.mem_size()];
+		let LoadedKernel {
+			load_info,
+			entry_point,
+		} = object.load_kernel(
+			// Safety: Initialized bytes are valid `MaybeUninit`s and the kernel only writes bytes
+			unsafe { &mut *(data.as_mut_slice() as *mut [u8] as *mut [MaybeUninit<u8>]) },
+			start_address,
+		);
+		Ok(Self {
+			start_address,
+			entry_point,
+			load_info,
+			data,
+		})
+	}
+}
+
+/// The platform information that was handed to the guest at boot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BootMetadata {
+	/// The CPU frequency in kHz, if it could be detected.
+	pub cpu_freq: Option<NonZeroU32>,
+	/// The number of CPUs of the VM.
+	pub num_cpus: NonZeroU64,
+	/// Whether the guest has PCI support.
+	pub has_pci: bool,
+	/// The boot time reported to the guest.
+	pub boot_time: SystemTime,
+}
+
+impl BootMetadata {
+	/// Creates the metadata for a VM with `num_cpus` CPUs booting at `boot_time`.
+	///
+	/// `cpu_freq_mhz` is the detected CPU frequency, `0` if the detection failed.
+	fn new(num_cpus: u32, cpu_freq_mhz: u32, boot_time: SystemTime) -> Self {
+		Self {
+			cpu_freq: NonZeroU32::new(cpu_freq_mhz * 1000),
+			num_cpus: u64::from(num_cpus).try_into().unwrap(),
+			has_pci: cfg!(target_os = "linux"),
+			boot_time,
+		}
+	}
+}
+
+impl From<BootMetadata> for PlatformInfo {
+	fn from(metadata: BootMetadata) -> Self {
+		PlatformInfo::Uhyve {
+			has_pci: metadata.has_pci,
+			num_cpus: metadata.num_cpus,
+			cpu_freq: metadata.cpu_freq,
+			boot_time: metadata.boot_time.into(),
+		}
+	}
+}
+
+/// Joins the arguments of the kernel and the application into the guest's arguments.
+fn guest_args(mut kernel_args: Vec<OsString>, app_args: Vec<OsString>) -> Vec<OsString> {
+	let has_separator = kernel_args.iter().any(|arg| arg == "--");
+	if has_separator {
+		warn!("Passing application arguments after `--` in `kernel_args` is deprecated, use `app_args` instead");
+	}
+	if !app_args.is_empty() {
+		if !has_separator {
+			kernel_args.push("--".into());
+		}
+		kernel_args.extend(app_args);
+	}
+	kernel_args
+}
+
+/// Returns the base address of an interrupt controller's MMIO region of `size` bytes, which is
+/// `base` if the controller is relocated and `default` otherwise.
+///
+/// Fails with `EINVAL` if a relocated region is not page-aligned or overlaps the guest RAM.
+fn mmio_base(
+	name: &str,
+	base: Option<GuestPhysAddr>,
+	default: u64,
+	size: u64,
+	mem: &MmapMemory,
+) -> HypervisorResult<GuestPhysAddr> {
+	let Some(base) = base else {
+		return Ok(GuestPhysAddr::new(default));
+	};
+	if cfg!(not(target_arch = "x86_64")) {
+		error!("Relocating the {name} is only supported on x86_64");
+		return Err(errno_error(libc::EINVAL));
+	}
+	if !base.as_u64().is_multiple_of(PAGE_SIZE as u64) {
+		error!(
+			"The {name} base address {:#x} must be page-aligned",
+			base.as_u64()
+		);
+		return Err(errno_error(libc::EINVAL));
+	}
+	let Some(end) = base.as_u64().checked_add(size) else {
+		error!(
+			"The {name} base address {:#x} is out of range",
+			base.as_u64()
+		);
+		return Err(errno_error(libc::EINVAL));
+	};
+	let range = base.as_u64()..end;
+	#[cfg(target_os = "linux")]
+	let overlaps_ram = crate::linux::x86_64::kvm_cpu::overlaps_guest_ram(mem, range);
+	#[cfg(not(target_os = "linux"))]
+	let overlaps_ram = range.start < mem.guest_range().end && mem.guest_range().start < range.end;
+	if overlaps_ram {
+		error!("The {name} must not overlap the guest RAM");
+		return Err(errno_error(libc::EINVAL));
+	}
+	Ok(base)
+}
+
 // TODO: move to architecture specific section
 fn detect_cpu_freq() -> u32 {
 	#[cfg(target_arch = "aarch64")]
 	let mhz: u32 = 0;
 	#[cfg(target_arch = "x86_64")]
 	let mhz = {
-		let cpuid = raw_cpuid::CpuId::new();
-		let mhz: u32 = detect_freq_from_cpuid(&cpuid).unwrap_or_else(|_| {
-			debug!("Failed to detect from cpuid");
-			detect_freq_from_cpuid_hypervisor_info(&cpuid).unwrap_or_else(|_| {
-				debug!("Failed to detect from hypervisor_info");
-				get_cpu_frequency_from_os().unwrap_or(0)
-			})
-		});
+		let mhz = detect_x86_64_cpu_freq(&raw_cpuid::CpuId::new()).unwrap_or(0);
 		debug!("detected a cpu frequency of {} Mhz", mhz);
 
 		mhz
@@ -70,42 +397,200 @@ pub type VcpuDefault = crate::linux::x86_64::kvm_cpu::KvmCpu;
 #[cfg(target_os = "macos")]
 pub type VcpuDefault = crate::macos::XhyveCpu;
 
+/// The name of the virtualization backend of [`VcpuDefault`], e.g., `kvm`.
+pub const DEFAULT_BACKEND_NAME: &str = VcpuDefault::NAME;
+
 pub struct UhyveVm<VCpuType: VirtualCPU = VcpuDefault> {
 	/// The starting position of the image in physical memory
 	offset: u64,
 	entry_point: u64,
 	stack_address: u64,
+	/// The initial stack pointer, if it differs from `stack_address`.
+	stack_pointer: Option<u64>,
 	pub mem: Arc<MmapMemory>,
+	/// The KVM VM the vCPUs are created in.
+	#[cfg(target_os = "linux")]
+	pub(crate) vm_fd: kvm_ioctls::VmFd,
 	num_cpus: u32,
 	path: PathBuf,
+	/// The kernel, once it was loaded. A kernel with an expected digest is loaded and verified
+	/// in [`UhyveVm::new`].
+	kernel_image: Option<Arc<KernelImage>>,
 	args: Vec<OsString>,
+	guest_env: Vec<(OsString, OsString)>,
+	/// The host environment variables the guest may read.
+	pub(crate) env_allowlist: Vec<String>,
+	/// The hostname reported to the guest.
+	pub(crate) guest_hostname: CString,
+	pub(crate) priority_bounds: Option<RangeInclusive<i32>>,
+	/// The read-only files served to the guest from memory.
+	virtual_files: Vec<(PathBuf, Vec<u8>)>,
+	/// The guest paths of the mapped host UNIX sockets with their host paths.
+	unix_sockets: Vec<(PathBuf, PathBuf)>,
+	raw_mappings: Vec<(GuestPhysAddr, PathBuf)>,
+	host_root: Option<PathBuf>,
+	/// The working directory of the guest, against which its relative paths are resolved.
+	pub(crate) cwd: RwLock<PathBuf>,
 	boot_info: *const RawBootInfo,
+	boot_metadata: Option<BootMetadata>,
+	/// The flattened device tree handed to the guest, once the kernel is loaded.
+	fdt: Vec<u8>,
+	boot_stack_size: u64,
+	/// Whether the arguments and environment are put onto the boot stack.
+	classic_stack_abi: bool,
 	verbose: bool,
+	/// Whether the guest's open files are synchronized before the result is returned.
+	flush_on_exit: bool,
+	/// Whether the guest is booted with fixed values instead of host-dependent ones.
+	deterministic: bool,
+	/// Maximum wall-clock time the VM may run without a heartbeat of the guest.
+	pub(crate) timeout: Option<Duration>,
+	/// The time of the guest's last heartbeat.
+	last_heartbeat: Mutex<Option<Instant>>,
+	/// The resource limits of the process while the VM runs.
+	pub(crate) rlimits: Vec<(Resource, u64)>,
+	/// The exit code by which the guest signals a panic.
+	panic_exit_code: Option<i32>,
+	/// The highest log level the guest may set.
+	pub(crate) max_guest_log_level: Option<LevelFilter>,
+	/// The log level the guest set for this VM, which filters its log messages.
+	pub(crate) guest_log_level: Mutex<LevelFilter>,
+	/// The CPU features hidden from the guest.
+	#[cfg(target_os = "linux")]
+	pub(crate) cpu_features: Option<CpuFeatureMask>,
+	/// The scale of the guest's TSC frequency.
+	#[cfg(target_os = "linux")]
+	pub(crate) tsc_scale: Option<f64>,
+	/// Whether to count the guest's instructions and cycles.
+	#[cfg(target_os = "linux")]
+	pub(crate) perf_counters: bool,
+	/// Whether the boot pagetables contain read-only regions, which the kernel must not write to.
+	#[cfg(target_os = "linux")]
+	pub(crate) write_protect: bool,
+	/// Where to write a core dump if the guest crashes.
+	#[cfg(target_os = "linux")]
+	pub(crate) core_dump: Option<PathBuf>,
+	apic_base: GuestPhysAddr,
+	ioapic_base: GuestPhysAddr,
 	pub virtio_device: Arc<Mutex<VirtioNetPciDevice>>,
+	/// The application name reported by the guest.
+	pub(crate) app_name: Mutex<Option<String>>,
+	/// The panic message reported by the guest.
+	pub(crate) panic_message: Mutex<Option<String>>,
+	/// The test results reported by the guest.
+	pub(crate) test_reports: Mutex<Vec<TestReport>>,
+	/// The metrics reported by the guest.
+	pub(crate) metrics: Mutex<HashMap<String, Vec<f64>>>,
+	/// The state of the vCPU that stopped abnormally.
+	pub(crate) fault: Mutex<Option<GuestFault>>,
+	/// The size of the guest memory the guest boots with.
+	pub(crate) boot_memory_size: usize,
+	/// The size of the guest memory currently committed via ballooning.
+	pub(crate) committed_memory: Mutex<usize>,
+	/// The host file descriptors opened by the guest.
+	pub(crate) fdmap: FdMap,
+	/// Records the guest's file system operations.
+	pub(crate) audit_log: Option<AuditLog>,
+	/// Throttles the guest's hypercalls, if a rate limit is set.
+	pub(crate) hypercall_limiter: Option<RateLimiter>,
+	/// The barrier the guest waits at until the host is ready.
+	pub(crate) barrier: Arc<GuestBarrier>,
+	pub(crate) stats: StatsCollector,
+	/// Whether transparent huge pages or same page merging were requested for the guest memory.
+	memory_advice: bool,
+	/// Receives the guest's serial output.
+	pub(crate) serial: SerialOutput,
+	/// Receives the events the guest reports.
+	pub(crate) event_hook: Option<EventHook>,
 	#[allow(dead_code)] // gdb is not supported on macos
 	pub(super) gdb_port: Option<u16>,
 	_vcpu_type: PhantomData<VCpuType>,
 }
 impl<VCpuType: VirtualCPU> UhyveVm<VCpuType> {
 	pub fn new(kernel_path: PathBuf, params: Params) -> HypervisorResult<UhyveVm<VCpuType>> {
-		let memory_size = params.memory_size.get();
+		// A kernel with an expected digest is verified before anything is set up for it.
+		let kernel_image = params
+			.kernel_sha256
+			.as_deref()
+			.map(|digest| {
+				KernelImage::load(&kernel_path, Some(digest))
+					.map(Arc::new)
+					.map_err(|err| {
+						error!("Unable to load the kernel: {err}");
+						errno_error(err.errno())
+					})
+			})
+			.transpose()?;
+
+		// The memory up to the maximum size is mapped, but only the boot memory is committed.
+		let boot_memory_size = params.memory_size.get();
+		let memory_size = match params.max_memory_size {
+			Some(max_memory_size) if max_memory_size.get() < boot_memory_size => {
+				error!("The maximum memory size must not be smaller than the memory size");
+				return Err(errno_error(libc::EINVAL));
+			}
+			Some(max_memory_size) => max_memory_size.get(),
+			None => boot_memory_size,
+		};
 
 		#[cfg(target_os = "linux")]
-		let mem = MmapMemory::new(0, memory_size, arch::RAM_START, params.thp, params.ksm);
+		let mut mem = match params.hugepages {
+			Some(page_size) => {
+				match MmapMemory::new_hugetlb(0, memory_size, arch::RAM_START, page_size) {
+					Ok(mem) => mem,
+					Err(err @ HugePageError::Allocation(..)) => {
+						warn!("{err}, falling back to regular pages");
+						MmapMemory::new(0, memory_size, arch::RAM_START, params.thp, params.ksm)
+					}
+					Err(err @ HugePageError::UnalignedSize { .. }) => {
+						error!("{err}");
+						return Err(HypervisorError::new(libc::EINVAL));
+					}
+				}
+			}
+			None => MmapMemory::new(0, memory_size, arch::RAM_START, params.thp, params.ksm),
+		};
 		#[cfg(not(target_os = "linux"))]
 		let mem = MmapMemory::new(0, memory_size, arch::RAM_START, false, false);
+		// The allocation may be larger than requested
+		let memory_size = mem.memory_size;
+		let boot_memory_size = boot_memory_size.next_multiple_of(GUEST_PAGE_SIZE as usize);
+
+		#[cfg(target_os = "linux")]
+		if params.lock_memory {
+			if let Err(err) = mem.lock() {
+				error!("{err}");
+				return Err(HypervisorError::new(err.errno()));
+			}
+		}
 
 		// create virtio interface
 		// TODO: Remove allow once fixed:
 		// https://github.com/rust-lang/rust-clippy/issues/11382
 		#[allow(clippy::arc_with_non_send_sync)]
-		let virtio_device = Arc::new(Mutex::new(VirtioNetPciDevice::new()));
+		let virtio_device = Arc::new(Mutex::new(if params.deterministic {
+			VirtioNetPciDevice::with_mac_addr(DETERMINISTIC_MAC_ADDR)
+		} else {
+			VirtioNetPciDevice::new()
+		}));
+
+		let apic_base = mmio_base("APIC", params.apic_base, APIC_DEFAULT_BASE, 0x1000, &mem)?;
+		let ioapic_base = mmio_base("IOAPIC", params.ioapic_base, IOAPIC_BASE, IOAPIC_SIZE, &mem)?;
 
 		#[cfg(target_os = "linux")]
-		initialize_kvm(&mem, params.pit)?;
+		let vm_fd = initialize_kvm(&mem, params.pit, ioapic_base)?;
 
 		let cpu_count = params.cpu_count.get();
 
+		#[cfg(target_os = "linux")]
+		if let Some(scale) = params
+			.tsc_scale
+			.filter(|scale| !(scale.is_finite() && *scale > 0.0))
+		{
+			error!("The TSC scale must be a positive number (is {scale})");
+			return Err(errno_error(libc::EINVAL));
+		}
+
 		assert!(
 			params.gdb_port.is_none() || cfg!(target_os = "linux"),
 			"gdb is only supported on linux (yet)"
@@ -115,22 +600,212 @@ impl<VCpuType: VirtualCPU> UhyveVm<VCpuType> {
 			"gdbstub is only supported with one CPU"
 		);
 
+		let identity_map_length = match params.identity_map_length {
+			Some(length) if !length.is_multiple_of(0x20_0000) => {
+				error!("The identity map length {length:#x} is not a multiple of 2 MiB");
+				return Err(errno_error(libc::EINVAL));
+			}
+			Some(length) if length > memory_size as u64 || length > MAX_IDENTITY_MAP_LENGTH => {
+				error!(
+					"The identity map length {length:#x} exceeds the guest memory size or 1 GiB"
+				);
+				return Err(errno_error(libc::EINVAL));
+			}
+			Some(length) => length,
+			None => MAX_IDENTITY_MAP_LENGTH,
+		};
+		if let Some(region) = params
+			.read_only_regions
+			.iter()
+			.find(|region| region.start > region.end || region.end.as_u64() > identity_map_length)
+		{
+			error!("The read-only region {region:?} doesn't lie within the identity map");
+			return Err(errno_error(libc::EINVAL));
+		}
+		// The boot pagetables map the memory with 2 MiB pages, so a smaller granularity would
+		// protect more than requested.
+		if let Some(region) = params.read_only_regions.iter().find(|region| {
+			!region.start.as_u64().is_multiple_of(0x20_0000)
+				|| !region.end.as_u64().is_multiple_of(0x20_0000)
+		}) {
+			error!("The read-only region {region:?} is not aligned to 2 MiB");
+			return Err(errno_error(libc::EINVAL));
+		}
+
+		// Resolve the host root once, so that changes of the working directory don't affect it.
+		let host_root = params
+			.host_root
+			.map(|root| {
+				if root.is_absolute() {
+					return Ok(root);
+				}
+				env::current_dir()
+					.map(|cwd| cwd.join(&root))
+					.map_err(|err| {
+						error!("Unable to resolve the host root {}: {err}", root.display());
+						errno_error(err.raw_os_error().unwrap_or(libc::ENOENT))
+					})
+			})
+			.transpose()?;
+
+		// Snapshot the environment, so that the sizes reported to the guest stay valid.
+		let guest_env = match params.guest_env {
+			Some(env) => env
+				.into_iter()
+				.map(|(key, value)| (key.into(), value.into()))
+				.collect(),
+			None => env::vars_os().collect(),
+		};
+
+		let guest_hostname = CString::new(
+			params
+				.guest_hostname
+				.unwrap_or_else(|| DEFAULT_GUEST_HOSTNAME.to_string()),
+		)
+		.map_err(|err| {
+			error!("The guest hostname must not contain a nul byte: {err}");
+			errno_error(libc::EINVAL)
+		})?;
+
+		if let Some(bounds) = params
+			.priority_bounds
+			.as_ref()
+			.filter(|bounds| bounds.is_empty())
+		{
+			error!("The priority bounds {bounds:?} must not be empty");
+			return Err(errno_error(libc::EINVAL));
+		}
+
+		let mut virtual_files = params
+			.virtual_files
+			.into_iter()
+			.map(|(path, content)| (path.into(), content))
+			.collect::<Vec<_>>();
+		for mount in &params.archives {
+			match archive_files(&mount.archive, Path::new(&mount.guest_dir)) {
+				Ok(files) => virtual_files.extend(files),
+				Err(err) => {
+					error!(
+						"Unable to read the archive {}: {err}",
+						mount.archive.display()
+					);
+					return Err(errno_error(libc::EINVAL));
+				}
+			}
+		}
+
+		let fdmap = FdMap::with_write_quota(params.write_quota_bytes)
+			.with_max_write_size(params.max_write_size)
+			.with_max_open_files(params.max_open_files)
+			.with_max_created_files(params.max_created_files)
+			.with_file_creation_disabled(params.disable_file_creation);
+		for &(guest_fd, host_fd) in &params.inherited_fds {
+			if let Err(err) = fdmap.inherit(guest_fd, host_fd) {
+				error!("Unable to pass host fd {host_fd} to the guest as fd {guest_fd}: {err}");
+				return Err(errno_error(err.raw_os_error().unwrap_or(libc::EBADF)));
+			}
+		}
+
+		let audit_log = params
+			.audit_log
+			.as_deref()
+			.map(|path| {
+				AuditLog::open(path).map_err(|err| {
+					error!("Unable to open the audit log {}: {err}", path.display());
+					errno_error(err.raw_os_error().unwrap_or(libc::EIO))
+				})
+			})
+			.transpose()?;
+
+		if params.serial_tcp.is_some() && params.log_serial_prefix.is_some() {
+			error!("The serial output can't be both logged and streamed over TCP");
+			return Err(errno_error(libc::EINVAL));
+		}
+		let mut serial = SerialOutput::new(
+			params.log_serial_prefix,
+			params.structured_output,
+			params.unbuffered_serial,
+		);
+		if let Some(addr) = params.serial_tcp {
+			let tcp = TcpOutput::bind(addr).map_err(|err| {
+				error!("Unable to listen for serial output clients on {addr}: {err}");
+				errno_error(err.raw_os_error().unwrap_or(libc::EIO))
+			})?;
+			serial = serial.with_tcp(tcp);
+		}
+
 		let mut vm = Self {
 			offset: 0,
 			entry_point: 0,
 			stack_address: 0,
+			stack_pointer: None,
 			mem: mem.into(),
+			#[cfg(target_os = "linux")]
+			vm_fd,
 			num_cpus: cpu_count,
 			path: kernel_path,
-			args: params.kernel_args,
+			kernel_image,
+			args: guest_args(params.kernel_args, params.app_args),
+			guest_env,
+			env_allowlist: params.env_allowlist,
+			guest_hostname,
+			priority_bounds: params.priority_bounds,
+			virtual_files,
+			unix_sockets: params
+				.unix_sockets
+				.into_iter()
+				.map(|mapping| (mapping.guest_path.into(), mapping.host_path))
+				.collect(),
+			raw_mappings: params.raw_mappings,
+			host_root,
+			cwd: RwLock::new(PathBuf::new()),
 			boot_info: ptr::null(),
+			boot_metadata: None,
+			fdt: Vec::new(),
+			boot_stack_size: params.boot_stack_size.unwrap_or(KERNEL_STACK_SIZE),
+			classic_stack_abi: params.classic_stack_abi,
 			verbose: params.verbose,
+			flush_on_exit: params.flush_on_exit,
+			deterministic: params.deterministic,
+			timeout: params.timeout,
+			last_heartbeat: Mutex::new(None),
+			rlimits: params.rlimits,
+			panic_exit_code: params.panic_exit_code,
+			max_guest_log_level: params.max_guest_log_level,
+			guest_log_level: Mutex::new(LevelFilter::Trace),
+			#[cfg(target_os = "linux")]
+			cpu_features: params.cpu_features,
+			#[cfg(target_os = "linux")]
+			tsc_scale: params.tsc_scale,
+			#[cfg(target_os = "linux")]
+			perf_counters: params.perf_counters,
+			#[cfg(target_os = "linux")]
+			write_protect: !params.read_only_regions.is_empty(),
+			#[cfg(target_os = "linux")]
+			core_dump: params.core_dump,
+			apic_base,
+			ioapic_base,
 			virtio_device,
+			app_name: Mutex::new(None),
+			panic_message: Mutex::new(None),
+			test_reports: Default::default(),
+			metrics: Default::default(),
+			fault: Mutex::new(None),
+			boot_memory_size,
+			committed_memory: Mutex::new(boot_memory_size),
+			fdmap,
+			audit_log,
+			hypercall_limiter: params.hypercall_rate_limit.map(RateLimiter::new),
+			barrier: Arc::new(GuestBarrier::new(params.barrier_timeout)),
+			stats: Default::default(),
+			memory_advice: params.thp || params.ksm,
+			serial,
+			event_hook: params.event_hook,
 			gdb_port: params.gdb_port,
 			_vcpu_type: PhantomData,
 		};
 
-		vm.init_guest_mem();
+		vm.init_guest_mem(identity_map_length, &params.read_only_regions);
 
 		Ok(vm)
 	}
@@ -152,6 +827,23 @@ impl<VCpuType: VirtualCPU> UhyveVm<VCpuType> {
 		self.stack_address
 	}
 
+	/// Returns the initial stack pointer of the vCPUs.
+	///
+	/// This is the stack address, unless the arguments were put onto the stack.
+	pub fn stack_pointer(&self) -> u64 {
+		self.stack_pointer.unwrap_or(self.stack_address)
+	}
+
+	/// Returns the guest-physical base address of the local APICs.
+	pub fn apic_base(&self) -> GuestPhysAddr {
+		self.apic_base
+	}
+
+	/// Returns the guest-physical base address of the IOAPIC.
+	pub fn ioapic_base(&self) -> GuestPhysAddr {
+		self.ioapic_base
+	}
+
 	/// Returns the number of cores for the vm.
 	pub fn num_cpus(&self) -> u32 {
 		self.num_cpus
@@ -165,86 +857,374 @@ impl<VCpuType: VirtualCPU> UhyveVm<VCpuType> {
 		&self.args
 	}
 
+	/// Returns the environment variables passed to the guest.
+	pub fn guest_env(&self) -> &[(OsString, OsString)] {
+		&self.guest_env
+	}
+
+	/// Returns the host directory against which relative guest paths are resolved.
+	pub fn host_root(&self) -> Option<&Path> {
+		self.host_root.as_deref()
+	}
+
+	/// Returns the platform information that was handed to the guest, once the kernel is loaded.
+	pub fn boot_metadata(&self) -> Option<&BootMetadata> {
+		self.boot_metadata.as_ref()
+	}
+
+	/// Returns the device tree that was handed to the guest, once the kernel is loaded.
+	pub fn device_tree(&self) -> Option<DeviceTree> {
+		DeviceTree::parse(&self.fdt)
+	}
+
+	/// Records a heartbeat of the guest, which restarts the timeout, along with its `progress` in
+	/// percent. Progress values above 100 aren't recorded.
+	pub(crate) fn heartbeat(&self, progress: u8) {
+		*self.last_heartbeat.lock().unwrap() = Some(Instant::now());
+		if progress <= 100 {
+			self.stats.record_progress(progress);
+		}
+	}
+
+	/// Returns the deadline of the run once `deadline` has passed, which is later if the guest sent
+	/// a heartbeat in the meantime, or `None` if the VM timed out.
+	pub(crate) fn extended_deadline(&self, deadline: Instant) -> Option<Instant> {
+		let heartbeat = (*self.last_heartbeat.lock().unwrap())?;
+		Some(heartbeat + self.timeout?).filter(|&extended| extended > deadline)
+	}
+
+	/// Assembles the [`VmResult`] of a run that ended with the exit code `code`.
+	pub(crate) fn result(&self, code: i32) -> VmResult {
+		self.serial.flush();
+		if self.flush_on_exit {
+			hypercall::sync_writable_fds(&self.fdmap);
+		}
+		let panic_message = self.panic_message.lock().unwrap().take();
+		let mut stats = self.stats.finish();
+		if self.memory_advice {
+			stats.memory_advice = self.memory_advice_status();
+		}
+		VmResult {
+			code,
+			app_name: self.app_name.lock().unwrap().take(),
+			panicked: panic_message.is_some() || self.panic_exit_code == Some(code),
+			panic_message,
+			cancelled: false,
+			timed_out: false,
+			stats,
+			boot_metadata: self.boot_metadata,
+			test_reports: std::mem::take(&mut self.test_reports.lock().unwrap()),
+			metrics: std::mem::take(&mut self.metrics.lock().unwrap()),
+			fault: self.fault.lock().unwrap().take(),
+			output_lines: self.serial.take_lines(),
+		}
+	}
+
+	/// Returns how effective the transparent huge pages and same page merging of the guest memory
+	/// are, or `None` if the host doesn't report it.
+	pub fn memory_advice_status(&self) -> Option<MemoryAdviceStatus> {
+		#[cfg(target_os = "linux")]
+		{
+			self.mem
+				.advice_status()
+				.inspect_err(|err| warn!("Unable to read the memory advice status: {err}"))
+				.ok()
+		}
+		#[cfg(not(target_os = "linux"))]
+		{
+			None
+		}
+	}
+
+	/// Returns a snapshot of the VM's state for the guest's synthetic files.
+	pub(crate) fn proc_info(&self) -> ProcInfo<'_> {
+		ProcInfo {
+			num_cpus: self.num_cpus,
+			memory_size: *self.committed_memory.lock().unwrap(),
+			cpu_freq: self.boot_metadata.and_then(|metadata| metadata.cpu_freq),
+			uptime: self.stats.elapsed(),
+			virtual_files: &self.virtual_files,
+			unix_sockets: &self.unix_sockets,
+		}
+	}
+
+	/// Copies `len` bytes at the guest virtual address `vaddr` out of the guest memory.
+	///
+	/// The guest should be halted, otherwise the bytes may be inconsistent.
+	pub fn read_guest_virt(
+		&self,
+		vaddr: GuestVirtAddr,
+		len: usize,
+	) -> Result<Vec<u8>, GuestMemoryError> {
+		// Safety: The slices are copied before this fn returns.
+		let slices = unsafe { guest_virt_slices(&self.mem, vaddr, len) }?;
+		Ok(slices.concat())
+	}
+
+	/// Copies `data` to the guest virtual address `vaddr`.
+	///
+	/// Nothing is written, if any part of the range is not mapped.
+	pub fn write_guest_virt(
+		&self,
+		vaddr: GuestVirtAddr,
+		data: &[u8],
+	) -> Result<(), GuestMemoryError> {
+		// Safety: The slices only live during this fn call.
+		let slices = unsafe { guest_virt_slices(&self.mem, vaddr, data.len()) }?;
+		let mut offset = 0;
+		for slice in slices {
+			slice.copy_from_slice(&data[offset..][..slice.len()]);
+			offset += slice.len();
+		}
+		Ok(())
+	}
+
 	/// Initialize the page tables for the guest
-	fn init_guest_mem(&mut self) {
+	fn init_guest_mem(
+		&mut self,
+		identity_map_length: u64,
+		read_only_regions: &[Range<GuestPhysAddr>],
+	) {
 		debug!("Initialize guest memory");
 		crate::arch::init_guest_mem(
 			unsafe { self.mem.as_slice_mut() } // slice only lives during this fn call
 				.try_into()
 				.expect("Guest memory is not large enough for pagetables"),
+			identity_map_length,
+			read_only_regions,
 		);
 	}
 
+	/// Loads the kernel into the guest memory.
+	///
+	/// The kernel is only parsed on the first call, subsequent calls reuse its image.
 	pub fn load_kernel(&mut self) -> LoadKernelResult<()> {
-		let elf = fs::read(self.kernel_path())?;
-		let object = KernelObject::parse(&elf).map_err(LoadKernelError::ParseKernelError)?;
+		let image = match &self.kernel_image {
+			Some(image) => image.clone(),
+			None => {
+				let image = Arc::new(KernelImage::load(self.kernel_path(), None)?);
+				self.kernel_image = Some(image.clone());
+				image
+			}
+		};
 
-		// TODO: should be a random start address, if we have a relocatable executable
-		let kernel_start_address = object.start_addr().unwrap_or(0x400000) as usize;
-		let kernel_end_address = kernel_start_address + object.mem_size();
+		let kernel_start_address = image.start_address as usize;
+		let kernel_end_address = kernel_start_address + image.data.len();
 		self.offset = kernel_start_address as u64;
 
-		if kernel_end_address > self.mem.memory_size - self.mem.guest_address.as_u64() as usize {
+		if kernel_end_address > self.boot_memory_size - self.mem.guest_address.as_u64() as usize {
 			return Err(LoadKernelError::InsufficientMemory);
 		}
 
-		let LoadedKernel {
-			load_info,
-			entry_point,
-		} = object.load_kernel(
-			// Safety: Slice only lives during this fn call, so no aliasing happens
-			&mut unsafe { self.mem.as_slice_uninit_mut() }
-				[kernel_start_address..kernel_end_address],
-			kernel_start_address as u64,
-		);
-		self.entry_point = entry_point;
+		self.mem
+			.write_at(GuestPhysAddr::new(kernel_start_address as u64), &image.data)
+			.map_err(|_| LoadKernelError::InsufficientMemory)?;
+		self.entry_point = image.entry_point;
+		let load_info = LoadInfo {
+			kernel_image_addr_range: image.load_info.kernel_image_addr_range.clone(),
+			tls_info: image.load_info.tls_info,
+		};
 
+		let (boot_time, cpu_freq) = if self.deterministic {
+			(
+				SystemTime::UNIX_EPOCH + Duration::from_secs(DETERMINISTIC_BOOT_TIME_SECS),
+				DETERMINISTIC_CPU_FREQ_MHZ,
+			)
+		} else {
+			(SystemTime::now(), detect_cpu_freq())
+		};
+		let boot_metadata = BootMetadata::new(self.num_cpus(), cpu_freq, boot_time);
+		self.boot_metadata = Some(boot_metadata);
+		let phys_addr_range = self.mem.guest_address.as_u64()
+			..self.mem.guest_address.as_u64() + self.boot_memory_size as u64;
+		let reserved = RESERVED_REGIONS
+			.iter()
+			.map(|(_, range)| range.clone())
+			.collect::<Vec<_>>();
+		let fdt = fdt::reserved_regions_fdt(phys_addr_range.clone(), &reserved);
+		assert!(fdt.len() <= PAGE_SIZE, "The device tree exceeds its region");
+		self.mem
+			.write_at(FDT_ADDR, &fdt)
+			.map_err(|_| LoadKernelError::InsufficientMemory)?;
+		self.fdt = fdt;
 		let boot_info = BootInfo {
 			hardware_info: HardwareInfo {
-				phys_addr_range: self.mem.guest_address.as_u64()
-					..self.mem.guest_address.as_u64() + self.mem.memory_size as u64,
+				phys_addr_range,
 				serial_port_base: self.verbose().then(|| {
 					SerialPortBase::new((uhyve_interface::HypercallAddress::Uart as u16).into())
 						.unwrap()
 				}),
-				device_tree: None,
+				device_tree: NonZeroU64::new(FDT_ADDR.as_u64()),
 			},
 			load_info,
-			platform_info: PlatformInfo::Uhyve {
-				has_pci: cfg!(target_os = "linux"),
-				num_cpus: u64::from(self.num_cpus()).try_into().unwrap(),
-				cpu_freq: NonZeroU32::new(detect_cpu_freq() * 1000),
-				boot_time: SystemTime::now().into(),
-			},
+			platform_info: boot_metadata.into(),
 		};
-		unsafe {
-			let raw_boot_info_ptr =
-				self.mem.host_address.add(BOOT_INFO_ADDR.as_u64() as usize) as *mut RawBootInfo;
-			*raw_boot_info_ptr = RawBootInfo::from(boot_info);
-			self.boot_info = raw_boot_info_ptr;
-		}
+		self.mem
+			.write_struct_at(BOOT_INFO_ADDR, RawBootInfo::from(boot_info))
+			.map_err(|_| LoadKernelError::InsufficientMemory)?;
+		self.boot_info = self.mem.host_address(BOOT_INFO_ADDR).unwrap() as *const RawBootInfo;
 
+		// The stack must not grow into the boot structures
+		let reserved_end = RESERVED_REGIONS
+			.iter()
+			.map(|(_, range)| range.end)
+			.max()
+			.unwrap();
 		self.stack_address = (kernel_start_address as u64)
-			.checked_sub(KERNEL_STACK_SIZE)
-			.expect(
-				"there should be enough space for the boot stack before the kernel start address",
-			);
+			.checked_sub(self.boot_stack_size)
+			.filter(|&stack_address| stack_address >= reserved_end)
+			.ok_or(LoadKernelError::InsufficientStackSpace {
+				stack_size: self.boot_stack_size,
+				kernel_start: kernel_start_address as u64,
+			})?;
+
+		if self.classic_stack_abi {
+			self.write_initial_stack()?;
+		}
+
+		self.load_raw_mappings(self.stack_address..kernel_end_address as u64)?;
+		self.stats.start();
+		Ok(())
+	}
+
+	/// Puts the arguments and the environment onto the boot stack in the System V layout.
+	fn write_initial_stack(&mut self) -> LoadKernelResult<()> {
+		// Safety: Slice only lives during this fn call, so no aliasing happens
+		let stack = unsafe {
+			self.mem.slice_at_mut(
+				GuestPhysAddr::new(self.stack_address),
+				self.boot_stack_size as usize,
+			)
+		}
+		.map_err(|_| LoadKernelError::InsufficientMemory)?;
+		let args = [self.path.as_os_str()]
+			.into_iter()
+			.chain(self.args.iter().map(OsString::as_os_str));
+		let env = self
+			.guest_env
+			.iter()
+			.map(|(key, value)| (key.as_os_str(), value.as_os_str()));
+		let stack_pointer = sysv_stack::write_initial_stack(stack, self.stack_address, args, env)
+			.ok_or(LoadKernelError::ArgumentsExceedStack {
+			stack_size: self.boot_stack_size,
+		})?;
+		self.stack_pointer = Some(stack_pointer);
+		Ok(())
+	}
+
+	/// Copies the files of the raw mappings verbatim into the guest memory.
+	///
+	/// The mappings must neither overlap each other, nor the boot structures or the kernel `image`.
+	fn load_raw_mappings(&self, image: Range<u64>) -> LoadKernelResult<()> {
+		let mut occupied = RESERVED_REGIONS
+			.iter()
+			.map(|(name, range)| (name.to_string(), range.clone()))
+			.chain([("kernel image".to_string(), image)])
+			.collect::<Vec<_>>();
+		let mem_range = self.mem.guest_range();
+
+		for (addr, path) in &self.raw_mappings {
+			let data = fs::read(path)?;
+			let range = addr.as_u64()..addr.as_u64() + data.len() as u64;
+			if range.start < mem_range.start || range.end > mem_range.end {
+				return Err(LoadKernelError::MappingOutOfRange {
+					path: path.clone(),
+					addr: *addr,
+				});
+			}
+			if let Some((region, _)) = occupied
+				.iter()
+				.find(|(_, other)| other.start < range.end && range.start < other.end)
+			{
+				return Err(LoadKernelError::MappingOverlap {
+					path: path.clone(),
+					addr: *addr,
+					region: region.clone(),
+				});
+			}
+
+			let offset = (range.start - mem_range.start) as usize;
+			// Safety: Slice only lives during this fn call, so no aliasing happens
+			let dest = &mut unsafe { self.mem.as_slice_mut() }[offset..offset + data.len()];
+			dest.copy_from_slice(&data);
+			occupied.push((path.display().to_string(), range));
+		}
 
 		Ok(())
 	}
 }
 
+impl UhyveVm {
+	/// Runs the kernel `runs` times and aggregates the statistics of the runs.
+	///
+	/// Every run starts with a fresh VM and guest memory, but the kernel is only parsed once.
+	pub fn run_repeated(
+		kernel_path: PathBuf,
+		params: Params,
+		runs: NonZeroU32,
+		cpu_affinity: Option<Vec<CoreId>>,
+	) -> HypervisorResult<(Vec<VmResult>, AggregatedStats)> {
+		let image = KernelImage::load(&kernel_path, params.kernel_sha256.as_deref())
+			.map(Arc::new)
+			.map_err(|err| {
+				error!("Unable to load the kernel: {err}");
+				errno_error(err.errno())
+			})?;
+		// The kernel was verified above.
+		let params = Params {
+			kernel_sha256: None,
+			..params
+		};
+		let results = (0..runs.get())
+			.map(|_| {
+				let mut vm = UhyveVm::new(kernel_path.clone(), params.clone())?;
+				vm.kernel_image = Some(image.clone());
+				vm.run(cpu_affinity.clone())
+			})
+			.collect::<HypervisorResult<Vec<_>>>()?;
+		let stats = results
+			.iter()
+			.map(|result| result.stats.clone())
+			.collect::<Vec<_>>();
+		Ok((results, AggregatedStats::new(&stats)))
+	}
+}
+
 impl<VCpuType: VirtualCPU> fmt::Debug for UhyveVm<VCpuType> {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		f.debug_struct("UhyveVm")
 			.field("entry_point", &self.entry_point)
 			.field("stack_address", &self.stack_address)
+			.field("stack_pointer", &self.stack_pointer)
 			.field("mem", &self.mem)
 			.field("num_cpus", &self.num_cpus)
 			.field("path", &self.path)
+			.field("guest_hostname", &self.guest_hostname)
+			.field("priority_bounds", &self.priority_bounds)
+			.field("host_root", &self.host_root)
+			.field("cwd", &self.cwd)
 			.field("boot_info", &self.boot_info)
+			.field("boot_metadata", &self.boot_metadata)
 			.field("verbose", &self.verbose)
+			.field("flush_on_exit", &self.flush_on_exit)
+			.field("deterministic", &self.deterministic)
+			.field("timeout", &self.timeout)
+			.field("last_heartbeat", &self.last_heartbeat)
+			.field("panic_exit_code", &self.panic_exit_code)
 			.field("virtio_device", &self.virtio_device)
+			.field("app_name", &self.app_name)
+			.field("panic_message", &self.panic_message)
+			.field("test_reports", &self.test_reports)
+			.field("metrics", &self.metrics)
+			.field("fault", &self.fault)
+			.field("boot_memory_size", &self.boot_memory_size)
+			.field("committed_memory", &self.committed_memory)
+			.field("fdmap", &self.fdmap)
+			.field("audit_log", &self.audit_log)
+			.field("hypercall_limiter", &self.hypercall_limiter)
+			.field("stats", &self.stats)
+			.field("serial", &self.serial)
+			.field("event_hook", &self.event_hook)
 			.finish()
 	}
 }
@@ -254,3 +1234,121 @@ impl<VCpuType: VirtualCPU> fmt::Debug for UhyveVm<VCpuType> {
 #[allow(clippy::non_send_fields_in_send_ty)]
 unsafe impl<VCpuType: VirtualCPU> Send for UhyveVm<VCpuType> {}
 unsafe impl<VCpuType: VirtualCPU> Sync for UhyveVm<VCpuType> {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	#[cfg(target_arch = "x86_64")]
+	fn test_mmio_base() {
+		let mem = MmapMemory::new(0, 0x20_0000, arch::RAM_START, false, false);
+		let base = |base: u64| mmio_base("APIC", Some(GuestPhysAddr::new(base)), 0, 0x1000, &mem);
+		assert_eq!(
+			mmio_base("APIC", None, APIC_DEFAULT_BASE, 0x1000, &mem).unwrap(),
+			GuestPhysAddr::new(APIC_DEFAULT_BASE)
+		);
+		assert_eq!(base(0xfed0_0000).unwrap(), GuestPhysAddr::new(0xfed0_0000));
+		// Misaligned
+		assert_eq!(base(0xfed0_0010).unwrap_err().errno(), libc::EINVAL);
+		// Overlapping the guest RAM
+		assert_eq!(
+			base(arch::RAM_START.as_u64()).unwrap_err().errno(),
+			libc::EINVAL
+		);
+	}
+
+	#[test]
+	fn test_kernel_integrity() {
+		let dir = assert_fs::TempDir::new().unwrap();
+		let path = dir.path().join("kernel");
+		// A zeroed ELF header that fails to parse
+		let kernel = [0; 64];
+		fs::write(&path, kernel).unwrap();
+		let digest = Sha256::digest(kernel)
+			.iter()
+			.map(|byte| format!("{byte:02X}"))
+			.collect::<String>();
+
+		// The digest is checked before the kernel is parsed
+		assert!(matches!(
+			KernelImage::load(&path, Some(&digest)),
+			Err(LoadKernelError::ParseKernelError(_))
+		));
+		let wrong = "0".repeat(64);
+		match KernelImage::load(&path, Some(&wrong)) {
+			Err(LoadKernelError::IntegrityMismatch { expected, found }) => {
+				assert_eq!(expected, wrong);
+				assert_eq!(found, digest.to_lowercase());
+			}
+			result => panic!("unexpected result {result:?}"),
+		}
+	}
+
+	#[test]
+	fn test_guest_args() {
+		let args = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+
+		assert_eq!(
+			guest_args(args(&["-freq", "2000"]), args(&["--verbose", "input"])),
+			args(&["-freq", "2000", "--", "--verbose", "input"])
+		);
+		assert_eq!(
+			guest_args(args(&["-freq", "2000"]), Vec::new()),
+			args(&["-freq", "2000"])
+		);
+		assert_eq!(
+			guest_args(Vec::new(), args(&["input"])),
+			args(&["--", "input"])
+		);
+		// Deprecated: application arguments in the kernel arguments
+		assert_eq!(
+			guest_args(args(&["-freq", "2000", "--", "input"]), args(&["more"])),
+			args(&["-freq", "2000", "--", "input", "more"])
+		);
+	}
+
+	#[test]
+	fn test_guest_fault_display() {
+		let fault = GuestFault {
+			cpu_id: 1,
+			reason: "Shutdown".to_string(),
+			rip: 0x1002,
+			rsp: 0x8000,
+			cr2: 0xdead_0000,
+			code: vec![0x90, 0x90, 0x0f, 0x0b],
+			code_start: 0x1000,
+		};
+		assert_eq!(
+			fault.to_string(),
+			"CPU 1 stopped with Shutdown at rip=0x1002 rsp=0x8000 cr2=0xdead0000\n\
+			 code at 0x1000: 90 90 [0f] 0b"
+		);
+	}
+
+	#[test]
+	fn test_boot_metadata() {
+		let boot_time = SystemTime::now();
+		assert_eq!(BootMetadata::new(1, 0, boot_time).cpu_freq, None);
+
+		let metadata = BootMetadata::new(4, 2400, boot_time);
+		assert_eq!(metadata.cpu_freq, NonZeroU32::new(2_400_000));
+		assert_eq!(metadata.num_cpus.get(), 4);
+		assert_eq!(metadata.boot_time, boot_time);
+
+		// The guest receives exactly the exposed metadata
+		let PlatformInfo::Uhyve {
+			has_pci,
+			num_cpus,
+			cpu_freq,
+			boot_time,
+		} = PlatformInfo::from(metadata)
+		else {
+			panic!("unexpected platform info");
+		};
+		assert_eq!(has_pci, metadata.has_pci);
+		assert_eq!(num_cpus, metadata.num_cpus);
+		assert_eq!(cpu_freq, metadata.cpu_freq);
+		assert_eq!(SystemTime::from(boot_time), metadata.boot_time);
+	}
+}
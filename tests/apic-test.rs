@@ -0,0 +1,23 @@
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+
+#[allow(dead_code)]
+mod common;
+
+use common::build_hermit_bin;
+use uhyve_interface::GuestPhysAddr;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn ioapic_base_test() {
+	let bin_path = build_hermit_bin("output_lines");
+	let ioapic_base = GuestPhysAddr::new(0xfeb0_0000);
+	let params = Params {
+		verbose: true,
+		apic_base: Some(GuestPhysAddr::new(0xfed0_0000)),
+		ioapic_base: Some(ioapic_base),
+		..Default::default()
+	};
+	let vm = UhyveVm::new(bin_path, params).unwrap();
+	assert_eq!(vm.ioapic_base(), ioapic_base);
+	assert_eq!(vm.programmed_ioapic_base().unwrap(), ioapic_base);
+}
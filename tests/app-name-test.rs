@@ -0,0 +1,17 @@
+#[allow(dead_code)]
+mod common;
+
+use common::build_hermit_bin;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn app_name_test() {
+	let bin_path = build_hermit_bin("app_name");
+	let params = Params {
+		verbose: true,
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(0, res.code);
+	assert_eq!(res.app_name.as_deref(), Some("uhyve-app-name-test"));
+}
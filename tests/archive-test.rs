@@ -0,0 +1,79 @@
+#[allow(dead_code)]
+mod common;
+
+use std::fs::File;
+
+use common::build_hermit_bin;
+use uhyvelib::{
+	params::{ArchiveMount, Params},
+	vm::UhyveVm,
+};
+
+#[test]
+fn archive_test() {
+	let content = "hello from the archive";
+	let dir = assert_fs::TempDir::new().unwrap();
+	let archive = dir.path().join("fixtures.tar");
+	let mut builder = tar::Builder::new(File::create(&archive).unwrap());
+	let mut header = tar::Header::new_gnu();
+	header.set_size(content.len() as u64);
+	header.set_mode(0o644);
+	header.set_cksum();
+	builder
+		.append_data(&mut header, "uhyve.conf", content.as_bytes())
+		.unwrap();
+	builder
+		.append_data(&mut header, "conf.d/extra.conf", content.as_bytes())
+		.unwrap();
+	builder.into_inner().unwrap();
+
+	// The kernel reads `/etc/uhyve.conf`, which is served from the archive.
+	let bin_path = build_hermit_bin("virtual_file");
+	let params = Params {
+		verbose: true,
+		archives: vec![ArchiveMount {
+			archive: archive.clone(),
+			guest_dir: "/etc".to_string(),
+		}],
+		guest_env: Some(vec![("EXPECTED_CONTENT".to_string(), content.to_string())]),
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path.clone(), params)
+		.unwrap()
+		.run(None)
+		.unwrap();
+	assert_eq!(0, res.code);
+
+	// The directories of the archive can be listed
+	let params = Params {
+		verbose: true,
+		archives: vec![ArchiveMount {
+			archive,
+			guest_dir: "/etc".to_string(),
+		}],
+		guest_env: Some(vec![
+			("DIR".to_string(), "/etc".to_string()),
+			(
+				"EXPECTED_ENTRIES".to_string(),
+				"conf.d/,uhyve.conf".to_string(),
+			),
+		]),
+		..Default::default()
+	};
+	let res = UhyveVm::new(build_hermit_bin("readdir"), params)
+		.unwrap()
+		.run(None)
+		.unwrap();
+	assert_eq!(0, res.code);
+
+	// Unreadable archives are rejected when the VM is created
+	let params = Params {
+		archives: vec![ArchiveMount {
+			archive: dir.path().join("missing.tar"),
+			guest_dir: "/etc".to_string(),
+		}],
+		..Default::default()
+	};
+	let err = <UhyveVm>::new(bin_path, params).unwrap_err();
+	assert_eq!(err.errno(), libc::EINVAL);
+}
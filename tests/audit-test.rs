@@ -0,0 +1,43 @@
+#[allow(dead_code)]
+mod common;
+
+use std::fs::read_to_string;
+
+use common::build_hermit_bin;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn audit_log_test() {
+	let host_root = assert_fs::TempDir::new().unwrap();
+	let log_dir = assert_fs::TempDir::new().unwrap();
+	let log_path = log_dir.path().join("audit.log");
+	let bin_path = build_hermit_bin("audit");
+	let params = Params {
+		verbose: true,
+		host_root: Some(host_root.path().to_path_buf()),
+		max_created_files: Some(1),
+		audit_log: Some(log_path.clone()),
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(0, res.code);
+
+	let log = read_to_string(log_path).unwrap();
+	let record = |op: &str, name: &str| {
+		log.lines()
+			.find(|line| line.contains(&format!(" op={op} ")) && line.contains(name))
+			.unwrap_or_else(|| panic!("No {op} of {name} in the audit log:\n{log}"))
+	};
+	assert!(!record("open", "allowed.txt").contains(" result=-"));
+	assert!(record("open", "denied.txt").ends_with(&format!(" result={}", -libc::ENOSPC)));
+	assert!(record("open", "missing.txt").ends_with(&format!(" result={}", -libc::ENOENT)));
+	assert!(record("unlink", "allowed.txt").ends_with(" result=0"));
+
+	// An audit log that can't be opened is rejected when the VM is created
+	let params = Params {
+		audit_log: Some(log_dir.path().join("missing/audit.log")),
+		..Default::default()
+	};
+	let err = <UhyveVm>::new(build_hermit_bin("audit"), params).unwrap_err();
+	assert_eq!(err.errno(), libc::ENOENT);
+}
@@ -0,0 +1,39 @@
+#![cfg(target_os = "linux")]
+
+#[allow(dead_code)]
+mod common;
+
+use std::{
+	thread,
+	time::{Duration, Instant},
+};
+
+use common::build_hermit_bin;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn barrier_test() {
+	let bin_path = build_hermit_bin("barrier");
+	let params = Params {
+		verbose: true,
+		..Default::default()
+	};
+	let vm: UhyveVm = UhyveVm::new(bin_path, params).unwrap();
+	let mut handle = vm.spawn(None);
+
+	let start = Instant::now();
+	while !handle.is_at_barrier() {
+		assert!(
+			start.elapsed() < Duration::from_secs(10),
+			"the guest never reached the barrier"
+		);
+		thread::sleep(Duration::from_millis(10));
+	}
+	thread::sleep(Duration::from_millis(100));
+	assert!(handle.try_join().is_none());
+	assert!(handle.is_at_barrier());
+
+	handle.release_barrier();
+	let res = handle.join().unwrap();
+	assert_eq!(res.code, 0);
+}
@@ -0,0 +1,48 @@
+#[allow(dead_code)]
+mod common;
+
+use common::build_hermit_bin;
+use uhyvelib::{
+	consts::KERNEL_STACK_SIZE,
+	params::Params,
+	vm::{LoadKernelError, UhyveVm},
+};
+
+#[test]
+fn boot_stack_size_test() {
+	let bin_path = build_hermit_bin("exit_code");
+
+	let mut vm: UhyveVm = UhyveVm::new(bin_path.clone(), Params::default()).unwrap();
+	vm.load_kernel().unwrap();
+	assert_eq!(vm.stack_address(), vm.get_offset() - KERNEL_STACK_SIZE);
+
+	let params = Params {
+		boot_stack_size: Some(4 * KERNEL_STACK_SIZE),
+		..Default::default()
+	};
+	let mut vm: UhyveVm = UhyveVm::new(bin_path.clone(), params.clone()).unwrap();
+	vm.load_kernel().unwrap();
+	assert_eq!(vm.stack_address(), vm.get_offset() - 4 * KERNEL_STACK_SIZE);
+
+	// The guest boots on the larger stack
+	let params = Params {
+		verbose: true,
+		guest_env: Some(vec![("EXIT_CODE".to_string(), "5".to_string())]),
+		..params
+	};
+	let res = UhyveVm::new(bin_path.clone(), params)
+		.unwrap()
+		.run(None)
+		.unwrap();
+	assert_eq!(res.code, 5);
+
+	let params = Params {
+		boot_stack_size: Some(u64::MAX),
+		..Default::default()
+	};
+	let mut vm: UhyveVm = UhyveVm::new(bin_path, params).unwrap();
+	assert!(matches!(
+		vm.load_kernel(),
+		Err(LoadKernelError::InsufficientStackSpace { .. })
+	));
+}
@@ -0,0 +1,19 @@
+#[allow(dead_code)]
+mod common;
+
+use common::build_hermit_bin;
+use uhyve_interface::HypercallAddress;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn brk_test() {
+	let bin_path = build_hermit_bin("brk");
+	let params = Params {
+		verbose: true,
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(0, res.code);
+	assert_eq!(res.stats.hypercalls[&HypercallAddress::Brk], 1);
+	assert!(res.stats.peak_brk.unwrap().as_u64() >= 4 * 1024 * 1024);
+}
@@ -0,0 +1,46 @@
+#![cfg(target_os = "linux")]
+
+#[allow(dead_code)]
+mod common;
+
+use std::{thread, time::Duration};
+
+use common::build_hermit_bin;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn cancel_test() {
+	let bin_path = build_hermit_bin("endless");
+	let params = Params {
+		verbose: true,
+		..Default::default()
+	};
+	let vm: UhyveVm = UhyveVm::new(bin_path, params).unwrap();
+	let mut handle = vm.spawn(None);
+
+	thread::sleep(Duration::from_secs(1));
+	assert!(handle.try_join().is_none());
+
+	handle.cancel();
+	let res = handle.join().unwrap();
+	assert!(res.cancelled);
+	assert_eq!(res.code, libc::ECANCELED);
+}
+
+#[test]
+fn cancel_after_spawn_test() {
+	let bin_path = build_hermit_bin("endless");
+	let params = Params {
+		verbose: true,
+		cpu_count: 2.try_into().unwrap(),
+		..Default::default()
+	};
+	let vm: UhyveVm = UhyveVm::new(bin_path, params).unwrap();
+	let handle = vm.spawn(None);
+
+	// The vCPUs are still being created and must not miss the cancellation
+	handle.cancel();
+	let res = handle.join().unwrap();
+	assert!(res.cancelled);
+	assert_eq!(res.code, libc::ECANCELED);
+}
@@ -0,0 +1,23 @@
+#[allow(dead_code)]
+mod common;
+
+use std::fs;
+
+use common::build_hermit_bin;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn chdir_test() {
+	let bin_path = build_hermit_bin("chdir");
+	let root = assert_fs::TempDir::new().unwrap();
+	fs::create_dir(root.path().join("data")).unwrap();
+	fs::write(root.path().join("data/file.txt"), "uhyve-chdir-test").unwrap();
+
+	let params = Params {
+		verbose: true,
+		host_root: Some(root.path().to_path_buf()),
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(0, res.code);
+}
@@ -0,0 +1,44 @@
+#[allow(dead_code)]
+mod common;
+
+use std::ffi::CStr;
+
+use common::build_hermit_bin;
+use uhyve_interface::GuestVirtAddr;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn classic_stack_test() {
+	let bin_path = build_hermit_bin("app_name");
+	let params = Params {
+		classic_stack_abi: true,
+		kernel_args: vec!["uhyve-classic-stack-test".into()],
+		guest_env: Some(vec![("KEY".into(), "value".into())]),
+		..Default::default()
+	};
+	let mut vm: UhyveVm = UhyveVm::new(bin_path.clone(), params).unwrap();
+	vm.load_kernel().unwrap();
+
+	let rsp = vm.stack_pointer();
+	assert_eq!(rsp % 16, 0);
+	assert!(rsp > vm.stack_address());
+	let word = |addr: u64| {
+		let bytes = vm.read_guest_virt(GuestVirtAddr::new(addr), 8).unwrap();
+		u64::from_ne_bytes(bytes.try_into().unwrap())
+	};
+	let string = |addr: u64| {
+		let bytes = vm.read_guest_virt(GuestVirtAddr::new(addr), 256).unwrap();
+		CStr::from_bytes_until_nul(&bytes)
+			.unwrap()
+			.to_str()
+			.unwrap()
+			.to_owned()
+	};
+
+	assert_eq!(word(rsp), 2);
+	assert_eq!(string(word(rsp + 8)), bin_path.to_str().unwrap());
+	assert_eq!(string(word(rsp + 16)), "uhyve-classic-stack-test");
+	assert_eq!(word(rsp + 24), 0);
+	assert_eq!(string(word(rsp + 32)), "KEY=value");
+	assert_eq!(word(rsp + 40), 0);
+}
@@ -0,0 +1,20 @@
+#[allow(dead_code)]
+mod common;
+
+use common::build_hermit_bin;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn clock_compare_test() {
+	let bin_path = build_hermit_bin("clock_compare");
+	let params = Params {
+		verbose: true,
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(0, res.code);
+	let drift = res.stats.clock_drift.unwrap();
+	assert_eq!(drift.samples, 2);
+	// The guest slept for 100 ms, the clocks must not drift apart by as much
+	assert!(drift.max_drift_ns < 100_000_000);
+}
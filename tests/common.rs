@@ -48,6 +48,9 @@ pub fn run_simple_vm(kernel_path: PathBuf) {
 			.unwrap(),
 		..Default::default()
 	};
-	let code = UhyveVm::new(kernel_path, params).unwrap().run(None);
-	assert_eq!(0, code);
+	let res = UhyveVm::new(kernel_path, params)
+		.unwrap()
+		.run(None)
+		.unwrap();
+	assert_eq!(0, res.code);
 }
@@ -0,0 +1,22 @@
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+
+#[allow(dead_code)]
+mod common;
+
+use common::build_hermit_bin;
+use uhyvelib::{
+	params::{CpuFeatureMask, CpuidRegister, Params},
+	vm::UhyveVm,
+};
+
+#[test]
+fn cpuid_test() {
+	let bin_path = build_hermit_bin("cpuid");
+	let params = Params {
+		verbose: true,
+		cpu_features: Some(CpuFeatureMask::new().hide(1, 0, CpuidRegister::Ecx, 1 << 30)),
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(0, res.code);
+}
@@ -0,0 +1,36 @@
+#[allow(dead_code)]
+mod common;
+
+use std::time::{Duration, SystemTime};
+
+use common::build_hermit_bin;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn deterministic_test() {
+	let bin_path = build_hermit_bin("app_name");
+	let run = || {
+		let params = Params {
+			verbose: true,
+			deterministic: true,
+			..Default::default()
+		};
+		UhyveVm::new(bin_path.clone(), params)
+			.unwrap()
+			.run(None)
+			.unwrap()
+	};
+
+	let res = run();
+	let other_res = run();
+	assert_eq!(0, res.code);
+	assert_eq!(
+		res.boot_metadata.unwrap().boot_time,
+		SystemTime::UNIX_EPOCH + Duration::from_secs(1_577_836_800)
+	);
+	assert_eq!(res.boot_metadata, other_res.boot_metadata);
+	assert_eq!(res.code, other_res.code);
+	assert_eq!(res.app_name, other_res.app_name);
+	assert_eq!(res.panic_message, other_res.panic_message);
+	assert_eq!(res.stats.hypercalls, other_res.stats.hypercalls);
+}
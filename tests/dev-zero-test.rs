@@ -0,0 +1,19 @@
+#[allow(dead_code)]
+mod common;
+
+use common::build_hermit_bin;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn dev_zero_test() {
+	// The devices are available even though /dev lies outside of the host root.
+	let host_root = assert_fs::TempDir::new().unwrap();
+	let bin_path = build_hermit_bin("dev_zero");
+	let params = Params {
+		verbose: true,
+		host_root: Some(host_root.path().to_path_buf()),
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(0, res.code);
+}
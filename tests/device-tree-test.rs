@@ -0,0 +1,30 @@
+#[allow(dead_code)]
+mod common;
+
+use common::build_hermit_bin;
+use uhyvelib::{consts::RESERVED_REGIONS, params::Params, vm::UhyveVm};
+
+#[test]
+fn device_tree_test() {
+	let bin_path = build_hermit_bin("exit_code");
+	let params = Params::default();
+	let memory_size = params.memory_size.get() as u64;
+	let mut vm: UhyveVm = UhyveVm::new(bin_path, params).unwrap();
+	assert_eq!(vm.device_tree(), None);
+
+	vm.load_kernel().unwrap();
+	let tree = vm.device_tree().unwrap();
+	let start = vm.mem.guest_address.as_u64();
+	assert_eq!(tree.memory().len(), 1);
+	assert_eq!(tree.memory()[0], start..start + memory_size);
+	let memory = tree.root.child(&format!("memory@{start:x}")).unwrap();
+	assert_eq!(memory.property("device_type"), Some(&b"memory\0"[..]));
+	assert!(tree.root.child("chosen").is_some());
+	assert_eq!(
+		tree.reserved,
+		RESERVED_REGIONS
+			.iter()
+			.map(|(_, range)| range.clone())
+			.collect::<Vec<_>>()
+	);
+}
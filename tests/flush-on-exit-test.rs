@@ -0,0 +1,31 @@
+#[allow(dead_code)]
+mod common;
+
+use std::fs;
+
+use common::build_hermit_bin;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn flush_on_exit_test() {
+	let host_root = assert_fs::TempDir::new().unwrap();
+	let bin_path = build_hermit_bin("flush_on_exit");
+	let params = Params {
+		verbose: true,
+		host_root: Some(host_root.path().to_path_buf()),
+		structured_output: true,
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(0, res.code);
+	let lines = res
+		.output_lines
+		.iter()
+		.filter(|line| line.starts_with("flush-on-exit: "))
+		.collect::<Vec<_>>();
+	assert_eq!(lines, ["flush-on-exit: first", "flush-on-exit: last"]);
+	assert_eq!(
+		fs::read(host_root.path().join("flush.txt")).unwrap(),
+		[b'x'; 1 << 20]
+	);
+}
@@ -0,0 +1,22 @@
+use std::path::PathBuf;
+
+use uhyve_interface::GuestVirtAddr;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn guest_virt_access_test() {
+	// The kernel is not loaded, so any path will do
+	let vm: UhyveVm = UhyveVm::new(PathBuf::from("unused"), Params::default()).unwrap();
+
+	// The range crosses a page boundary
+	let vaddr = GuestVirtAddr::new(0x40_0000 - 4);
+	let data = (0..16).collect::<Vec<u8>>();
+	vm.write_guest_virt(vaddr, &data).unwrap();
+	assert_eq!(vm.read_guest_virt(vaddr, data.len()).unwrap(), data);
+	assert_eq!(vm.read_guest_virt(vaddr + 4u64, 4).unwrap(), [4, 5, 6, 7]);
+
+	// Ranges beyond the guest memory are rejected as a whole
+	let end = GuestVirtAddr::new(vm.mem.memory_size as u64 - 8);
+	assert!(vm.write_guest_virt(end, &data).is_err());
+	assert!(vm.read_guest_virt(end, data.len()).is_err());
+}
@@ -0,0 +1,21 @@
+#[allow(dead_code)]
+mod common;
+
+use std::time::Duration;
+
+use common::build_hermit_bin;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn heartbeat_test() {
+	let bin_path = build_hermit_bin("heartbeat");
+	let params = Params {
+		verbose: true,
+		timeout: Some(Duration::from_secs(1)),
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert!(!res.timed_out);
+	assert_eq!(0, res.code);
+	assert_eq!(res.stats.progress, Some(100));
+}
@@ -0,0 +1,23 @@
+#[allow(dead_code)]
+mod common;
+
+use common::build_hermit_bin;
+use uhyvelib::{
+	params::Params,
+	vm::{UhyveVm, DEFAULT_BACKEND_NAME},
+};
+
+#[test]
+fn host_info_test() {
+	let bin_path = build_hermit_bin("host_info");
+	let params = Params {
+		verbose: true,
+		guest_env: Some(vec![(
+			"EXPECTED_BACKEND".to_string(),
+			DEFAULT_BACKEND_NAME.to_string(),
+		)]),
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(0, res.code);
+}
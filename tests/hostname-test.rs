@@ -0,0 +1,41 @@
+#[allow(dead_code)]
+mod common;
+
+use common::build_hermit_bin;
+use uhyvelib::{consts::DEFAULT_GUEST_HOSTNAME, params::Params, vm::UhyveVm};
+
+fn run_hostname_kernel(guest_hostname: Option<&str>, expected: &str) {
+	let bin_path = build_hermit_bin("hostname");
+	let params = Params {
+		verbose: true,
+		guest_hostname: guest_hostname.map(str::to_string),
+		guest_env: Some(vec![(
+			"EXPECTED_HOSTNAME".to_string(),
+			expected.to_string(),
+		)]),
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(0, res.code);
+}
+
+#[test]
+fn hostname_test() {
+	run_hostname_kernel(Some("uhyve-guest"), "uhyve-guest");
+}
+
+#[test]
+fn default_hostname_test() {
+	run_hostname_kernel(None, DEFAULT_GUEST_HOSTNAME);
+}
+
+#[test]
+fn nul_hostname_test() {
+	let bin_path = build_hermit_bin("hostname");
+	let params = Params {
+		guest_hostname: Some("uhyve\0guest".to_string()),
+		..Default::default()
+	};
+	let err = <UhyveVm>::new(bin_path, params).unwrap_err();
+	assert_eq!(err.errno(), libc::EINVAL);
+}
@@ -0,0 +1,34 @@
+#[allow(dead_code)]
+mod common;
+
+use std::{io::Write, os::fd::AsRawFd};
+
+use common::build_hermit_bin;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn inherited_fd_test() {
+	let bin_path = build_hermit_bin("inherited_fd");
+	let (reader, mut writer) = std::io::pipe().unwrap();
+	writer.write_all(b"uhyve-inherited-fd-test").unwrap();
+	drop(writer);
+
+	let params = Params {
+		verbose: true,
+		inherited_fds: vec![(10, reader.as_raw_fd())],
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path.clone(), params)
+		.unwrap()
+		.run(None)
+		.unwrap();
+	assert_eq!(0, res.code);
+
+	// Invalid host descriptors are rejected when the VM is created
+	let params = Params {
+		inherited_fds: vec![(10, -1)],
+		..Default::default()
+	};
+	let err = <UhyveVm>::new(bin_path, params).unwrap_err();
+	assert_eq!(err.errno(), libc::EBADF);
+}
@@ -0,0 +1,38 @@
+#[allow(dead_code)]
+mod common;
+
+use std::{fs, path::PathBuf};
+
+use common::build_hermit_bin;
+use sha2::{Digest, Sha256};
+use uhyvelib::{
+	params::Params,
+	vm::{HypervisorResult, UhyveVm},
+};
+
+fn vm_with_digest(bin_path: PathBuf, digest: &str) -> HypervisorResult<UhyveVm> {
+	let params = Params {
+		verbose: true,
+		kernel_sha256: Some(digest.to_string()),
+		..Default::default()
+	};
+	UhyveVm::new(bin_path, params)
+}
+
+#[test]
+fn kernel_integrity_test() {
+	let bin_path = build_hermit_bin("app_name");
+	let digest = Sha256::digest(fs::read(&bin_path).unwrap())
+		.iter()
+		.map(|byte| format!("{byte:02x}"))
+		.collect::<String>();
+	let res = vm_with_digest(bin_path.clone(), &digest)
+		.unwrap()
+		.run(None)
+		.unwrap();
+	assert_eq!(0, res.code);
+
+	// A mismatching kernel is rejected before the VM is created
+	let err = vm_with_digest(bin_path, &"0".repeat(64)).unwrap_err();
+	assert_eq!(err.errno(), libc::EBADMSG);
+}
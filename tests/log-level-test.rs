@@ -0,0 +1,62 @@
+#[allow(dead_code)]
+mod common;
+
+use std::sync::Mutex;
+
+use common::build_hermit_bin;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+/// Keeps the messages of all log records.
+struct CapturingLogger {
+	records: Mutex<Vec<(Level, String)>>,
+}
+
+impl Log for CapturingLogger {
+	fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+		true
+	}
+
+	fn log(&self, record: &Record<'_>) {
+		self.records
+			.lock()
+			.unwrap()
+			.push((record.level(), record.args().to_string()));
+	}
+
+	fn flush(&self) {}
+}
+
+static LOGGER: CapturingLogger = CapturingLogger {
+	records: Mutex::new(Vec::new()),
+};
+
+#[test]
+fn log_level_test() {
+	let bin_path = build_hermit_bin("log_level");
+	log::set_logger(&LOGGER).unwrap();
+	log::set_max_level(LevelFilter::Trace);
+
+	let params = Params {
+		max_guest_log_level: Some(LevelFilter::Debug),
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(res.code, 0);
+	// The guest's level doesn't affect the process-wide one
+	assert_eq!(log::max_level(), LevelFilter::Trace);
+
+	let records = LOGGER.records.lock().unwrap();
+	assert!(records
+		.iter()
+		.any(|(level, message)| *level == Level::Debug && message == "uhyve-log-level-debug"));
+	assert!(!records
+		.iter()
+		.any(|(_, message)| message == "uhyve-log-level-trace"));
+	assert!(records
+		.iter()
+		.any(|(level, message)| *level == Level::Warn && message == "uhyve-log-level-warn"));
+	assert!(!records
+		.iter()
+		.any(|(_, message)| message == "uhyve-log-level-info"));
+}
@@ -0,0 +1,27 @@
+#[allow(dead_code)]
+mod common;
+
+use byte_unit::{Byte, Unit};
+use common::build_hermit_bin;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn mem_balloon_test() {
+	let bin_path = build_hermit_bin("mem_balloon");
+	let params = Params {
+		verbose: true,
+		memory_size: Byte::from_u64_with_unit(64, Unit::MiB)
+			.unwrap()
+			.try_into()
+			.unwrap(),
+		max_memory_size: Some(
+			Byte::from_u64_with_unit(128, Unit::MiB)
+				.unwrap()
+				.try_into()
+				.unwrap(),
+		),
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(0, res.code);
+}
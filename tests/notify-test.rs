@@ -0,0 +1,28 @@
+#[allow(dead_code)]
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use common::build_hermit_bin;
+use uhyvelib::{
+	params::{EventHook, Params},
+	vm::UhyveVm,
+};
+
+#[test]
+fn notify_test() {
+	let bin_path = build_hermit_bin("notify");
+	let events = Arc::new(Mutex::new(Vec::new()));
+	let hook = {
+		let events = events.clone();
+		EventHook::new(move |event, payload| events.lock().unwrap().push((event, payload.to_vec())))
+	};
+	let params = Params {
+		verbose: true,
+		event_hook: Some(hook),
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(0, res.code);
+	assert_eq!(*events.lock().unwrap(), [(1, b"ready".to_vec())]);
+}
@@ -0,0 +1,23 @@
+#[allow(dead_code)]
+mod common;
+
+use common::build_hermit_bin;
+use uhyvelib::{params::Params, stats::OutOfMemoryStats, vm::UhyveVm};
+
+#[test]
+fn out_of_memory_test() {
+	let bin_path = build_hermit_bin("out_of_memory");
+	let params = Params {
+		verbose: true,
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(1, res.code);
+	assert_eq!(
+		res.stats.out_of_memory,
+		Some(OutOfMemoryStats {
+			failures: 1,
+			peak_size: 1 << 40,
+		})
+	);
+}
@@ -0,0 +1,31 @@
+#[allow(dead_code)]
+mod common;
+
+use common::build_hermit_bin;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn output_lines_test() {
+	let bin_path = build_hermit_bin("output_lines");
+	let params = Params {
+		verbose: true,
+		structured_output: true,
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(0, res.code);
+	// The kernel's own log messages are part of the output as well
+	let lines = res
+		.output_lines
+		.iter()
+		.filter(|line| line.starts_with("output-lines: "))
+		.collect::<Vec<_>>();
+	assert_eq!(
+		lines,
+		[
+			"output-lines: first",
+			"output-lines: second",
+			"output-lines: third"
+		]
+	);
+}
@@ -0,0 +1,38 @@
+#[allow(dead_code)]
+mod common;
+
+use common::build_hermit_bin;
+use uhyvelib::{consts::GUEST_PANIC_EXIT_CODE, params::Params, vm::UhyveVm};
+
+#[test]
+fn panic_test() {
+	let bin_path = build_hermit_bin("panic");
+	let params = Params {
+		verbose: true,
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(res.code, GUEST_PANIC_EXIT_CODE);
+	assert_eq!(res.panic_message.as_deref(), Some("uhyve-panic-test"));
+	assert!(res.panicked);
+}
+
+#[test]
+fn panic_exit_code_test() {
+	let bin_path = build_hermit_bin("exit_code");
+	for (code, panicked) in [(42, true), (1, false)] {
+		let params = Params {
+			verbose: true,
+			guest_env: Some(vec![("EXIT_CODE".to_string(), code.to_string())]),
+			panic_exit_code: Some(42),
+			..Default::default()
+		};
+		let res = UhyveVm::new(bin_path.clone(), params)
+			.unwrap()
+			.run(None)
+			.unwrap();
+		assert_eq!(res.code, code);
+		assert_eq!(res.panicked, panicked);
+		assert_eq!(res.panic_message, None);
+	}
+}
@@ -0,0 +1,44 @@
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+
+#[allow(dead_code)]
+mod common;
+
+use common::build_hermit_bin;
+use kvm_bindings::KVM_MAX_CPUID_ENTRIES;
+use kvm_ioctls::Kvm;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+/// Returns whether KVM offers a virtual PMU with at least two fixed counters.
+fn has_fixed_counters() -> bool {
+	let cpuid = Kvm::new()
+		.unwrap()
+		.get_supported_cpuid(KVM_MAX_CPUID_ENTRIES)
+		.unwrap();
+	cpuid
+		.as_slice()
+		.iter()
+		.find(|entry| entry.function == 0x0A)
+		.is_some_and(|entry| entry.eax & 0xff >= 2 && entry.edx & 0x1f >= 2)
+}
+
+#[test]
+fn perf_counters_test() {
+	let bin_path = build_hermit_bin("output_lines");
+	let params = Params {
+		verbose: true,
+		perf_counters: true,
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(0, res.code);
+	let Some(counters) = res.stats.perf_counters else {
+		assert!(
+			!has_fixed_counters(),
+			"KVM supports fixed counters, but they weren't recorded"
+		);
+		eprintln!("Skipping, the host has no virtual PMU with fixed counters");
+		return;
+	};
+	assert!(counters.instructions > 0);
+	assert!(counters.cycles > 0);
+}
@@ -0,0 +1,17 @@
+#[allow(dead_code)]
+mod common;
+
+use common::build_hermit_bin;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn proc_cpuinfo_test() {
+	let bin_path = build_hermit_bin("proc_cpuinfo");
+	let params = Params {
+		verbose: true,
+		cpu_count: 2.try_into().unwrap(),
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(0, res.code);
+}
@@ -0,0 +1,62 @@
+#[allow(dead_code)]
+mod common;
+
+use std::fs;
+
+use common::build_hermit_bin;
+use uhyve_interface::GuestPhysAddr;
+use uhyvelib::{
+	params::Params,
+	vm::{LoadKernelError, UhyveVm},
+};
+
+#[test]
+fn raw_mapping_test() {
+	let dir = assert_fs::TempDir::new().unwrap();
+	let blob_path = dir.path().join("blob.bin");
+	let blob = (0..=255).collect::<Vec<u8>>();
+	fs::write(&blob_path, &blob).unwrap();
+	let bin_path = build_hermit_bin("raw_mapping");
+
+	let addr = GuestPhysAddr::new(0x300_0000);
+	let params = Params {
+		raw_mappings: vec![(addr, blob_path.clone())],
+		..Default::default()
+	};
+	let mut vm: UhyveVm = UhyveVm::new(bin_path.clone(), params).unwrap();
+	vm.load_kernel().unwrap();
+	assert_eq!(unsafe { vm.mem.slice_at(addr, blob.len()) }.unwrap(), blob);
+
+	// The guest reads the blob back at its guest-physical address
+	let params = Params {
+		verbose: true,
+		structured_output: true,
+		raw_mappings: vec![(addr, blob_path.clone())],
+		guest_env: Some(vec![(
+			"RAW_MAPPING_LEN".to_string(),
+			blob.len().to_string(),
+		)]),
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path.clone(), params)
+		.unwrap()
+		.run(None)
+		.unwrap();
+	assert_eq!(0, res.code);
+	let hex = blob
+		.iter()
+		.map(|byte| format!("{byte:02x}"))
+		.collect::<String>();
+	assert!(res.output_lines.contains(&format!("raw-mapping: {hex}")));
+
+	// Mappings must not clobber the boot structures
+	let params = Params {
+		raw_mappings: vec![(GuestPhysAddr::new(0x9000), blob_path)],
+		..Default::default()
+	};
+	let mut vm: UhyveVm = UhyveVm::new(bin_path, params).unwrap();
+	assert!(matches!(
+		vm.load_kernel(),
+		Err(LoadKernelError::MappingOverlap { .. })
+	));
+}
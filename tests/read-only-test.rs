@@ -0,0 +1,46 @@
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+
+#[allow(dead_code)]
+mod common;
+
+use common::build_hermit_bin;
+use uhyve_interface::GuestPhysAddr;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn read_only_test() {
+	let bin_path = build_hermit_bin("read_only");
+	let params = Params {
+		verbose: true,
+		read_only_regions: vec![GuestPhysAddr::new(0x200_0000)..GuestPhysAddr::new(0x220_0000)],
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(res.code, libc::EFAULT);
+	let fault = res.fault.unwrap();
+	assert_eq!(fault.reason, "Shutdown");
+	assert_eq!(fault.cr2, 0x200_0000);
+}
+
+#[test]
+fn unaligned_read_only_region_test() {
+	let bin_path = build_hermit_bin("read_only");
+	let params = Params {
+		read_only_regions: vec![GuestPhysAddr::new(0x200_0000)..GuestPhysAddr::new(0x200_1000)],
+		..Default::default()
+	};
+	let err = <UhyveVm>::new(bin_path, params).unwrap_err();
+	assert_eq!(err.errno(), libc::EINVAL);
+}
+
+#[test]
+fn read_only_region_outside_identity_map_test() {
+	let bin_path = build_hermit_bin("read_only");
+	let params = Params {
+		identity_map_length: Some(0x400_0000),
+		read_only_regions: vec![GuestPhysAddr::new(0x400_0000)..GuestPhysAddr::new(0x420_0000)],
+		..Default::default()
+	};
+	let err = <UhyveVm>::new(bin_path, params).unwrap_err();
+	assert_eq!(err.errno(), libc::EINVAL);
+}
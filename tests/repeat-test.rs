@@ -0,0 +1,43 @@
+#[allow(dead_code)]
+mod common;
+
+use std::{fs::remove_file, num::NonZeroU32, path::Path};
+
+use common::build_hermit_bin;
+use uhyve_interface::HypercallAddress;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn repeat_test() {
+	let bin_path = build_hermit_bin("create_file");
+	let params = Params {
+		verbose: true,
+		..Default::default()
+	};
+	let (results, stats) =
+		UhyveVm::run_repeated(bin_path, params, NonZeroU32::new(3).unwrap(), None).unwrap();
+	remove_file(Path::new("foo.txt")).unwrap();
+
+	assert_eq!(results.len(), 3);
+	assert!(results.iter().all(|result| result.code == 0));
+	assert_eq!(stats.runs, 3);
+
+	let writes = stats.hypercalls[&HypercallAddress::FileWrite];
+	assert!(writes.min > 0);
+	assert_eq!(writes.min, writes.max);
+	assert!(results
+		.iter()
+		.all(|result| result.stats.hypercalls[&HypercallAddress::FileWrite] == writes.min));
+}
+
+#[test]
+fn repeat_load_error_test() {
+	let dir = assert_fs::TempDir::new().unwrap();
+	let res = UhyveVm::run_repeated(
+		dir.path().join("missing"),
+		Params::default(),
+		NonZeroU32::new(3).unwrap(),
+		None,
+	);
+	assert_eq!(res.unwrap_err().errno(), libc::ENOENT);
+}
@@ -0,0 +1,18 @@
+#[allow(dead_code)]
+mod common;
+
+use common::build_hermit_bin;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn report_metric_test() {
+	let bin_path = build_hermit_bin("report_metric");
+	let params = Params {
+		verbose: true,
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(0, res.code);
+	assert_eq!(res.metrics.len(), 1);
+	assert_eq!(res.metrics["iteration"], [0.5, 1.0, 1.5]);
+}
@@ -0,0 +1,33 @@
+#![cfg(target_os = "linux")]
+
+#[allow(dead_code)]
+mod common;
+
+use std::fs;
+
+use common::build_hermit_bin;
+use uhyvelib::{
+	params::{Params, Resource},
+	vm::UhyveVm,
+};
+
+#[test]
+fn fsize_rlimit_test() {
+	let host_root = assert_fs::TempDir::new().unwrap();
+	fs::write(host_root.path().join("src.bin"), [0xaa; 8192]).unwrap();
+	let bin_path = build_hermit_bin("rlimit");
+	let params = Params {
+		verbose: true,
+		host_root: Some(host_root.path().to_path_buf()),
+		rlimits: vec![(Resource::RLIMIT_FSIZE, 4096)],
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(0, res.code);
+	assert_eq!(
+		fs::metadata(host_root.path().join("dst.bin"))
+			.unwrap()
+			.len(),
+		4096
+	);
+}
@@ -0,0 +1,41 @@
+#[allow(dead_code)]
+mod common;
+
+use common::build_hermit_bin;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn nonzero_exit_test() {
+	let bin_path = build_hermit_bin("exit_code");
+	let params = Params {
+		verbose: true,
+		guest_env: Some(vec![("EXIT_CODE".to_string(), "3".to_string())]),
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(res.code, 3);
+	assert!(!res.panicked);
+	assert!(res.fault.is_none());
+}
+
+#[test]
+fn invalid_write_test() {
+	let bin_path = build_hermit_bin("invalid_write");
+	let params = Params {
+		verbose: true,
+		..Default::default()
+	};
+	// Invalid writes are reported to the guest and don't stop the VM
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(res.code, 0);
+	assert!(!res.panicked);
+}
+
+#[test]
+fn load_error_test() {
+	let dir = assert_fs::TempDir::new().unwrap();
+	let res = UhyveVm::new(dir.path().join("missing"), Params::default())
+		.unwrap()
+		.run(None);
+	assert_eq!(res.unwrap_err().errno(), libc::ENOENT);
+}
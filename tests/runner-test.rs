@@ -0,0 +1,31 @@
+#[allow(dead_code)]
+mod common;
+
+use common::build_hermit_bin;
+use uhyvelib::{consts::GUEST_PANIC_EXIT_CODE, params::Params, runner::UhyveRunner, vm::UhyveVm};
+
+#[test]
+fn runner_test() {
+	let params = Params {
+		verbose: true,
+		..Default::default()
+	};
+	let mut runner = UhyveRunner::new();
+	runner
+		.add(UhyveVm::new(build_hermit_bin("app_name"), params.clone()).unwrap())
+		.add(UhyveVm::new(build_hermit_bin("panic"), params).unwrap());
+	let results = runner
+		.run()
+		.into_iter()
+		.map(Result::unwrap)
+		.collect::<Vec<_>>();
+
+	assert_eq!(results.len(), 2);
+	assert_eq!(results[0].code, 0);
+	assert_eq!(results[0].app_name.as_deref(), Some("uhyve-app-name-test"));
+	assert_eq!(results[1].code, GUEST_PANIC_EXIT_CODE);
+	assert_eq!(
+		results[1].panic_message.as_deref(),
+		Some("uhyve-panic-test")
+	);
+}
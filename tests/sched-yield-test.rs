@@ -0,0 +1,10 @@
+#[allow(dead_code)]
+mod common;
+
+use common::{build_hermit_bin, run_simple_vm};
+
+#[test]
+fn sched_yield_test() {
+	let bin_path = build_hermit_bin("sched_yield");
+	run_simple_vm(bin_path);
+}
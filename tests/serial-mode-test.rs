@@ -0,0 +1,24 @@
+#[allow(dead_code)]
+mod common;
+
+use common::build_hermit_bin;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn serial_mode_test() {
+	let bin_path = build_hermit_bin("serial_mode");
+	let params = Params {
+		verbose: true,
+		structured_output: true,
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(0, res.code);
+	// The output is complete in both modes
+	let lines = res
+		.output_lines
+		.iter()
+		.filter(|line| line.starts_with("serial-mode: "))
+		.collect::<Vec<_>>();
+	assert_eq!(lines, ["serial-mode: unbuffered", "serial-mode: buffered"]);
+}
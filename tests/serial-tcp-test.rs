@@ -0,0 +1,63 @@
+#[allow(dead_code)]
+mod common;
+
+use std::{
+	io::Read,
+	net::{TcpListener, TcpStream},
+	time::Duration,
+};
+
+use common::build_hermit_bin;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn serial_tcp_test() {
+	let bin_path = build_hermit_bin("output_lines");
+	let addr = TcpListener::bind("127.0.0.1:0")
+		.unwrap()
+		.local_addr()
+		.unwrap();
+	let params = Params {
+		verbose: true,
+		serial_tcp: Some(addr),
+		..Default::default()
+	};
+	let vm = UhyveVm::new(bin_path, params).unwrap();
+	let mut client = TcpStream::connect(addr).unwrap();
+	client
+		.set_read_timeout(Some(Duration::from_secs(10)))
+		.unwrap();
+	let res = vm.run(None).unwrap();
+	assert_eq!(0, res.code);
+
+	let mut output = Vec::new();
+	let mut buf = [0; 4096];
+	while !String::from_utf8_lossy(&output).contains("output-lines: third\n") {
+		let len = client.read(&mut buf).unwrap();
+		assert_ne!(len, 0, "the output ended early: {output:?}");
+		output.extend_from_slice(&buf[..len]);
+	}
+	let output = String::from_utf8(output).unwrap();
+	assert!(output.contains("output-lines: first\n"));
+	assert!(output.contains("output-lines: second\n"));
+}
+
+#[test]
+fn serial_tcp_error_test() {
+	let bin_path = build_hermit_bin("output_lines");
+	let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+	let params = Params {
+		serial_tcp: Some(listener.local_addr().unwrap()),
+		..Default::default()
+	};
+	let err = <UhyveVm>::new(bin_path.clone(), params).unwrap_err();
+	assert_eq!(err.errno(), libc::EADDRINUSE);
+
+	let params = Params {
+		serial_tcp: Some("127.0.0.1:0".parse().unwrap()),
+		log_serial_prefix: Some("[vm] ".to_string()),
+		..Default::default()
+	};
+	let err = <UhyveVm>::new(bin_path, params).unwrap_err();
+	assert_eq!(err.errno(), libc::EINVAL);
+}
@@ -0,0 +1,31 @@
+#![cfg(target_os = "linux")]
+
+#[allow(dead_code)]
+mod common;
+
+use common::build_hermit_bin;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn set_priority_test() {
+	let bin_path = build_hermit_bin("set_priority");
+	let params = Params {
+		verbose: true,
+		priority_bounds: Some(0..=10),
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(0, res.code);
+}
+
+#[test]
+#[allow(clippy::reversed_empty_ranges)]
+fn empty_priority_bounds_test() {
+	let bin_path = build_hermit_bin("set_priority");
+	let params = Params {
+		priority_bounds: Some(10..=0),
+		..Default::default()
+	};
+	let err = <UhyveVm>::new(bin_path, params).unwrap_err();
+	assert_eq!(err.errno(), libc::EINVAL);
+}
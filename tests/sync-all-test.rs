@@ -0,0 +1,26 @@
+#[allow(dead_code)]
+mod common;
+
+use std::fs::read;
+
+use common::build_hermit_bin;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn sync_all_test() {
+	let host_root = assert_fs::TempDir::new().unwrap();
+	let bin_path = build_hermit_bin("sync_all");
+	let params = Params {
+		verbose: true,
+		host_root: Some(host_root.path().to_path_buf()),
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(0, res.code);
+	for i in 0..3 {
+		assert_eq!(
+			read(host_root.path().join(format!("sync{i}.txt"))).unwrap(),
+			b"durable"
+		);
+	}
+}
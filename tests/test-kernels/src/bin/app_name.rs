@@ -0,0 +1,20 @@
+use std::ptr::addr_of_mut;
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+use uhyve_interface::{parameters::SetAppNameParams, GuestVirtAddr, HypercallAddress};
+use uhyve_test_kernels::hypercall;
+
+static mut PARAMS: SetAppNameParams = SetAppNameParams {
+	name: GuestVirtAddr::zero(),
+	len: 0,
+};
+
+fn main() {
+	let name = "uhyve-app-name-test";
+	unsafe {
+		PARAMS.name = GuestVirtAddr::from_ptr(name.as_ptr());
+		PARAMS.len = name.len();
+		hypercall(HypercallAddress::SetAppName, addr_of_mut!(PARAMS));
+	}
+}
@@ -0,0 +1,12 @@
+use std::fs::{self, File};
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+
+fn main() {
+	File::create("/root/allowed.txt").unwrap();
+	// Exceeds the limit of one created file
+	assert!(File::create("/root/denied.txt").is_err());
+	assert!(File::open("/root/missing.txt").is_err());
+	fs::remove_file("/root/allowed.txt").unwrap();
+}
@@ -0,0 +1,16 @@
+use std::ptr::addr_of_mut;
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+use uhyve_interface::{parameters::BarrierParams, HypercallAddress};
+use uhyve_test_kernels::hypercall;
+
+static mut PARAMS: BarrierParams = BarrierParams { ret: -1 };
+
+fn main() {
+	unsafe {
+		hypercall(HypercallAddress::Barrier, addr_of_mut!(PARAMS));
+		assert_eq!({ PARAMS.ret }, 0);
+	}
+	println!("Released from the barrier");
+}
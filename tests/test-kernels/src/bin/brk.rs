@@ -0,0 +1,22 @@
+use std::ptr::addr_of_mut;
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+use uhyve_interface::{parameters::BrkParams, GuestVirtAddr, HypercallAddress};
+use uhyve_test_kernels::hypercall;
+
+/// The size of the allocation, which the host checks against the reported heap end.
+const ALLOCATION_SIZE: usize = 4 * 1024 * 1024;
+
+static mut BRK: BrkParams = BrkParams {
+	brk: GuestVirtAddr::zero(),
+};
+
+fn main() {
+	let buf = vec![1u8; ALLOCATION_SIZE];
+	unsafe {
+		BRK.brk = GuestVirtAddr::new(buf.as_ptr_range().end as u64);
+		hypercall(HypercallAddress::Brk, addr_of_mut!(BRK));
+	}
+	assert!(buf.iter().all(|&byte| byte == 1));
+}
@@ -0,0 +1,62 @@
+use std::ptr::{addr_of, addr_of_mut};
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+use uhyve_interface::{
+	parameters::{ChdirParams, GetCwdParams, OpenParams, ReadPrams},
+	GuestPhysAddr, GuestVirtAddr, HypercallAddress,
+};
+use uhyve_test_kernels::hypercall;
+
+static DIR: [u8; 5] = *b"data\0";
+static FILE: [u8; 9] = *b"file.txt\0";
+const BUF_LEN: usize = 64;
+static mut BUF: [u8; BUF_LEN] = [0; BUF_LEN];
+
+static mut CHDIR: ChdirParams = ChdirParams {
+	path: GuestPhysAddr::zero(),
+	ret: -1,
+};
+static mut GETCWD: GetCwdParams = GetCwdParams {
+	buf: GuestVirtAddr::zero(),
+	len: 0,
+	ret: -1,
+};
+static mut OPEN: OpenParams = OpenParams {
+	name: GuestPhysAddr::zero(),
+	flags: 0,
+	mode: 0,
+	ret: -1,
+};
+static mut READ: ReadPrams = ReadPrams {
+	fd: -1,
+	buf: GuestVirtAddr::zero(),
+	len: 0,
+	ret: -1,
+};
+
+fn main() {
+	unsafe {
+		CHDIR.path = GuestPhysAddr::new(addr_of!(DIR) as u64);
+		hypercall(HypercallAddress::Chdir, addr_of_mut!(CHDIR));
+		assert_eq!({ CHDIR.ret }, 0);
+
+		GETCWD.buf = GuestVirtAddr::new(addr_of!(BUF) as u64);
+		GETCWD.len = BUF_LEN;
+		hypercall(HypercallAddress::GetCwd, addr_of_mut!(GETCWD));
+		assert_eq!({ GETCWD.ret }, 4);
+		let buf = &*addr_of!(BUF);
+		assert_eq!(&buf[..5], b"data\0");
+
+		OPEN.name = GuestPhysAddr::new(addr_of!(FILE) as u64);
+		hypercall(HypercallAddress::FileOpen, addr_of_mut!(OPEN));
+		assert!({ OPEN.ret } > 2);
+
+		READ.fd = OPEN.ret;
+		READ.buf = GuestVirtAddr::new(addr_of!(BUF) as u64);
+		READ.len = BUF_LEN;
+		hypercall(HypercallAddress::FileRead, addr_of_mut!(READ));
+		let buf = &*addr_of!(BUF);
+		assert_eq!(&buf[..{ READ.ret } as usize], b"uhyve-chdir-test");
+	}
+}
@@ -0,0 +1,38 @@
+use std::{
+	ptr::addr_of_mut,
+	thread,
+	time::{Duration, Instant},
+};
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+use uhyve_interface::{parameters::ClockCompareParams, HypercallAddress};
+use uhyve_test_kernels::hypercall;
+
+static mut PARAMS: ClockCompareParams = ClockCompareParams {
+	guest_ns: 0,
+	host_ns: 0,
+	drift_ns: 0,
+};
+
+/// Reports the guest's clock, measured from `start`, and returns the host's clock.
+fn compare(start: Instant) -> u64 {
+	unsafe {
+		PARAMS.guest_ns = start.elapsed().as_nanos() as u64;
+		hypercall(HypercallAddress::ClockCompare, addr_of_mut!(PARAMS));
+		PARAMS.host_ns
+	}
+}
+
+fn main() {
+	let start = Instant::now();
+	let first = compare(start);
+	thread::sleep(Duration::from_millis(100));
+	let second = compare(start);
+	assert!(second >= first);
+	println!(
+		"Host clock advanced by {} ns, drift {} ns",
+		second - first,
+		unsafe { PARAMS.drift_ns }
+	);
+}
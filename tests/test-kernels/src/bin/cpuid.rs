@@ -0,0 +1,15 @@
+#[cfg(target_os = "hermit")]
+use hermit as _;
+
+/// The RDRAND bit in `ecx` of CPUID leaf 1, which is hidden from the guest.
+const RDRAND: u32 = 1 << 30;
+
+fn main() {
+	#[cfg(target_arch = "x86_64")]
+	{
+		// `__cpuid` is only safe on recent toolchains
+		#[allow(unused_unsafe)]
+		let cpuid = unsafe { core::arch::x86_64::__cpuid(1) };
+		assert_eq!(cpuid.ecx & RDRAND, 0);
+	}
+}
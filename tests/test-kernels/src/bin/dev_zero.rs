@@ -0,0 +1,14 @@
+use std::{fs::File, io::prelude::*};
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+
+fn main() {
+	let mut buf = vec![0xff; 4096];
+	File::open("/dev/zero")
+		.unwrap()
+		.read_exact(&mut buf)
+		.unwrap();
+	assert!(buf.iter().all(|&b| b == 0));
+	println!("Read {} zero bytes", buf.len());
+}
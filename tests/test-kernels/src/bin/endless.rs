@@ -0,0 +1,10 @@
+use std::{thread, time::Duration};
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+
+fn main() {
+	loop {
+		thread::sleep(Duration::from_millis(100));
+	}
+}
@@ -0,0 +1,14 @@
+use std::{fs::File, io::prelude::*, process};
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+
+fn main() {
+	let mut file = File::create("/root/flush.txt").unwrap();
+	file.write_all(&[b'x'; 1 << 20]).unwrap();
+	println!("flush-on-exit: first");
+	// The last line has no line ending and the file stays open.
+	print!("flush-on-exit: last");
+	std::io::stdout().flush().unwrap();
+	process::exit(0);
+}
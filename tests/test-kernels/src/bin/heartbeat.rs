@@ -0,0 +1,20 @@
+use std::{ptr::addr_of_mut, thread, time::Duration};
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+use uhyve_interface::{parameters::HeartbeatParams, HypercallAddress};
+use uhyve_test_kernels::hypercall;
+
+static mut PARAMS: HeartbeatParams = HeartbeatParams { progress: 0 };
+
+fn main() {
+	// Runs for about three seconds, well beyond the timeout of one second.
+	for step in 1..=6 {
+		thread::sleep(Duration::from_millis(500));
+		unsafe {
+			PARAMS.progress = step * 100 / 6;
+			hypercall(HypercallAddress::Heartbeat, addr_of_mut!(PARAMS));
+		}
+	}
+	println!("Done");
+}
@@ -0,0 +1,39 @@
+use std::{
+	ffi::CStr,
+	ptr::{addr_of, addr_of_mut},
+};
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+use uhyve_interface::{parameters::HostInfoParams, GuestVirtAddr, HypercallAddress};
+use uhyve_test_kernels::hypercall;
+
+const BUF_LEN: usize = 64;
+static mut VERSION: [u8; BUF_LEN] = [0; BUF_LEN];
+static mut BACKEND: [u8; BUF_LEN] = [0; BUF_LEN];
+
+static mut PARAMS: HostInfoParams = HostInfoParams {
+	version: GuestVirtAddr::zero(),
+	version_len: 0,
+	backend: GuestVirtAddr::zero(),
+	backend_len: 0,
+	ret: -1,
+};
+
+fn main() {
+	let expected_backend = std::env::var("EXPECTED_BACKEND").unwrap();
+	unsafe {
+		PARAMS.version = GuestVirtAddr::new(addr_of!(VERSION) as u64);
+		PARAMS.version_len = BUF_LEN;
+		PARAMS.backend = GuestVirtAddr::new(addr_of!(BACKEND) as u64);
+		PARAMS.backend_len = BUF_LEN;
+		hypercall(HypercallAddress::HostInfo, addr_of_mut!(PARAMS));
+		assert_eq!({ PARAMS.ret }, 0);
+
+		let version = CStr::from_bytes_until_nul(&*addr_of!(VERSION)).unwrap();
+		let backend = CStr::from_bytes_until_nul(&*addr_of!(BACKEND)).unwrap();
+		println!("Running on uhyve {version:?} ({backend:?})");
+		assert!(!version.is_empty());
+		assert_eq!(backend.to_str().unwrap(), expected_backend);
+	}
+}
@@ -0,0 +1,32 @@
+use std::{
+	ffi::CStr,
+	ptr::{addr_of, addr_of_mut},
+};
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+use uhyve_interface::{parameters::GetHostnameParams, GuestVirtAddr, HypercallAddress};
+use uhyve_test_kernels::hypercall;
+
+const BUF_LEN: usize = 64;
+static mut BUF: [u8; BUF_LEN] = [0; BUF_LEN];
+
+static mut PARAMS: GetHostnameParams = GetHostnameParams {
+	buf: GuestVirtAddr::zero(),
+	len: 0,
+	ret: -1,
+};
+
+fn main() {
+	let expected_hostname = std::env::var("EXPECTED_HOSTNAME").unwrap();
+	unsafe {
+		PARAMS.buf = GuestVirtAddr::new(addr_of!(BUF) as u64);
+		PARAMS.len = BUF_LEN;
+		hypercall(HypercallAddress::GetHostname, addr_of_mut!(PARAMS));
+		assert_eq!({ PARAMS.ret }, expected_hostname.len() as isize);
+
+		let hostname = CStr::from_bytes_until_nul(&*addr_of!(BUF)).unwrap();
+		println!("Hostname: {hostname:?}");
+		assert_eq!(hostname.to_str().unwrap(), expected_hostname);
+	}
+}
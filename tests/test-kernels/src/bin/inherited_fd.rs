@@ -0,0 +1,28 @@
+use std::ptr::{addr_of, addr_of_mut};
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+use uhyve_interface::{parameters::ReadPrams, GuestVirtAddr, HypercallAddress};
+use uhyve_test_kernels::hypercall;
+
+/// The guest fd the host passes the pipe's read end as.
+const PIPE_FD: i32 = 10;
+const BUF_LEN: usize = 64;
+static mut BUF: [u8; BUF_LEN] = [0; BUF_LEN];
+
+static mut READ: ReadPrams = ReadPrams {
+	fd: PIPE_FD,
+	buf: GuestVirtAddr::zero(),
+	len: 0,
+	ret: -1,
+};
+
+fn main() {
+	unsafe {
+		READ.buf = GuestVirtAddr::new(addr_of!(BUF) as u64);
+		READ.len = BUF_LEN;
+		hypercall(HypercallAddress::FileRead, addr_of_mut!(READ));
+		let buf = &*addr_of!(BUF);
+		assert_eq!(&buf[..{ READ.ret } as usize], b"uhyve-inherited-fd-test");
+	}
+}
@@ -0,0 +1,36 @@
+use std::ptr::{addr_of, addr_of_mut};
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+use uhyve_interface::{parameters::Write2Params, GuestVirtAddr, HypercallAddress};
+use uhyve_test_kernels::hypercall;
+
+const EBADF: i32 = 9;
+
+static BUF: [u8; 4] = *b"lost";
+
+static mut PARAMS: Write2Params = Write2Params {
+	fd: -1,
+	buf: GuestVirtAddr::zero(),
+	len: 0,
+	ret: 0,
+};
+
+/// Writes `BUF` to `fd` and returns the result reported by Uhyve.
+unsafe fn write_to(fd: i32) -> i32 {
+	unsafe {
+		PARAMS.fd = fd;
+		PARAMS.buf = GuestVirtAddr::new(addr_of!(BUF) as u64);
+		PARAMS.len = BUF.len();
+		hypercall(HypercallAddress::FileWrite2, addr_of_mut!(PARAMS));
+		PARAMS.ret
+	}
+}
+
+fn main() {
+	// The shared stdin is not writable
+	assert_eq!(unsafe { write_to(0) }, -EBADF);
+	// The guest never opened this descriptor
+	assert_eq!(unsafe { write_to(1000) }, -EBADF);
+	println!("Invalid writes were rejected");
+}
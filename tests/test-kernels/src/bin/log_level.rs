@@ -0,0 +1,41 @@
+use std::ptr::addr_of_mut;
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+use uhyve_interface::{
+	parameters::{LogMessageParams, SetLogLevelParams},
+	GuestVirtAddr, HypercallAddress,
+};
+use uhyve_test_kernels::hypercall;
+
+/// Trace, which uhyve caps to the configured ceiling.
+static mut LOG_LEVEL: SetLogLevelParams = SetLogLevelParams { level: 5, ret: -1 };
+static mut LOG_MESSAGE: LogMessageParams = LogMessageParams {
+	level: 0,
+	buf: GuestVirtAddr::zero(),
+	len: 0,
+};
+
+unsafe fn log_message(level: u8, message: &str) {
+	LOG_MESSAGE.level = level;
+	LOG_MESSAGE.buf = GuestVirtAddr::from_ptr(message.as_ptr());
+	LOG_MESSAGE.len = message.len();
+	hypercall(HypercallAddress::LogMessage, addr_of_mut!(LOG_MESSAGE));
+}
+
+fn main() {
+	unsafe {
+		hypercall(HypercallAddress::SetLogLevel, addr_of_mut!(LOG_LEVEL));
+		// debug
+		assert_eq!({ LOG_LEVEL.ret }, 4);
+		log_message(5, "uhyve-log-level-trace");
+		log_message(4, "uhyve-log-level-debug");
+
+		// Lowering the level to warn filters the guest's info messages
+		LOG_LEVEL.level = 2;
+		hypercall(HypercallAddress::SetLogLevel, addr_of_mut!(LOG_LEVEL));
+		assert_eq!({ LOG_LEVEL.ret }, 2);
+		log_message(3, "uhyve-log-level-info");
+		log_message(2, "uhyve-log-level-warn");
+	}
+}
@@ -0,0 +1,27 @@
+use std::ptr::addr_of_mut;
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+use uhyve_interface::{parameters::MemBalloonParams, HypercallAddress};
+use uhyve_test_kernels::hypercall;
+
+const MIB: usize = 1024 * 1024;
+
+static mut PARAMS: MemBalloonParams = MemBalloonParams { size: 0, ret: -1 };
+
+/// Requests a guest memory of `size` bytes and returns the result and the new size.
+fn balloon(size: usize) -> (i32, usize) {
+	unsafe {
+		PARAMS.size = size;
+		hypercall(HypercallAddress::MemBalloon, addr_of_mut!(PARAMS));
+		({ PARAMS.ret }, { PARAMS.size })
+	}
+}
+
+fn main() {
+	// The VM boots with 64 MiB and may grow to 128 MiB
+	assert_eq!(balloon(128 * MIB), (0, 128 * MIB));
+	assert_eq!(balloon(256 * MIB), (-22, 128 * MIB));
+	assert_eq!(balloon(64 * MIB), (0, 64 * MIB));
+	println!("Ballooned the guest memory");
+}
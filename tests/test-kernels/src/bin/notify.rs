@@ -0,0 +1,22 @@
+use std::ptr::addr_of_mut;
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+use uhyve_interface::{parameters::NotifyParams, GuestVirtAddr, HypercallAddress};
+use uhyve_test_kernels::hypercall;
+
+static mut PARAMS: NotifyParams = NotifyParams {
+	event: 0,
+	payload: GuestVirtAddr::zero(),
+	payload_len: 0,
+};
+
+fn main() {
+	let payload = "ready";
+	unsafe {
+		PARAMS.event = 1;
+		PARAMS.payload = GuestVirtAddr::from_ptr(payload.as_ptr());
+		PARAMS.payload_len = payload.len();
+		hypercall(HypercallAddress::Notify, addr_of_mut!(PARAMS));
+	}
+}
@@ -0,0 +1,27 @@
+use std::{
+	alloc::{alloc, Layout},
+	ptr::addr_of_mut,
+};
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+use uhyve_interface::{parameters::OutOfMemoryParams, HypercallAddress};
+use uhyve_test_kernels::hypercall;
+
+/// Far more than the memory of the VM.
+const SIZE: usize = 1 << 40;
+
+static mut PARAMS: OutOfMemoryParams = OutOfMemoryParams { size: 0 };
+
+fn main() {
+	let layout = Layout::from_size_align(SIZE, 4096).unwrap();
+	let ptr = unsafe { alloc(layout) };
+	assert!(ptr.is_null());
+
+	unsafe {
+		PARAMS.size = SIZE;
+		hypercall(HypercallAddress::OutOfMemory, addr_of_mut!(PARAMS));
+	}
+	eprintln!("Unable to allocate {SIZE} bytes");
+	std::process::exit(1);
+}
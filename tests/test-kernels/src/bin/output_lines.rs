@@ -0,0 +1,9 @@
+#[cfg(target_os = "hermit")]
+use hermit as _;
+
+fn main() {
+	println!("output-lines: first");
+	println!("output-lines: second");
+	print!("output-lines: ");
+	println!("third");
+}
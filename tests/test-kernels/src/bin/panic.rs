@@ -0,0 +1,32 @@
+use std::{panic, ptr::addr_of_mut};
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+use uhyve_interface::{parameters::PanicParams, GuestVirtAddr, HypercallAddress};
+use uhyve_test_kernels::hypercall;
+
+static mut PARAMS: PanicParams = PanicParams {
+	message: GuestVirtAddr::zero(),
+	message_len: 0,
+	file: GuestVirtAddr::zero(),
+	file_len: 0,
+	line: 0,
+};
+
+fn main() {
+	panic::set_hook(Box::new(|info| {
+		let message = info.payload().downcast_ref::<&str>().unwrap();
+		let location = info.location().unwrap();
+		unsafe {
+			PARAMS = PanicParams {
+				message: GuestVirtAddr::from_ptr(message.as_ptr()),
+				message_len: message.len(),
+				file: GuestVirtAddr::from_ptr(location.file().as_ptr()),
+				file_len: location.file().len(),
+				line: location.line(),
+			};
+			hypercall(HypercallAddress::Panic, addr_of_mut!(PARAMS));
+		}
+	}));
+	panic!("uhyve-panic-test");
+}
@@ -0,0 +1,51 @@
+use std::ptr::{addr_of, addr_of_mut};
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+use uhyve_interface::{
+	parameters::{OpenParams, ReadPrams},
+	GuestPhysAddr, GuestVirtAddr, HypercallAddress,
+};
+use uhyve_test_kernels::hypercall;
+
+/// The number of CPUs the VM is configured with.
+const CPUS: usize = 2;
+
+static CPUINFO: [u8; 14] = *b"/proc/cpuinfo\0";
+const BUF_LEN: usize = 4096;
+static mut BUF: [u8; BUF_LEN] = [0; BUF_LEN];
+
+static mut OPEN: OpenParams = OpenParams {
+	name: GuestPhysAddr::zero(),
+	flags: 0,
+	mode: 0,
+	ret: -1,
+};
+static mut READ: ReadPrams = ReadPrams {
+	fd: -1,
+	buf: GuestVirtAddr::zero(),
+	len: 0,
+	ret: -1,
+};
+
+fn main() {
+	unsafe {
+		OPEN.name = GuestPhysAddr::new(addr_of!(CPUINFO) as u64);
+		hypercall(HypercallAddress::FileOpen, addr_of_mut!(OPEN));
+		assert!({ OPEN.ret } > 2);
+
+		READ.fd = OPEN.ret;
+		READ.buf = GuestVirtAddr::new(addr_of!(BUF) as u64);
+		READ.len = BUF_LEN;
+		hypercall(HypercallAddress::FileRead, addr_of_mut!(READ));
+		let buf = &*addr_of!(BUF);
+		let cpuinfo = std::str::from_utf8(&buf[..{ READ.ret } as usize]).unwrap();
+		assert_eq!(
+			cpuinfo
+				.lines()
+				.filter(|line| line.starts_with("processor"))
+				.count(),
+			CPUS
+		);
+	}
+}
@@ -0,0 +1,16 @@
+#[cfg(target_os = "hermit")]
+use hermit as _;
+
+/// The guest-physical address the blob is mapped to.
+const RAW_MAPPING: u64 = 0x300_0000;
+
+fn main() {
+	let len = std::env::var("RAW_MAPPING_LEN").unwrap().parse().unwrap();
+	// The low guest memory is identity mapped.
+	let blob = unsafe { std::slice::from_raw_parts(RAW_MAPPING as *const u8, len) };
+	let hex = blob
+		.iter()
+		.map(|byte| format!("{byte:02x}"))
+		.collect::<String>();
+	println!("raw-mapping: {hex}");
+}
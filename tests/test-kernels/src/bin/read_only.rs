@@ -0,0 +1,18 @@
+#[cfg(target_os = "hermit")]
+use hermit as _;
+
+/// The start of the guest-physical region that is mapped read-only.
+const READ_ONLY: u64 = 0x200_0000;
+
+fn main() {
+	#[cfg(target_arch = "x86_64")]
+	unsafe {
+		use core::arch::asm;
+
+		// Without an IDT, the page fault of the write escalates to a triple fault that is
+		// reported by uhyve.
+		let idt = [0u16; 5];
+		asm!("lidt [{}]", in(reg) idt.as_ptr(), options(nostack));
+		(READ_ONLY as *mut u64).write_volatile(0xdead_beef);
+	}
+}
@@ -0,0 +1,40 @@
+use std::{
+	ffi::CString,
+	ptr::{addr_of, addr_of_mut},
+};
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+use uhyve_interface::{parameters::ReaddirParams, GuestPhysAddr, GuestVirtAddr, HypercallAddress};
+use uhyve_test_kernels::hypercall;
+
+const BUF_LEN: usize = 256;
+static mut BUF: [u8; BUF_LEN] = [0; BUF_LEN];
+
+static mut PARAMS: ReaddirParams = ReaddirParams {
+	name: GuestPhysAddr::zero(),
+	buf: GuestVirtAddr::zero(),
+	len: 0,
+	ret: -1,
+};
+
+fn main() {
+	let dir = CString::new(std::env::var("DIR").unwrap()).unwrap();
+	// Comma separated names of the entries of `DIR`
+	let expected_entries = std::env::var("EXPECTED_ENTRIES").unwrap();
+	unsafe {
+		PARAMS.name = GuestPhysAddr::new(dir.as_ptr() as u64);
+		PARAMS.buf = GuestVirtAddr::new(addr_of!(BUF) as u64);
+		PARAMS.len = BUF_LEN;
+		hypercall(HypercallAddress::FileReaddir, addr_of_mut!(PARAMS));
+		let len = { PARAMS.ret };
+		assert!(len > 0, "readdir failed: {len}");
+
+		let entries: Vec<_> = (*addr_of!(BUF))[..len as usize - 1]
+			.split(|&byte| byte == 0)
+			.map(|entry| std::str::from_utf8(entry).unwrap())
+			.collect();
+		println!("Entries of {dir:?}: {entries:?}");
+		assert_eq!(entries.join(","), expected_entries);
+	}
+}
@@ -0,0 +1,28 @@
+use std::ptr::addr_of_mut;
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+use uhyve_interface::{parameters::ReportMetricParams, GuestVirtAddr, HypercallAddress};
+use uhyve_test_kernels::hypercall;
+
+static NAME: &str = "iteration";
+
+static mut PARAMS: ReportMetricParams = ReportMetricParams {
+	name: GuestVirtAddr::zero(),
+	name_len: 0,
+	value: 0.0,
+	ret: -1,
+};
+
+fn main() {
+	for i in 1..=3 {
+		unsafe {
+			PARAMS.name = GuestVirtAddr::new(NAME.as_ptr() as u64);
+			PARAMS.name_len = NAME.len();
+			PARAMS.value = f64::from(i) * 0.5;
+			hypercall(HypercallAddress::ReportMetric, addr_of_mut!(PARAMS));
+			assert_eq!({ PARAMS.ret }, 0);
+		}
+	}
+	println!("Reported {NAME:?} three times");
+}
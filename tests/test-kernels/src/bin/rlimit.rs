@@ -0,0 +1,59 @@
+use std::ptr::{addr_of, addr_of_mut};
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+use uhyve_interface::{
+	parameters::{OpenParams, SendfileParams},
+	GuestPhysAddr, HypercallAddress,
+};
+use uhyve_test_kernels::hypercall;
+
+/// The file size limit of the VM in bytes.
+const FSIZE_LIMIT: isize = 4096;
+const O_WRONLY: i32 = 0o1;
+const O_CREAT: i32 = 0o100;
+const EFBIG: isize = 27;
+
+static SRC: [u8; 8] = *b"src.bin\0";
+static DST: [u8; 8] = *b"dst.bin\0";
+
+static mut OPEN: OpenParams = OpenParams {
+	name: GuestPhysAddr::zero(),
+	flags: 0,
+	mode: 0,
+	ret: -1,
+};
+static mut SENDFILE: SendfileParams = SendfileParams {
+	out_fd: -1,
+	in_fd: -1,
+	offset: 0,
+	count: 0,
+	ret: -1,
+};
+
+unsafe fn open(name: &'static [u8; 8], flags: i32) -> i32 {
+	unsafe {
+		OPEN.name = GuestPhysAddr::new(name.as_ptr() as u64);
+		OPEN.flags = flags;
+		OPEN.mode = 0o644;
+		hypercall(HypercallAddress::FileOpen, addr_of_mut!(OPEN));
+		assert!({ OPEN.ret } > 2);
+		OPEN.ret
+	}
+}
+
+fn main() {
+	unsafe {
+		SENDFILE.in_fd = open(&*addr_of!(SRC), 0);
+		SENDFILE.out_fd = open(&*addr_of!(DST), O_WRONLY | O_CREAT);
+		SENDFILE.count = 2 * FSIZE_LIMIT as usize;
+
+		// The transfer stops at the file size limit
+		hypercall(HypercallAddress::Sendfile, addr_of_mut!(SENDFILE));
+		assert_eq!({ SENDFILE.ret }, FSIZE_LIMIT);
+		// Writing beyond it fails
+		hypercall(HypercallAddress::Sendfile, addr_of_mut!(SENDFILE));
+		assert_eq!({ SENDFILE.ret }, -EFBIG);
+	}
+	println!("Writes are limited to {FSIZE_LIMIT} bytes");
+}
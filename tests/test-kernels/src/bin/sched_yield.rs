@@ -0,0 +1,23 @@
+#[cfg(target_os = "hermit")]
+use hermit as _;
+use uhyve_interface::HypercallAddress;
+use uhyve_test_kernels::hypercall;
+
+/// Sums the squares up to `n`, yielding the host CPU after each step if `yield_cpu` is set.
+fn sum_of_squares(n: u64, yield_cpu: bool) -> u64 {
+	(1..=n)
+		.map(|i| {
+			if yield_cpu {
+				unsafe { hypercall(HypercallAddress::SchedYield, core::ptr::null_mut::<u8>()) };
+			}
+			i * i
+		})
+		.sum()
+}
+
+fn main() {
+	let n = 1000;
+	assert_eq!(sum_of_squares(n, true), sum_of_squares(n, false));
+	assert_eq!(sum_of_squares(n, true), n * (n + 1) * (2 * n + 1) / 6);
+	println!("Yielded {n} times");
+}
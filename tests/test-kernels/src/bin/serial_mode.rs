@@ -0,0 +1,30 @@
+use std::{io::Write, ptr::addr_of_mut};
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+use uhyve_interface::{
+	parameters::{SetSerialModeParams, SERIAL_MODE_BUFFERED, SERIAL_MODE_UNBUFFERED},
+	HypercallAddress,
+};
+use uhyve_test_kernels::hypercall;
+
+static mut PARAMS: SetSerialModeParams = SetSerialModeParams { mode: 0, ret: -1 };
+
+fn set_serial_mode(mode: u8) {
+	unsafe {
+		PARAMS.mode = mode;
+		hypercall(HypercallAddress::SetSerialMode, addr_of_mut!(PARAMS));
+		assert_eq!({ PARAMS.ret }, 0);
+	}
+}
+
+fn main() {
+	set_serial_mode(SERIAL_MODE_UNBUFFERED);
+	for byte in ["s", "e", "r", "i", "a", "l"] {
+		print!("{byte}");
+		std::io::stdout().flush().unwrap();
+	}
+	println!("-mode: unbuffered");
+	set_serial_mode(SERIAL_MODE_BUFFERED);
+	println!("serial-mode: buffered");
+}
@@ -0,0 +1,22 @@
+use std::ptr::addr_of_mut;
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+use uhyve_interface::{parameters::SetPriorityParams, HypercallAddress};
+use uhyve_test_kernels::hypercall;
+
+static mut PARAMS: SetPriorityParams = SetPriorityParams {
+	set: 1,
+	nice: 19,
+	ret: -1,
+};
+
+fn main() {
+	unsafe {
+		hypercall(HypercallAddress::SetPriority, addr_of_mut!(PARAMS));
+		assert_eq!({ PARAMS.ret }, 0);
+		// The host clamps the niceness to its bounds
+		assert_eq!({ PARAMS.nice }, 10);
+		println!("Running with niceness {}", { PARAMS.nice });
+	}
+}
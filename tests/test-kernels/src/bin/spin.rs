@@ -0,0 +1,8 @@
+#[cfg(target_os = "hermit")]
+use hermit as _;
+
+fn main() {
+	loop {
+		std::hint::spin_loop();
+	}
+}
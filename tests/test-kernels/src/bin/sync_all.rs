@@ -0,0 +1,31 @@
+use std::{fs::File, io::prelude::*, ptr::addr_of_mut};
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+use uhyve_interface::{parameters::SyncAllParams, HypercallAddress};
+use uhyve_test_kernels::hypercall;
+
+const FILES: usize = 3;
+
+static mut PARAMS: SyncAllParams = SyncAllParams {
+	sync_host: 0,
+	synced: 0,
+	ret: -1,
+};
+
+fn main() {
+	let files = (0..FILES)
+		.map(|i| {
+			let mut file = File::create(format!("/root/sync{i}.txt")).unwrap();
+			file.write_all(b"durable").unwrap();
+			file
+		})
+		.collect::<Vec<_>>();
+
+	unsafe {
+		hypercall(HypercallAddress::SyncAll, addr_of_mut!(PARAMS));
+		assert_eq!({ PARAMS.ret }, 0);
+		assert!({ PARAMS.synced } as usize >= files.len());
+		println!("Synchronized {} files", { PARAMS.synced });
+	}
+}
@@ -0,0 +1,24 @@
+use std::ptr::addr_of_mut;
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+use uhyve_interface::{parameters::TestResultParams, GuestVirtAddr, HypercallAddress};
+use uhyve_test_kernels::hypercall;
+
+static mut PARAMS: TestResultParams = TestResultParams {
+	passed: 0,
+	failed: 0,
+	name: GuestVirtAddr::zero(),
+	name_len: 0,
+};
+
+fn main() {
+	let name = "uhyve-test-result-test";
+	unsafe {
+		PARAMS.passed = 3;
+		PARAMS.failed = 1;
+		PARAMS.name = GuestVirtAddr::from_ptr(name.as_ptr());
+		PARAMS.name_len = name.len();
+		hypercall(HypercallAddress::TestResult, addr_of_mut!(PARAMS));
+	}
+}
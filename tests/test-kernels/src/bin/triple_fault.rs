@@ -0,0 +1,22 @@
+#[cfg(target_os = "hermit")]
+use hermit as _;
+
+/// A canonical address that is not mapped in the guest.
+#[cfg(target_arch = "x86_64")]
+const UNMAPPED: u64 = 0x7fff_0000_0000;
+
+/// Marks the guest memory, so that it can be found in core dumps.
+static SIGNATURE: &[u8] = b"uhyve triple fault signature";
+
+fn main() {
+	core::hint::black_box(SIGNATURE);
+	#[cfg(target_arch = "x86_64")]
+	unsafe {
+		use core::arch::asm;
+
+		// Without an IDT, the page fault of the jump escalates to a triple fault.
+		let idt = [0u16; 5];
+		asm!("lidt [{}]", in(reg) idt.as_ptr(), options(nostack));
+		asm!("jmp {}", in(reg) UNMAPPED, options(noreturn));
+	}
+}
@@ -0,0 +1,41 @@
+use std::ptr::{addr_of, addr_of_mut};
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+use uhyve_interface::{
+	parameters::{NanoSleepParams, NotifyParams},
+	GuestVirtAddr, HypercallAddress,
+};
+use uhyve_test_kernels::hypercall;
+
+/// The wall-clock time the TSC is measured over.
+const INTERVAL_NS: u64 = 200_000_000;
+
+static mut SLEEP: NanoSleepParams = NanoSleepParams {
+	duration_ns: INTERVAL_NS,
+	remaining_ns: 0,
+	ret: -1,
+};
+static mut CYCLES: [u8; 8] = [0; 8];
+static mut NOTIFY: NotifyParams = NotifyParams {
+	event: 1,
+	payload: GuestVirtAddr::zero(),
+	payload_len: 8,
+};
+
+fn main() {
+	// The host sleeps in real time, independent of the guest's TSC.
+	let start = unsafe { core::arch::x86_64::_rdtsc() };
+	unsafe {
+		hypercall(HypercallAddress::NanoSleep, addr_of_mut!(SLEEP));
+		assert_eq!({ SLEEP.ret }, 0);
+	}
+	let cycles = unsafe { core::arch::x86_64::_rdtsc() } - start;
+	println!("Measured {cycles} TSC cycles in {INTERVAL_NS} ns");
+
+	unsafe {
+		CYCLES = cycles.to_le_bytes();
+		NOTIFY.payload = GuestVirtAddr::new(addr_of!(CYCLES) as u64);
+		hypercall(HypercallAddress::Notify, addr_of_mut!(NOTIFY));
+	}
+}
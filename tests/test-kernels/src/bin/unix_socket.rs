@@ -0,0 +1,56 @@
+use std::ptr::{addr_of, addr_of_mut};
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+use uhyve_interface::{
+	parameters::{OpenParams, ReadPrams, WriteParams},
+	GuestPhysAddr, GuestVirtAddr, HypercallAddress,
+};
+use uhyve_test_kernels::hypercall;
+
+const O_RDWR: i32 = 0o2;
+
+static NAME: [u8; 7] = *b"daemon\0";
+static REQUEST: [u8; 4] = *b"ping";
+static mut RESPONSE: [u8; 4] = [0; 4];
+
+static mut OPEN: OpenParams = OpenParams {
+	name: GuestPhysAddr::zero(),
+	flags: O_RDWR,
+	mode: 0,
+	ret: -1,
+};
+static mut WRITE: WriteParams = WriteParams {
+	fd: -1,
+	buf: GuestVirtAddr::zero(),
+	len: 0,
+};
+static mut READ: ReadPrams = ReadPrams {
+	fd: -1,
+	buf: GuestVirtAddr::zero(),
+	len: 0,
+	ret: -1,
+};
+
+fn main() {
+	unsafe {
+		OPEN.name = GuestPhysAddr::new(addr_of!(NAME) as u64);
+		hypercall(HypercallAddress::FileOpen, addr_of_mut!(OPEN));
+		let fd = OPEN.ret;
+		assert!(fd > 2);
+
+		WRITE.fd = fd;
+		WRITE.buf = GuestVirtAddr::new(addr_of!(REQUEST) as u64);
+		WRITE.len = REQUEST.len();
+		hypercall(HypercallAddress::FileWrite, addr_of_mut!(WRITE));
+		assert_eq!({ WRITE.len }, REQUEST.len());
+
+		READ.fd = fd;
+		READ.buf = GuestVirtAddr::new(addr_of!(RESPONSE) as u64);
+		READ.len = 4;
+		hypercall(HypercallAddress::FileRead, addr_of_mut!(READ));
+		assert_eq!({ READ.ret }, 4);
+		assert_eq!(&*addr_of!(RESPONSE), b"pong");
+	}
+	println!("Exchanged a message with the host daemon");
+}
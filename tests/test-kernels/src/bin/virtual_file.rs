@@ -0,0 +1,70 @@
+use std::ptr::{addr_of, addr_of_mut};
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+use uhyve_interface::{
+	parameters::{Lseek2Params, OpenParams, ReadPrams},
+	GuestPhysAddr, GuestVirtAddr, HypercallAddress,
+};
+use uhyve_test_kernels::hypercall;
+
+static PATH: [u8; 16] = *b"/etc/uhyve.conf\0";
+const BUF_LEN: usize = 256;
+static mut BUF: [u8; BUF_LEN] = [0; BUF_LEN];
+
+static mut OPEN: OpenParams = OpenParams {
+	name: GuestPhysAddr::zero(),
+	flags: 0,
+	mode: 0,
+	ret: -1,
+};
+static mut READ: ReadPrams = ReadPrams {
+	fd: -1,
+	buf: GuestVirtAddr::zero(),
+	len: 0,
+	ret: -1,
+};
+static mut LSEEK: Lseek2Params = Lseek2Params {
+	fd: -1,
+	offset: 0,
+	whence: 0,
+	ret: -1,
+};
+
+/// Reads from the current offset of `fd` into `BUF` and returns the bytes read.
+unsafe fn read(fd: i32) -> &'static [u8] {
+	unsafe {
+		READ.fd = fd;
+		READ.buf = GuestVirtAddr::new(addr_of!(BUF) as u64);
+		READ.len = BUF_LEN;
+		hypercall(HypercallAddress::FileRead, addr_of_mut!(READ));
+		assert!({ READ.ret } >= 0);
+		&(&*addr_of!(BUF))[..{ READ.ret } as usize]
+	}
+}
+
+fn main() {
+	let expected = std::env::var("EXPECTED_CONTENT").unwrap();
+	unsafe {
+		OPEN.name = GuestPhysAddr::new(addr_of!(PATH) as u64);
+
+		// Virtual files are read-only
+		OPEN.flags = 0o2;
+		hypercall(HypercallAddress::FileOpen, addr_of_mut!(OPEN));
+		assert!({ OPEN.ret } < 0);
+
+		OPEN.flags = 0;
+		hypercall(HypercallAddress::FileOpen, addr_of_mut!(OPEN));
+		let fd = OPEN.ret;
+		assert!(fd > 2);
+		assert_eq!(read(fd), expected.as_bytes());
+
+		LSEEK.fd = fd;
+		LSEEK.offset = 6;
+		LSEEK.whence = 0;
+		hypercall(HypercallAddress::FileLseek2, addr_of_mut!(LSEEK));
+		assert_eq!({ LSEEK.ret }, 0);
+		assert_eq!(read(fd), &expected.as_bytes()[6..]);
+	}
+	println!("Read virtual file: {expected:?}");
+}
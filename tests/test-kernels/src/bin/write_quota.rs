@@ -0,0 +1,12 @@
+use std::{fs::File, io::prelude::*};
+
+#[cfg(target_os = "hermit")]
+use hermit as _;
+
+fn main() {
+	let mut file = File::create("/root/quota.txt").unwrap();
+	file.write_all(&[b'a'; 16]).unwrap();
+	// Exceeds the write quota of 24 bytes
+	assert!(file.write_all(&[b'b'; 16]).is_err());
+	println!("Write quota enforced");
+}
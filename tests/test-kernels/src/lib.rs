@@ -0,0 +1,29 @@
+//! Helpers shared by the test kernels.
+
+use uhyve_interface::HypercallAddress;
+
+/// Issues the hypercall at `port` with the parameters at `params`.
+///
+/// The address of `params` is handed to Uhyve unchanged, so it has to be identity mapped.
+/// This holds for statics, as Hermit maps the application image 1:1.
+///
+/// # Safety
+///
+/// `params` must point to the parameter struct matching `port`.
+pub unsafe fn hypercall<T>(port: HypercallAddress, params: *mut T) {
+	let data = params as u64;
+	#[cfg(target_arch = "x86_64")]
+	core::arch::asm!(
+		"out dx, eax",
+		in("dx") port as u16,
+		in("eax") data as u32,
+		options(nostack, preserves_flags)
+	);
+	#[cfg(target_arch = "aarch64")]
+	core::arch::asm!(
+		"str x8, [{port}]",
+		port = in(reg) port as u16 as u64,
+		in("x8") data,
+		options(nostack)
+	);
+}
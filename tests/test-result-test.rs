@@ -0,0 +1,29 @@
+#[allow(dead_code)]
+mod common;
+
+use common::build_hermit_bin;
+use uhyvelib::{
+	params::Params,
+	vm::{TestReport, UhyveVm},
+};
+
+#[test]
+fn test_result_test() {
+	let bin_path = build_hermit_bin("test_result");
+	let params = Params {
+		verbose: true,
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(0, res.code);
+	assert_eq!(
+		res.test_reports,
+		[TestReport {
+			name: Some("uhyve-test-result-test".to_string()),
+			passed: 3,
+			failed: 1,
+		}]
+	);
+	assert_eq!(res.tests_passed(), 3);
+	assert_eq!(res.tests_failed(), 1);
+}
@@ -0,0 +1,26 @@
+#[allow(dead_code)]
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::build_hermit_bin;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn timeout_test() {
+	let bin_path = build_hermit_bin("spin");
+	let params = Params {
+		verbose: true,
+		timeout: Some(Duration::from_secs(2)),
+		..Default::default()
+	};
+	let start = Instant::now();
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	let elapsed = start.elapsed();
+
+	assert!(res.timed_out);
+	assert!(!res.cancelled);
+	assert_eq!(res.code, libc::ETIMEDOUT);
+	assert!(elapsed >= Duration::from_secs(2));
+	assert!(elapsed < Duration::from_secs(4), "took {elapsed:?}");
+}
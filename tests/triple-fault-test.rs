@@ -0,0 +1,46 @@
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+
+#[allow(dead_code)]
+mod common;
+
+use std::fs;
+
+use common::build_hermit_bin;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn triple_fault_test() {
+	let bin_path = build_hermit_bin("triple_fault");
+	let params = Params {
+		verbose: true,
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(res.code, libc::EFAULT);
+	let fault = res.fault.unwrap();
+	assert_eq!(fault.reason, "Shutdown");
+	assert_eq!(fault.rip, 0x7fff_0000_0000);
+	assert_eq!(fault.cr2, 0x7fff_0000_0000);
+	assert!(fault.code.is_empty());
+}
+
+#[test]
+fn core_dump_test() {
+	let bin_path = build_hermit_bin("triple_fault");
+	let dir = assert_fs::TempDir::new().unwrap();
+	let core_dump = dir.path().join("core");
+	let params = Params {
+		verbose: true,
+		core_dump: Some(core_dump.clone()),
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(res.code, libc::EFAULT);
+
+	let dump = fs::read(core_dump).unwrap();
+	assert_eq!(&dump[..4], b"\x7fELF");
+	let signature = b"uhyve triple fault signature";
+	assert!(dump
+		.windows(signature.len())
+		.any(|window| window == signature));
+}
@@ -0,0 +1,60 @@
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+
+#[allow(dead_code)]
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use common::build_hermit_bin;
+use uhyvelib::{
+	params::{EventHook, Params},
+	vm::UhyveVm,
+};
+
+/// Returns the TSC cycles the guest measures over a fixed wall-clock interval.
+fn measure_cycles(tsc_scale: Option<f64>) -> u64 {
+	let bin_path = build_hermit_bin("tsc_measure");
+	let cycles = Arc::new(Mutex::new(None));
+	let hook = {
+		let cycles = cycles.clone();
+		EventHook::new(move |_event, payload| {
+			*cycles.lock().unwrap() = Some(u64::from_le_bytes(payload.try_into().unwrap()))
+		})
+	};
+	let params = Params {
+		verbose: true,
+		tsc_scale,
+		event_hook: Some(hook),
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(0, res.code);
+	let cycles = cycles
+		.lock()
+		.unwrap()
+		.expect("the guest reported no cycles");
+	cycles
+}
+
+#[test]
+fn invalid_tsc_scale_test() {
+	for tsc_scale in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+		let params = Params {
+			tsc_scale: Some(tsc_scale),
+			..Default::default()
+		};
+		let err = <UhyveVm>::new(build_hermit_bin("tsc_measure"), params).unwrap_err();
+		assert_eq!(err.errno(), libc::EINVAL);
+	}
+}
+
+#[test]
+fn tsc_scale_test() {
+	let unscaled = measure_cycles(None);
+	let scaled = measure_cycles(Some(2.0));
+	let ratio = scaled as f64 / unscaled as f64;
+	assert!(
+		(1.7..2.3).contains(&ratio),
+		"a TSC scale of 2 results in a ratio of {ratio}"
+	);
+}
@@ -0,0 +1,41 @@
+#[allow(dead_code)]
+mod common;
+
+use std::{
+	io::{Read, Write},
+	os::unix::net::UnixListener,
+	thread,
+};
+
+use common::build_hermit_bin;
+use uhyvelib::{
+	params::{Params, UnixSocketMapping},
+	vm::UhyveVm,
+};
+
+#[test]
+fn unix_socket_test() {
+	let dir = assert_fs::TempDir::new().unwrap();
+	let socket = dir.path().join("daemon.sock");
+	let listener = UnixListener::bind(&socket).unwrap();
+	let daemon = thread::spawn(move || {
+		let (mut stream, _) = listener.accept().unwrap();
+		let mut request = [0; 4];
+		stream.read_exact(&mut request).unwrap();
+		stream.write_all(b"pong").unwrap();
+		request
+	});
+
+	let bin_path = build_hermit_bin("unix_socket");
+	let params = Params {
+		verbose: true,
+		unix_sockets: vec![UnixSocketMapping {
+			host_path: socket,
+			guest_path: "daemon".to_string(),
+		}],
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(0, res.code);
+	assert_eq!(&daemon.join().unwrap(), b"ping");
+}
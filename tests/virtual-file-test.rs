@@ -0,0 +1,19 @@
+#[allow(dead_code)]
+mod common;
+
+use common::build_hermit_bin;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn virtual_file_test() {
+	let content = "hello from the host";
+	let bin_path = build_hermit_bin("virtual_file");
+	let params = Params {
+		verbose: true,
+		virtual_files: vec![("/etc/uhyve.conf".to_string(), content.as_bytes().to_vec())],
+		guest_env: Some(vec![("EXPECTED_CONTENT".to_string(), content.to_string())]),
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(0, res.code);
+}
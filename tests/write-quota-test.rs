@@ -0,0 +1,25 @@
+#[allow(dead_code)]
+mod common;
+
+use std::fs::read;
+
+use common::build_hermit_bin;
+use uhyvelib::{params::Params, vm::UhyveVm};
+
+#[test]
+fn write_quota_test() {
+	let host_root = assert_fs::TempDir::new().unwrap();
+	let bin_path = build_hermit_bin("write_quota");
+	let params = Params {
+		verbose: true,
+		host_root: Some(host_root.path().to_path_buf()),
+		write_quota_bytes: Some(24),
+		..Default::default()
+	};
+	let res = UhyveVm::new(bin_path, params).unwrap().run(None).unwrap();
+	assert_eq!(res.code, 0);
+	assert_eq!(
+		read(host_root.path().join("quota.txt")).unwrap(),
+		[b'a'; 16]
+	);
+}
@@ -3,8 +3,8 @@
 //! The Uhyve hypercall interface works as follows:
 //!
 //! - On `x86_64` you use an out port instruction. The address of the `out`-port corresponds to the
-//! hypercall you want to use. You can obtain it from the [`IoPorts`] enum. The data send to
-//! that port is the physical memory address (of the VM) of the parameters of that hypercall.
+//!   hypercall you want to use. You can obtain it from the [`IoPorts`] enum. The data send to
+//!   that port is the physical memory address (of the VM) of the parameters of that hypercall.
 //! - On `aarch64` you write to the respective [`HypercallAddress`]. The 64-bit value written to that location is the guest's physical memory address of the hypercall's parameter.
 
 #![cfg_attr(not(feature = "std"), no_std)]
@@ -30,7 +30,7 @@ use parameters::*;
 
 /// The version of the Uhyve interface. Note: This is not the same as the semver of the crate but
 /// should be increased on every version bump that changes the API.
-pub const UHYVE_INTERFACE_VERSION: u32 = 1;
+pub const UHYVE_INTERFACE_VERSION: u32 = 2;
 
 /// Enum containing all valid port mappings for hypercalls.
 ///
@@ -38,7 +38,7 @@ pub const UHYVE_INTERFACE_VERSION: u32 = 1;
 /// e.g., `HypercallPorts::FileWrite as u16`.
 #[non_exhaustive]
 #[repr(u16)]
-#[derive(Debug, Eq, PartialEq, TryFromPrimitive)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, TryFromPrimitive)]
 pub enum HypercallAddress {
 	/// Port address = `0x400`
 	FileWrite = 0x400,
@@ -69,6 +69,72 @@ pub enum HypercallAddress {
 	Uart = 0x800,
 	/// Port address = `0x840`
 	FileUnlink = 0x840,
+	/// Port address = `0x880`
+	SetAppName = 0x880,
+	/// Port address = `0x8C0`
+	LogMessage = 0x8C0,
+	/// Port address = `0x900`
+	Madvise = 0x900,
+	/// Port address = `0x940`
+	Panic = 0x940,
+	/// Port address = `0x980`
+	FileOpenat = 0x980,
+	/// Port address = `0x9C0`
+	Notify = 0x9C0,
+	/// Port address = `0xA00`
+	Chdir = 0xA00,
+	/// Port address = `0xA40`
+	GetCwd = 0xA40,
+	/// Port address = `0xA80`
+	Brk = 0xA80,
+	/// Port address = `0xAC0`
+	FileFadvise = 0xAC0,
+	/// Port address = `0xB00`
+	TestResult = 0xB00,
+	/// Port address = `0xB40`
+	HostInfo = 0xB40,
+	/// Port address = `0xB80`
+	FileOpen2 = 0xB80,
+	/// Port address = `0xBC0`
+	GetEnv = 0xBC0,
+	/// Port address = `0xC00`
+	Barrier = 0xC00,
+	/// Port address = `0xC40`
+	NanoSleep = 0xC40,
+	/// Port address = `0xC80`
+	FileStatvfs = 0xC80,
+	/// Port address = `0xCC0`
+	SetLogLevel = 0xCC0,
+	/// Port address = `0xD00`
+	FileReadExact = 0xD00,
+	/// Port address = `0xD40`
+	Sendfile = 0xD40,
+	/// Port address = `0xD80`
+	MemBalloon = 0xD80,
+	/// Port address = `0xDC0`
+	GetHostname = 0xDC0,
+	/// Port address = `0xE00`
+	SchedYield = 0xE00,
+	/// Port address = `0xE40`
+	SyncAll = 0xE40,
+	/// Port address = `0xE80`
+	OutOfMemory = 0xE80,
+	/// Port address = `0xEC0`
+	SetPriority = 0xEC0,
+	/// Port address = `0xF00`
+	Heartbeat = 0xF00,
+	/// Port address = `0xF40`
+	ReportMetric = 0xF40,
+	/// Port address = `0xF80`
+	ClockCompare = 0xF80,
+	/// Port address = `0xFC0`
+	SetSerialMode = 0xFC0,
+	/// Port address = `0x1000`
+	FileReaddir = 0x1000,
+	/// Port address = `0x1040`
+	FileWrite2 = 0x1040,
+	/// Port address = `0x1080`
+	FileLseek2 = 0x1080,
 }
 impl From<Hypercall<'_>> for HypercallAddress {
 	fn from(value: Hypercall) -> Self {
@@ -79,10 +145,43 @@ impl From<Hypercall<'_>> for HypercallAddress {
 			Hypercall::FileClose(_) => Self::FileClose,
 			Hypercall::FileLseek(_) => Self::FileLseek,
 			Hypercall::FileOpen(_) => Self::FileOpen,
+			Hypercall::FileOpenat(_) => Self::FileOpenat,
 			Hypercall::FileRead(_) => Self::FileRead,
 			Hypercall::FileWrite(_) => Self::FileWrite,
 			Hypercall::FileUnlink(_) => Self::FileUnlink,
 			Hypercall::SerialWriteByte(_) => Self::Uart,
+			Hypercall::SetAppName(_) => Self::SetAppName,
+			Hypercall::LogMessage(_) => Self::LogMessage,
+			Hypercall::Madvise(_) => Self::Madvise,
+			Hypercall::Panic(_) => Self::Panic,
+			Hypercall::Notify(_) => Self::Notify,
+			Hypercall::Chdir(_) => Self::Chdir,
+			Hypercall::GetCwd(_) => Self::GetCwd,
+			Hypercall::Brk(_) => Self::Brk,
+			Hypercall::FileFadvise(_) => Self::FileFadvise,
+			Hypercall::TestResult(_) => Self::TestResult,
+			Hypercall::HostInfo(_) => Self::HostInfo,
+			Hypercall::FileOpen2(_) => Self::FileOpen2,
+			Hypercall::GetEnv(_) => Self::GetEnv,
+			Hypercall::Barrier(_) => Self::Barrier,
+			Hypercall::NanoSleep(_) => Self::NanoSleep,
+			Hypercall::FileStatvfs(_) => Self::FileStatvfs,
+			Hypercall::SetLogLevel(_) => Self::SetLogLevel,
+			Hypercall::FileReadExact(_) => Self::FileReadExact,
+			Hypercall::Sendfile(_) => Self::Sendfile,
+			Hypercall::MemBalloon(_) => Self::MemBalloon,
+			Hypercall::GetHostname(_) => Self::GetHostname,
+			Hypercall::SchedYield => Self::SchedYield,
+			Hypercall::SyncAll(_) => Self::SyncAll,
+			Hypercall::OutOfMemory(_) => Self::OutOfMemory,
+			Hypercall::SetPriority(_) => Self::SetPriority,
+			Hypercall::Heartbeat(_) => Self::Heartbeat,
+			Hypercall::ReportMetric(_) => Self::ReportMetric,
+			Hypercall::ClockCompare(_) => Self::ClockCompare,
+			Hypercall::SetSerialMode(_) => Self::SetSerialMode,
+			Hypercall::FileReaddir(_) => Self::FileReaddir,
+			Hypercall::FileWrite2(_) => Self::FileWrite2,
+			Hypercall::FileLseek2(_) => Self::FileLseek2,
 		}
 	}
 }
@@ -102,11 +201,92 @@ pub enum Hypercall<'a> {
 	FileClose(&'a mut CloseParams),
 	FileLseek(&'a mut LseekParams),
 	FileOpen(&'a mut OpenParams),
+	/// Open a file relative to a directory the guest opened before.
+	FileOpenat(&'a mut OpenatParams),
 	FileRead(&'a mut ReadPrams),
-	FileWrite(&'a WriteParams),
+	FileWrite(&'a mut WriteParams),
 	FileUnlink(&'a mut UnlinkParams),
 	/// Write a char to the terminal.
 	SerialWriteByte(u8),
+	/// Report the name of the application running in the guest to the host.
+	SetAppName(&'a SetAppNameParams),
+	/// Emit a log record with a severity level on the host.
+	LogMessage(&'a LogMessageParams),
+	/// Advise the host on the usage of a range of guest memory, e.g., to release it.
+	Madvise(&'a mut MadviseParams),
+	/// Report a panic of the guest to the host and exit the VM with a failure code.
+	Panic(&'a PanicParams),
+	/// Notify the host that the guest reached a milestone, e.g., that it is ready.
+	Notify(&'a NotifyParams),
+	/// Change the working directory against which the guest's relative paths are resolved.
+	Chdir(&'a mut ChdirParams),
+	/// Get the working directory of the guest.
+	GetCwd(&'a mut GetCwdParams),
+	/// Report the new end of the guest's heap. This is advisory, the host doesn't change any
+	/// mappings.
+	Brk(&'a BrkParams),
+	/// Advise the host on the access pattern of a file.
+	FileFadvise(&'a mut FadviseParams),
+	/// Report the results of a test suite to the host, e.g., from a test kernel.
+	TestResult(&'a TestResultParams),
+	/// Query the uhyve version and the virtualization backend.
+	HostInfo(&'a mut HostInfoParams),
+	/// Open a file like [`FileOpen`](Hypercall::FileOpen) and report whether it was created.
+	FileOpen2(&'a mut Open2Params),
+	/// Look up a host environment variable the guest is allowed to read.
+	GetEnv(&'a mut GetEnvParams),
+	/// Wait until the host is ready, e.g., until a test harness attached to the VM.
+	Barrier(&'a mut BarrierParams),
+	/// Sleep for a duration, yielding the host CPU.
+	NanoSleep(&'a mut NanoSleepParams),
+	/// Get statistics of the file system containing a file, e.g., its free space.
+	FileStatvfs(&'a mut StatvfsParams),
+	/// Change the level up to which the host forwards the guest's log messages, e.g., to trace a
+	/// problematic phase.
+	SetLogLevel(&'a mut SetLogLevelParams),
+	/// Read from a file like [`FileRead`](Hypercall::FileRead), but until the buffer is full or
+	/// the end of the file is reached.
+	FileReadExact(&'a mut ReadPrams),
+	/// Copy data from a file to another file descriptor on the host, without passing it through
+	/// the guest memory.
+	Sendfile(&'a mut SendfileParams),
+	/// Grow or shrink the guest memory within the maximum memory size. The memory above the boot
+	/// memory is mapped from the start, but only backed by host memory while committed.
+	MemBalloon(&'a mut MemBalloonParams),
+	/// Query the hostname of the guest, which is configured on the host.
+	GetHostname(&'a mut GetHostnameParams),
+	/// Yield the host CPU to other threads, e.g., while spinning. The value written to the port
+	/// is ignored.
+	SchedYield,
+	/// Flush all files the guest has open for writing to the host's storage at once, e.g., before
+	/// shutting down.
+	SyncAll(&'a mut SyncAllParams),
+	/// Report a failed allocation of the guest to the host, e.g., before exiting because of it.
+	/// This is advisory, the host doesn't provide more memory.
+	OutOfMemory(&'a OutOfMemoryParams),
+	/// Query or adjust the niceness of the calling vCPU's host thread, e.g., to deprioritize
+	/// background work. The host clamps the niceness to a configured range.
+	SetPriority(&'a mut SetPriorityParams),
+	/// Signal that the guest is alive, which restarts the timeout of the VM, and optionally
+	/// report its progress.
+	Heartbeat(&'a HeartbeatParams),
+	/// Report a value of a named metric, e.g., a latency, to the host. The host collects the
+	/// values of each metric in the order they are reported.
+	ReportMetric(&'a mut ReportMetricParams),
+	/// Compare the guest's monotonic clock with the host's, e.g., to diagnose clock drift. The
+	/// host records the drift in its statistics of the run.
+	ClockCompare(&'a mut ClockCompareParams),
+	/// Switch the forwarding of the serial output between buffered and unbuffered, e.g., for
+	/// interactive applications.
+	SetSerialMode(&'a mut SetSerialModeParams),
+	/// List the entries of a directory of the virtual files, e.g., of a mounted archive.
+	FileReaddir(&'a mut ReaddirParams),
+	/// Write to a file like [`FileWrite`](Hypercall::FileWrite), but report errors, e.g., an
+	/// exhausted write quota, in a separate field.
+	FileWrite2(&'a mut Write2Params),
+	/// Seek in a file like [`FileLseek`](Hypercall::FileLseek), but report errors in a separate
+	/// field.
+	FileLseek2(&'a mut Lseek2Params),
 }
 impl<'a> Hypercall<'a> {
 	/// Get a hypercall's port address.
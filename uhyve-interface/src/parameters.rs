@@ -20,9 +20,15 @@ impl CmdsizeParams {
 	/// Update the struct with the lengths of the given command.
 	/// - `path` is usually the path and name of the application. E.g., "/home/hermit/app"
 	/// - `args` is a list of strings that form the parameters. (E.g., `["-v", "myarg"]`)
+	/// - `env` is the list of environment variables as key-value pairs.
 	///
 	/// Note that this hypercall only transfers the sizes. It usually has to be followed up with the [`Cmdval` Hypercall](crate::Hypercall::Cmdval).
-	pub fn update(&mut self, path: &std::path::Path, args: &[std::ffi::OsString]) {
+	pub fn update(
+		&mut self,
+		path: &std::path::Path,
+		args: &[std::ffi::OsString],
+		env: &[(std::ffi::OsString, std::ffi::OsString)],
+	) {
 		self.argc = 0;
 
 		self.argsz[0] = path.as_os_str().len() as i32 + 1;
@@ -36,7 +42,7 @@ impl CmdsizeParams {
 
 		self.envc = 0;
 		// let mut counter = 0;
-		for (key, value) in std::env::vars_os() {
+		for (key, value) in env {
 			if self.envc < MAX_ARGC_ENVC.try_into().unwrap() {
 				self.envsz[self.envc as usize] = (key.len() + value.len()) as i32 + 2;
 				self.envc += 1;
@@ -71,7 +77,7 @@ pub struct ExitParams {
 pub struct UnlinkParams {
 	/// Address of the file that should be unlinked.
 	pub name: GuestPhysAddr,
-	/// On success, `0` is returned.  On error, `-1` is returned.
+	/// On success, `0` is returned.  On error, a negative errno is returned.
 	pub ret: i32,
 }
 
@@ -84,10 +90,29 @@ pub struct WriteParams {
 	/// Buffer to be written into the file.
 	pub buf: GuestVirtAddr,
 	/// Number of bytes in the buffer to be written.
+	///
+	/// Set by the host to the number of bytes actually written, `0` on error.
 	pub len: usize,
 }
 
-/// Parameters for a [`FileRead`](crate::Hypercall::FileRead) hypercall.
+/// Parameters for a [`FileOpenat`](crate::Hypercall::FileOpenat) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct OpenatParams {
+	/// File descriptor of the directory `name` is relative to.
+	pub dirfd: i32,
+	/// Relative pathname of the file to be opened. It must not leave the directory.
+	pub name: GuestPhysAddr,
+	/// Posix file access mode flags.
+	pub flags: i32,
+	/// Access permissions upon opening/creating a file.
+	pub mode: i32,
+	/// File descriptor upon successful opening or a negative errno upon failure.
+	pub ret: i32,
+}
+
+/// Parameters for a [`FileRead`](crate::Hypercall::FileRead) or
+/// [`FileReadExact`](crate::Hypercall::FileReadExact) hypercall.
 #[repr(C, packed)]
 #[derive(Debug, Copy, Clone)]
 pub struct ReadPrams {
@@ -97,7 +122,8 @@ pub struct ReadPrams {
 	pub buf: GuestVirtAddr,
 	/// Number of bytes to read into the buffer.
 	pub len: usize,
-	/// Number of bytes read on success. `-1` on failure.
+	/// Number of bytes read on success or a negative errno upon failure, e.g. `-EFAULT` if the
+	/// buffer is not mapped.
 	pub ret: isize,
 }
 
@@ -107,7 +133,7 @@ pub struct ReadPrams {
 pub struct CloseParams {
 	/// File descriptor of the file.
 	pub fd: i32,
-	/// Zero on success, `-1` on failure.
+	/// Zero on success or a negative errno upon failure.
 	pub ret: i32,
 }
 
@@ -121,7 +147,7 @@ pub struct OpenParams {
 	pub flags: i32,
 	/// Access permissions upon opening/creating a file.
 	pub mode: i32,
-	/// File descriptor upon successful opening or `-1` upon failure.
+	/// File descriptor upon successful opening or a negative errno upon failure.
 	pub ret: i32,
 }
 
@@ -132,7 +158,445 @@ pub struct LseekParams {
 	/// File descriptor of the file.
 	pub fd: i32,
 	/// Offset in the file.
+	///
+	/// Set by the host to the resulting offset from the start of the file, `-1` on error.
 	pub offset: isize,
 	/// `whence` value of the lseek call.
 	pub whence: i32,
 }
+
+/// Parameters for a [`SetAppName`](crate::Hypercall::SetAppName) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct SetAppNameParams {
+	/// Buffer containing the UTF-8 encoded name (without a terminating zero).
+	pub name: GuestVirtAddr,
+	/// Length of the name in bytes. Names longer than [`MAX_APP_NAME_LEN`] are truncated.
+	pub len: usize,
+}
+
+/// The maximum length of a name set by the [`SetAppName`](crate::Hypercall::SetAppName) hypercall.
+pub const MAX_APP_NAME_LEN: usize = 64;
+
+/// Parameters for a [`LogMessage`](crate::Hypercall::LogMessage) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct LogMessageParams {
+	/// Severity of the message: `1` (error), `2` (warn), `3` (info), `4` (debug) or `5` (trace).
+	pub level: u8,
+	/// Buffer containing the UTF-8 encoded message.
+	pub buf: GuestVirtAddr,
+	/// Length of the message in bytes.
+	pub len: usize,
+}
+
+/// [`FadviseParams::advice`]: No special access pattern.
+pub const FADVISE_NORMAL: i32 = 0;
+/// [`FadviseParams::advice`]: The range is accessed in random order.
+pub const FADVISE_RANDOM: i32 = 1;
+/// [`FadviseParams::advice`]: The range is accessed sequentially.
+pub const FADVISE_SEQUENTIAL: i32 = 2;
+/// [`FadviseParams::advice`]: The range will be accessed soon.
+pub const FADVISE_WILLNEED: i32 = 3;
+/// [`FadviseParams::advice`]: The range won't be accessed soon.
+pub const FADVISE_DONTNEED: i32 = 4;
+/// [`FadviseParams::advice`]: The range is accessed only once.
+pub const FADVISE_NOREUSE: i32 = 5;
+
+/// Parameters for a [`FileFadvise`](crate::Hypercall::FileFadvise) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct FadviseParams {
+	/// File descriptor of the file.
+	pub fd: i32,
+	/// Start of the range in the file.
+	pub offset: i64,
+	/// Length of the range in bytes. `0` extends the range to the end of the file.
+	pub len: i64,
+	/// One of the `FADVISE_*` constants, e.g., [`FADVISE_SEQUENTIAL`].
+	pub advice: i32,
+	/// `0` on success, the negated error number on failure, e.g., `-EBADF` for unknown file
+	/// descriptors or `-EINVAL` for unknown advice.
+	pub ret: i32,
+}
+
+/// [`MadviseParams::advice`]: The guest no longer needs the range. The host releases the memory
+/// and the range reads back as zero afterwards.
+pub const MADVISE_DONTNEED: i32 = 1;
+/// [`MadviseParams::advice`]: The host may release the memory lazily. Until the range is written
+/// again, it contains either the old data or zeros.
+pub const MADVISE_FREE: i32 = 2;
+
+/// Parameters for a [`Madvise`](crate::Hypercall::Madvise) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct MadviseParams {
+	/// Start of the range. Must be aligned to 4 KiB.
+	pub addr: GuestVirtAddr,
+	/// Length of the range in bytes. Must be a multiple of 4 KiB.
+	pub len: usize,
+	/// Either [`MADVISE_DONTNEED`] or [`MADVISE_FREE`].
+	pub advice: i32,
+	/// Set by the host: `0` on success, `-EINVAL` if the advice is unknown or the range is
+	/// unaligned and `-ENOMEM` if the range is not completely mapped. In the error case, the memory
+	/// is left untouched.
+	pub ret: i32,
+}
+
+/// Parameters for a [`Panic`](crate::Hypercall::Panic) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct PanicParams {
+	/// Buffer containing the UTF-8 encoded panic message.
+	pub message: GuestVirtAddr,
+	/// Length of the message in bytes.
+	pub message_len: usize,
+	/// Buffer containing the UTF-8 encoded name of the source file that panicked.
+	pub file: GuestVirtAddr,
+	/// Length of the file name in bytes. `0` if the location is unknown.
+	pub file_len: usize,
+	/// Line of the panic in `file`.
+	pub line: u32,
+}
+
+/// Parameters for a [`Notify`](crate::Hypercall::Notify) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct NotifyParams {
+	/// Identifier of the event. Its meaning is up to the guest and the host application.
+	pub event: u32,
+	/// Buffer containing an optional payload of the event.
+	pub payload: GuestVirtAddr,
+	/// Length of the payload in bytes. `0` if there is no payload.
+	pub payload_len: usize,
+}
+
+/// Parameters for a [`Chdir`](crate::Hypercall::Chdir) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct ChdirParams {
+	/// Address of the zero terminated path of the new working directory.
+	pub path: GuestPhysAddr,
+	/// `0` on success, the negated error number on failure.
+	pub ret: i32,
+}
+
+/// Parameters for a [`GetCwd`](crate::Hypercall::GetCwd) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct GetCwdParams {
+	/// Buffer the zero terminated working directory is written to.
+	pub buf: GuestVirtAddr,
+	/// Size of the buffer in bytes.
+	pub len: usize,
+	/// Length of the working directory without the terminating zero on success, the negated
+	/// error number on failure, e.g., `-ERANGE` if the buffer is too small.
+	pub ret: isize,
+}
+
+/// Parameters for a [`Brk`](crate::Hypercall::Brk) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct BrkParams {
+	/// The new end of the heap after the guest grew or shrank it.
+	pub brk: GuestVirtAddr,
+}
+
+/// Parameters for a [`TestResult`](crate::Hypercall::TestResult) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct TestResultParams {
+	/// Number of tests that passed.
+	pub passed: u32,
+	/// Number of tests that failed.
+	pub failed: u32,
+	/// Buffer containing the UTF-8 encoded name of the test suite (without a terminating zero).
+	pub name: GuestVirtAddr,
+	/// Length of the name in bytes. `0` if the suite has no name.
+	pub name_len: usize,
+}
+
+/// Parameters for a [`HostInfo`](crate::Hypercall::HostInfo) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct HostInfoParams {
+	/// Buffer the zero terminated uhyve version, e.g., `0.3.0`, is written to.
+	pub version: GuestVirtAddr,
+	/// Size of the version buffer in bytes.
+	pub version_len: usize,
+	/// Buffer the zero terminated name of the virtualization backend, e.g., `kvm`, is written to.
+	pub backend: GuestVirtAddr,
+	/// Size of the backend buffer in bytes.
+	pub backend_len: usize,
+	/// `0` on success, the negated error number on failure, e.g., `-ERANGE` if a buffer is too
+	/// small.
+	pub ret: i32,
+}
+
+/// Parameters for a [`FileOpen2`](crate::Hypercall::FileOpen2) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct Open2Params {
+	/// Pathname of the file to be opened.
+	pub name: GuestPhysAddr,
+	/// Posix file access mode flags.
+	pub flags: i32,
+	/// Access permissions upon opening/creating a file.
+	pub mode: i32,
+	/// File descriptor upon successful opening or the negated error number upon failure.
+	pub ret: i32,
+	/// `1` if the file was newly created by the open, `0` otherwise.
+	pub created: u8,
+}
+
+/// Parameters for a [`GetEnv`](crate::Hypercall::GetEnv) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct GetEnvParams {
+	/// Zero terminated name of the environment variable.
+	pub name: GuestPhysAddr,
+	/// Buffer the zero terminated value is written to.
+	pub buf: GuestVirtAddr,
+	/// Size of the buffer in bytes.
+	pub len: usize,
+	/// Length of the value without the terminating zero on success, the negated error number on
+	/// failure, e.g., `-ENOENT` if the variable is not set or not allowed to be read.
+	pub ret: isize,
+}
+
+/// Parameters for a [`Barrier`](crate::Hypercall::Barrier) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct BarrierParams {
+	/// `0` once the host released the guest, `-ECANCELED` if the VM is stopped while waiting.
+	pub ret: i32,
+}
+
+/// Parameters for a [`NanoSleep`](crate::Hypercall::NanoSleep) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct NanoSleepParams {
+	/// Duration of the sleep in nanoseconds.
+	pub duration_ns: u64,
+	/// Time in nanoseconds that was left when the sleep ended early, `0` otherwise.
+	pub remaining_ns: u64,
+	/// `0` on success, the negated error number on failure, e.g., `-EINTR` if the sleep ended
+	/// early because the VM is stopping.
+	pub ret: i32,
+}
+
+/// Parameters for a [`FileStatvfs`](crate::Hypercall::FileStatvfs) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct StatvfsParams {
+	/// Zero terminated path of a file on the file system.
+	pub name: GuestPhysAddr,
+	/// Size of a block in bytes.
+	pub block_size: u64,
+	/// Total number of blocks.
+	pub blocks: u64,
+	/// Number of free blocks.
+	pub blocks_free: u64,
+	/// Number of blocks available to unprivileged users.
+	pub blocks_available: u64,
+	/// `0` on success, the negated error number on failure, e.g., `-ENOENT` if the path doesn't
+	/// exist or is outside of the host directory.
+	pub ret: i32,
+}
+
+/// Parameters for a [`SetLogLevel`](crate::Hypercall::SetLogLevel) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct SetLogLevelParams {
+	/// Maximum level of the guest's log messages the host forwards: `0` (off), `1` (error),
+	/// `2` (warn), `3` (info), `4` (debug) or `5` (trace).
+	pub level: u8,
+	/// The level that was set, which may be lower than the requested one, on success, the negated
+	/// error number on failure, e.g., `-EPERM` if the guest may not change the level.
+	pub ret: i32,
+}
+
+/// Parameters for a [`Sendfile`](crate::Hypercall::Sendfile) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct SendfileParams {
+	/// File descriptor the data is written to.
+	pub out_fd: i32,
+	/// File descriptor of the regular file the data is read from.
+	pub in_fd: i32,
+	/// Offset in the source file to read from, which is advanced by the transferred bytes. If
+	/// negative, the current offset of `in_fd` is used and advanced instead.
+	pub offset: i64,
+	/// Number of bytes to transfer.
+	pub count: usize,
+	/// Number of transferred bytes on success, the negated error number on failure, e.g.,
+	/// `-EINVAL` if `in_fd` is not a regular file.
+	pub ret: isize,
+}
+
+/// Parameters for a [`MemBalloon`](crate::Hypercall::MemBalloon) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct MemBalloonParams {
+	/// Requested size of the guest memory in bytes, which is rounded up to a multiple of 2 MiB.
+	/// Set to the size of the guest memory on return.
+	pub size: usize,
+	/// `0` on success, the negated error number on failure, e.g., `-EINVAL` if the size is smaller
+	/// than the boot memory or exceeds the maximum memory size.
+	pub ret: i32,
+}
+
+/// Parameters for a [`GetHostname`](crate::Hypercall::GetHostname) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct GetHostnameParams {
+	/// Buffer the zero terminated hostname is written to.
+	pub buf: GuestVirtAddr,
+	/// Size of the buffer in bytes.
+	pub len: usize,
+	/// Length of the hostname without the terminating zero on success, the negated error number
+	/// on failure, e.g., `-ERANGE` if the buffer is too small.
+	pub ret: isize,
+}
+
+/// Parameters for a [`SyncAll`](crate::Hypercall::SyncAll) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct SyncAllParams {
+	/// `1` to also flush the host's file system buffers, e.g., of files the guest already closed.
+	pub sync_host: u8,
+	/// Set by the host to the number of file descriptors that were synchronized.
+	pub synced: u32,
+	/// `0` if all writable file descriptors were synchronized, otherwise the negated error number
+	/// of the first failure, e.g., `-EIO`.
+	pub ret: i32,
+}
+
+/// Parameters for an [`OutOfMemory`](crate::Hypercall::OutOfMemory) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct OutOfMemoryParams {
+	/// Size in bytes of the allocation that failed.
+	pub size: usize,
+}
+
+/// Parameters for a [`SetPriority`](crate::Hypercall::SetPriority) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct SetPriorityParams {
+	/// `1` to change the niceness of the vCPU thread, `0` to only query it.
+	pub set: u8,
+	/// The requested niceness. Set by the host to the niceness of the vCPU thread after the call.
+	pub nice: i32,
+	/// `0` on success, otherwise the negated error number, e.g., `-EPERM` if the host doesn't
+	/// allow the guest to change its priority.
+	pub ret: i32,
+}
+
+/// Parameters for a [`Heartbeat`](crate::Hypercall::Heartbeat) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct HeartbeatParams {
+	/// Progress of the guest in percent. Values above `100` report no progress.
+	pub progress: u8,
+}
+
+/// Parameters for a [`ReportMetric`](crate::Hypercall::ReportMetric) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct ReportMetricParams {
+	/// Buffer containing the UTF-8 encoded name of the metric (without a terminating zero).
+	pub name: GuestVirtAddr,
+	/// Length of the name in bytes, at most [`MAX_METRIC_NAME_LEN`].
+	pub name_len: usize,
+	/// The measured value.
+	pub value: f64,
+	/// `0` on success, otherwise the negated error number, e.g., `-EINVAL` for an empty, too
+	/// long or invalid name.
+	pub ret: i32,
+}
+
+/// The maximum length of a metric name reported by the
+/// [`ReportMetric`](crate::Hypercall::ReportMetric) hypercall.
+pub const MAX_METRIC_NAME_LEN: usize = 256;
+
+/// Parameters for a [`ClockCompare`](crate::Hypercall::ClockCompare) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct ClockCompareParams {
+	/// The guest's monotonic clock in nanoseconds. Its origin is arbitrary, but must not change
+	/// between calls.
+	pub guest_ns: u64,
+	/// Set by the host to its monotonic clock in nanoseconds since the start of the guest.
+	pub host_ns: u64,
+	/// Set by the host to the drift of the guest's clock in nanoseconds since the first call,
+	/// positive if the guest's clock runs slow.
+	pub drift_ns: i64,
+}
+
+/// Parameters for a [`SetSerialMode`](crate::Hypercall::SetSerialMode) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct SetSerialModeParams {
+	/// [`SERIAL_MODE_BUFFERED`] or [`SERIAL_MODE_UNBUFFERED`]. The mode applies to the serial
+	/// port as well as to writes to stdout and stderr.
+	pub mode: u8,
+	/// `0` on success, `-EINVAL` for an unknown mode.
+	pub ret: i32,
+}
+
+/// The host forwards the serial output line by line, which favors throughput.
+pub const SERIAL_MODE_BUFFERED: u8 = 0;
+/// The host forwards each write to the serial port right away, which favors latency.
+pub const SERIAL_MODE_UNBUFFERED: u8 = 1;
+
+/// Parameters for a [`FileReaddir`](crate::Hypercall::FileReaddir) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct ReaddirParams {
+	/// Address of the zero terminated path of the directory.
+	pub name: GuestPhysAddr,
+	/// Buffer the zero terminated names of the entries are written to. The names of
+	/// subdirectories end with a `/`.
+	pub buf: GuestVirtAddr,
+	/// Size of the buffer in bytes.
+	pub len: usize,
+	/// Size of all names including their terminating zeros on success, the negated error number
+	/// on failure, e.g., `-ENOENT` if there is no such directory. The names are only written if
+	/// they fit into the buffer.
+	pub ret: isize,
+}
+
+/// Parameters for a [`FileWrite2`](crate::Hypercall::FileWrite2) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct Write2Params {
+	/// File descriptor of the file.
+	pub fd: i32,
+	/// Buffer to be written into the file.
+	pub buf: GuestVirtAddr,
+	/// Number of bytes in the buffer to be written.
+	///
+	/// Set by the host to the number of bytes actually written, `0` on error.
+	pub len: usize,
+	/// `0` on success, the negated error number on failure, e.g., `-EDQUOT` if the write quota is
+	/// exhausted.
+	pub ret: i32,
+}
+
+/// Parameters for a [`FileLseek2`](crate::Hypercall::FileLseek2) hypercall.
+#[repr(C, packed)]
+#[derive(Debug, Copy, Clone)]
+pub struct Lseek2Params {
+	/// File descriptor of the file.
+	pub fd: i32,
+	/// Offset in the file.
+	///
+	/// Set by the host to the resulting offset from the start of the file on success.
+	pub offset: isize,
+	/// `whence` value of the lseek call.
+	pub whence: i32,
+	/// `0` on success, the negated error number on failure, e.g., `-EBADF` for unknown file
+	/// descriptors or `-EINVAL` for an invalid `whence`.
+	pub ret: i32,
+}